@@ -0,0 +1,277 @@
+//! Resources that encapsulate their own construction.
+
+use crate::instrument::ScopeOptions;
+use crate::{scoped, AsyncClose, BoxFuture, UseScope};
+use std::future::Future;
+
+/// A resource that knows how to acquire itself.
+///
+/// Implementing `Acquire` keeps the construction of a resource, such as opening a device or
+/// reading its configuration from the environment, next to the type. [`acquire_use`] then
+/// acquires, uses and drops the resource in one call, so callers never handle a half-built value.
+///
+/// # Examples
+/// ```rust
+/// use std::collections::TryReserveError;
+/// use use_with::{acquire_use, Acquire};
+///
+/// struct Scratch {
+///     buffer: Vec<u8>,
+/// }
+///
+/// impl Acquire for Scratch {
+///     type Error = TryReserveError;
+///
+///     fn acquire() -> Result<Self, Self::Error> {
+///         let mut buffer = Vec::new();
+///         buffer.try_reserve(4096)?;
+///         Ok(Scratch { buffer })
+///     }
+/// }
+///
+/// let len = acquire_use(|scratch: &mut Scratch| {
+///     scratch.buffer.extend_from_slice(b"hello");
+///     scratch.buffer.len()
+/// })?;
+///
+/// assert_eq!(len, 5);
+/// # Ok::<(), TryReserveError>(())
+/// ```
+pub trait Acquire: Sized {
+    /// The error returned when acquiring the resource fails.
+    type Error;
+
+    /// Acquires a new instance of the resource.
+    fn acquire() -> Result<Self, Self::Error>;
+}
+
+/// Acquires a resource of type `R`, executes a closure on it and drops it afterwards.
+///
+/// # Parameters
+/// - `f`: A closure that borrows the acquired resource mutably.
+///
+/// # Returns
+/// - `Ok(U)` with the result of the closure `f`.
+/// - `Err(R::Error)` without running the closure if acquiring the resource failed.
+#[track_caller]
+pub fn acquire_use<R, U, F>(f: F) -> Result<U, R::Error>
+where
+    R: Acquire,
+    F: FnOnce(&mut R) -> U,
+{
+    let resource = R::acquire()?;
+    Ok(UseScope::new(resource).use_with(|mut resource| f(&mut resource)))
+}
+
+/// A resource that acquires itself asynchronously.
+///
+/// This is the asynchronous counterpart of [`Acquire`], for resources whose construction involves
+/// I/O, such as connection handshakes or authentication. [`acquire_use_async`] acquires, uses and
+/// drops the resource in a single awaited expression; for resources that also implement
+/// [`AsyncClose`], [`acquire_close_async`] closes them explicitly instead.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{acquire_close_async, AcquireAsync, AsyncClose};
+///
+/// struct Session {
+///     user: String,
+/// }
+///
+/// impl AcquireAsync for Session {
+///     type Error = std::io::Error;
+///
+///     async fn acquire_async() -> Result<Self, Self::Error> {
+///         // Connect and authenticate, ...
+///         Ok(Session { user: String::from("admin") })
+///     }
+/// }
+///
+/// impl AsyncClose for Session {
+///     type Error = std::io::Error;
+///
+///     async fn close_async(self) -> Result<(), Self::Error> {
+///         // Log out, ...
+///         Ok(())
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let user = acquire_close_async(|session: &mut Session| {
+///     Box::pin(async move { session.user.clone() })
+/// })
+/// .await?;
+///
+/// assert_eq!(user, "admin");
+/// # Ok(())
+/// # }
+/// ```
+pub trait AcquireAsync: Sized {
+    /// The error returned when acquiring the resource fails.
+    type Error;
+
+    /// Acquires a new instance of the resource asynchronously.
+    fn acquire_async() -> impl Future<Output = Result<Self, Self::Error>> + Send;
+}
+
+/// A resource that checks asynchronously whether it is still usable.
+///
+/// Long-lived resources such as pooled connections can break while they are idle. Pools call
+/// [`validate_async`](ValidateAsync::validate_async) before handing out such a resource, and
+/// discard it if validation fails.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{AcquireAsync, ValidateAsync};
+///
+/// struct Session {
+///     expired: bool,
+/// }
+///
+/// impl AcquireAsync for Session {
+///     type Error = String;
+///
+///     async fn acquire_async() -> Result<Self, Self::Error> {
+///         Ok(Session { expired: false })
+///     }
+/// }
+///
+/// impl ValidateAsync for Session {
+///     async fn validate_async(&mut self) -> Result<(), Self::Error> {
+///         // Send a ping, ...
+///         if self.expired {
+///             return Err(String::from("session expired"));
+///         }
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait ValidateAsync: AcquireAsync {
+    /// Checks asynchronously whether the resource is still usable.
+    fn validate_async(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+/// Acquires a resource of type `R` asynchronously, executes an asynchronous closure on it and
+/// drops it afterwards.
+///
+/// # Parameters
+/// - `f`: A closure that borrows the acquired resource mutably and returns a boxed future.
+///
+/// # Returns
+/// - A future that resolves to `Ok(U)` with the result of the closure `f`, or to `Err(R::Error)`
+///   without running the closure if acquiring the resource failed.
+#[track_caller]
+pub fn acquire_use_async<R, U, F>(f: F) -> impl Future<Output = Result<U, R::Error>> + Send
+where
+    R: AcquireAsync + Send,
+    F: for<'a> FnOnce(&'a mut R) -> BoxFuture<'a, U> + Send,
+    U: Send,
+{
+    let options = ScopeOptions::new();
+    async move {
+        let resource = R::acquire_async().await?;
+        let result = UseScope::with_options(resource, options)
+            .use_with_async(|mut resource| async move { f(&mut resource).await })
+            .await;
+        Ok(result)
+    }
+}
+
+/// Acquires a resource of type `R` asynchronously, executes an asynchronous closure on it and
+/// closes it explicitly afterwards.
+///
+/// The resource is closed as with [`Use::use_close_async`](crate::Use::use_close_async).
+///
+/// # Parameters
+/// - `f`: A closure that borrows the acquired resource mutably and returns a boxed future.
+///
+/// # Returns
+/// - A future that resolves to `Ok(U)` with the result of the closure `f` if the resource was
+///   acquired and closed successfully, or to the error of acquiring or closing the resource.
+#[track_caller]
+pub fn acquire_close_async<R, E, U, F>(f: F) -> impl Future<Output = Result<U, E>> + Send
+where
+    R: AcquireAsync<Error = E> + AsyncClose<Error = E> + Send,
+    F: for<'a> FnOnce(&'a mut R) -> BoxFuture<'a, U> + Send,
+    U: Send,
+{
+    let options = ScopeOptions::new();
+    async move {
+        let resource = R::acquire_async().await?;
+        scoped::use_close_async(resource, options, f).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    thread_local! {
+        static AVAILABLE: Cell<bool> = const { Cell::new(true) };
+    }
+
+    struct Device;
+
+    impl Acquire for Device {
+        type Error = &'static str;
+
+        fn acquire() -> Result<Self, Self::Error> {
+            if AVAILABLE.with(Cell::get) {
+                Ok(Device)
+            } else {
+                Err("device busy")
+            }
+        }
+    }
+
+    #[test]
+    fn test_closure_runs_only_if_acquired() {
+        assert_eq!(acquire_use(|_device: &mut Device| 42), Ok(42));
+
+        AVAILABLE.with(|available| available.set(false));
+        let result = acquire_use(|_device: &mut Device| unreachable!("device is unavailable"));
+        assert_eq!(result, Err::<(), _>("device busy"));
+    }
+
+    struct Connection(Arc<AtomicUsize>);
+
+    impl AcquireAsync for Connection {
+        type Error = &'static str;
+
+        async fn acquire_async() -> Result<Self, Self::Error> {
+            Ok(Connection(Arc::new(AtomicUsize::new(0))))
+        }
+    }
+
+    impl AsyncClose for Connection {
+        type Error = &'static str;
+
+        async fn close_async(self) -> Result<(), Self::Error> {
+            match self.0.load(Ordering::SeqCst) {
+                0 => Ok(()),
+                _ => Err("pending requests"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acquired_resource_is_closed() {
+        let sent = acquire_use_async(|conn: &mut Connection| {
+            Box::pin(async move { conn.0.fetch_add(1, Ordering::SeqCst) + 1 })
+        })
+        .await;
+        assert_eq!(sent, Ok(1));
+
+        let closed = acquire_close_async(|conn: &mut Connection| {
+            Box::pin(async move {
+                conn.0.fetch_add(1, Ordering::SeqCst);
+            })
+        })
+        .await;
+        assert_eq!(closed, Err("pending requests"));
+    }
+}
@@ -0,0 +1,91 @@
+//! [`Close`] implementations for standard library types.
+//!
+//! Only available with the `std-adapters` feature.
+
+use crate::io::Durable;
+use crate::{Close, PanicPayload};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{Shutdown, TcpStream};
+use std::thread::JoinHandle;
+
+/// Flushes the file and syncs its contents to disk via [`File::sync_all`].
+impl Close for File {
+    type Error = io::Error;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        self.sync()
+    }
+}
+
+/// Flushes the buffer into the underlying writer, which is then dropped.
+impl<W: Write> Close for BufWriter<W> {
+    type Error = io::Error;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        self.flush()
+    }
+}
+
+/// Shuts down both directions of the connection.
+///
+/// A connection that the peer has already closed is not an error.
+impl Close for TcpStream {
+    type Error = io::Error;
+
+    fn close(self) -> Result<(), Self::Error> {
+        match self.shutdown(Shutdown::Both) {
+            Err(error) if error.kind() == io::ErrorKind::NotConnected => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Waits for the thread to finish, returning its panic payload if it panicked.
+///
+/// The value returned by the thread is discarded.
+impl<T> Close for JoinHandle<T> {
+    type Error = PanicPayload;
+
+    fn close(self) -> Result<(), Self::Error> {
+        self.join().map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AutoClose, Use};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn close_all(resource: impl AutoClose) -> bool {
+        resource.use_close(|_| ()).is_ok()
+    }
+
+    #[test]
+    fn test_join_handle_waits_for_thread() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::spawn({
+            let finished = Arc::clone(&finished);
+            move || finished.store(true, Ordering::SeqCst)
+        });
+        assert!(close_all(handle));
+        assert!(finished.load(Ordering::SeqCst));
+
+        let panicking = std::thread::spawn(|| panic!("worker failed"));
+        assert!(!close_all(panicking));
+    }
+
+    #[test]
+    fn test_tcp_stream_is_shut_down() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+
+        assert!(close_all(client));
+        let mut buf = [0; 1];
+        assert_eq!(io::Read::read(&mut server, &mut buf).unwrap(), 0);
+    }
+}
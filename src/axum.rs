@@ -0,0 +1,291 @@
+//! Integrations with [`axum`](https://docs.rs/axum).
+//!
+//! Provides [`Scoped`], an extractor that checks a resource out of a deadpool [`Pool`] in the
+//! state of the router for the duration of a handler.
+
+use crate::instrument::ScopeOptions;
+use crate::{BoxFuture, UseScope};
+use ::axum::extract::{FromRef, FromRequestParts};
+use ::axum::http::request::Parts;
+use ::axum::http::StatusCode;
+use ::axum::response::{IntoResponse, Response};
+use ::deadpool::managed::{Manager, Object, Pool, PoolError};
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+
+/// A resource checked out of a deadpool [`Pool`] for the duration of a handler.
+///
+/// The extractor takes the pool from the state of the router through [`FromRef`]. When the
+/// handler finishes, the resource returns to the pool, unless it is poisoned: poisoned resources
+/// are detached from the pool and dropped, so that the pool replaces them. A resource is poisoned
+///
+/// - explicitly through [`poison`](Scoped::poison),
+/// - if the handler panics while holding it, or
+/// - if a scope of [`use_async`](Scoped::use_async) is interrupted, which happens when the client
+///   disconnects and the server drops the handler in the middle of the scope.
+///
+/// A resource that was only accessed through [`Deref`] is returned to the pool even if the client
+/// disconnects, so work that must not be cut short belongs into [`use_async`](Scoped::use_async).
+///
+/// # Examples
+/// ```rust
+/// use axum::routing::get;
+/// use axum::Router;
+/// use deadpool::managed::Pool;
+/// use use_with::axum::Scoped;
+/// use use_with::{AcquireAsync, Acquired};
+///
+/// struct Session(u32);
+///
+/// impl AcquireAsync for Session {
+///     type Error = std::io::Error;
+///
+///     async fn acquire_async() -> Result<Self, Self::Error> {
+///         Ok(Session(42))
+///     }
+/// }
+///
+/// async fn handler(mut session: Scoped<Acquired<Session>>) -> String {
+///     session
+///         .use_async(|session| Box::pin(async move { session.0.to_string() }))
+///         .await
+/// }
+///
+/// let pool: Pool<Acquired<Session>> = Pool::builder(Acquired::new()).build().unwrap();
+/// let app: Router = Router::new().route("/", get(handler)).with_state(pool);
+/// ```
+pub struct Scoped<M: Manager> {
+    object: Option<Object<M>>,
+    poisoned: bool,
+    interrupted: bool,
+}
+
+impl<M: Manager> Scoped<M> {
+    /// Wraps a resource checked out of a pool.
+    pub fn new(object: Object<M>) -> Self {
+        Self {
+            object: Some(object),
+            poisoned: false,
+            interrupted: false,
+        }
+    }
+
+    /// Runs the asynchronous closure `f` on the resource as a use scope.
+    ///
+    /// If the returned future is dropped before it completes, the resource is poisoned.
+    #[track_caller]
+    pub fn use_async<'s, U, F>(&'s mut self, f: F) -> impl Future<Output = U> + Send + 's
+    where
+        F: for<'a> FnOnce(&'a mut M::Type) -> BoxFuture<'a, U> + Send + 's,
+        U: Send + 's,
+    {
+        let options = ScopeOptions::new();
+        async move {
+            self.interrupted = true;
+            let resource: &mut M::Type = self.object.as_mut().expect("the resource is held");
+            let result = UseScope::with_options(resource, options)
+                .use_with_async(f)
+                .await;
+            self.interrupted = false;
+            result
+        }
+    }
+
+    /// Poisons the resource, so that it is not returned to the pool.
+    pub fn poison(&mut self) {
+        self.poisoned = true;
+    }
+
+    /// Returns whether the resource will be discarded instead of returned to the pool.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned || self.interrupted || std::thread::panicking()
+    }
+}
+
+impl<M: Manager> Deref for Scoped<M> {
+    type Target = M::Type;
+
+    fn deref(&self) -> &Self::Target {
+        self.object.as_ref().expect("the resource is held")
+    }
+}
+
+impl<M: Manager> DerefMut for Scoped<M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.object.as_mut().expect("the resource is held")
+    }
+}
+
+impl<M: Manager> fmt::Debug for Scoped<M>
+where
+    M::Type: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scoped")
+            .field("resource", &**self)
+            .field("poisoned", &self.is_poisoned())
+            .finish()
+    }
+}
+
+impl<M: Manager> Drop for Scoped<M> {
+    fn drop(&mut self) {
+        let Some(object) = self.object.take() else {
+            return;
+        };
+        if self.is_poisoned() {
+            drop(Object::take(object));
+        }
+    }
+}
+
+impl<M, S> FromRequestParts<S> for Scoped<M>
+where
+    M: Manager + 'static,
+    Pool<M>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = CheckoutRejection<M::Error>;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let pool = Pool::<M>::from_ref(state);
+        pool.get().await.map(Scoped::new).map_err(CheckoutRejection)
+    }
+}
+
+/// The rejection of [`Scoped`] when no resource could be checked out of the pool.
+///
+/// Responds with `503 Service Unavailable`, without revealing the error.
+pub struct CheckoutRejection<E>(pub PoolError<E>);
+
+impl<E: fmt::Debug> fmt::Debug for CheckoutRejection<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CheckoutRejection").field(&self.0).finish()
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for CheckoutRejection<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "checking out a resource failed: {}", self.0)
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for CheckoutRejection<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl<E> IntoResponse for CheckoutRejection<E> {
+    fn into_response(self) -> Response {
+        (StatusCode::SERVICE_UNAVAILABLE, "resource unavailable").into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AcquireAsync, Acquired};
+    use ::axum::body::Body;
+    use ::axum::http::Request;
+    use ::axum::routing::get;
+    use ::axum::Router;
+    use ::tower::ServiceExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    struct Connection(usize);
+
+    impl AcquireAsync for Connection {
+        type Error = String;
+
+        async fn acquire_async() -> Result<Self, Self::Error> {
+            static CREATED: AtomicUsize = AtomicUsize::new(0);
+            Ok(Connection(CREATED.fetch_add(1, Ordering::SeqCst)))
+        }
+    }
+
+    type ConnectionPool = Pool<Acquired<Connection>>;
+
+    fn pool() -> ConnectionPool {
+        Pool::builder(Acquired::new()).max_size(1).build().unwrap()
+    }
+
+    async fn checkout(pool: &ConnectionPool) -> Scoped<Acquired<Connection>> {
+        let (mut parts, ()) = Request::new(()).into_parts();
+        Scoped::from_request_parts(&mut parts, pool).await.unwrap()
+    }
+
+    #[::tokio::test]
+    async fn test_resource_returns_to_the_pool_after_the_handler() {
+        async fn handler(mut conn: Scoped<Acquired<Connection>>) -> String {
+            conn.use_async(|conn| Box::pin(async move { conn.0.to_string() }))
+                .await
+        }
+
+        let pool = pool();
+        let app = Router::new()
+            .route("/", get(handler))
+            .with_state(pool.clone());
+        for _ in 0..2 {
+            let request = Request::new(Body::empty());
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert_eq!(pool.status().size, 1);
+        assert_eq!(pool.status().available, 1);
+    }
+
+    #[::tokio::test]
+    async fn test_poisoned_resource_is_discarded() {
+        let pool = pool();
+        let mut conn = checkout(&pool).await;
+        conn.poison();
+        drop(conn);
+        assert_eq!(pool.status().size, 0);
+    }
+
+    #[::tokio::test(start_paused = true)]
+    async fn test_interrupted_scope_poisons_the_resource() {
+        let pool = pool();
+        let mut conn = checkout(&pool).await;
+        let first = conn.0;
+        let scope = conn.use_async(|_conn| Box::pin(std::future::pending::<()>()));
+        let disconnected = ::tokio::time::timeout(Duration::from_secs(1), scope).await;
+        assert!(disconnected.is_err());
+        assert!(conn.is_poisoned());
+        drop(conn);
+
+        assert_eq!(pool.status().size, 0);
+        assert_ne!(checkout(&pool).await.0, first);
+    }
+
+    #[::tokio::test]
+    async fn test_failed_checkout_is_rejected() {
+        struct Unreachable;
+
+        impl AcquireAsync for Unreachable {
+            type Error = String;
+
+            async fn acquire_async() -> Result<Self, Self::Error> {
+                Err(String::from("connection refused"))
+            }
+        }
+
+        let pool: Pool<Acquired<Unreachable>> = Pool::builder(Acquired::new()).build().unwrap();
+        let (mut parts, ()) = Request::new(()).into_parts();
+        let Err(rejection) = Scoped::from_request_parts(&mut parts, &pool).await else {
+            panic!("the resource is unreachable");
+        };
+        assert_eq!(
+            rejection.to_string(),
+            "checking out a resource failed: Error occurred while creating a new object: connection refused"
+        );
+        assert_eq!(
+            rejection.into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
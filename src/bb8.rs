@@ -0,0 +1,138 @@
+//! Integrations with [`bb8`](https://docs.rs/bb8).
+//!
+//! Lets resources defined through this crate's traits be pooled by bb8, and bb8's pools serve as
+//! factories of this crate:
+//!
+//! - [`Acquired<R>`](crate::Acquired) is a bb8 [`ManageConnection`] for every resource
+//!   implementing [`ValidateAsync`], so a pool is built without a hand-written manager. bb8
+//!   connects through [`AcquireAsync`](crate::AcquireAsync) and checks idle connections through
+//!   [`ValidateAsync`].
+//! - A bb8 [`Pool`] is an [`AsyncResourceFactory`] of owned [`PooledConnection`]s, so utilities
+//!   taking a factory check resources out of the pool instead of creating them.
+
+use crate::{Acquired, AsyncResourceFactory, ValidateAsync};
+use ::bb8::{ManageConnection, Pool, PooledConnection, RunError};
+use std::fmt;
+use std::future::Future;
+
+/// Connects through [`AcquireAsync`](crate::AcquireAsync) and validates connections through
+/// [`ValidateAsync`].
+///
+/// Connections are never considered broken without validating them, as the traits of this crate
+/// offer no synchronous check.
+///
+/// # Examples
+/// ```rust
+/// use bb8::Pool;
+/// use use_with::{AcquireAsync, Acquired, ValidateAsync};
+///
+/// struct Session(u32);
+///
+/// impl AcquireAsync for Session {
+///     type Error = std::io::Error;
+///
+///     async fn acquire_async() -> Result<Self, Self::Error> {
+///         Ok(Session(42))
+///     }
+/// }
+///
+/// impl ValidateAsync for Session {
+///     async fn validate_async(&mut self) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let pool = Pool::builder()
+///     .max_size(4)
+///     .build(Acquired::<Session>::new())
+///     .await?;
+///
+/// let session = pool.get().await.unwrap();
+/// assert_eq!(session.0, 42);
+/// # Ok(())
+/// # }
+/// ```
+impl<R> ManageConnection for Acquired<R>
+where
+    R: ValidateAsync + Send + 'static,
+    R::Error: fmt::Debug + Send + 'static,
+{
+    type Connection = R;
+    type Error = R::Error;
+
+    fn connect(&self) -> impl Future<Output = Result<R, R::Error>> + Send {
+        R::acquire_async()
+    }
+
+    fn is_valid(&self, connection: &mut R) -> impl Future<Output = Result<(), R::Error>> + Send {
+        connection.validate_async()
+    }
+
+    fn has_broken(&self, _connection: &mut R) -> bool {
+        false
+    }
+}
+
+/// Checks connections out of the pool.
+///
+/// The connections are owned, so they return to the pool when dropped even if the pool itself
+/// has been dropped by then.
+impl<M: ManageConnection> AsyncResourceFactory<PooledConnection<'static, M>> for Pool<M> {
+    type Error = RunError<M::Error>;
+
+    fn create_async(
+        &self,
+    ) -> impl Future<Output = Result<PooledConnection<'static, M>, Self::Error>> + Send {
+        self.get_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AcquireAsync, Use};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    static BROKEN: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Debug)]
+    struct Connection(usize);
+
+    impl AcquireAsync for Connection {
+        type Error = String;
+
+        async fn acquire_async() -> Result<Self, Self::Error> {
+            Ok(Connection(CREATED.fetch_add(1, Ordering::SeqCst)))
+        }
+    }
+
+    impl ValidateAsync for Connection {
+        async fn validate_async(&mut self) -> Result<(), Self::Error> {
+            if BROKEN.swap(false, Ordering::SeqCst) {
+                return Err(format!("connection {} broke", self.0));
+            }
+            Ok(())
+        }
+    }
+
+    #[::tokio::test]
+    async fn test_pooled_connections_are_reused_until_invalid() {
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(Acquired::<Connection>::new())
+            .await
+            .unwrap();
+
+        let first = pool.create_async().await.unwrap().use_with(|conn| conn.0);
+        let second = pool.create_async().await.unwrap().use_with(|conn| conn.0);
+        assert_eq!(first, second);
+
+        BROKEN.store(true, Ordering::SeqCst);
+        let replacement = pool.create_async().await.unwrap().use_with(|conn| conn.0);
+        assert_ne!(replacement, first);
+        assert_eq!(CREATED.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,246 @@
+//! Type-erased resources behind `Box<dyn Trait>`.
+
+use crate::{AsyncClose, BoxFuture, Close, UseScope};
+use alloc::boxed::Box;
+use core::any::Any;
+use core::future::Future;
+
+/// An object-safe counterpart of [`Close`], for closing resources behind trait objects.
+///
+/// [`Close::close`] consumes the resource by value, which trait objects do not support. `DynClose`
+/// closes a boxed resource instead, and is implemented for every type implementing [`Close`].
+/// In turn, `Box<T>` implements [`Close`] for every `T: DynClose`, including trait objects. Make
+/// `DynClose` a supertrait of your own trait to close its trait objects, or store resources of
+/// different types as `Box<dyn DynClose<Error = E>>`.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{Close, DynClose, Use};
+///
+/// trait Storage: DynClose<Error = std::io::Error> {
+///     fn put(&mut self, key: &str);
+/// }
+///
+/// struct Memory(Vec<String>);
+///
+/// impl Storage for Memory {
+///     fn put(&mut self, key: &str) {
+///         self.0.push(key.to_owned());
+///     }
+/// }
+///
+/// impl Close for Memory {
+///     type Error = std::io::Error;
+///
+///     fn close(self) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// let storage: Box<dyn Storage> = Box::new(Memory(Vec::new()));
+/// storage.use_close(|storage| storage.put("key"))?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub trait DynClose {
+    /// The error returned when closing the resource fails.
+    type Error;
+
+    /// Closes the boxed resource, consuming it.
+    fn close_boxed(self: Box<Self>) -> Result<(), Self::Error>;
+}
+
+impl<T: Close> DynClose for T {
+    type Error = T::Error;
+
+    fn close_boxed(self: Box<Self>) -> Result<(), Self::Error> {
+        (*self).close()
+    }
+}
+
+impl<T: ?Sized + DynClose> Close for Box<T> {
+    type Error = T::Error;
+
+    fn close(self) -> Result<(), Self::Error> {
+        self.close_boxed()
+    }
+}
+
+/// An object-safe counterpart of [`AsyncClose`], for closing resources behind trait objects.
+///
+/// This is the asynchronous counterpart of [`DynClose`]. It is implemented for every type
+/// implementing [`AsyncClose`] that is `Send + 'static`, and `Box<T>` implements [`AsyncClose`]
+/// for every `T: DynAsyncClose`.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{AsyncClose, DynAsyncClose, Use};
+///
+/// struct Session;
+///
+/// impl AsyncClose for Session {
+///     type Error = std::io::Error;
+///
+///     async fn close_async(self) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let sessions: Vec<Box<dyn DynAsyncClose<Error = std::io::Error> + Send>> =
+///     vec![Box::new(Session), Box::new(Session)];
+///
+/// for session in sessions {
+///     session.use_close_async(|_session| Box::pin(async {})).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub trait DynAsyncClose {
+    /// The error returned when closing the resource fails.
+    type Error;
+
+    /// Closes the boxed resource asynchronously, consuming it.
+    fn close_boxed_async(self: Box<Self>) -> BoxFuture<'static, Result<(), Self::Error>>;
+}
+
+impl<T: AsyncClose + Send + 'static> DynAsyncClose for T {
+    type Error = T::Error;
+
+    fn close_boxed_async(self: Box<Self>) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin((*self).close_async())
+    }
+}
+
+impl<T: ?Sized + DynAsyncClose> AsyncClose for Box<T> {
+    type Error = T::Error;
+
+    fn close_async(self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        self.close_boxed_async()
+    }
+}
+
+/// Runs closures on the contents of boxes, which may be trait objects.
+///
+/// [`Use::use_with`](crate::Use::use_with) passes a `Box<dyn Trait>` on as it is.
+/// [`use_boxed`](Self::use_boxed) lends the contents as `&mut dyn Trait` instead, so that
+/// closures and helper functions can be written against the trait object directly, and drops the
+/// box afterwards.
+///
+/// # Examples
+/// ```rust
+/// use std::io::Write;
+/// use use_with::BoxUseExt;
+///
+/// let sink: Box<dyn Write> = Box::new(Vec::new());
+/// let written = sink.use_boxed(|sink| sink.write(b"hello"))?;
+///
+/// assert_eq!(written, 5);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub trait BoxUseExt<T: ?Sized> {
+    /// Executes a closure on the contents of the box, dropping the box afterwards.
+    fn use_boxed<U, F: FnOnce(&mut T) -> U>(self, f: F) -> U;
+}
+
+impl<T: ?Sized> BoxUseExt<T> for Box<T> {
+    #[track_caller]
+    fn use_boxed<U, F: FnOnce(&mut T) -> U>(self, f: F) -> U {
+        UseScope::new(self).use_with(|mut boxed| f(&mut boxed))
+    }
+}
+
+/// Runs closures on type-erased resources of a known type.
+///
+/// Plugin systems and registries commonly store resources as `Box<dyn Any>`.
+/// [`use_as`](Self::use_as) downcasts such a box, lends the resource to the closure and drops it
+/// afterwards. If the resource is of a different type, the box is returned unchanged so it can be
+/// tried as another type or put back.
+///
+/// # Examples
+/// ```rust
+/// use std::any::Any;
+/// use use_with::AnyUseExt;
+///
+/// let resource: Box<dyn Any> = Box::new(vec![1, 2, 3]);
+///
+/// let resource = resource.use_as::<String, _>(|text| text.len()).unwrap_err();
+/// let sum = resource.use_as::<Vec<i32>, _>(|numbers| numbers.iter().sum::<i32>());
+/// assert_eq!(sum.ok(), Some(6));
+/// ```
+pub trait AnyUseExt: Sized {
+    /// Downcasts the resource to `T` and executes a closure on it, dropping it afterwards.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(Self)` with the original box, without running the closure, if the resource is not a `T`.
+    fn use_as<T: Any, U>(self, f: impl FnOnce(&mut T) -> U) -> Result<U, Self>;
+}
+
+impl AnyUseExt for Box<dyn Any> {
+    #[track_caller]
+    fn use_as<T: Any, U>(self, f: impl FnOnce(&mut T) -> U) -> Result<U, Self> {
+        let resource = self.downcast::<T>()?;
+        Ok(UseScope::new(resource).use_with(|mut resource| f(&mut resource)))
+    }
+}
+
+impl AnyUseExt for Box<dyn Any + Send> {
+    #[track_caller]
+    fn use_as<T: Any, U>(self, f: impl FnOnce(&mut T) -> U) -> Result<U, Self> {
+        let resource = self.downcast::<T>()?;
+        Ok(UseScope::new(resource).use_with(|mut resource| f(&mut resource)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{DropCounter, DropProbe};
+    use crate::Use;
+    use std::cell::RefCell;
+    use std::io;
+
+    struct Recorded<'a>(&'a str, &'a RefCell<Vec<&'a str>>);
+
+    impl Close for Recorded<'_> {
+        type Error = io::Error;
+
+        fn close(self) -> Result<(), Self::Error> {
+            self.1.borrow_mut().push(self.0);
+            match self.0 {
+                "failing" => Err(io::Error::other("close failed")),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_heterogeneous_resources_are_closed() {
+        let closed = RefCell::new(Vec::new());
+        let resources: Vec<Box<dyn DynClose<Error = io::Error>>> = vec![
+            Box::new(Recorded("first", &closed)),
+            Box::new(Box::new(Recorded("nested", &closed))),
+            Box::new(Recorded("failing", &closed)),
+        ];
+
+        let results: Vec<_> = resources
+            .into_iter()
+            .map(|resource| resource.use_close(|_resource| ()).is_ok())
+            .collect();
+        assert_eq!(results, [true, true, false]);
+        assert_eq!(*closed.borrow(), ["first", "nested", "failing"]);
+    }
+
+    #[test]
+    fn test_use_as_returns_mismatched_box() {
+        let counter = DropCounter::new();
+        let resource: Box<dyn Any + Send> = Box::new(counter.probe());
+
+        let resource = resource.use_as::<String, _>(|_| ()).unwrap_err();
+        assert_eq!(counter.count(), 0);
+
+        assert!(resource.use_as::<DropProbe, _>(|_probe| ()).is_ok());
+        assert_eq!(counter.count(), 1);
+    }
+}
@@ -0,0 +1,507 @@
+//! Resources that are cached for reuse and retired on expiry or eviction.
+
+use crate::{Close, ResourceFactory, UseScope};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// A lazily built resource that is reused until its time to live expires.
+///
+/// The resource is created from its factory on first use and served to subsequent use scopes
+/// until it is older than the time to live or [invalidated](Self::invalidate). The expired
+/// resource is then retired and a fresh one is created. This suits resources that are costly to
+/// build but go stale, such as access tokens, TLS configurations or schema metadata.
+///
+/// Caches created with [`new`](Self::new) close retired resources, reporting failures to close
+/// them to the [observers](crate::observer) like any [`use_close`](crate::Use::use_close) scope.
+/// Caches of resources that do not implement [`Close`] are created with
+/// [`dropping`](Self::dropping) and drop them instead. A custom teardown, e.g. one that handles
+/// the errors of closing, is set with [`retire_with`](Self::retire_with). A resource whose use
+/// scope panicked is retired as well, rather than cached again.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use std::time::Duration;
+/// use use_with::Cached;
+///
+/// let issued = Cell::new(0);
+/// let mut token = Cached::dropping(
+///     || {
+///         issued.set(issued.get() + 1);
+///         Ok::<_, std::io::Error>(format!("token-{}", issued.get()))
+///     },
+///     Duration::from_secs(300),
+/// );
+///
+/// assert_eq!(token.use_cached(|token| token.clone())?, "token-1");
+/// assert_eq!(token.use_cached(|token| token.clone())?, "token-1");
+///
+/// token.invalidate();
+/// assert_eq!(token.use_cached(|token| token.clone())?, "token-2");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Cached<T, C> {
+    factory: C,
+    entry: Option<(T, Instant)>,
+    ttl: Duration,
+    retire: Retire<T>,
+}
+
+/// Tears down a resource that is no longer cached.
+enum Retire<T> {
+    Function(fn(T)),
+    Custom(Box<dyn FnMut(T) + Send + Sync>),
+}
+
+impl<T> Retire<T> {
+    fn retire(&mut self, resource: T) {
+        match self {
+            Retire::Function(retire) => retire(resource),
+            Retire::Custom(retire) => retire(resource),
+        }
+    }
+}
+
+/// Closes a retired resource in a use scope, which reports a failure to the observers.
+#[track_caller]
+fn close_retired<T: Close>(resource: T) {
+    let _ = UseScope::new(resource).use_close(|_| ());
+}
+
+impl<T: Close, C> Cached<T, C> {
+    /// Wraps a factory whose resources are reused for `ttl` after their creation and closed
+    /// once they are retired.
+    pub fn new(factory: C, ttl: Duration) -> Self {
+        let mut cached = Self::dropping(factory, ttl);
+        cached.retire = Retire::Function(close_retired);
+        cached
+    }
+}
+
+impl<T, C> Cached<T, C> {
+    /// Wraps a factory whose resources are reused for `ttl` after their creation and dropped
+    /// once they are retired.
+    pub fn dropping(factory: C, ttl: Duration) -> Self {
+        Self {
+            factory,
+            entry: None,
+            ttl,
+            retire: Retire::Function(drop),
+        }
+    }
+
+    /// Sets the function that tears down expired, invalidated or panicked resources.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    /// use use_with::{Cached, Close};
+    ///
+    /// struct Session;
+    ///
+    /// impl Close for Session {
+    ///     type Error = std::io::Error;
+    ///
+    ///     fn close(self) -> Result<(), Self::Error> {
+    ///         Err(std::io::Error::other("session already expired"))
+    ///     }
+    /// }
+    ///
+    /// let failures = Arc::new(Mutex::new(Vec::new()));
+    /// let mut session = Cached::new(|| Ok::<_, std::io::Error>(Session), Duration::from_secs(60))
+    ///     .retire_with({
+    ///         let failures = Arc::clone(&failures);
+    ///         move |session: Session| {
+    ///             if let Err(error) = session.close() {
+    ///                 failures.lock().unwrap().push(error.to_string());
+    ///             }
+    ///         }
+    ///     });
+    ///
+    /// session.use_cached(|_session| ())?;
+    /// session.invalidate();
+    /// assert_eq!(*failures.lock().unwrap(), ["session already expired"]);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn retire_with(mut self, retire: impl FnMut(T) + Send + Sync + 'static) -> Self {
+        self.retire = Retire::Custom(Box::new(retire));
+        self
+    }
+
+    /// Returns whether a resource is cached and has not expired yet.
+    pub fn is_cached(&self) -> bool {
+        self.entry
+            .as_ref()
+            .is_some_and(|(_, created)| created.elapsed() < self.ttl)
+    }
+
+    /// Retires the cached resource, if any, so that the next use scope creates a fresh one.
+    pub fn invalidate(&mut self) {
+        if let Some((resource, _)) = self.entry.take() {
+            self.retire.retire(resource);
+        }
+    }
+}
+
+impl<T, C: ResourceFactory<T>> Cached<T, C> {
+    /// Executes a closure on the cached resource, creating a fresh one if none is cached or the
+    /// cached one has expired.
+    ///
+    /// If the closure panics, the resource is retired instead of being cached again.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(C::Error)` without running the closure if creating the resource failed.
+    #[track_caller]
+    pub fn use_cached<U, F>(&mut self, f: F) -> Result<U, C::Error>
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        if !self.is_cached() {
+            self.invalidate();
+        }
+        let (resource, created) = match self.entry.take() {
+            Some(entry) => entry,
+            None => (self.factory.create()?, Instant::now()),
+        };
+        let retire = &mut self.retire;
+        let (result, resource) =
+            UseScope::new(resource).use_and_return_or_retire(f, |resource| retire.retire(resource));
+        self.entry = Some((resource, created));
+        Ok(result)
+    }
+}
+
+impl<T, C> Drop for Cached<T, C> {
+    fn drop(&mut self) {
+        self.invalidate();
+    }
+}
+
+impl<T: fmt::Debug, C> fmt::Debug for Cached<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cached")
+            .field(
+                "resource",
+                &self.entry.as_ref().map(|(resource, _)| resource),
+            )
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A cache of resources per key, built once per key and evicted in least recently used order.
+///
+/// Resources such as connections per tenant or per host are created from the factory on first
+/// use of their key and reused afterwards. Once the cache holds `capacity` resources, using a new
+/// key evicts the resource used least recently.
+///
+/// Caches created with [`new`](Self::new) close evicted resources, reporting failures to close
+/// them to the [observers](crate::observer); caches created with [`dropping`](Self::dropping)
+/// drop them. A custom teardown is set with [`retire_with`](Self::retire_with). A resource whose
+/// use scope panicked is retired as well, rather than cached again.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{Close, KeyedCache};
+///
+/// struct Connection {
+///     host: String,
+/// }
+///
+/// impl Close for Connection {
+///     type Error = std::io::Error;
+///
+///     fn close(self) -> Result<(), Self::Error> {
+///         println!("closing connection to {}", self.host);
+///         Ok(())
+///     }
+/// }
+///
+/// let mut connections = KeyedCache::new(2, |host: &&str| {
+///     Ok::<_, std::io::Error>(Connection { host: host.to_string() })
+/// });
+///
+/// connections.use_for("alpha", |conn| assert_eq!(conn.host, "alpha"))?;
+/// connections.use_for("beta", |conn| assert_eq!(conn.host, "beta"))?;
+/// connections.use_for("alpha", |_conn| ())?;
+///
+/// // Closes the connection to `beta`, which was used least recently.
+/// connections.use_for("gamma", |_conn| ())?;
+/// assert!(connections.contains_key(&"alpha"));
+/// assert!(!connections.contains_key(&"beta"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct KeyedCache<K, T, C> {
+    factory: C,
+    entries: HashMap<K, (T, u64)>,
+    capacity: usize,
+    clock: u64,
+    retire: Retire<T>,
+}
+
+impl<K, T: Close, C> KeyedCache<K, T, C> {
+    /// Creates an empty cache holding up to `capacity` resources created by `factory`, which
+    /// are closed once they are retired.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize, factory: C) -> Self {
+        let mut cache = Self::dropping(capacity, factory);
+        cache.retire = Retire::Function(close_retired);
+        cache
+    }
+}
+
+impl<K, T, C> KeyedCache<K, T, C> {
+    /// Creates an empty cache holding up to `capacity` resources created by `factory`, which
+    /// are dropped once they are retired.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn dropping(capacity: usize, factory: C) -> Self {
+        assert!(
+            capacity > 0,
+            "the capacity of a keyed cache must not be zero"
+        );
+        Self {
+            factory,
+            entries: HashMap::new(),
+            capacity,
+            clock: 0,
+            retire: Retire::Function(drop),
+        }
+    }
+
+    /// Sets the function that tears down evicted, invalidated or panicked resources.
+    pub fn retire_with(mut self, retire: impl FnMut(T) + Send + Sync + 'static) -> Self {
+        self.retire = Retire::Custom(Box::new(retire));
+        self
+    }
+
+    /// Returns the number of cached resources.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no resources are cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Retires all cached resources.
+    pub fn clear(&mut self) {
+        for (_, (resource, _)) in self.entries.drain() {
+            self.retire.retire(resource);
+        }
+    }
+}
+
+impl<K: Eq + Hash, T, C> KeyedCache<K, T, C> {
+    /// Returns whether a resource is cached for `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Retires the resource cached for `key`, if any, returning whether one was cached.
+    pub fn invalidate(&mut self, key: &K) -> bool {
+        match self.entries.remove(key) {
+            Some((resource, _)) => {
+                self.retire.retire(resource);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Executes a closure on the resource cached for `key`, creating it first if necessary.
+    ///
+    /// If the cache is full, creating a resource retires the one used least recently. If the
+    /// closure panics, the resource is retired instead of being cached again.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(E)` without running the closure if creating the resource failed.
+    #[track_caller]
+    pub fn use_for<U, E, F>(&mut self, key: K, f: F) -> Result<U, E>
+    where
+        K: Clone,
+        C: Fn(&K) -> Result<T, E>,
+        F: FnOnce(&mut T) -> U,
+    {
+        self.clock += 1;
+        let resource = match self.entries.remove(&key) {
+            Some((resource, _)) => resource,
+            None => {
+                let resource = (self.factory)(&key)?;
+                if self.entries.len() >= self.capacity {
+                    self.evict_least_recently_used();
+                }
+                resource
+            }
+        };
+        let retire = &mut self.retire;
+        let (result, resource) =
+            UseScope::new(resource).use_and_return_or_retire(f, |resource| retire.retire(resource));
+        self.entries.insert(key, (resource, self.clock));
+        Ok(result)
+    }
+
+    /// Retires the resource that was used least recently.
+    fn evict_least_recently_used(&mut self)
+    where
+        K: Clone,
+    {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            self.invalidate(&oldest);
+        }
+    }
+}
+
+impl<K, T, C> Drop for KeyedCache<K, T, C> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<K: fmt::Debug, T: fmt::Debug, C> fmt::Debug for KeyedCache<K, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedCache")
+            .field("capacity", &self.capacity)
+            .field(
+                "resources",
+                &self
+                    .entries
+                    .iter()
+                    .map(|(key, (resource, _))| (key, resource))
+                    .collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{DropCounter, DropSpy};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RETIRED: AtomicUsize = AtomicUsize::new(0);
+
+    #[cfg(use_with_loom)]
+    #[test]
+    fn loom_shared_cache_swaps_resources_under_load() {
+        use crate::sync::{thread, Arc, Mutex};
+
+        loom::model(|| {
+            let counter = DropCounter::new();
+            let created = Arc::new(crate::sync::AtomicUsize::new(0));
+            let cache = {
+                let (counter, created) = (counter.clone(), Arc::clone(&created));
+                Arc::new(Mutex::new(KeyedCache::dropping(1, move |_key: &u8| {
+                    created.fetch_add(1, crate::sync::Ordering::SeqCst);
+                    Ok::<_, ()>(counter.probe())
+                })))
+            };
+
+            // With a capacity of one, each key swaps out the resource of the other.
+            let other = {
+                let cache = Arc::clone(&cache);
+                thread::spawn(move || cache.lock().unwrap().use_for(2, |_probe| ()))
+            };
+            assert_eq!(cache.lock().unwrap().use_for(1, |_probe| ()), Ok(()));
+            assert_eq!(cache.lock().unwrap().use_for(2, |_probe| ()), Ok(()));
+            assert_eq!(other.join().unwrap(), Ok(()));
+
+            let cache = Arc::try_unwrap(cache)
+                .unwrap_or_else(|_| unreachable!("the thread has finished"))
+                .into_inner()
+                .unwrap();
+            let created = created.load(crate::sync::Ordering::SeqCst);
+            assert_eq!(cache.len(), 1);
+            assert_eq!(counter.count() + 1, created);
+            drop(cache);
+            assert_eq!(counter.count(), created);
+        });
+    }
+
+    #[test]
+    fn test_expired_resources_are_retired() {
+        let counter = DropCounter::new();
+        let mut cached = Cached::dropping(|| Ok::<_, ()>(counter.probe()), Duration::ZERO)
+            .retire_with(|probe| {
+                RETIRED.fetch_add(1, Ordering::SeqCst);
+                drop(probe);
+            });
+
+        assert_eq!(cached.use_cached(|_probe| ()), Ok(()));
+        assert!(!cached.is_cached());
+        assert_eq!(cached.use_cached(|_probe| ()), Ok(()));
+        assert_eq!(RETIRED.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.count(), 1);
+
+        drop(cached);
+        assert_eq!(RETIRED.load(Ordering::SeqCst), 2);
+        assert_eq!(counter.count(), 2);
+    }
+
+    #[test]
+    fn test_least_recently_used_resource_is_evicted() {
+        let spy = DropSpy::new();
+        let mut cache = KeyedCache::dropping(2, |key: &&'static str| Ok::<_, ()>(spy.probe(*key)));
+
+        for key in ["a", "b", "a", "c", "a"] {
+            assert_eq!(cache.use_for(key, |_probe| ()), Ok(()));
+        }
+        assert_eq!(cache.len(), 2);
+        assert_eq!(spy.drop_count("b"), 1);
+        assert!(cache.contains_key(&"a") && cache.contains_key(&"c"));
+
+        drop(cache);
+        assert_eq!(spy.drop_count("a"), 1);
+        assert_eq!(spy.drop_count("c"), 1);
+    }
+
+    #[test]
+    fn test_retired_and_panicked_resources_are_closed() {
+        static CLOSED: AtomicUsize = AtomicUsize::new(0);
+        struct Resource;
+
+        impl Close for Resource {
+            type Error = ();
+
+            fn close(self) -> Result<(), Self::Error> {
+                CLOSED.fetch_add(1, Ordering::SeqCst);
+                Err(())
+            }
+        }
+
+        let mut cache = KeyedCache::new(1, |_key: &u8| Ok::<_, ()>(Resource));
+        assert_eq!(cache.use_for(1, |_resource| ()), Ok(()));
+        assert_eq!(cache.use_for(2, |_resource| ()), Ok(()));
+        assert_eq!(CLOSED.load(Ordering::SeqCst), 1);
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cache.use_for(2, |_resource| panic!("body panicked"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(CLOSED.load(Ordering::SeqCst), 2);
+        assert!(cache.is_empty());
+
+        let mut cached = Cached::new(|| Ok::<_, ()>(Resource), Duration::from_secs(60));
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cached.use_cached(|_resource| panic!("body panicked"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(CLOSED.load(Ordering::SeqCst), 3);
+        assert!(!cached.is_cached());
+    }
+}
@@ -0,0 +1,194 @@
+//! Scoped borrows of `RefCell`s.
+
+use crate::UseScope;
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+
+/// The error returned when a [`RefCell`] cannot be borrowed because of a conflicting borrow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowConflict {
+    resource_type: &'static str,
+    mutable: bool,
+}
+
+impl BorrowConflict {
+    fn of<T: ?Sized>(mutable: bool) -> Self {
+        Self {
+            resource_type: std::any::type_name::<T>(),
+            mutable,
+        }
+    }
+
+    /// Returns the type name of the cell that could not be borrowed.
+    pub fn resource_type(&self) -> &'static str {
+        self.resource_type
+    }
+
+    /// Returns whether a mutable borrow was requested.
+    pub fn is_mutable(&self) -> bool {
+        self.mutable
+    }
+}
+
+impl fmt::Display for BorrowConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.mutable {
+            write!(f, "`{}` is already borrowed", self.resource_type)
+        } else {
+            write!(f, "`{}` is already mutably borrowed", self.resource_type)
+        }
+    }
+}
+
+impl std::error::Error for BorrowConflict {}
+
+/// Runs closures on borrows of a [`RefCell`] as use scopes.
+///
+/// The closures receive a reference to the contents rather than the [`Ref`] or [`RefMut`] guard,
+/// so a borrow cannot outlive the closure. This rules out the long-lived borrows that make
+/// unrelated code panic with a `BorrowMutError` far away from where the borrow was taken.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::RefCell;
+/// use use_with::RefCellUseExt;
+///
+/// let log = RefCell::new(Vec::new());
+///
+/// log.use_borrow_mut(|log| log.push("started"));
+///
+/// let conflict = log.use_borrow(|_entries| log.try_use_borrow_mut(|log| log.clear()));
+/// assert!(conflict.unwrap_err().is_mutable());
+///
+/// assert_eq!(log.use_borrow(|log| log.len()), 1);
+/// ```
+pub trait RefCellUseExt<T: ?Sized> {
+    /// Borrows the contents immutably for the duration of the closure `f`.
+    ///
+    /// # Panics
+    /// Panics if the contents are currently mutably borrowed.
+    fn use_borrow<U, F>(&self, f: F) -> U
+    where
+        F: FnOnce(&T) -> U;
+
+    /// Borrows the contents mutably for the duration of the closure `f`.
+    ///
+    /// # Panics
+    /// Panics if the contents are currently borrowed.
+    fn use_borrow_mut<U, F>(&self, f: F) -> U
+    where
+        F: FnOnce(&mut T) -> U;
+
+    /// Borrows the contents immutably for the duration of the closure `f`, unless they are
+    /// currently mutably borrowed.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(BorrowConflict)` without running the closure if the contents are mutably borrowed.
+    fn try_use_borrow<U, F>(&self, f: F) -> Result<U, BorrowConflict>
+    where
+        F: FnOnce(&T) -> U;
+
+    /// Borrows the contents mutably for the duration of the closure `f`, unless they are
+    /// currently borrowed.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(BorrowConflict)` without running the closure if the contents are borrowed.
+    fn try_use_borrow_mut<U, F>(&self, f: F) -> Result<U, BorrowConflict>
+    where
+        F: FnOnce(&mut T) -> U;
+}
+
+impl<T: ?Sized> RefCellUseExt<T> for RefCell<T> {
+    #[track_caller]
+    fn use_borrow<U, F>(&self, f: F) -> U
+    where
+        F: FnOnce(&T) -> U,
+    {
+        match self.try_borrow() {
+            Ok(borrow) => borrow_scope(borrow, f),
+            Err(_) => panic!("{}", BorrowConflict::of::<RefCell<T>>(false)),
+        }
+    }
+
+    #[track_caller]
+    fn use_borrow_mut<U, F>(&self, f: F) -> U
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        match self.try_borrow_mut() {
+            Ok(borrow) => borrow_mut_scope(borrow, f),
+            Err(_) => panic!("{}", BorrowConflict::of::<RefCell<T>>(true)),
+        }
+    }
+
+    #[track_caller]
+    fn try_use_borrow<U, F>(&self, f: F) -> Result<U, BorrowConflict>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        match self.try_borrow() {
+            Ok(borrow) => Ok(borrow_scope(borrow, f)),
+            Err(_) => Err(BorrowConflict::of::<RefCell<T>>(false)),
+        }
+    }
+
+    #[track_caller]
+    fn try_use_borrow_mut<U, F>(&self, f: F) -> Result<U, BorrowConflict>
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        match self.try_borrow_mut() {
+            Ok(borrow) => Ok(borrow_mut_scope(borrow, f)),
+            Err(_) => Err(BorrowConflict::of::<RefCell<T>>(true)),
+        }
+    }
+}
+
+#[track_caller]
+fn borrow_scope<T: ?Sized, U>(borrow: Ref<'_, T>, f: impl FnOnce(&T) -> U) -> U {
+    UseScope::new(borrow).use_with(|borrow| f(&borrow))
+}
+
+#[track_caller]
+fn borrow_mut_scope<T: ?Sized, U>(borrow: RefMut<'_, T>, f: impl FnOnce(&mut T) -> U) -> U {
+    UseScope::new(borrow).use_with(|mut borrow| f(&mut borrow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_borrows_end_with_the_closure() {
+        let cell = RefCell::new(vec![1]);
+        cell.use_borrow_mut(|data| data.push(2));
+        assert_eq!(cell.use_borrow(|data| data.len()), 2);
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    fn test_conflicting_borrows() {
+        let cell = RefCell::new(0);
+
+        let shared = cell.use_borrow(|_value| cell.try_use_borrow(|value| *value));
+        assert_eq!(shared, Ok(0));
+
+        let conflict = cell
+            .use_borrow_mut(|_value| cell.try_use_borrow(|value| *value))
+            .unwrap_err();
+        assert!(!conflict.is_mutable());
+        assert!(conflict.resource_type().contains("RefCell<i32>"));
+        assert!(conflict
+            .to_string()
+            .ends_with("is already mutably borrowed"));
+    }
+
+    #[test]
+    #[should_panic(expected = "is already borrowed")]
+    fn test_use_borrow_mut_panics_on_conflict() {
+        let cell = RefCell::new(0);
+        cell.use_borrow(|_value| cell.use_borrow_mut(|value| *value += 1));
+    }
+}
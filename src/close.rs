@@ -0,0 +1,80 @@
+//! Explicit, fallible teardown of resources.
+
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+
+/// A boxed future borrowing from its environment for `'a`.
+///
+/// Used as the return type of bodies that borrow the resource of an asynchronous use scope,
+/// such as in [`Use::use_close_async`](crate::Use::use_close_async).
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A resource that requires an explicit, fallible teardown step.
+///
+/// Rust's [`Drop`] cannot report errors, which is why flushing a file or shutting down a
+/// connection on drop silently discards failures. Types implementing `Close` expose their
+/// teardown as a consuming method instead, so that the [`Use::use_close`](crate::Use::use_close)
+/// combinator can run it after the body and hand the outcome back to the caller.
+///
+/// # Examples
+/// ```rust
+/// use use_with::Close;
+///
+/// struct Connection;
+///
+/// impl Close for Connection {
+///     type Error = std::io::Error;
+///
+///     fn close(self) -> Result<(), Self::Error> {
+///         // Say goodbye to the peer, flush buffers, ...
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait Close {
+    /// The error returned when closing the resource fails.
+    type Error;
+
+    /// Closes the resource, consuming it.
+    fn close(self) -> Result<(), Self::Error>;
+}
+
+/// A resource that requires an explicit, fallible and asynchronous teardown step.
+///
+/// This is the asynchronous counterpart of [`Close`], used by
+/// [`Use::use_close_async`](crate::Use::use_close_async) for resources whose teardown
+/// involves I/O, such as closing handshakes of network protocols.
+///
+/// # Examples
+/// ```rust
+/// use use_with::AsyncClose;
+///
+/// struct Session;
+///
+/// impl AsyncClose for Session {
+///     type Error = std::io::Error;
+///
+///     async fn close_async(self) -> Result<(), Self::Error> {
+///         // Send a goodbye message and wait for the acknowledgement, ...
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait AsyncClose {
+    /// The error returned when closing the resource fails.
+    type Error;
+
+    /// Closes the resource asynchronously, consuming it.
+    fn close_async(self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+/// A resource that is closed explicitly at the end of its use scope, like Kotlin's `Closeable`.
+///
+/// This is an alias of [`Close`], implemented for every type implementing it. With the
+/// `std-adapters` feature, [`File`](std::fs::File), [`BufWriter`](std::io::BufWriter),
+/// [`TcpStream`](std::net::TcpStream) and [`JoinHandle`](std::thread::JoinHandle) implement
+/// [`Close`], so that [`Use::use_close`](crate::Use::use_close) works on them out of the box.
+pub trait AutoClose: Close {}
+
+impl<T: Close> AutoClose for T {}
@@ -0,0 +1,184 @@
+//! Context managers that observe how their scope ended, and ad-hoc setup and teardown pairs.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+/// How the body of a use scope ended, as reported to [`Exit::exit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The body returned `Ok`.
+    Success,
+    /// The body returned `Err`.
+    Error,
+    /// The body panicked.
+    Panic,
+}
+
+impl Outcome {
+    /// Returns whether the body returned `Ok`.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Outcome::Success)
+    }
+}
+
+/// A resource that is entered before its scope and exited afterwards, like a Python context
+/// manager.
+///
+/// Entering turns the resource into a guard that is lent to the body of
+/// [`Use::use_context`](crate::Use::use_context). Once the body is done, the guard is exited with
+/// the [`Outcome`] of the body, so that e.g. a transaction can commit on success and roll back
+/// otherwise, or a progress bar can render a final state.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{Enter, Exit, Outcome, Use};
+///
+/// struct Database {
+///     rows: Vec<&'static str>,
+/// }
+///
+/// struct Transaction<'a> {
+///     database: &'a mut Database,
+///     pending: Vec<&'static str>,
+/// }
+///
+/// impl<'a> Enter for &'a mut Database {
+///     type Guard = Transaction<'a>;
+///
+///     fn enter(self) -> Self::Guard {
+///         Transaction { database: self, pending: Vec::new() }
+///     }
+/// }
+///
+/// impl Exit for Transaction<'_> {
+///     fn exit(self, outcome: &Outcome) {
+///         if outcome.is_success() {
+///             self.database.rows.extend(self.pending);
+///         }
+///     }
+/// }
+///
+/// let mut database = Database { rows: Vec::new() };
+///
+/// let committed = (&mut database).use_context(|tx| {
+///     tx.pending.push("alice");
+///     Ok::<_, String>(tx.pending.len())
+/// });
+/// assert_eq!(committed, Ok(1));
+///
+/// let rolled_back = (&mut database).use_context(|tx| {
+///     tx.pending.push("bob");
+///     Err::<(), _>(String::from("constraint violated"))
+/// });
+/// assert!(rolled_back.is_err());
+/// assert_eq!(database.rows, ["alice"]);
+/// ```
+pub trait Enter {
+    /// The guard lent to the body, which is exited afterwards.
+    type Guard: Exit;
+
+    /// Enters the scope, turning the resource into its guard.
+    fn enter(self) -> Self::Guard;
+}
+
+/// The guard of an [`Enter`] resource, which is told how its scope ended.
+pub trait Exit {
+    /// Exits the scope, consuming the guard.
+    fn exit(self, outcome: &Outcome);
+}
+
+/// Builds a resource from a pair of setup and teardown closures.
+///
+/// `enter` runs immediately and produces the state of the resource, which the returned
+/// [`Context`] dereferences to. `exit` receives the state when the context is dropped, e.g. at the
+/// end of a [`use_with`](crate::Use::use_with) scope. This pairs one-off setup and teardown code
+/// without defining a type with a [`Drop`] implementation.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::RefCell;
+/// use use_with::{context, using, Use};
+///
+/// let log = RefCell::new(Vec::new());
+///
+/// let indented = context(
+///     || {
+///         log.borrow_mut().push("begin");
+///         2
+///     },
+///     |_indent| log.borrow_mut().push("end"),
+/// )
+/// .use_with(|indent| {
+///     log.borrow_mut().push("body");
+///     *indent * 2
+/// });
+/// assert_eq!(indented, 4);
+///
+/// using!(context(|| (), |()| log.borrow_mut().push("done")), _ctx -> {});
+/// assert_eq!(*log.borrow(), ["begin", "body", "end", "done"]);
+/// ```
+pub fn context<S, N, X>(enter: N, exit: X) -> Context<S, X>
+where
+    N: FnOnce() -> S,
+    X: FnOnce(S),
+{
+    Context {
+        state: Some(enter()),
+        exit: Some(exit),
+    }
+}
+
+/// A resource built from setup and teardown closures by [`context`].
+pub struct Context<S, X: FnOnce(S)> {
+    state: Option<S>,
+    exit: Option<X>,
+}
+
+impl<S, X: FnOnce(S)> Deref for Context<S, X> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        self.state.as_ref().expect("state is present until dropped")
+    }
+}
+
+impl<S, X: FnOnce(S)> DerefMut for Context<S, X> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.state.as_mut().expect("state is present until dropped")
+    }
+}
+
+impl<S: fmt::Debug, X: FnOnce(S)> fmt::Debug for Context<S, X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Context").field(&self.state).finish()
+    }
+}
+
+impl<S, X: FnOnce(S)> Drop for Context<S, X> {
+    fn drop(&mut self) {
+        if let (Some(state), Some(exit)) = (self.state.take(), self.exit.take()) {
+            exit(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropSpy;
+    use crate::Use;
+
+    #[test]
+    fn test_exit_receives_state_after_scope() {
+        let spy = DropSpy::new();
+        context(|| spy.probe("state"), drop).use_with(|ctx| {
+            assert_eq!(spy.drop_count("state"), 0);
+            drop(ctx);
+            assert_eq!(spy.drop_count("state"), 1);
+        });
+
+        let mut exited = None;
+        context(|| 1, |state| exited = Some(state)).use_with(|mut ctx| *ctx += 1);
+        assert_eq!(exited, Some(2));
+    }
+}
@@ -0,0 +1,73 @@
+//! Scoped use of resources that are either borrowed or owned.
+
+use crate::UseScope;
+use alloc::borrow::{Cow, ToOwned};
+use core::borrow::Borrow;
+
+/// Runs closures on [`Cow`] resources, regardless of whether they are borrowed or owned.
+///
+/// Caching layers often hand out a borrowed resource on a hit and an owned one on a miss.
+/// [`use_cow`](Self::use_cow) lends either to the closure as a plain reference: a borrowed
+/// resource is left untouched for its owner, while an owned resource is dropped once the closure
+/// returns. Both cases share one code path without cloning.
+///
+/// # Examples
+/// ```rust
+/// use std::borrow::Cow;
+/// use use_with::CowUseExt;
+///
+/// fn normalize(input: &str) -> Cow<'_, str> {
+///     if input.contains(' ') {
+///         Cow::Owned(input.replace(' ', "_"))
+///     } else {
+///         Cow::Borrowed(input)
+///     }
+/// }
+///
+/// assert_eq!(normalize("cached").use_cow(str::len), 6);
+/// assert_eq!(normalize("fresh value").use_cow(|key| key.to_uppercase()), "FRESH_VALUE");
+/// ```
+pub trait CowUseExt<B: ?Sized> {
+    /// Executes a closure on the borrowed or owned resource, dropping it afterwards if it is owned.
+    fn use_cow<U, F: FnOnce(&B) -> U>(self, f: F) -> U;
+}
+
+impl<B: ?Sized + ToOwned> CowUseExt<B> for Cow<'_, B> {
+    #[track_caller]
+    fn use_cow<U, F: FnOnce(&B) -> U>(self, f: F) -> U {
+        UseScope::new(self).use_with(|cow| match &cow {
+            Cow::Borrowed(borrowed) => f(borrowed),
+            Cow::Owned(owned) => f(owned.borrow()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{DropCounter, DropProbe};
+
+    #[derive(Debug)]
+    struct Handle(#[allow(dead_code)] DropProbe);
+
+    impl Clone for Handle {
+        fn clone(&self) -> Self {
+            unreachable!("use_cow must not clone the resource")
+        }
+    }
+
+    #[test]
+    fn test_only_owned_resources_are_dropped() {
+        let counter = DropCounter::new();
+        let cached = Handle(counter.probe());
+
+        Cow::Borrowed(&cached).use_cow(|_handle| ());
+        assert_eq!(counter.count(), 0);
+
+        Cow::<Handle>::Owned(Handle(counter.probe())).use_cow(|_handle| ());
+        assert_eq!(counter.count(), 1);
+
+        drop(cached);
+        assert_eq!(counter.count(), 2);
+    }
+}
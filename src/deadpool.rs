@@ -0,0 +1,175 @@
+//! Integrations with [`deadpool`](https://docs.rs/deadpool).
+//!
+//! Lets resources defined through this crate's traits be pooled by deadpool, and deadpool's pools
+//! serve as factories of this crate:
+//!
+//! - [`Acquired<R>`](crate::Acquired) is a deadpool [`Manager`] for every resource implementing
+//!   [`AcquireAsync`], so a pool is built without a hand-written manager.
+//! - A deadpool [`Pool`] is an [`AsyncResourceFactory`] of its [`Object`]s, so utilities taking a
+//!   factory check resources out of the pool instead of creating them.
+//! - Closing an [`Object`] through [`Close`] or [`AsyncClose`] detaches it from its pool and
+//!   closes the pooled resource, retiring it. Dropping the object returns it to the pool instead.
+
+use crate::{AcquireAsync, Acquired, AsyncClose, AsyncResourceFactory, Close};
+use ::deadpool::managed::{Manager, Metrics, Object, Pool, PoolError, RecycleResult};
+use std::future::Future;
+
+/// Creates resources through their [`AcquireAsync`] implementation and recycles them as they are.
+///
+/// # Examples
+/// ```rust
+/// use deadpool::managed::Pool;
+/// use use_with::{AcquireAsync, Acquired};
+///
+/// struct Session(u32);
+///
+/// impl AcquireAsync for Session {
+///     type Error = std::io::Error;
+///
+///     async fn acquire_async() -> Result<Self, Self::Error> {
+///         Ok(Session(42))
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let pool: Pool<Acquired<Session>> = Pool::builder(Acquired::new())
+///     .max_size(4)
+///     .build()
+///     .unwrap();
+///
+/// let session = pool.get().await.unwrap();
+/// assert_eq!(session.0, 42);
+/// # }
+/// ```
+impl<R> Manager for Acquired<R>
+where
+    R: AcquireAsync + Send,
+    R::Error: Send,
+{
+    type Type = R;
+    type Error = R::Error;
+
+    fn create(&self) -> impl Future<Output = Result<R, R::Error>> + Send {
+        R::acquire_async()
+    }
+
+    async fn recycle(&self, _resource: &mut R, _metrics: &Metrics) -> RecycleResult<R::Error> {
+        Ok(())
+    }
+}
+
+/// Checks resources out of the pool.
+///
+/// # Examples
+/// ```rust
+/// use deadpool::managed::Pool;
+/// use use_with::{AcquireAsync, Acquired, AsyncResourceFactory};
+///
+/// struct Session(u32);
+///
+/// impl AcquireAsync for Session {
+///     type Error = std::io::Error;
+///
+///     async fn acquire_async() -> Result<Self, Self::Error> {
+///         Ok(Session(42))
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let pool = Pool::builder(Acquired::<Session>::new()).build().unwrap();
+///
+/// let session = pool.create_async().await.unwrap();
+/// assert_eq!(session.0, 42);
+/// # }
+/// ```
+impl<M: Manager> AsyncResourceFactory<Object<M>> for Pool<M> {
+    type Error = PoolError<M::Error>;
+
+    fn create_async(&self) -> impl Future<Output = Result<Object<M>, Self::Error>> + Send {
+        self.get()
+    }
+}
+
+impl<M> Close for Object<M>
+where
+    M: Manager,
+    M::Type: Close,
+{
+    type Error = <M::Type as Close>::Error;
+
+    /// Detaches the resource from its pool and closes it.
+    fn close(self) -> Result<(), Self::Error> {
+        Object::take(self).close()
+    }
+}
+
+impl<M> AsyncClose for Object<M>
+where
+    M: Manager,
+    M::Type: AsyncClose,
+{
+    type Error = <M::Type as AsyncClose>::Error;
+
+    /// Detaches the resource from its pool and closes it.
+    fn close_async(self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        Object::take(self).close_async()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    static CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Connection(usize);
+
+    impl AcquireAsync for Connection {
+        type Error = String;
+
+        async fn acquire_async() -> Result<Self, Self::Error> {
+            Ok(Connection(CREATED.fetch_add(1, Ordering::SeqCst)))
+        }
+    }
+
+    impl AsyncClose for Connection {
+        type Error = String;
+
+        async fn close_async(self) -> Result<(), Self::Error> {
+            CLOSED.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[::tokio::test]
+    async fn test_pooled_resources_are_reused_and_retired_when_closed() {
+        let pool = Pool::builder(Acquired::<Connection>::new())
+            .max_size(1)
+            .build()
+            .unwrap();
+
+        let first = pool.create_async().await.unwrap().use_with(|conn| conn.0);
+        let second = pool.create_async().await.unwrap().use_with(|conn| conn.0);
+        assert_eq!(first, second);
+        assert_eq!(pool.status().size, 1);
+
+        let closed = pool
+            .create_async()
+            .await
+            .unwrap()
+            .use_close_async(|conn| Box::pin(async move { conn.0 }))
+            .await;
+        assert_eq!(closed, Ok(first));
+        assert_eq!(CLOSED.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.status().size, 0);
+
+        let replacement = pool.create_async().await.unwrap().use_with(|conn| conn.0);
+        assert_ne!(replacement, first);
+        assert_eq!(CREATED.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,150 @@
+//! Introspection of the resources that are currently held.
+//!
+//! With the `diagnostics` feature enabled, every use scope registers itself while it holds
+//! its resource. [`dump`] returns a structured snapshot of all of them, suitable for wiring
+//! into a `/debug/resources` endpoint of a service. The snapshot implements [`Display`](fmt::Display)
+//! for a human-readable rendering; its entries expose all fields for custom formats.
+//!
+//! # Examples
+//! ```rust
+//! use use_with::{diagnostics, Use};
+//!
+//! struct Connection;
+//!
+//! Connection.use_with(|_conn| {
+//!     let dump = diagnostics::dump();
+//!     assert_eq!(dump.resources().len(), 1);
+//!     println!("{dump}");
+//! });
+//! ```
+
+use crate::registry;
+use crate::ScopeId;
+use std::fmt;
+use std::panic::Location;
+use std::thread::ThreadId;
+use std::time::Duration;
+
+/// A snapshot of all resources held by use scopes at the time of [`dump`].
+#[derive(Debug, Clone)]
+pub struct ResourceDump {
+    resources: Vec<HeldResource>,
+}
+
+impl ResourceDump {
+    /// Returns the held resources, ordered from the oldest to the youngest scope.
+    pub fn resources(&self) -> &[HeldResource] {
+        &self.resources
+    }
+}
+
+impl fmt::Display for ResourceDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} resource(s) held", self.resources.len())?;
+        for resource in &self.resources {
+            writeln!(f, "{resource}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A resource held by a single use scope.
+#[derive(Debug, Clone)]
+pub struct HeldResource {
+    id: ScopeId,
+    resource_type: &'static str,
+    location: &'static Location<'static>,
+    held_for: Duration,
+    thread_id: ThreadId,
+    thread_name: Option<String>,
+}
+
+impl HeldResource {
+    /// Returns the unique identifier of the use scope.
+    pub fn id(&self) -> ScopeId {
+        self.id
+    }
+
+    /// Returns the type name of the held resource.
+    pub fn resource_type(&self) -> &'static str {
+        self.resource_type
+    }
+
+    /// Returns the source location at which the use scope was entered.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns for how long the resource has been held.
+    pub fn held_for(&self) -> Duration {
+        self.held_for
+    }
+
+    /// Returns the identifier of the thread that entered the use scope.
+    ///
+    /// Asynchronous scopes may have moved to other threads since.
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// Returns the name of the thread that entered the use scope, if it has one.
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+}
+
+impl fmt::Display for HeldResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} held for {:?} at {} on thread {:?}",
+            self.id, self.resource_type, self.held_for, self.location, self.thread_id
+        )?;
+        if let Some(name) = &self.thread_name {
+            write!(f, " ({name})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a snapshot of all resources that are currently held by use scopes.
+pub fn dump() -> ResourceDump {
+    let resources = registry::live()
+        .iter()
+        .map(|(id, entry)| HeldResource {
+            id: *id,
+            resource_type: entry.resource_type,
+            location: entry.location,
+            held_for: entry.acquired.elapsed(),
+            thread_id: entry.thread_id,
+            thread_name: entry.thread_name.clone(),
+        })
+        .collect();
+    ResourceDump { resources }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+
+    #[test]
+    fn test_dump_lists_held_resources() {
+        struct DumpedResource;
+
+        let find = |dump: &ResourceDump| {
+            dump.resources()
+                .iter()
+                .find(|res| res.resource_type().ends_with("DumpedResource"))
+                .cloned()
+        };
+
+        let held = DumpedResource.use_with(|_res| find(&dump()));
+        let held = held.expect("held resource was not listed");
+        assert_eq!(held.location().file(), file!());
+        assert_eq!(held.thread_id(), std::thread::current().id());
+        assert!(held.to_string().contains("DumpedResource"));
+
+        assert!(find(&dump()).is_none());
+    }
+}
@@ -0,0 +1,230 @@
+//! Implementations of this crate's traits for [`Either`].
+//!
+//! Only available with the `either` feature.
+//!
+//! Resources that are acquired as one of two types, such as a plain or an encrypted connection,
+//! can be wrapped in an [`Either`] and used in a single use scope. Closing, entering and exiting
+//! dispatch to whichever variant is held, and errors are reported as an [`Either`] of the errors
+//! of both variants. The body can operate on the held variant through [`either::for_both!`].
+//!
+//! # Examples
+//! ```rust
+//! use either::{for_both, Either};
+//! use use_with::{Close, Use};
+//!
+//! struct Plain(Vec<u8>);
+//! struct Encrypted(Vec<u8>);
+//!
+//! impl Close for Plain {
+//!     type Error = std::io::Error;
+//!
+//!     fn close(self) -> Result<(), Self::Error> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! impl Close for Encrypted {
+//!     type Error = String;
+//!
+//!     fn close(self) -> Result<(), Self::Error> {
+//!         // Send the closing alert, ...
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let connection: Either<Plain, Encrypted> = Either::Right(Encrypted(Vec::new()));
+//!
+//! let sent = connection.use_close(|connection| {
+//!     for_both!(connection, inner => inner.0.extend_from_slice(b"hello"));
+//!     for_both!(connection, inner => inner.0.len())
+//! });
+//! assert_eq!(sent.ok(), Some(5));
+//! ```
+
+use crate::{AsyncClose, Close, Enter, Exit, Outcome};
+use ::either::Either;
+use core::future::Future;
+
+/// Closes the held variant.
+impl<A: Close, B: Close> Close for Either<A, B> {
+    type Error = Either<A::Error, B::Error>;
+
+    fn close(self) -> Result<(), Self::Error> {
+        match self {
+            Either::Left(left) => left.close().map_err(Either::Left),
+            Either::Right(right) => right.close().map_err(Either::Right),
+        }
+    }
+}
+
+/// Closes the held variant asynchronously.
+impl<A: AsyncClose, B: AsyncClose> AsyncClose for Either<A, B> {
+    type Error = Either<A::Error, B::Error>;
+
+    fn close_async(self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let closing = self.map_either(A::close_async, B::close_async);
+        async move {
+            match closing {
+                Either::Left(left) => left.await.map_err(Either::Left),
+                Either::Right(right) => right.await.map_err(Either::Right),
+            }
+        }
+    }
+}
+
+/// Enters the held variant, guarding the scope with its guard.
+impl<A: Enter, B: Enter> Enter for Either<A, B> {
+    type Guard = Either<A::Guard, B::Guard>;
+
+    fn enter(self) -> Self::Guard {
+        self.map_either(A::enter, B::enter)
+    }
+}
+
+/// Exits the held guard.
+impl<A: Exit, B: Exit> Exit for Either<A, B> {
+    fn exit(self, outcome: &Outcome) {
+        match self {
+            Either::Left(left) => left.exit(outcome),
+            Either::Right(right) => right.exit(outcome),
+        }
+    }
+}
+
+/// The connection is lost if the error of the held variant says so.
+#[cfg(feature = "std")]
+impl<A, B> crate::ConnectionLost for Either<A, B>
+where
+    A: crate::ConnectionLost,
+    B: crate::ConnectionLost,
+{
+    fn is_connection_lost(&self) -> bool {
+        ::either::for_both!(self, error => error.is_connection_lost())
+    }
+}
+
+/// Syncs the held writer.
+#[cfg(feature = "std")]
+impl<A, B> crate::io::Durable for Either<A, B>
+where
+    A: crate::io::Durable,
+    B: crate::io::Durable,
+{
+    fn sync(&mut self) -> std::io::Result<()> {
+        ::either::for_both!(self, writer => writer.sync())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+    use std::sync::Mutex;
+
+    struct Flushing<'a>(&'a Mutex<Vec<&'static str>>);
+
+    impl Close for Flushing<'_> {
+        type Error = &'static str;
+
+        fn close(self) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().push("flushed");
+            Ok(())
+        }
+    }
+
+    struct Failing;
+
+    impl Close for Failing {
+        type Error = u32;
+
+        fn close(self) -> Result<(), Self::Error> {
+            Err(42)
+        }
+    }
+
+    impl AsyncClose for Failing {
+        type Error = u32;
+
+        async fn close_async(self) -> Result<(), Self::Error> {
+            Err(7)
+        }
+    }
+
+    impl AsyncClose for Flushing<'_> {
+        type Error = &'static str;
+
+        async fn close_async(self) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().push("flushed asynchronously");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_close_dispatches_to_the_held_variant() {
+        let log = Mutex::new(Vec::new());
+        let left: Either<Flushing, Failing> = Either::Left(Flushing(&log));
+        assert_eq!(left.use_close(|_| ()), Ok(()));
+        assert_eq!(*log.lock().unwrap(), ["flushed"]);
+
+        let right: Either<Flushing, Failing> = Either::Right(Failing);
+        assert_eq!(right.use_close(|_| ()), Err(Either::Right(42)));
+    }
+
+    #[::tokio::test]
+    async fn test_async_close_dispatches_to_the_held_variant() {
+        let log = Mutex::new(Vec::new());
+        let left: Either<Flushing, Failing> = Either::Left(Flushing(&log));
+        let result = left.use_close_async(|_| Box::pin(async {})).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(*log.lock().unwrap(), ["flushed asynchronously"]);
+
+        let right: Either<Flushing, Failing> = Either::Right(Failing);
+        let result = right.use_close_async(|_| Box::pin(async {})).await;
+        assert_eq!(result, Err(Either::Right(7)));
+    }
+
+    struct Logged<'a>(&'a Mutex<Vec<&'static str>>, &'static str);
+
+    impl Exit for Logged<'_> {
+        fn exit(self, outcome: &Outcome) {
+            let event = if outcome.is_success() {
+                self.1
+            } else {
+                "failed"
+            };
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    impl<'a> Enter for Logged<'a> {
+        type Guard = Logged<'a>;
+
+        fn enter(self) -> Self::Guard {
+            self
+        }
+    }
+
+    #[test]
+    fn test_context_dispatches_to_the_held_variant() {
+        let log = Mutex::new(Vec::new());
+        let left: Either<Logged, Logged> = Either::Left(Logged(&log, "left"));
+        assert_eq!(left.use_context(|_| Ok::<_, ()>(())), Ok(()));
+        let right: Either<Logged, Logged> = Either::Right(Logged(&log, "right"));
+        assert_eq!(right.use_context(|_| Err::<(), _>(())), Err(()));
+        assert_eq!(*log.lock().unwrap(), ["left", "failed"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_connection_loss_is_classified_by_the_held_error() {
+        use crate::ConnectionLost;
+        use std::io;
+
+        let lost: Either<io::Error, io::Error> =
+            Either::Right(io::Error::from(io::ErrorKind::ConnectionReset));
+        assert!(lost.is_connection_lost());
+        let denied: Either<io::Error, io::Error> =
+            Either::Left(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(!denied.is_connection_lost());
+    }
+}
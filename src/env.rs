@@ -0,0 +1,372 @@
+//! Scoped changes to the process environment.
+//!
+//! The environment and the working directory are global to the process, so changes made within a
+//! scope are visible to all threads while the scope is active. Tests that run in parallel must not
+//! rely on conflicting values of the same variable.
+//!
+//! All scopes of this module are serialized through a single process-wide lock, so that scopes on
+//! different threads neither observe nor restore each other's changes; nested scopes on the same
+//! thread are allowed. The lock does not protect code outside these scopes, which may still read
+//! or change the environment and the working directory concurrently.
+//!
+//! # Thread safety of environment variables
+//! On most Unix platforms, changing an environment variable while another thread reads the
+//! environment is undefined behavior. [`std::env`](mod@std::env) guards its own accesses with a
+//! lock, but the C library's `getenv` does not take it, and it is called implicitly by code such as
+//! DNS resolution, time zone conversion or C libraries linked into the process. The 2024 edition of
+//! Rust therefore marks [`env::set_var`] and [`env::remove_var`] as `unsafe`. [`use_env_var`] and
+//! [`use_env_vars`] change the environment when the scope is entered and again when it is left, so
+//! they are only sound while no other thread reads the environment other than through
+//! [`std::env`](mod@std::env), such as in single-threaded programs or in tests that do not run C
+//! code concurrently.
+
+use crate::sync::{thread, Condvar, Mutex, ThreadId};
+use crate::{Close, Use, UseScope};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Restores environment variables to their saved values when dropped.
+#[derive(Debug)]
+struct EnvVarGuard {
+    saved: Vec<(OsString, Option<OsString>)>,
+    // Released only after the variables were restored.
+    _lock: ProcessStateGuard<'static>,
+}
+
+impl EnvVarGuard {
+    /// Saves the current value of `key` and sets it to `value`, or removes it if `value` is `None`.
+    ///
+    /// Like [`env::set_var`], this is undefined behavior on most Unix platforms if another thread
+    /// reads the environment through the C library's `getenv` at the same time. The held lock
+    /// only serializes the scopes of this module.
+    fn set(&mut self, key: &OsStr, value: Option<&OsStr>) {
+        self.saved.push((key.to_owned(), env::var_os(key)));
+        match value {
+            Some(value) => env::set_var(key, value),
+            None => env::remove_var(key),
+        }
+    }
+}
+
+/// Restores the variables, which is subject to the same hazard as [`EnvVarGuard::set`].
+impl Drop for EnvVarGuard {
+    fn drop(&mut self) {
+        // Restored in reverse order, so that a variable changed twice ends up with its original value.
+        for (key, value) in self.saved.drain(..).rev() {
+            match value {
+                Some(value) => env::set_var(&key, value),
+                None => env::remove_var(&key),
+            }
+        }
+    }
+}
+
+/// Executes a closure with the environment variable `key` set to `value`.
+///
+/// The previous value of the variable is restored afterwards, or the variable is removed if it
+/// was not set before. The variable is restored even if the closure panics.
+///
+/// Concurrent scopes changing the environment or the working directory are serialized, and
+/// nested calls on the same thread are allowed. Code outside such scopes is not synchronized
+/// with them, though, which makes changing the environment undefined behavior on most Unix
+/// platforms if another thread reads it through the C library, as explained in the
+/// [module documentation](self#thread-safety-of-environment-variables).
+///
+/// # Examples
+/// ```rust
+/// use use_with::env::use_env_var;
+///
+/// let level = use_env_var("USE_WITH_EXAMPLE_LEVEL", "debug", || {
+///     std::env::var("USE_WITH_EXAMPLE_LEVEL").unwrap()
+/// });
+///
+/// assert_eq!(level, "debug");
+/// assert!(std::env::var_os("USE_WITH_EXAMPLE_LEVEL").is_none());
+/// ```
+#[track_caller]
+pub fn use_env_var<K, V, U, F>(key: K, value: V, f: F) -> U
+where
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+    F: FnOnce() -> U,
+{
+    use_env_vars([(key, Some(value))], f)
+}
+
+/// Executes a closure with several environment variables set or removed.
+///
+/// Each variable is set to the given value, or removed if the value is `None`. All variables are
+/// restored to their previous state afterwards, even if the closure panics.
+///
+/// The scope is serialized like [`use_env_var`], and subject to the same
+/// [thread safety hazard](self#thread-safety-of-environment-variables).
+///
+/// # Examples
+/// ```rust
+/// use use_with::env::use_env_vars;
+///
+/// use_env_vars(
+///     [("USE_WITH_EXAMPLE_HOST", Some("localhost")), ("USE_WITH_EXAMPLE_PROXY", None)],
+///     || {
+///         assert_eq!(std::env::var("USE_WITH_EXAMPLE_HOST").unwrap(), "localhost");
+///         assert!(std::env::var_os("USE_WITH_EXAMPLE_PROXY").is_none());
+///     },
+/// );
+/// ```
+#[track_caller]
+pub fn use_env_vars<I, K, V, U, F>(vars: I, f: F) -> U
+where
+    I: IntoIterator<Item = (K, Option<V>)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+    F: FnOnce() -> U,
+{
+    let mut guard = EnvVarGuard {
+        saved: Vec::new(),
+        _lock: PROCESS_STATE.acquire(),
+    };
+    for (key, value) in vars {
+        guard.set(key.as_ref(), value.as_ref().map(AsRef::as_ref));
+    }
+    UseScope::new(guard).use_with(|_guard| f())
+}
+
+/// Serializes scopes changing the environment or the working directory, allowing nested scopes
+/// on the same thread.
+#[derive(Debug)]
+struct ProcessStateLock {
+    owner: Mutex<(Option<ThreadId>, usize)>,
+    released: Condvar,
+}
+
+#[cfg(not(use_with_loom))]
+static PROCESS_STATE: ProcessStateLock = ProcessStateLock::new();
+#[cfg(use_with_loom)]
+loom::lazy_static! {
+    static ref PROCESS_STATE: ProcessStateLock = ProcessStateLock::new();
+}
+
+impl ProcessStateLock {
+    #[cfg(not(use_with_loom))]
+    const fn new() -> Self {
+        Self {
+            owner: Mutex::new((None, 0)),
+            released: Condvar::new(),
+        }
+    }
+
+    #[cfg(use_with_loom)]
+    fn new() -> Self {
+        Self {
+            owner: Mutex::new((None, 0)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until no other thread holds the lock and acquires it for the current thread.
+    fn acquire(&self) -> ProcessStateGuard<'_> {
+        let current = thread::current().id();
+        let mut owner = self.owner.lock().unwrap_or_else(|e| e.into_inner());
+        while owner.0.is_some_and(|thread| thread != current) {
+            owner = self.released.wait(owner).unwrap_or_else(|e| e.into_inner());
+        }
+        *owner = (Some(current), owner.1 + 1);
+        ProcessStateGuard(self)
+    }
+}
+
+/// Grants exclusive access to the environment and the working directory until dropped.
+#[derive(Debug)]
+struct ProcessStateGuard<'a>(&'a ProcessStateLock);
+
+impl Drop for ProcessStateGuard<'_> {
+    fn drop(&mut self) {
+        let mut owner = self.0.owner.lock().unwrap_or_else(|e| e.into_inner());
+        owner.1 -= 1;
+        if owner.1 == 0 {
+            owner.0 = None;
+            self.0.released.notify_one();
+        }
+    }
+}
+
+/// Changes back to the previous working directory when closed.
+#[derive(Debug)]
+struct CurrentDirGuard {
+    previous: PathBuf,
+    // Released only after the previous directory was restored.
+    _lock: ProcessStateGuard<'static>,
+}
+
+impl Close for CurrentDirGuard {
+    type Error = io::Error;
+
+    fn close(self) -> Result<(), Self::Error> {
+        env::set_current_dir(&self.previous)
+    }
+}
+
+/// Executes a closure with the working directory of the process changed to `path`.
+///
+/// The previous working directory is restored afterwards, even if the closure panics.
+///
+/// The working directory is global to the process, which affects all threads that resolve
+/// relative paths while the closure runs. Concurrent scopes changing the working directory or the
+/// environment are serialized, so that they do not observe each other's changes; nested calls on
+/// the same thread are allowed. Code outside such scopes is not synchronized with them.
+///
+/// # Returns
+/// - `Ok(U)` with the result of the closure `f`.
+/// - `Err(io::Error)` if changing into `path` or restoring the previous directory failed. The
+///   closure is not run if changing into `path` failed.
+///
+/// # Examples
+/// ```rust
+/// use use_with::env::use_current_dir;
+///
+/// let temp = std::env::temp_dir().canonicalize()?;
+/// let before = std::env::current_dir()?;
+///
+/// let inside = use_current_dir(&temp, std::env::current_dir)??;
+///
+/// assert_eq!(inside.canonicalize()?, temp);
+/// assert_eq!(std::env::current_dir()?, before);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[track_caller]
+pub fn use_current_dir<P, U, F>(path: P, f: F) -> io::Result<U>
+where
+    P: AsRef<Path>,
+    F: FnOnce() -> U,
+{
+    let lock = PROCESS_STATE.acquire();
+    let previous = env::current_dir()?;
+    env::set_current_dir(path)?;
+    CurrentDirGuard {
+        previous,
+        _lock: lock,
+    }
+    .use_close(|_guard| f())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic;
+
+    #[test]
+    fn test_previous_value_is_restored() {
+        env::set_var("USE_WITH_TEST_RESTORED", "before");
+        use_env_var("USE_WITH_TEST_RESTORED", "during", || {
+            assert_eq!(env::var("USE_WITH_TEST_RESTORED").unwrap(), "during");
+        });
+        assert_eq!(env::var("USE_WITH_TEST_RESTORED").unwrap(), "before");
+        env::remove_var("USE_WITH_TEST_RESTORED");
+    }
+
+    #[test]
+    fn test_variables_are_restored_after_panic() {
+        env::set_var("USE_WITH_TEST_REMOVED", "before");
+        let result = panic::catch_unwind(|| {
+            use_env_vars(
+                [
+                    ("USE_WITH_TEST_ADDED", Some("1")),
+                    ("USE_WITH_TEST_REMOVED", None),
+                    ("USE_WITH_TEST_ADDED", Some("2")),
+                ],
+                || {
+                    assert_eq!(env::var("USE_WITH_TEST_ADDED").unwrap(), "2");
+                    assert!(env::var_os("USE_WITH_TEST_REMOVED").is_none());
+                    panic!("interrupted");
+                },
+            )
+        });
+        assert!(result.is_err());
+
+        assert!(env::var_os("USE_WITH_TEST_ADDED").is_none());
+        assert_eq!(env::var("USE_WITH_TEST_REMOVED").unwrap(), "before");
+        env::remove_var("USE_WITH_TEST_REMOVED");
+    }
+
+    #[cfg(use_with_loom)]
+    #[test]
+    fn loom_process_state_lock_excludes_other_threads_and_is_released_by_panics() {
+        use crate::sync::{Arc, AtomicUsize, Ordering};
+        use std::panic::AssertUnwindSafe;
+
+        loom::model(|| {
+            let lock = Arc::new(ProcessStateLock::new());
+            let holders = Arc::new(AtomicUsize::new(0));
+            let other = {
+                let (lock, holders) = (Arc::clone(&lock), Arc::clone(&holders));
+                thread::spawn(move || {
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        let _guard = lock.acquire();
+                        assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                        holders.fetch_sub(1, Ordering::SeqCst);
+                        panic!("scope failed");
+                    }));
+                    assert!(result.is_err());
+                })
+            };
+            {
+                let _outer = lock.acquire();
+                let _nested = lock.acquire();
+                assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                holders.fetch_sub(1, Ordering::SeqCst);
+            }
+            other.join().unwrap();
+
+            // The scope that panicked released the lock while unwinding.
+            drop(lock.acquire());
+        });
+    }
+
+    #[test]
+    fn test_concurrent_scopes_are_serialized() {
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                thread::spawn(move || {
+                    let value = i.to_string();
+                    use_env_var("USE_WITH_TEST_SERIALIZED", &value, || {
+                        for _ in 0..100 {
+                            assert_eq!(env::var("USE_WITH_TEST_SERIALIZED").unwrap(), value);
+                            thread::yield_now();
+                        }
+                        // Nested scopes on the same thread do not wait for themselves.
+                        use_env_vars([("USE_WITH_TEST_SERIALIZED", None::<&str>)], || {
+                            assert!(env::var_os("USE_WITH_TEST_SERIALIZED").is_none());
+                        });
+                    });
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(env::var_os("USE_WITH_TEST_SERIALIZED").is_none());
+    }
+
+    #[test]
+    fn test_current_dir_is_restored_after_panic() {
+        let before = env::current_dir().unwrap();
+        let temp = env::temp_dir().canonicalize().unwrap();
+
+        let result = panic::catch_unwind(|| {
+            use_current_dir(&temp, || {
+                let nested = use_current_dir("/", || env::current_dir().unwrap()).unwrap();
+                assert_eq!(nested, Path::new("/"));
+                assert_eq!(env::current_dir().unwrap().canonicalize().unwrap(), temp);
+                panic!("interrupted");
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(env::current_dir().unwrap(), before);
+
+        let missing = use_current_dir(temp.join("use-with-missing-dir"), || unreachable!());
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(env::current_dir().unwrap(), before);
+    }
+}
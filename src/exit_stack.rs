@@ -0,0 +1,165 @@
+//! Dynamic composition of cleanup actions.
+
+use crate::small_vec::SmallVec;
+use crate::unwind::panic;
+use alloc::boxed::Box;
+use core::fmt;
+use core::panic::AssertUnwindSafe;
+
+/// The number of cleanup actions an [`ExitStack`] stores without allocating its list.
+const INLINE_ACTIONS: usize = 4;
+
+/// A stack of cleanup actions that run in reverse order when the stack is dropped.
+///
+/// This is the counterpart of Python's `contextlib.ExitStack`: where [`using!`](crate::using)
+/// and nested use scopes fix the set of resources at compile time, an `ExitStack` collects
+/// resources and callbacks at runtime, e.g. one per entry of a configuration file. If setting up
+/// the whole group fails halfway, dropping the stack cleans up what was set up so far; once it
+/// succeeds, [`pop_all`](Self::pop_all) moves the cleanup actions to a new stack owned by the
+/// caller.
+///
+/// If a cleanup action panics, the remaining ones still run, after which the first panic resumes.
+///
+/// The list of cleanup actions is stored inline for up to four actions, so that a stack with a
+/// handful of actions does not allocate a list. The actions themselves are boxed, so every
+/// callback that captures state and every pushed resource that is not zero-sized still costs one
+/// allocation.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::RefCell;
+/// use use_with::ExitStack;
+///
+/// let log = RefCell::new(Vec::new());
+/// let log = &log;
+///
+/// let opened = {
+///     let mut stack = ExitStack::new();
+///     for name in ["primary", "replica"] {
+///         log.borrow_mut().push(format!("open {name}"));
+///         stack.callback(move || log.borrow_mut().push(format!("close {name}")));
+///     }
+///     // All connections were opened, keep them open beyond this block.
+///     stack.pop_all()
+/// };
+/// assert_eq!(log.borrow().len(), 2);
+///
+/// drop(opened);
+/// assert_eq!(
+///     *log.borrow(),
+///     ["open primary", "open replica", "close replica", "close primary"]
+/// );
+/// ```
+#[must_use = "an exit stack runs its cleanup actions as soon as it is dropped"]
+pub struct ExitStack<'a> {
+    actions: SmallVec<Box<dyn FnOnce() + 'a>, INLINE_ACTIONS>,
+}
+
+impl<'a> ExitStack<'a> {
+    /// Creates an empty exit stack.
+    pub const fn new() -> Self {
+        Self {
+            actions: SmallVec::new(),
+        }
+    }
+
+    /// Pushes a callback that runs when the stack is unwound.
+    pub fn callback<F: FnOnce() + 'a>(&mut self, f: F) {
+        self.actions.push(Box::new(f));
+    }
+
+    /// Pushes a resource that is dropped when the stack is unwound.
+    ///
+    /// To close resources implementing [`Close`](crate::Close) explicitly, push a
+    /// [`callback`](Self::callback) that closes them and handles the error instead.
+    pub fn push<T: 'a>(&mut self, resource: T) {
+        self.callback(move || drop(resource));
+    }
+
+    /// Returns the number of pending cleanup actions.
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Returns whether no cleanup actions are pending.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Moves all cleanup actions to a new stack, leaving this one empty.
+    pub fn pop_all(&mut self) -> ExitStack<'a> {
+        ExitStack {
+            actions: core::mem::take(&mut self.actions),
+        }
+    }
+
+    /// Discards all cleanup actions without running them.
+    ///
+    /// Resources pushed onto the stack are dropped nonetheless.
+    pub fn dismiss(&mut self) {
+        self.actions.clear();
+    }
+
+    /// Runs all cleanup actions in reverse order of their pushing.
+    ///
+    /// This is the same as dropping the stack.
+    pub fn close(self) {
+        drop(self);
+    }
+}
+
+impl Default for ExitStack<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ExitStack<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExitStack")
+            .field("pending", &self.actions.len())
+            .finish()
+    }
+}
+
+impl Drop for ExitStack<'_> {
+    fn drop(&mut self) {
+        let mut first_panic = None;
+        while let Some(action) = self.actions.pop() {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(action)) {
+                first_panic.get_or_insert(payload);
+            }
+        }
+        if let Some(payload) = first_panic {
+            if !panic::panicking() {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::testing::DropSpy;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_unwinds_in_reverse_order_despite_panics() {
+        let spy = DropSpy::new();
+        let order = RefCell::new(Vec::new());
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut stack = ExitStack::new();
+            stack.push(spy.probe("resource"));
+            stack.callback(|| order.borrow_mut().push("first"));
+            stack.callback(|| panic!("cleanup failed"));
+            stack.callback(|| order.borrow_mut().push("last"));
+            assert_eq!(stack.len(), 4);
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(*order.borrow(), ["last", "first"]);
+        assert_eq!(spy.drop_count("resource"), 1);
+    }
+}
@@ -0,0 +1,190 @@
+//! Factories that build fresh resources on demand.
+
+use crate::{Acquire, AcquireAsync, UseScope};
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+
+/// Builds fresh instances of a resource on demand.
+///
+/// Higher-level utilities that need to replace a resource, such as caches or reconnecting
+/// wrappers, take a factory instead of a resource. Closures returning a `Result` are factories,
+/// as is [`Acquired`] for types implementing [`Acquire`].
+///
+/// # Examples
+/// ```rust
+/// use use_with::ResourceFactory;
+///
+/// let factory = || Ok::<_, std::io::Error>(Vec::<u8>::with_capacity(1024));
+///
+/// let capacity = factory.use_fresh(|buffer| buffer.capacity())?;
+/// assert!(capacity >= 1024);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub trait ResourceFactory<T> {
+    /// The error returned when creating a resource fails.
+    type Error;
+
+    /// Creates a new instance of the resource.
+    fn create(&self) -> Result<T, Self::Error>;
+
+    /// Creates a new instance of the resource, executes a closure on it and drops it afterwards.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(Self::Error)` without running the closure if creating the resource failed.
+    #[track_caller]
+    fn use_fresh<U, F>(&self, f: F) -> Result<U, Self::Error>
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        let resource = self.create()?;
+        Ok(UseScope::new(resource).use_with(|mut resource| f(&mut resource)))
+    }
+}
+
+impl<T, E, F> ResourceFactory<T> for F
+where
+    F: Fn() -> Result<T, E>,
+{
+    type Error = E;
+
+    fn create(&self) -> Result<T, Self::Error> {
+        self()
+    }
+}
+
+/// Builds fresh instances of a resource asynchronously.
+///
+/// This is the asynchronous counterpart of [`ResourceFactory`]. Closures returning a future of a
+/// `Result` are factories, as is [`Acquired`] for types implementing [`AcquireAsync`].
+///
+/// # Examples
+/// ```rust
+/// use use_with::AsyncResourceFactory;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let factory = || async { Ok::<_, std::io::Error>(String::from("connection")) };
+///
+/// let connection = factory.create_async().await?;
+/// assert_eq!(connection, "connection");
+/// # Ok(())
+/// # }
+/// ```
+pub trait AsyncResourceFactory<T> {
+    /// The error returned when creating a resource fails.
+    type Error;
+
+    /// Creates a new instance of the resource asynchronously.
+    fn create_async(&self) -> impl Future<Output = Result<T, Self::Error>> + Send;
+}
+
+impl<T, E, F, Fut> AsyncResourceFactory<T> for F
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, E>> + Send,
+{
+    type Error = E;
+
+    fn create_async(&self) -> impl Future<Output = Result<T, Self::Error>> + Send {
+        self()
+    }
+}
+
+/// A factory creating resources through their [`Acquire`] or [`AcquireAsync`] implementation.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{Acquire, Acquired, ResourceFactory};
+///
+/// struct Counter(u32);
+///
+/// impl Acquire for Counter {
+///     type Error = std::convert::Infallible;
+///
+///     fn acquire() -> Result<Self, Self::Error> {
+///         Ok(Counter(0))
+///     }
+/// }
+///
+/// let factory = Acquired::<Counter>::new();
+/// assert_eq!(factory.use_fresh(|counter| counter.0).ok(), Some(0));
+/// ```
+pub struct Acquired<R> {
+    _resource: PhantomData<fn() -> R>,
+}
+
+impl<R> Acquired<R> {
+    /// Creates a factory for resources of type `R`.
+    pub const fn new() -> Self {
+        Self {
+            _resource: PhantomData,
+        }
+    }
+}
+
+impl<R> Default for Acquired<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> Clone for Acquired<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for Acquired<R> {}
+
+impl<R> fmt::Debug for Acquired<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Acquired")
+            .field(&std::any::type_name::<R>())
+            .finish()
+    }
+}
+
+impl<R: Acquire> ResourceFactory<R> for Acquired<R> {
+    type Error = R::Error;
+
+    fn create(&self) -> Result<R, Self::Error> {
+        R::acquire()
+    }
+}
+
+impl<R: AcquireAsync> AsyncResourceFactory<R> for Acquired<R> {
+    type Error = R::Error;
+
+    fn create_async(&self) -> impl Future<Output = Result<R, Self::Error>> + Send {
+        R::acquire_async()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropCounter;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_use_fresh_creates_and_drops_per_call() {
+        let counter = DropCounter::new();
+        let created = Cell::new(0);
+        let factory = || {
+            created.set(created.get() + 1);
+            match created.get() {
+                3 => Err("exhausted"),
+                _ => Ok(counter.probe()),
+            }
+        };
+
+        assert_eq!(factory.use_fresh(|_probe| ()), Ok(()));
+        assert_eq!(factory.use_fresh(|_probe| ()), Ok(()));
+        assert_eq!(counter.count(), 2);
+
+        let result = factory.use_fresh(|_probe| unreachable!("creation failed"));
+        assert_eq!(result, Err::<(), _>("exhausted"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Fallback resources for primaries that failed to be acquired.
+
+use crate::UseScope;
+use std::fmt;
+
+/// The error returned by [`ResultUseExt::use_with_or_else`] when both the primary and the fallback
+/// resource failed to be acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FallbackError<P, F> {
+    /// The error of acquiring the primary resource.
+    pub primary: P,
+    /// The error of acquiring the fallback resource.
+    pub fallback: F,
+}
+
+impl<P: fmt::Display, F: fmt::Display> fmt::Display for FallbackError<P, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (after the primary resource failed: {})",
+            self.fallback, self.primary
+        )
+    }
+}
+
+impl<P, F> std::error::Error for FallbackError<P, F>
+where
+    P: fmt::Debug + fmt::Display,
+    F: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.fallback)
+    }
+}
+
+/// Runs closures on a resource that failed to be acquired, falling back to another one.
+///
+/// # Examples
+/// ```rust
+/// use std::fs::File;
+/// use std::io::Read;
+/// use use_with::ResultUseExt;
+///
+/// let config = File::open("/etc/app/config.toml")
+///     .use_with_or_else(
+///         || File::open("/dev/null"),
+///         |mut file, primary| {
+///             let mut config = String::new();
+///             file.read_to_string(&mut config).map_err(|error| match primary {
+///                 Some(primary) => format!("{error} (reading the fallback after: {primary})"),
+///                 None => error.to_string(),
+///             })?;
+///             Ok::<_, String>(config)
+///         },
+///     );
+/// # let _ = config;
+/// ```
+pub trait ResultUseExt<T, E> {
+    /// Executes a closure on the acquired resource, or on a fallback if acquiring it failed.
+    ///
+    /// If `self` is an error, the fallback resource is created by `fallback` and used in place of
+    /// the primary one. Should that fail as well, the errors of both are returned together.
+    ///
+    /// The closure receives the error of the primary resource if it runs on the fallback, so that
+    /// failures of the fallback can report why it was used.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(FallbackError)` without running the closure if both resources failed.
+    fn use_with_or_else<U, G, EF, F>(self, fallback: G, f: F) -> Result<U, FallbackError<E, EF>>
+    where
+        G: FnOnce() -> Result<T, EF>,
+        F: FnOnce(T, Option<&E>) -> U;
+}
+
+impl<T, E> ResultUseExt<T, E> for Result<T, E> {
+    #[track_caller]
+    fn use_with_or_else<U, G, EF, F>(self, fallback: G, f: F) -> Result<U, FallbackError<E, EF>>
+    where
+        G: FnOnce() -> Result<T, EF>,
+        F: FnOnce(T, Option<&E>) -> U,
+    {
+        let (resource, primary) = match self {
+            Ok(resource) => (resource, None),
+            Err(primary) => match fallback() {
+                Ok(resource) => (resource, Some(primary)),
+                Err(fallback) => return Err(FallbackError { primary, fallback }),
+            },
+        };
+        Ok(UseScope::new(resource).use_with(|resource| f(resource, primary.as_ref())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_is_used_only_if_primary_failed() {
+        let primary = Ok::<_, &str>("primary");
+        let used = primary.use_with_or_else(
+            || unreachable!("primary succeeded"),
+            |res, primary| (res, primary.copied()),
+        );
+        assert_eq!(used, Ok::<_, FallbackError<_, ()>>(("primary", None)));
+
+        let used = Err("timeout").use_with_or_else(
+            || Ok::<_, ()>("replica"),
+            |res, primary| (res, primary.copied()),
+        );
+        assert_eq!(used, Ok(("replica", Some("timeout"))));
+
+        let failed =
+            Err::<&str, _>("timeout").use_with_or_else(|| Err("refused"), |res, _primary| res);
+        let error = failed.unwrap_err();
+        assert_eq!((error.primary, error.fallback), ("timeout", "refused"));
+        assert_eq!(
+            error.to_string(),
+            "refused (after the primary resource failed: timeout)"
+        );
+    }
+}
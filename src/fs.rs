@@ -0,0 +1,241 @@
+//! Temporary files and directories that are removed when their use scope ends.
+
+use crate::{Close, Use};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of attempts to find an unused name for a temporary location.
+const ATTEMPTS: u32 = 64;
+
+/// Creates a temporary location with a unique name in [`std::env::temp_dir`].
+fn create_unique<T>(create: impl Fn(&Path) -> io::Result<T>) -> io::Result<(PathBuf, T)> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    for _ in 0..ATTEMPTS {
+        let name = format!(
+            "use-with-{}-{}-{nanos:08x}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        match create(&path) {
+            Ok(created) => return Ok((path, created)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "failed to find an unused name for a temporary location",
+    ))
+}
+
+/// A temporary directory that is removed with all its contents when it is closed.
+///
+/// Dropping a `TempDir` without closing it removes the directory as well, but ignores errors.
+///
+/// See [`use_temp_dir`] for running a closure on a fresh temporary directory.
+#[derive(Debug)]
+pub struct TempDir {
+    path: Option<PathBuf>,
+}
+
+impl TempDir {
+    /// Creates a new, empty directory in [`std::env::temp_dir`].
+    pub fn new() -> io::Result<Self> {
+        let (path, ()) = create_unique(|path| fs::create_dir(path))?;
+        Ok(Self { path: Some(path) })
+    }
+
+    /// Returns the path of the directory.
+    pub fn path(&self) -> &Path {
+        self.path.as_deref().expect("path is present until closed")
+    }
+}
+
+impl Close for TempDir {
+    type Error = io::Error;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        let path = self.path.take().expect("path is present until closed");
+        fs::remove_dir_all(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let _ = fs::remove_dir_all(path);
+        }
+    }
+}
+
+/// A temporary file that is closed and removed when it is closed.
+///
+/// Dropping a `TempFile` without closing it removes the file as well, but ignores errors.
+///
+/// See [`use_temp_file`] for running a closure on a fresh temporary file.
+#[derive(Debug)]
+pub struct TempFile {
+    file: Option<File>,
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Creates a new, empty file in [`std::env::temp_dir`], opened for reading and writing.
+    pub fn new() -> io::Result<Self> {
+        let (path, file) = create_unique(|path| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(path)
+        })?;
+        Ok(Self {
+            file: Some(file),
+            path,
+        })
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the open file.
+    pub fn file(&mut self) -> &mut File {
+        self.file.as_mut().expect("file is present until closed")
+    }
+}
+
+impl Close for TempFile {
+    type Error = io::Error;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        // The handle is closed first, since open files cannot be removed on all platforms.
+        drop(self.file.take());
+        fs::remove_file(&self.path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Creates a temporary directory, executes a closure on its path and removes it afterwards.
+///
+/// The directory and all of its contents are removed once the closure returns or panics.
+///
+/// # Returns
+/// - `Ok(U)` with the result of the closure `f`.
+/// - `Err(io::Error)` if creating or removing the directory failed.
+///
+/// # Examples
+/// ```rust
+/// use use_with::fs::use_temp_dir;
+///
+/// let (path, contents) = use_temp_dir(|dir| {
+///     std::fs::write(dir.join("config.toml"), "debug = true")?;
+///     let contents = std::fs::read_to_string(dir.join("config.toml"))?;
+///     Ok::<_, std::io::Error>((dir.to_path_buf(), contents))
+/// })??;
+///
+/// assert_eq!(contents, "debug = true");
+/// assert!(!path.exists());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[track_caller]
+pub fn use_temp_dir<U, F>(f: F) -> io::Result<U>
+where
+    F: FnOnce(&Path) -> U,
+{
+    TempDir::new()?.use_close(|dir| f(dir.path()))
+}
+
+/// Creates a temporary file, executes a closure on it and removes it afterwards.
+///
+/// The closure receives the open file and its path. The file is closed and removed once the
+/// closure returns or panics.
+///
+/// # Returns
+/// - `Ok(U)` with the result of the closure `f`.
+/// - `Err(io::Error)` if creating or removing the file failed.
+///
+/// # Examples
+/// ```rust
+/// use std::io::{Read, Seek, Write};
+/// use use_with::fs::use_temp_file;
+///
+/// let contents = use_temp_file(|file, _path| {
+///     file.write_all(b"scratch")?;
+///     file.rewind()?;
+///     let mut contents = String::new();
+///     file.read_to_string(&mut contents)?;
+///     Ok::<_, std::io::Error>(contents)
+/// })??;
+///
+/// assert_eq!(contents, "scratch");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[track_caller]
+pub fn use_temp_file<U, F>(f: F) -> io::Result<U>
+where
+    F: FnOnce(&mut File, &Path) -> U,
+{
+    TempFile::new()?.use_close(|temp| {
+        let path = temp.path.clone();
+        f(temp.file(), &path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic;
+
+    #[test]
+    fn test_temp_dir_is_removed_after_panic() {
+        let mut created = None;
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            use_temp_dir(|dir| {
+                fs::create_dir(dir.join("nested")).unwrap();
+                created = Some(dir.to_path_buf());
+                panic!("interrupted");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(!created.unwrap().exists());
+    }
+
+    #[test]
+    fn test_removal_failure_is_reported() {
+        let error = use_temp_file(|_file, path| fs::remove_file(path).unwrap()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+        let error = use_temp_dir(|dir| fs::remove_dir(dir).unwrap()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_temp_locations_are_unique() {
+        let first = TempFile::new().unwrap();
+        let second = TempFile::new().unwrap();
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().exists());
+
+        let path = first.path().to_path_buf();
+        first.close().unwrap();
+        assert!(!path.exists());
+        drop(second);
+    }
+}
@@ -0,0 +1,285 @@
+//! Process-wide resources with an explicit shutdown.
+
+use crate::instrument::ScopeOptions;
+use crate::sync::RwLock;
+use crate::{AsyncClose, Close, UseScope};
+use std::fmt;
+use std::future::Future;
+use std::sync::PoisonError;
+
+/// The lifecycle of the resource of a [`Global`].
+enum State<T> {
+    Uninit,
+    Ready(T),
+    ShutDown,
+}
+
+/// A lazily initialized, process-wide resource that can be shut down explicitly.
+///
+/// Statics are never dropped in Rust, so resources stored in them never get to flush buffers or
+/// say goodbye to their peers. A `Global` is initialized on first use, like a
+/// [`OnceLock`](std::sync::OnceLock), and lends the resource to use scopes through
+/// [`use_global`](Self::use_global). [`shutdown`](Self::shutdown) waits for running scopes,
+/// closes the resource and turns away all further use.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{Close, Global};
+///
+/// struct Telemetry {
+///     endpoint: &'static str,
+/// }
+///
+/// impl Close for Telemetry {
+///     type Error = std::io::Error;
+///
+///     fn close(self) -> Result<(), Self::Error> {
+///         // Flush pending metrics, ...
+///         Ok(())
+///     }
+/// }
+///
+/// static TELEMETRY: Global<Telemetry> = Global::new(|| Telemetry { endpoint: "localhost:4317" });
+///
+/// let endpoint = TELEMETRY.use_global(|telemetry| telemetry.endpoint);
+/// assert_eq!(endpoint, Some("localhost:4317"));
+///
+/// TELEMETRY.shutdown()?;
+/// assert_eq!(TELEMETRY.use_global(|telemetry| telemetry.endpoint), None);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Global<T> {
+    init: fn() -> T,
+    state: RwLock<State<T>>,
+}
+
+impl<T> Global<T> {
+    /// Creates a global resource that is initialized by `init` on first use.
+    #[cfg(not(use_with_loom))]
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            state: RwLock::new(State::Uninit),
+        }
+    }
+
+    /// Creates a global resource that is initialized by `init` on first use.
+    #[cfg(use_with_loom)]
+    pub fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            state: RwLock::new(State::Uninit),
+        }
+    }
+
+    /// Executes a closure with shared access to the resource, initializing it first if necessary.
+    ///
+    /// Scopes run concurrently with each other, but not with [`shutdown`](Self::shutdown).
+    ///
+    /// # Returns
+    /// - `Some(U)` with the result of the closure `f`.
+    /// - `None` without running the closure if the resource has been shut down.
+    #[track_caller]
+    pub fn use_global<U, F: FnOnce(&T) -> U>(&self, f: F) -> Option<U> {
+        loop {
+            let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+            match &*state {
+                State::Ready(resource) => return Some(UseScope::new(resource).use_with(f)),
+                State::ShutDown => return None,
+                State::Uninit => drop(state),
+            }
+            let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+            if let State::Uninit = *state {
+                *state = State::Ready((self.init)());
+            }
+        }
+    }
+
+    /// Returns whether the resource has been shut down.
+    pub fn is_shut_down(&self) -> bool {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        matches!(*state, State::ShutDown)
+    }
+
+    /// Marks the resource as shut down, returning it if it was initialized.
+    fn take(&self) -> Option<T> {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        match std::mem::replace(&mut *state, State::ShutDown) {
+            State::Ready(resource) => Some(resource),
+            State::Uninit | State::ShutDown => None,
+        }
+    }
+}
+
+impl<T: Close> Global<T> {
+    /// Closes the resource and prevents any further use of it.
+    ///
+    /// Waits for running scopes to finish first. If the resource was never initialized or has
+    /// already been shut down, nothing is closed. The resource is closed in a use scope, which
+    /// reports the closing and its failure to the [observers](crate::observer).
+    #[track_caller]
+    pub fn shutdown(&self) -> Result<(), T::Error> {
+        match self.take() {
+            Some(resource) => UseScope::new(resource).use_close(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: AsyncClose> Global<T> {
+    /// Closes the resource asynchronously and prevents any further use of it.
+    ///
+    /// This is the asynchronous counterpart of [`shutdown`](Self::shutdown). Waiting for running
+    /// scopes blocks the current thread.
+    #[track_caller]
+    pub fn shutdown_async(&self) -> impl Future<Output = Result<(), T::Error>> + '_ {
+        let options = ScopeOptions::new();
+        async move {
+            match self.take() {
+                Some(resource) => {
+                    UseScope::with_options(resource, options)
+                        .use_close_async(|_| Box::pin(async {}))
+                        .await
+                }
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Global<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_tuple("Global");
+        match self.state.try_read().as_deref() {
+            Ok(State::Ready(resource)) => debug.field(resource),
+            Ok(State::Uninit) => debug.field(&format_args!("<uninit>")),
+            Ok(State::ShutDown) => debug.field(&format_args!("<shut down>")),
+            Err(_) => debug.field(&format_args!("<locked>")),
+        };
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(use_with_loom)]
+    use crate::sync::{thread, Arc};
+    use crate::sync::{AtomicUsize, Ordering};
+
+    #[cfg(use_with_loom)]
+    #[test]
+    fn loom_scopes_race_with_shutdown() {
+        loom::lazy_static! {
+            static ref CREATED: AtomicUsize = AtomicUsize::new(0);
+            static ref CLOSED: AtomicUsize = AtomicUsize::new(0);
+        }
+
+        struct Counted;
+
+        impl Close for Counted {
+            type Error = ();
+
+            fn close(self) -> Result<(), Self::Error> {
+                CLOSED.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        loom::model(|| {
+            let global = Arc::new(Global::new(|| {
+                CREATED.fetch_add(1, Ordering::SeqCst);
+                Counted
+            }));
+            let user = {
+                let global = Arc::clone(&global);
+                thread::spawn(move || global.use_global(|_| CLOSED.load(Ordering::SeqCst)))
+            };
+            assert_eq!(global.shutdown(), Ok(()));
+
+            // A scope either used the open resource or was turned away, but never saw it closed.
+            // It may also have initialized the resource right before the shutdown took it.
+            let used = user.join().unwrap();
+            assert!(matches!(used, None | Some(0)));
+            assert!(global.is_shut_down());
+            let created = CREATED.load(Ordering::SeqCst);
+            assert!(created >= usize::from(used.is_some()) && created <= 1);
+            assert_eq!(CLOSED.load(Ordering::SeqCst), created);
+        });
+    }
+
+    #[cfg(use_with_loom)]
+    #[test]
+    fn loom_concurrent_scopes_initialize_once() {
+        loom::lazy_static! {
+            static ref CREATED: AtomicUsize = AtomicUsize::new(0);
+        }
+
+        loom::model(|| {
+            let global = Arc::new(Global::new(|| CREATED.fetch_add(1, Ordering::SeqCst)));
+            let user = {
+                let global = Arc::clone(&global);
+                thread::spawn(move || global.use_global(|&id| id))
+            };
+            let id = global.use_global(|&id| id);
+
+            assert_eq!(user.join().unwrap(), id);
+            assert_eq!(CREATED.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[cfg(not(use_with_loom))]
+    #[test]
+    fn test_initialized_once_and_closed_on_shutdown() {
+        static INITIALIZED: AtomicUsize = AtomicUsize::new(0);
+        static CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Pool(usize);
+
+        impl Close for Pool {
+            type Error = ();
+
+            fn close(self) -> Result<(), Self::Error> {
+                CLOSED.fetch_add(self.0, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        static POOL: Global<Pool> =
+            Global::new(|| Pool(INITIALIZED.fetch_add(1, Ordering::SeqCst) + 1));
+
+        let sizes: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| POOL.use_global(|pool| pool.0)))
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        assert_eq!(sizes, [Some(1); 4]);
+        assert_eq!(INITIALIZED.load(Ordering::SeqCst), 1);
+
+        assert_eq!(POOL.shutdown(), Ok(()));
+        assert_eq!(CLOSED.load(Ordering::SeqCst), 1);
+        assert!(POOL.is_shut_down());
+        assert_eq!(POOL.use_global(|pool| pool.0), None);
+        assert_eq!(POOL.shutdown(), Ok(()));
+        assert_eq!(CLOSED.load(Ordering::SeqCst), 1);
+    }
+
+    // Loom cannot model poisoned locks, so recovering from them is tested with `std::sync`.
+    #[cfg(not(use_with_loom))]
+    #[test]
+    fn test_panicking_initialization_is_retried() {
+        static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+        static FLAKY: Global<usize> = Global::new(|| {
+            if ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("initialization failed");
+            }
+            42
+        });
+
+        let panicked = std::panic::catch_unwind(|| FLAKY.use_global(|&value| value));
+        assert!(panicked.is_err());
+        assert_eq!(FLAKY.use_global(|&value| value), Some(42));
+        assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+    }
+}
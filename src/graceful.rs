@@ -0,0 +1,260 @@
+//! Graceful shutdown of connection futures at the end of their use scope.
+//!
+//! The module is available with the `std` feature and does not depend on hyper or any other
+//! server: [`GracefulConnection`] works with every future that offers a method to initiate its
+//! shutdown, such as the connections of hyper 1.x, so there is no separate `hyper` feature.
+
+use crate::{AsyncClose, BoxFuture};
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Sleeps asynchronously for the given duration.
+type Sleep = Arc<dyn Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// A connection future that is shut down gracefully when it is closed.
+///
+/// Connections of HTTP servers such as hyper are futures that serve requests until they complete,
+/// and offer a method to initiate a graceful shutdown: in-flight requests are answered, but
+/// keep-alive connections accept no further ones. Dropping the future instead aborts in-flight
+/// requests. Closing a `GracefulConnection` through
+/// [`Use::use_close_async`](crate::Use::use_close_async) initiates the graceful shutdown and
+/// drives the connection to completion, returning its error. Without a
+/// [timeout](GracefulConnection::timeout_with), a client that never finishes its request keeps
+/// the shutdown waiting.
+///
+/// The wrapper is itself a future that drives the connection, so the body of the scope can serve
+/// requests until a shutdown signal arrives. If the connection completes within the body, closing
+/// it does nothing; its result has already been returned to the body.
+///
+/// ```rust,ignore
+/// use hyper::server::conn::http1;
+/// use std::time::Duration;
+/// use use_with::graceful::GracefulConnection;
+/// use use_with::Use;
+///
+/// let conn = http1::Builder::new().serve_connection(io, service);
+/// GracefulConnection::new(conn, http1::Connection::graceful_shutdown)
+///     .timeout_with(Duration::from_secs(30), tokio::time::sleep)
+///     .use_close_async(|conn| {
+///         Box::pin(async move {
+///             tokio::select! {
+///                 result = conn => result,
+///                 _ = shutdown_signal() => Ok(()),
+///             }
+///         })
+///     })
+///     .await??;
+/// ```
+pub struct GracefulConnection<C: Future> {
+    connection: Pin<Box<C>>,
+    shutdown: fn(Pin<&mut C>),
+    complete: bool,
+    timeout: Option<(Duration, Sleep)>,
+}
+
+impl<C: Future> GracefulConnection<C> {
+    /// Wraps a connection future that is shut down through `shutdown` when closed.
+    pub fn new(connection: C, shutdown: fn(Pin<&mut C>)) -> Self {
+        Self {
+            connection: Box::pin(connection),
+            shutdown,
+            complete: false,
+            timeout: None,
+        }
+    }
+
+    /// Limits the graceful shutdown to `timeout`, waited out through `sleep`.
+    ///
+    /// If the connection does not complete within the timeout, it is dropped, which aborts the
+    /// requests still in flight, and closing fails with [`ShutdownError::TimedOut`]. The `sleep`
+    /// function decouples the adapter from a particular runtime; with tokio, pass
+    /// `tokio::time::sleep`.
+    pub fn timeout_with<T, F>(mut self, timeout: Duration, sleep: T) -> Self
+    where
+        T: Fn(Duration) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.timeout = Some((timeout, Arc::new(move |delay| Box::pin(sleep(delay)))));
+        self
+    }
+
+    /// Returns whether the connection has completed.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+}
+
+impl<C: Future> fmt::Debug for GracefulConnection<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GracefulConnection")
+            .field("complete", &self.complete)
+            .field(
+                "timeout",
+                &self.timeout.as_ref().map(|(timeout, _)| timeout),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Future> Future for GracefulConnection<C> {
+    type Output = C::Output;
+
+    /// Drives the connection.
+    ///
+    /// # Panics
+    /// Panics if polled again after the connection completed.
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert!(!self.complete, "connection polled after completion");
+        let output = self.connection.as_mut().poll(cx);
+        self.complete = output.is_ready();
+        output
+    }
+}
+
+impl<C, E> AsyncClose for GracefulConnection<C>
+where
+    C: Future<Output = Result<(), E>> + Send,
+{
+    type Error = ShutdownError<E>;
+
+    async fn close_async(mut self) -> Result<(), Self::Error> {
+        if self.complete {
+            return Ok(());
+        }
+        (self.shutdown)(self.connection.as_mut());
+        let Some((timeout, sleep)) = self.timeout.take() else {
+            return poll_fn(|cx| self.connection.as_mut().poll(cx))
+                .await
+                .map_err(ShutdownError::Connection);
+        };
+        let mut expired = sleep(timeout);
+        poll_fn(|cx| {
+            if let Poll::Ready(result) = self.connection.as_mut().poll(cx) {
+                return Poll::Ready(result.map_err(ShutdownError::Connection));
+            }
+            expired
+                .as_mut()
+                .poll(cx)
+                .map(|()| Err(ShutdownError::TimedOut))
+        })
+        .await
+    }
+}
+
+/// The error returned when the graceful shutdown of a [`GracefulConnection`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShutdownError<E> {
+    /// The connection failed while shutting down.
+    Connection(E),
+    /// The connection did not complete within the timeout and was aborted.
+    TimedOut,
+}
+
+impl<E: fmt::Display> fmt::Display for ShutdownError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownError::Connection(error) => write!(f, "graceful shutdown failed: {error}"),
+            ShutdownError::TimedOut => f.write_str("the connection did not shut down in time"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ShutdownError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShutdownError::Connection(error) => Some(error),
+            ShutdownError::TimedOut => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+
+    /// A connection serving a number of requests, or finishing early once shut down.
+    struct Connection {
+        requests: u32,
+        shutting_down: bool,
+    }
+
+    impl Connection {
+        fn graceful_shutdown(mut self: Pin<&mut Self>) {
+            self.shutting_down = true;
+        }
+    }
+
+    impl Future for Connection {
+        type Output = Result<(), String>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            match self.requests {
+                0 => Poll::Ready(Ok(())),
+                _ if self.shutting_down && self.requests > 1 => {
+                    Poll::Ready(Err(format!("{} requests dropped", self.requests - 1)))
+                }
+                _ => {
+                    self.requests -= 1;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    fn connection(requests: u32) -> GracefulConnection<Connection> {
+        GracefulConnection::new(
+            Connection {
+                requests,
+                shutting_down: false,
+            },
+            Connection::graceful_shutdown,
+        )
+    }
+
+    #[::tokio::test]
+    async fn test_close_shuts_down_connection() {
+        connection(1)
+            .use_close_async(|_conn| Box::pin(async {}))
+            .await
+            .unwrap();
+
+        let error = connection(3)
+            .use_close_async(|_conn| Box::pin(async {}))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ShutdownError::Connection("2 requests dropped".to_owned())
+        );
+    }
+
+    #[::tokio::test(start_paused = true)]
+    async fn test_stuck_shutdown_times_out() {
+        let error = GracefulConnection::new(std::future::pending::<Result<(), String>>(), |_| {})
+            .timeout_with(Duration::from_secs(30), ::tokio::time::sleep)
+            .use_close_async(|_conn| Box::pin(async {}))
+            .await
+            .unwrap_err();
+        assert_eq!(error, ShutdownError::TimedOut);
+    }
+
+    #[::tokio::test]
+    async fn test_completed_connection_is_not_polled_again() {
+        let result = connection(3)
+            .use_close_async(|conn| {
+                Box::pin(async move {
+                    let result = (&mut *conn).await;
+                    assert!(conn.is_complete());
+                    result
+                })
+            })
+            .await;
+        assert_eq!(result, Ok(Ok(())));
+    }
+}
@@ -0,0 +1,841 @@
+//! Internal instrumentation hooks shared by the `use_*` combinators.
+//!
+//! Every combinator creates a [`Probe`] when it enters its scope and reports the
+//! lifecycle of the resource through it. The scope is considered left when the probe
+//! is dropped, which also happens when the body panics.
+//!
+//! A scope is only tracked if anything consumes its events: an observer, a threshold, a profiler,
+//! the panic hook, or one of the instrumentation features. Scopes that nothing consumes neither take
+//! an identifier nor a timestamp; they cost a check of a process-wide flag with `std`, and nothing
+//! at all without it.
+
+use crate::observer::{self, Failure, NoopObserver, UseEvent, UseObserver};
+#[cfg(feature = "std")]
+use crate::panic_hook::ActiveScope;
+#[cfg(feature = "std")]
+use crate::profiling::{self, Profile, Profiler};
+use crate::unwind::panic;
+#[cfg(feature = "std")]
+use crate::watchdog::{self, Watch};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::fmt;
+use core::future::{poll_fn, Future};
+use core::num::NonZeroU64;
+use core::panic::Location;
+use core::pin::Pin;
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::AtomicU64;
+#[cfg(feature = "std")]
+use core::sync::atomic::AtomicU8;
+#[cfg(not(target_has_atomic = "64"))]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use core::task::{Context, Poll};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// A process-wide unique identifier of a single use scope.
+///
+/// Identifiers are assigned in increasing order when a scope is entered and are
+/// included in all observer notifications and log records of that scope, so that
+/// acquire, body and close events can be correlated across async task boundaries.
+/// Only scopes whose events are consumed take an identifier, so unobserved scopes do not
+/// contend on the shared counter. On targets without 64-bit atomics, identifiers wrap around
+/// after 2^32 scopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScopeId(NonZeroU64);
+
+impl ScopeId {
+    /// Allocates the next identifier.
+    pub(crate) fn next() -> Self {
+        #[cfg(target_has_atomic = "64")]
+        let id = {
+            static NEXT: AtomicU64 = AtomicU64::new(1);
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        };
+        // Targets without 64-bit atomics, such as most microcontrollers, count in pointer width.
+        #[cfg(not(target_has_atomic = "64"))]
+        let id = {
+            static NEXT: AtomicUsize = AtomicUsize::new(1);
+            NEXT.fetch_add(1, Ordering::Relaxed) as u64
+        };
+        // Skips zero when the counter wraps around, instead of failing the scope.
+        Self(NonZeroU64::new(id).unwrap_or(NonZeroU64::MIN))
+    }
+
+    /// Returns the numeric value of the identifier.
+    pub fn get(self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl fmt::Display for ScopeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// A process-wide consumer of scope events that is installed at runtime.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy)]
+pub(crate) enum Consumer {
+    GlobalObserver = 1,
+    Profiler = 2,
+    PanicHook = 4,
+}
+
+/// The installed [`Consumer`]s, as a bit set that scopes check with a single load.
+#[cfg(feature = "std")]
+static CONSUMERS: AtomicU8 = AtomicU8::new(0);
+
+/// Registers whether `consumer` is installed.
+#[cfg(feature = "std")]
+pub(crate) fn set_consumer(consumer: Consumer, installed: bool) {
+    if installed {
+        CONSUMERS.fetch_or(consumer as u8, Ordering::Relaxed);
+    } else {
+        CONSUMERS.fetch_and(!(consumer as u8), Ordering::Relaxed);
+    }
+}
+
+/// Returns whether anything outside of the options of a scope consumes its events.
+#[inline(always)]
+fn consumed() -> bool {
+    if cfg!(any(
+        feature = "metrics",
+        feature = "leak-detector",
+        feature = "diagnostics",
+        feature = "otel"
+    )) {
+        return true;
+    }
+    #[cfg(feature = "log")]
+    if log::max_level() != log::LevelFilter::Off {
+        return true;
+    }
+    #[cfg(feature = "std")]
+    if CONSUMERS.load(Ordering::Relaxed) != 0 {
+        return true;
+    }
+    false
+}
+
+/// The configuration of a single use scope.
+///
+/// The `local` observer is statically dispatched, unlike the per-call `observer`.
+#[derive(Clone, Copy)]
+pub(crate) struct ScopeOptions<'o, O = NoopObserver> {
+    pub(crate) observer: Option<&'o dyn UseObserver>,
+    pub(crate) local: O,
+    pub(crate) location: &'static Location<'static>,
+    pub(crate) slow_teardown: Option<Duration>,
+    pub(crate) long_hold: Option<Duration>,
+}
+
+impl ScopeOptions<'_> {
+    /// Returns the default options of a scope entered at the caller's location.
+    #[inline]
+    #[track_caller]
+    pub(crate) fn new() -> Self {
+        Self {
+            observer: None,
+            local: NoopObserver,
+            location: Location::caller(),
+            slow_teardown: None,
+            long_hold: None,
+        }
+    }
+}
+
+impl<'o, O> ScopeOptions<'o, O> {
+    /// Separates the statically dispatched observer from the remaining options.
+    pub(crate) fn split_local(self) -> (ScopeOptions<'o>, O) {
+        let local = self.local;
+        let options = ScopeOptions {
+            observer: self.observer,
+            local: NoopObserver,
+            location: self.location,
+            slow_teardown: self.slow_teardown,
+            long_hold: self.long_hold,
+        };
+        (options, local)
+    }
+
+    /// Replaces the statically dispatched observer.
+    pub(crate) fn with_local<P>(self, local: P) -> ScopeOptions<'o, P> {
+        ScopeOptions {
+            observer: self.observer,
+            local,
+            location: self.location,
+            slow_teardown: self.slow_teardown,
+            long_hold: self.long_hold,
+        }
+    }
+}
+
+/// Where a [`Probe`] keeps the state of its scope, if the scope is tracked.
+pub(crate) trait Slot<'o> {
+    /// Creates the slot, calling `track` for the state of the scope if the slot can hold any.
+    fn new(track: impl FnOnce() -> Option<Box<Tracked<'o>>>) -> Self;
+
+    fn get(&self) -> Option<&Tracked<'o>>;
+
+    fn get_mut(&mut self) -> Option<&mut Tracked<'o>>;
+
+    fn take(&mut self) -> Option<Box<Tracked<'o>>>;
+}
+
+/// The state of a scope that may be tracked, boxed so that it only takes a pointer in the frame
+/// or future of the scope.
+pub(crate) type Tracking<'o> = Option<Box<Tracked<'o>>>;
+
+impl<'o> Slot<'o> for Tracking<'o> {
+    #[inline(always)]
+    fn new(track: impl FnOnce() -> Option<Box<Tracked<'o>>>) -> Self {
+        track()
+    }
+
+    #[inline(always)]
+    fn get(&self) -> Option<&Tracked<'o>> {
+        self.as_deref()
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self) -> Option<&mut Tracked<'o>> {
+        self.as_deref_mut()
+    }
+
+    #[inline(always)]
+    fn take(&mut self) -> Option<Box<Tracked<'o>>> {
+        Option::take(self)
+    }
+}
+
+/// The slot of scopes without options in builds in which nothing else could consume their
+/// events, and which are therefore never tracked.
+#[cfg(not(any(feature = "std", feature = "log")))]
+pub(crate) struct Untracked;
+
+#[cfg(not(any(feature = "std", feature = "log")))]
+impl<'o> Slot<'o> for Untracked {
+    #[inline(always)]
+    fn new(_track: impl FnOnce() -> Option<Box<Tracked<'o>>>) -> Self {
+        Untracked
+    }
+
+    #[inline(always)]
+    fn get(&self) -> Option<&Tracked<'o>> {
+        None
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self) -> Option<&mut Tracked<'o>> {
+        None
+    }
+
+    #[inline(always)]
+    fn take(&mut self) -> Option<Box<Tracked<'o>>> {
+        None
+    }
+}
+
+/// The slot of scopes entered through the plain [`Use`](crate::Use) methods, which have no options.
+#[cfg(any(feature = "std", feature = "log"))]
+pub(crate) type PlainSlot = Tracking<'static>;
+#[cfg(not(any(feature = "std", feature = "log")))]
+pub(crate) type PlainSlot = Untracked;
+
+/// A notification forwarded to the observers of a scope.
+#[derive(Clone, Copy)]
+enum Notification {
+    Acquire,
+    BodyEnd,
+    Close,
+    Error(Failure),
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    SlowTeardown(Duration),
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    LongHold(Duration),
+}
+
+impl Notification {
+    #[inline(always)]
+    fn send<O: UseObserver + ?Sized>(self, observer: &O, event: &UseEvent) {
+        match self {
+            Notification::Acquire => observer.on_acquire(event),
+            Notification::BodyEnd => observer.on_body_end(event),
+            Notification::Close => observer.on_close(event),
+            Notification::Error(failure) => observer.on_error(event, failure),
+            Notification::SlowTeardown(teardown) => observer.on_slow_teardown(event, teardown),
+            Notification::LongHold(held) => observer.on_long_hold(event, held),
+        }
+    }
+}
+
+/// Tracks a single use scope for instrumentation purposes.
+pub(crate) struct Probe<'o, O: UseObserver = NoopObserver, S: Slot<'o> = Tracking<'o>> {
+    local: O,
+    slot: S,
+    _observer: core::marker::PhantomData<&'o ()>,
+}
+
+/// The state of a use scope whose events are consumed.
+///
+/// Not generic over the resource or the body, so that entering and leaving tracked scopes is
+/// compiled once.
+pub(crate) struct Tracked<'o> {
+    event: UseEvent,
+    observer: Option<&'o dyn UseObserver>,
+    global: Option<Arc<dyn UseObserver>>,
+    body_ended: bool,
+    failure: Option<Failure>,
+    #[cfg(feature = "otel")]
+    otel: opentelemetry::Context,
+    /// Without `std`, there is no clock, and thus neither timing nor thresholds.
+    #[cfg(feature = "std")]
+    timing: Timing,
+}
+
+/// The timestamps of a scope and the consumers they are taken for.
+#[cfg(feature = "std")]
+struct Timing {
+    slow_teardown: Option<Duration>,
+    long_hold: Option<Duration>,
+    profiler: Option<Profiler>,
+    /// Reports the scope while it still holds its resource past the long hold threshold.
+    watch: Option<Watch>,
+    /// Timestamps are only taken when metrics, a profiler or a threshold consume them.
+    entered: Option<Instant>,
+    body_ended_at: Option<Instant>,
+    torn_down_at: Option<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl Timing {
+    /// Starts the timing of a scope, taking the entry timestamp only if anything consumes it.
+    #[inline(always)]
+    fn new(slow_teardown: Option<Duration>, long_hold: Option<Duration>) -> Self {
+        let profiler = profiling::profiler();
+        let timed = cfg!(feature = "metrics")
+            || profiler.is_some()
+            || slow_teardown.is_some()
+            || long_hold.is_some();
+        Self {
+            slow_teardown,
+            long_hold,
+            profiler,
+            watch: None,
+            entered: timed.then(Instant::now),
+            body_ended_at: None,
+            torn_down_at: None,
+        }
+    }
+}
+
+impl<'o, O: UseObserver> Probe<'o, O> {
+    /// Registers the entry into a use scope for a resource of type `T`.
+    #[inline(always)]
+    pub(crate) fn enter<T: ?Sized>(options: ScopeOptions<'o, O>) -> Self {
+        Self::enter_named(options, core::any::type_name::<T>())
+    }
+
+    /// Registers the entry into a use scope for a resource whose type is only known by name.
+    #[inline(always)]
+    pub(crate) fn enter_named(options: ScopeOptions<'o, O>, type_name: &'static str) -> Self {
+        Self::new(options, type_name)
+    }
+
+    /// Polls the asynchronous body of the scope to completion.
+    ///
+    /// The body is pinned by the caller, so that it is stored only once in the caller's future
+    /// instead of being moved into a wrapping future.
+    #[inline(always)]
+    pub(crate) fn run_async<'a, F: Future + ?Sized>(
+        &'a self,
+        mut body: Pin<&'a mut F>,
+    ) -> impl Future<Output = F::Output> + 'a {
+        poll_fn(move |cx| self.poll_body(body.as_mut(), cx))
+    }
+}
+
+impl<'o, O: UseObserver, S: Slot<'o>> Probe<'o, O, S> {
+    /// Registers the entry into a use scope, keeping its state in a slot of type `S`.
+    #[inline(always)]
+    pub(crate) fn new(options: ScopeOptions<'o, O>, type_name: &'static str) -> Self {
+        let ScopeOptions {
+            observer,
+            local,
+            location,
+            slow_teardown,
+            long_hold,
+        } = options;
+        let observed = observer.is_some()
+            || !local.is_noop()
+            || slow_teardown.is_some()
+            || long_hold.is_some();
+        let slot = S::new(|| {
+            (observed || consumed())
+                .then(|| Tracked::enter(observer, type_name, location, slow_teardown, long_hold))
+        });
+        let probe = Self {
+            local,
+            slot,
+            _observer: core::marker::PhantomData,
+        };
+        probe.notify(Notification::Acquire);
+        probe
+    }
+
+    /// Runs the synchronous body of the scope.
+    ///
+    /// With the `otel` feature, the span of this scope is the active span while the body runs,
+    /// so that nested use scopes become its children.
+    #[inline(always)]
+    pub(crate) fn run<U>(&self, body: impl FnOnce() -> U) -> U {
+        match self.slot.get() {
+            None => body(),
+            Some(tracked) => tracked.run(body),
+        }
+    }
+
+    /// Polls the asynchronous body of the scope once.
+    ///
+    /// With the `otel` feature, the span of this scope is the active span while the body is polled.
+    #[inline(always)]
+    pub(crate) fn poll_body<F: Future + ?Sized>(
+        &self,
+        body: Pin<&mut F>,
+        cx: &mut Context<'_>,
+    ) -> Poll<F::Output> {
+        match self.slot.get() {
+            None => body.poll(cx),
+            Some(tracked) => tracked.run(|| body.poll(cx)),
+        }
+    }
+
+    /// Registers that the body of the scope has finished and teardown begins.
+    #[inline(always)]
+    pub(crate) fn body_end(&mut self) {
+        if let Some(tracked) = self.slot.get_mut() {
+            tracked.body_end(&self.local);
+        }
+    }
+
+    /// Registers that closing the resource panicked after the body had panicked already.
+    ///
+    /// The panic of the closing is suppressed in favor of the body's panic, which stays the
+    /// primary failure of the scope.
+    pub(crate) fn close_panicked(&mut self) {
+        if let Some(tracked) = self.slot.get_mut() {
+            tracked.close_panicked(&self.local);
+        }
+    }
+
+    /// Returns the event describing this scope, if it is tracked.
+    #[cfg(feature = "std")]
+    pub(crate) fn event(&self) -> Option<&UseEvent> {
+        self.slot.get().map(|tracked| &tracked.event)
+    }
+
+    /// Registers that the body of the scope panicked.
+    ///
+    /// Called implicitly when the probe is dropped during unwinding, and explicitly by scopes that
+    /// catch the panic of their body.
+    pub(crate) fn panicked(&mut self) {
+        if let Some(tracked) = self.slot.get_mut() {
+            tracked.panicked(&self.local);
+        }
+    }
+
+    /// Registers that the resource was dropped, either by the body or by the scope itself.
+    #[inline(always)]
+    pub(crate) fn released(&mut self) {
+        if let Some(tracked) = self.slot.get_mut() {
+            tracked.released(&self.local);
+        }
+    }
+
+    /// Registers that the body took ownership of the resource and thus dropped or kept it itself,
+    /// so that the scope has no teardown to time.
+    #[inline(always)]
+    pub(crate) fn handed_over(&mut self) {
+        if let Some(tracked) = self.slot.get_mut() {
+            tracked.handed_over(&self.local);
+        }
+    }
+
+    /// Registers that the resource was explicitly closed, successfully or not.
+    #[inline(always)]
+    pub(crate) fn closed(&mut self, success: bool) {
+        if let Some(tracked) = self.slot.get_mut() {
+            tracked.closed(&self.local, success);
+        }
+    }
+
+    /// Leaves the scope before the probe is dropped.
+    #[inline(always)]
+    pub(crate) fn leave(&mut self) {
+        if let Some(tracked) = self.slot.take() {
+            tracked.leave(&self.local);
+        }
+    }
+
+    /// Forwards a notification to the observers of a tracked scope.
+    #[inline(always)]
+    fn notify(&self, notification: Notification) {
+        if let Some(tracked) = self.slot.get() {
+            tracked.notify(&self.local, notification);
+        }
+    }
+}
+
+impl<'o, O: UseObserver, S: Slot<'o>> Drop for Probe<'o, O, S> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+impl<'o> Tracked<'o> {
+    /// Starts tracking a scope that was just entered.
+    #[cold]
+    #[inline(never)]
+    fn enter(
+        observer: Option<&'o dyn UseObserver>,
+        type_name: &'static str,
+        location: &'static Location<'static>,
+        slow_teardown: Option<Duration>,
+        long_hold: Option<Duration>,
+    ) -> Box<Self> {
+        let id = ScopeId::next();
+        #[cfg(feature = "log")]
+        log::trace!("entering use scope {id} for `{type_name}` at {location}");
+
+        #[cfg(any(feature = "leak-detector", feature = "diagnostics"))]
+        crate::registry::register(id, type_name, location);
+
+        #[cfg(not(feature = "std"))]
+        let _ = (slow_teardown, long_hold);
+        #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+        let mut tracked = Box::new(Self {
+            event: UseEvent::new(id, type_name, location),
+            observer,
+            global: observer::global_observer(),
+            body_ended: false,
+            failure: None,
+            #[cfg(feature = "otel")]
+            otel: otel::start(id, type_name, location),
+            #[cfg(feature = "std")]
+            timing: Timing::new(slow_teardown, long_hold),
+        });
+        #[cfg(feature = "std")]
+        if let (Some(threshold), Some(entered)) = (long_hold, tracked.timing.entered) {
+            let watch = watchdog::watch(
+                tracked.event.clone(),
+                entered,
+                threshold,
+                tracked.global.clone(),
+            );
+            tracked.timing.watch = Some(watch);
+        }
+        tracked
+    }
+
+    /// Runs or polls the body of the scope.
+    #[inline(always)]
+    fn run<U>(&self, body: impl FnOnce() -> U) -> U {
+        #[cfg(feature = "otel")]
+        let _guard = self.otel.clone().attach();
+        #[cfg(feature = "std")]
+        let _active = ActiveScope::enter(&self.event);
+        body()
+    }
+
+    fn body_end<O: UseObserver>(&mut self, local: &O) {
+        self.body_ended = true;
+
+        #[cfg(feature = "std")]
+        if let Some(entered) = self.timing.entered {
+            let now = Instant::now();
+            #[cfg(feature = "metrics")]
+            metrics::histogram!("use_with.body.duration", "resource" => self.event.resource_type())
+                .record(now.duration_since(entered));
+            #[cfg(not(feature = "metrics"))]
+            let _ = entered;
+            self.timing.body_ended_at = Some(now);
+        }
+
+        self.notify(local, Notification::BodyEnd);
+    }
+
+    fn close_panicked<O: UseObserver>(&mut self, local: &O) {
+        #[cfg(feature = "log")]
+        log::warn!(
+            "suppressed panic while closing `{}` after the body of use scope {} at {} panicked",
+            self.event.resource_type(),
+            self.event.id(),
+            self.event.location()
+        );
+        #[cfg(feature = "metrics")]
+        metrics::counter!("use_with.failures", "resource" => self.event.resource_type(), "kind" => "close_panic")
+            .increment(1);
+
+        self.failure.get_or_insert(Failure::ClosePanic);
+        self.notify(local, Notification::Error(Failure::ClosePanic));
+    }
+
+    fn panicked<O: UseObserver>(&mut self, local: &O) {
+        #[cfg(feature = "metrics")]
+        metrics::counter!("use_with.failures", "resource" => self.event.resource_type(), "kind" => "panic")
+            .increment(1);
+
+        self.failure = Some(Failure::Panic);
+        self.notify(local, Notification::Error(Failure::Panic));
+    }
+
+    fn released<O: UseObserver>(&mut self, local: &O) {
+        #[cfg(feature = "std")]
+        if let Some(body_ended) = self.timing.body_ended_at {
+            let now = Instant::now();
+            self.check_teardown(local, now.duration_since(body_ended));
+            self.check_hold(local, now);
+            self.timing.torn_down_at = Some(now);
+        }
+        self.notify(local, Notification::Close);
+    }
+
+    fn handed_over<O: UseObserver>(&mut self, local: &O) {
+        #[cfg(feature = "std")]
+        {
+            if self.timing.slow_teardown.is_some() {
+                self.ignored_slow_teardown();
+            }
+            if self.timing.body_ended_at.is_some() {
+                self.check_hold(local, Instant::now());
+            }
+        }
+        self.notify(local, Notification::Close);
+    }
+
+    /// Reports a slow teardown threshold on a scope whose body took ownership of the resource,
+    /// which the scope cannot time, and rejects it in debug builds.
+    #[cfg(feature = "std")]
+    #[cold]
+    fn ignored_slow_teardown(&self) {
+        #[cfg(feature = "log")]
+        log::warn!(
+            "the slow teardown threshold of use scope {} at {} is ignored, since its body takes ownership of `{}`",
+            self.event.id(),
+            self.event.location(),
+            self.event.resource_type()
+        );
+        debug_assert!(
+            false,
+            "`slow_teardown` cannot time the teardown of `{}` in the use scope at {}, since its body takes ownership of the resource",
+            self.event.resource_type(),
+            self.event.location()
+        );
+    }
+
+    fn closed<O: UseObserver>(&mut self, local: &O, success: bool) {
+        #[cfg(feature = "std")]
+        if let Some(body_ended) = self.timing.body_ended_at {
+            let now = Instant::now();
+            #[cfg(feature = "metrics")]
+            metrics::histogram!("use_with.close.duration", "resource" => self.event.resource_type())
+                .record(now.duration_since(body_ended));
+            self.check_teardown(local, now.duration_since(body_ended));
+            self.check_hold(local, now);
+            self.timing.torn_down_at = Some(now);
+        }
+
+        if success {
+            self.notify(local, Notification::Close);
+        } else {
+            #[cfg(feature = "log")]
+            log::debug!(
+                "closing `{}` failed in use scope {} at {}",
+                self.event.resource_type(),
+                self.event.id(),
+                self.event.location()
+            );
+            #[cfg(feature = "metrics")]
+            metrics::counter!("use_with.failures", "resource" => self.event.resource_type(), "kind" => "close")
+                .increment(1);
+
+            // A panic of the body remains the primary failure of the scope.
+            self.failure.get_or_insert(Failure::Close);
+            self.notify(local, Notification::Error(Failure::Close));
+        }
+    }
+
+    /// Reports a teardown that took longer than the configured threshold.
+    #[cfg(feature = "std")]
+    fn check_teardown<O: UseObserver>(&self, local: &O, teardown: Duration) {
+        match self.timing.slow_teardown {
+            Some(threshold) if teardown > threshold => {}
+            _ => return,
+        }
+
+        #[cfg(feature = "log")]
+        log::warn!(
+            "closing `{}` in use scope {} at {} took {teardown:?}",
+            self.event.resource_type(),
+            self.event.id(),
+            self.event.location()
+        );
+        #[cfg(feature = "metrics")]
+        metrics::counter!("use_with.slow_teardowns", "resource" => self.event.resource_type())
+            .increment(1);
+
+        self.notify(local, Notification::SlowTeardown(teardown));
+    }
+
+    /// Reports a resource that was held for longer than the configured threshold.
+    ///
+    /// Checked once, when the resource is released or the scope is left by a panic. If the
+    /// watchdog already reported the scope while the resource was held, only the observers of
+    /// the scope itself are notified, since the watchdog cannot reach them.
+    #[cfg(feature = "std")]
+    fn check_hold<O: UseObserver>(&mut self, local: &O, released: Instant) {
+        let watch = self.timing.watch.take();
+        let (Some(threshold), Some(entered)) = (self.timing.long_hold.take(), self.timing.entered)
+        else {
+            return;
+        };
+        let held = released.duration_since(entered);
+        if held <= threshold {
+            return;
+        }
+        if !watch.as_ref().map_or(true, Watch::claim) {
+            self.notify_scoped(local, Notification::LongHold(held));
+            return;
+        }
+
+        #[cfg(feature = "log")]
+        log::warn!(
+            "use scope {} at {} held `{}` for {held:?}, longer than {threshold:?}",
+            self.event.id(),
+            self.event.location(),
+            self.event.resource_type()
+        );
+        #[cfg(feature = "metrics")]
+        metrics::counter!("use_with.long_holds", "resource" => self.event.resource_type())
+            .increment(1);
+
+        self.notify(local, Notification::LongHold(held));
+    }
+
+    /// Finishes tracking the scope when it is left.
+    ///
+    /// Takes the box, so that freeing it stays out of the inlined code of the scope.
+    #[cold]
+    #[inline(never)]
+    #[allow(clippy::boxed_local)]
+    fn leave<O: UseObserver>(mut self: Box<Self>, local: &O) {
+        #[cfg(any(feature = "leak-detector", feature = "diagnostics"))]
+        crate::registry::unregister(self.event.id());
+
+        // Scopes left by a panic never release their resource explicitly.
+        #[cfg(feature = "std")]
+        self.check_hold(local, Instant::now());
+
+        if !self.body_ended && self.failure.is_none() && panic::panicking() {
+            self.panicked(local);
+        }
+
+        #[cfg(feature = "otel")]
+        otel::end(&self.otel, self.failure);
+
+        #[cfg(feature = "std")]
+        if let (Some(profiler), Some(entered), Some(body_ended)) = (
+            &self.timing.profiler,
+            self.timing.entered,
+            self.timing.body_ended_at,
+        ) {
+            profiler(&Profile {
+                id: self.event.id(),
+                resource_type: self.event.resource_type(),
+                location: self.event.location(),
+                body: body_ended.duration_since(entered),
+                teardown: self
+                    .timing
+                    .torn_down_at
+                    .map(|torn_down| torn_down.duration_since(body_ended)),
+            });
+        }
+
+        #[cfg(feature = "log")]
+        log::trace!(
+            "leaving use scope {} for `{}`",
+            self.event.id(),
+            self.event.resource_type()
+        );
+    }
+
+    /// Forwards a notification to the statically dispatched, the per-call and the global observer.
+    #[inline(always)]
+    fn notify<O: UseObserver>(&self, local: &O, notification: Notification) {
+        self.notify_scoped(local, notification);
+        if let Some(observer) = &self.global {
+            notification.send(observer.as_ref(), &self.event);
+        }
+    }
+
+    /// Forwards a notification to the statically dispatched and the per-call observer only.
+    #[inline(always)]
+    fn notify_scoped<O: UseObserver>(&self, local: &O, notification: Notification) {
+        notification.send(local, &self.event);
+        if let Some(observer) = self.observer {
+            notification.send(observer, &self.event);
+        }
+    }
+}
+
+/// OpenTelemetry spans for use scopes.
+#[cfg(feature = "otel")]
+mod otel {
+    use super::ScopeId;
+    use crate::observer::Failure;
+    use opentelemetry::trace::{Status, TraceContextExt, Tracer};
+    use opentelemetry::{global, Context, KeyValue};
+    use std::panic::Location;
+
+    /// Starts the span of a use scope as a child of the currently active span.
+    pub(super) fn start(
+        id: ScopeId,
+        resource_type: &'static str,
+        location: &'static Location<'static>,
+    ) -> Context {
+        let tracer = global::tracer("use-with");
+        let span = tracer
+            .span_builder("use_with")
+            .with_attributes([
+                KeyValue::new("use_with.resource.kind", resource_type),
+                KeyValue::new("use_with.scope.id", id.get() as i64),
+                KeyValue::new("code.file.path", location.file()),
+                KeyValue::new("code.line.number", i64::from(location.line())),
+            ])
+            .start_with_context(&tracer, &Context::current());
+        Context::current_with_span(span)
+    }
+
+    /// Records the outcome of the use scope and ends its span.
+    pub(super) fn end(cx: &Context, failure: Option<Failure>) {
+        let span = cx.span();
+        let outcome = match failure {
+            None => "ok",
+            Some(Failure::Panic) => "panic",
+            Some(Failure::Close) => "close_error",
+            Some(Failure::ClosePanic) => "close_panic",
+            Some(Failure::DropPanic) => "drop_panic",
+        };
+        span.set_attribute(KeyValue::new("use_with.outcome", outcome));
+        if failure.is_some() {
+            span.set_status(Status::error(outcome));
+        }
+        span.end();
+    }
+}
@@ -0,0 +1,278 @@
+//! Adapters giving `std::io` types an explicit, fallible teardown.
+//!
+//! Dropping a writer discards any error that occurs while flushing its buffers or syncing its
+//! contents to disk, which turns an I/O failure into silent data loss. The adapters in this module
+//! implement [`Close`] instead, so that [`Use::use_close`](crate::Use::use_close) reports such
+//! errors to the caller.
+
+use crate::{Close, Use};
+use std::fs::File;
+use std::io::{self, BufWriter, LineWriter, Write};
+use std::path::Path;
+
+/// A writer whose written data can be made durable on its storage device.
+///
+/// Implemented for [`File`] and for buffering writers around durable writers, which flush their
+/// buffer before syncing the underlying writer.
+pub trait Durable: Write {
+    /// Flushes all buffered data and waits until it reached the storage device.
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+impl Durable for File {
+    fn sync(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.sync_all()
+    }
+}
+
+impl<W: Durable> Durable for BufWriter<W> {
+    fn sync(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.get_mut().sync()
+    }
+}
+
+impl<W: Durable> Durable for LineWriter<W> {
+    fn sync(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.get_mut().sync()
+    }
+}
+
+/// A writer that is flushed and synced to disk when it is closed.
+///
+/// Closing a `SyncedFile` flushes all buffered data and calls [`File::sync_all`] on the
+/// underlying file, returning the first error that occurs. This guarantees that the data has
+/// reached the storage device once [`Use::use_close`](crate::Use::use_close) returns
+/// successfully. Dropping a `SyncedFile` without closing it neither flushes nor syncs beyond
+/// what the wrapped writer does on its own.
+///
+/// # Examples
+/// ```rust
+/// use std::io::Write;
+/// use use_with::io::SyncedFile;
+/// use use_with::Use;
+///
+/// let path = std::env::temp_dir().join("use-with-synced-file-example.txt");
+///
+/// SyncedFile::create(&path)?.use_close(|file| writeln!(file, "committed"))??;
+///
+/// assert_eq!(std::fs::read_to_string(&path)?, "committed\n");
+/// # std::fs::remove_file(&path)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct SyncedFile<W: Durable = File> {
+    writer: W,
+}
+
+impl SyncedFile {
+    /// Creates or truncates the file at `path` for writing.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        File::create(path).map(Self::new)
+    }
+}
+
+impl<W: Durable> SyncedFile<W> {
+    /// Wraps a durable writer, such as a [`File`] or a [`BufWriter`] around one.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Returns a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the wrapped writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the wrapped writer without flushing or syncing it.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Durable> Write for SyncedFile<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.writer.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Durable> Close for SyncedFile<W> {
+    type Error = io::Error;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        self.writer.sync()
+    }
+}
+
+/// A buffered writer that reports errors of its final flush when it is closed.
+///
+/// [`BufWriter`] flushes its buffer when dropped, but ignores any error of that flush. Closing a
+/// `Buffered` writer flushes the buffer and the wrapped writer instead, and returns the error.
+///
+/// See [`use_buffered`] for running a closure on a buffered writer.
+#[derive(Debug)]
+pub struct Buffered<W: Write> {
+    writer: BufWriter<W>,
+}
+
+impl<W: Write> Buffered<W> {
+    /// Wraps a writer into a [`BufWriter`] with the default buffer capacity.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    /// Wraps a writer into a [`BufWriter`] with a buffer of at least `capacity` bytes.
+    pub fn with_capacity(capacity: usize, writer: W) -> Self {
+        Self {
+            writer: BufWriter::with_capacity(capacity, writer),
+        }
+    }
+
+    /// Returns a reference to the buffering writer.
+    pub fn get_ref(&self) -> &BufWriter<W> {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the buffering writer.
+    pub fn get_mut(&mut self) -> &mut BufWriter<W> {
+        &mut self.writer
+    }
+
+    /// Flushes the buffer and returns the wrapped writer.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+    }
+}
+
+impl<W: Write> Write for Buffered<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.writer.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Close for Buffered<W> {
+    type Error = io::Error;
+
+    fn close(self) -> Result<(), Self::Error> {
+        self.into_inner()?.flush()
+    }
+}
+
+/// Executes a closure on a buffered writer around `writer` and flushes it afterwards.
+///
+/// This is a shorthand for [`Use::use_close`] on a [`Buffered`] writer. Unlike dropping a
+/// [`BufWriter`], errors of the final flush are returned rather than ignored.
+///
+/// # Returns
+/// - `Ok(U)` with the result of the closure `f` if flushing succeeded.
+/// - `Err(io::Error)` if flushing the buffer or the wrapped writer failed.
+///
+/// # Examples
+/// ```rust
+/// use std::io::Write;
+/// use use_with::io::use_buffered;
+///
+/// let mut out = Vec::new();
+///
+/// use_buffered(&mut out, |w| {
+///     for i in 0..3 {
+///         writeln!(w, "line {i}")?;
+///     }
+///     Ok::<_, std::io::Error>(())
+/// })??;
+///
+/// assert_eq!(out, b"line 0\nline 1\nline 2\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[track_caller]
+pub fn use_buffered<W, U, F>(writer: W, f: F) -> io::Result<U>
+where
+    W: Write,
+    F: FnOnce(&mut Buffered<W>) -> U,
+{
+    Buffered::new(writer).use_close(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer that accepts everything but fails to flush.
+    struct FailingFlush(Vec<u8>);
+
+    impl Write for FailingFlush {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::other("disk full"))
+        }
+    }
+
+    impl Durable for FailingFlush {
+        fn sync(&mut self) -> io::Result<()> {
+            self.flush()
+        }
+    }
+
+    #[test]
+    fn test_buffered_data_is_synced_on_close() {
+        let path = std::env::temp_dir().join(format!("use-with-synced-{}.txt", std::process::id()));
+        let file = File::create(&path).unwrap();
+
+        SyncedFile::new(BufWriter::new(file))
+            .use_close(|file| file.write_all(b"durable"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"durable");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_flush_error_is_reported() {
+        let error = SyncedFile::new(FailingFlush(Vec::new()))
+            .use_close(|writer| writer.write_all(b"lost"))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "disk full");
+    }
+
+    #[test]
+    fn test_use_buffered_reports_flush_error() {
+        let result = use_buffered(FailingFlush(Vec::new()), |w| w.write_all(b"buffered"));
+        assert_eq!(result.unwrap_err().to_string(), "disk full");
+
+        let mut out = Vec::new();
+        let written = use_buffered(&mut out, |w| w.write(b"buffered").unwrap());
+        assert_eq!(written.unwrap(), 8);
+        assert_eq!(out, b"buffered");
+    }
+}
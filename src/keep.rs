@@ -0,0 +1,210 @@
+//! Slots that keep resources alive beyond the scope that used them.
+
+use crate::Use;
+use std::ops::{Deref, DerefMut};
+
+/// A caller-provided slot that a use scope can move its resource into instead of dropping it.
+///
+/// Decisions about whether a resource is worth keeping, such as caching a connection after a
+/// successful request or promoting a warmed-up buffer, are often made in the middle of the
+/// scope that uses it. The closure captures the `Keeper` and calls [`keep`](Self::keep) to
+/// transfer the resource; resources that are not kept are dropped at the end of the scope as usual.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{Keeper, Use};
+///
+/// struct Connection {
+///     healthy: bool,
+/// }
+///
+/// let mut keeper = Keeper::new();
+/// let status = Connection { healthy: true }.use_with(|conn| {
+///     let status = if conn.healthy { 200 } else { 503 };
+///     if conn.healthy {
+///         keeper.keep(conn);
+///     }
+///     status
+/// });
+///
+/// assert_eq!(status, 200);
+/// assert!(keeper.take().is_some_and(|conn| conn.healthy));
+/// ```
+#[derive(Debug)]
+pub struct Keeper<T> {
+    kept: Option<T>,
+}
+
+impl<T> Keeper<T> {
+    /// Creates an empty keeper.
+    pub const fn new() -> Self {
+        Self { kept: None }
+    }
+
+    /// Moves a resource into the keeper, returning the resource that was kept before, if any.
+    pub fn keep(&mut self, resource: T) -> Option<T> {
+        self.kept.replace(resource)
+    }
+
+    /// Returns whether a resource is kept.
+    pub fn is_kept(&self) -> bool {
+        self.kept.is_some()
+    }
+
+    /// Returns a reference to the kept resource, if any.
+    pub fn get(&self) -> Option<&T> {
+        self.kept.as_ref()
+    }
+
+    /// Returns a mutable reference to the kept resource, if any.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.kept.as_mut()
+    }
+
+    /// Takes the kept resource out of the keeper, leaving it empty.
+    pub fn take(&mut self) -> Option<T> {
+        self.kept.take()
+    }
+
+    /// Returns the kept resource, if any.
+    pub fn into_inner(self) -> Option<T> {
+        self.kept
+    }
+
+    /// Leases the kept resource, which returns to the keeper when the lease is dropped.
+    ///
+    /// Returns `None` if no resource is kept. While leased, the keeper cannot be accessed.
+    pub fn lease(&mut self) -> Option<Lease<'_, T>> {
+        let resource = self.kept.take()?;
+        Some(Lease {
+            resource: Some(resource),
+            owner: &mut self.kept,
+        })
+    }
+}
+
+impl<T> Default for Keeper<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A resource leased from a [`Keeper`], which returns to the keeper when the lease is dropped.
+///
+/// A lease is a middle ground between transferring ownership and borrowing: the resource is
+/// moved out of its owner and can be used like an owned value, but it finds its way back even if
+/// the code using it panics. Use [`Lease::detach`] to take over ownership for good.
+///
+/// # Examples
+/// ```rust
+/// use use_with::Keeper;
+///
+/// let mut keeper = Keeper::new();
+/// keeper.keep(vec![1, 2, 3]);
+///
+/// let sum = keeper.lease().unwrap().use_with(|numbers| {
+///     numbers.push(4);
+///     numbers.iter().sum::<i32>()
+/// });
+///
+/// assert_eq!(sum, 10);
+/// assert_eq!(keeper.get(), Some(&vec![1, 2, 3, 4]));
+/// ```
+#[derive(Debug)]
+pub struct Lease<'a, T> {
+    resource: Option<T>,
+    owner: &'a mut Option<T>,
+}
+
+impl<T> Lease<'_, T> {
+    /// Executes a closure on the leased resource and returns the resource to its owner afterwards.
+    ///
+    /// The resource is returned even if the closure panics.
+    #[track_caller]
+    pub fn use_with<U, F: FnOnce(&mut T) -> U>(self, f: F) -> U {
+        Use::use_with(self, |mut lease| f(&mut lease))
+    }
+
+    /// Takes over ownership of the resource instead of returning it to its owner.
+    ///
+    /// This is an associated function to avoid conflicts with methods of the resource.
+    pub fn detach(mut this: Self) -> T {
+        this.resource
+            .take()
+            .expect("resource is present until dropped")
+    }
+}
+
+impl<T> Deref for Lease<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.resource
+            .as_ref()
+            .expect("resource is present until dropped")
+    }
+}
+
+impl<T> DerefMut for Lease<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.resource
+            .as_mut()
+            .expect("resource is present until dropped")
+    }
+}
+
+impl<T> Drop for Lease<'_, T> {
+    fn drop(&mut self) {
+        if let Some(resource) = self.resource.take() {
+            *self.owner = Some(resource);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropCounter;
+    use std::panic;
+
+    #[test]
+    fn test_kept_resource_outlives_scope() {
+        let counter = DropCounter::new();
+        let mut keeper = Keeper::new();
+
+        counter
+            .probe()
+            .use_with(|probe| assert!(keeper.keep(probe).is_none()));
+        assert_eq!(counter.count(), 0);
+        assert!(keeper.is_kept());
+
+        counter.probe().use_with(|_probe| ());
+        assert_eq!(counter.count(), 1);
+
+        let previous = counter.probe().use_with(|probe| keeper.keep(probe));
+        drop(previous);
+        assert_eq!(counter.count(), 2);
+
+        drop(keeper);
+        assert_eq!(counter.count(), 3);
+    }
+
+    #[test]
+    fn test_lease_returns_after_panic() {
+        let mut keeper = Keeper::new();
+        keeper.keep(String::from("leased"));
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            keeper.lease().unwrap().use_with(|value| {
+                value.push('!');
+                panic!("interrupted");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(keeper.get().map(String::as_str), Some("leased!"));
+
+        let value = Lease::detach(keeper.lease().unwrap());
+        assert_eq!(value, "leased!");
+        assert!(keeper.lease().is_none());
+    }
+}
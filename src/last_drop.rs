@@ -0,0 +1,276 @@
+//! Cleanup of shared resources when their last reference is dropped.
+
+use crate::RefCellUseExt;
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// A resource whose cleanup closure runs when the resource is dropped.
+///
+/// Shared through an [`Arc`] or [`Rc`], the closure runs exactly once when the last strong reference is
+/// dropped, on the thread that drops it. It receives the resource by value, so teardown that
+/// consumes the resource, such as [`Close::close`](crate::Close::close), can be performed.
+/// Asynchronous teardown cannot run in a destructor; instead, the closure can queue the resource
+/// for it, e.g. by spawning a task or sending the resource to a channel.
+///
+/// Created by [`ArcUseExt::use_on_last_drop`] and [`RcUseExt::use_on_last_drop`].
+pub struct LastDrop<T, F: FnOnce(T)> {
+    resource: Option<T>,
+    cleanup: Option<F>,
+}
+
+impl<T, F: FnOnce(T)> LastDrop<T, F> {
+    /// Wraps a resource, running `cleanup` on it when the wrapper is dropped.
+    pub fn new(resource: T, cleanup: F) -> Self {
+        Self {
+            resource: Some(resource),
+            cleanup: Some(cleanup),
+        }
+    }
+
+    /// Returns the resource without running the cleanup closure.
+    ///
+    /// This is an associated function to avoid conflicts with methods of the resource.
+    pub fn into_inner(mut this: Self) -> T {
+        this.cleanup = None;
+        this.resource
+            .take()
+            .expect("resource is present until dropped")
+    }
+}
+
+impl<T, F: FnOnce(T)> Deref for LastDrop<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.resource
+            .as_ref()
+            .expect("resource is present until dropped")
+    }
+}
+
+impl<T: fmt::Debug, F: FnOnce(T)> fmt::Debug for LastDrop<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LastDrop").field(&self.resource).finish()
+    }
+}
+
+impl<T, F: FnOnce(T)> Drop for LastDrop<T, F> {
+    fn drop(&mut self) {
+        if let (Some(resource), Some(cleanup)) = (self.resource.take(), self.cleanup.take()) {
+            cleanup(resource);
+        }
+    }
+}
+
+/// Creates [`Arc`]s that run a cleanup closure when their last strong reference is dropped.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+/// use use_with::ArcUseExt;
+///
+/// static CLOSED: AtomicBool = AtomicBool::new(false);
+///
+/// let connection = Arc::use_on_last_drop(String::from("connection"), |conn| {
+///     println!("closing {conn}");
+///     CLOSED.store(true, Ordering::SeqCst);
+/// });
+///
+/// let worker = std::thread::spawn({
+///     let connection = Arc::clone(&connection);
+///     move || connection.len()
+/// });
+/// drop(connection);
+/// assert_eq!(worker.join().unwrap(), 10);
+///
+/// // The last reference was dropped by either thread.
+/// assert!(CLOSED.load(Ordering::SeqCst));
+/// ```
+pub trait ArcUseExt<T> {
+    /// Wraps a resource into an `Arc` that runs `cleanup` once its last strong reference is dropped.
+    fn use_on_last_drop<F: FnOnce(T)>(resource: T, cleanup: F) -> Arc<LastDrop<T, F>>;
+}
+
+impl<T> ArcUseExt<T> for Arc<T> {
+    fn use_on_last_drop<F: FnOnce(T)>(resource: T, cleanup: F) -> Arc<LastDrop<T, F>> {
+        Arc::new(LastDrop::new(resource, cleanup))
+    }
+}
+
+/// Creates [`Rc`]s that run a cleanup closure when their last strong reference is dropped.
+///
+/// This is the single-threaded counterpart of [`ArcUseExt`], for resources shared between
+/// callbacks of UI or WebAssembly code. See [`LocalUse`] for scoped access to such resources.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+/// use use_with::RcUseExt;
+///
+/// let closed = Cell::new(false);
+/// let canvas = Rc::use_on_last_drop("canvas", |_canvas| closed.set(true));
+///
+/// let on_resize = {
+///     let canvas = Rc::clone(&canvas);
+///     move || canvas.len()
+/// };
+/// drop(canvas);
+/// assert_eq!(on_resize(), 6);
+/// assert!(!closed.get());
+///
+/// drop(on_resize);
+/// assert!(closed.get());
+/// ```
+pub trait RcUseExt<T> {
+    /// Wraps a resource into an `Rc` that runs `cleanup` once its last strong reference is dropped.
+    fn use_on_last_drop<F: FnOnce(T)>(resource: T, cleanup: F) -> Rc<LastDrop<T, F>>;
+}
+
+impl<T> RcUseExt<T> for Rc<T> {
+    fn use_on_last_drop<F: FnOnce(T)>(resource: T, cleanup: F) -> Rc<LastDrop<T, F>> {
+        Rc::new(LastDrop::new(resource, cleanup))
+    }
+}
+
+/// The cleanup closure of a [`LocalUse`].
+type LocalCleanup<T> = Box<dyn FnOnce(RefCell<T>)>;
+
+/// A resource shared by handles on a single thread, with scoped access and a final teardown hook.
+///
+/// `LocalUse` is the single-threaded counterpart of [`SharedUse`](crate::SharedUse). Clones share
+/// the same resource, which is dropped, after running the cleanup closure given to
+/// [`with_cleanup`](Self::with_cleanup), once the last handle is dropped. Access is scoped
+/// like with [`RefCellUseExt`]: [`use_read`](Self::use_read) lends the resource immutably and
+/// [`use_write`](Self::use_write) mutably, so no borrow outlives its closure.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+/// use use_with::LocalUse;
+///
+/// let flushed = Rc::new(Cell::new(0));
+/// let log = LocalUse::with_cleanup(Vec::new(), {
+///     let flushed = Rc::clone(&flushed);
+///     move |entries: Vec<&str>| flushed.set(entries.len())
+/// });
+///
+/// let on_click = {
+///     let log = log.clone();
+///     move || log.use_write(|entries| entries.push("clicked"))
+/// };
+/// on_click();
+/// on_click();
+/// assert_eq!(log.use_read(|entries| entries.len()), 2);
+///
+/// drop(log);
+/// drop(on_click);
+/// assert_eq!(flushed.get(), 2);
+/// ```
+pub struct LocalUse<T> {
+    shared: Rc<LastDrop<RefCell<T>, LocalCleanup<T>>>,
+}
+
+impl<T: 'static> LocalUse<T> {
+    /// Wraps a resource to be shared by handles on the current thread.
+    pub fn new(resource: T) -> Self {
+        Self::with_cleanup(resource, drop)
+    }
+
+    /// Wraps a resource that is passed to `cleanup` once the last handle is dropped.
+    pub fn with_cleanup<F: FnOnce(T) + 'static>(resource: T, cleanup: F) -> Self {
+        let cleanup: LocalCleanup<T> = Box::new(move |cell| cleanup(cell.into_inner()));
+        Self {
+            shared: Rc::new(LastDrop::new(RefCell::new(resource), cleanup)),
+        }
+    }
+}
+
+impl<T> LocalUse<T> {
+    /// Executes a closure with shared access to the resource.
+    ///
+    /// # Panics
+    /// Panics if the resource is currently used by a [`use_write`](Self::use_write) scope.
+    #[track_caller]
+    pub fn use_read<U, F: FnOnce(&T) -> U>(&self, f: F) -> U {
+        self.shared.use_borrow(f)
+    }
+
+    /// Executes a closure with exclusive access to the resource.
+    ///
+    /// # Panics
+    /// Panics if the resource is currently used by another scope.
+    #[track_caller]
+    pub fn use_write<U, F: FnOnce(&mut T) -> U>(&self, f: F) -> U {
+        self.shared.use_borrow_mut(f)
+    }
+
+    /// Returns the number of handles sharing the resource.
+    pub fn handle_count(&self) -> usize {
+        Rc::strong_count(&self.shared)
+    }
+}
+
+impl<T> Clone for LocalUse<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Rc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for LocalUse<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LocalUse").field(&*self.shared).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropSpy;
+
+    #[test]
+    fn test_cleanup_runs_once_on_last_drop() {
+        let spy = DropSpy::new();
+        let shared = Arc::use_on_last_drop(spy.probe("resource"), |probe| {
+            assert_eq!(spy.drop_count("resource"), 0);
+            drop(probe);
+        });
+        let clones: Vec<_> = (0..3).map(|_| Arc::clone(&shared)).collect();
+
+        drop(shared);
+        drop(clones);
+        assert_eq!(spy.drop_count("resource"), 1);
+    }
+
+    #[test]
+    fn test_into_inner_skips_cleanup() {
+        let shared = Arc::use_on_last_drop(42, |_| panic!("cleanup must not run"));
+        let wrapper = Arc::into_inner(shared).unwrap();
+        assert_eq!(LastDrop::into_inner(wrapper), 42);
+    }
+
+    #[test]
+    fn test_local_use_cleans_up_after_last_handle() {
+        let spy = DropSpy::new();
+        let first = LocalUse::with_cleanup(vec![spy.probe("resource")], |probes| {
+            assert_eq!(probes.len(), 2);
+        });
+        let second = first.clone();
+        assert_eq!(first.handle_count(), 2);
+
+        second.use_write(|probes| probes.push(spy.probe("added")));
+        drop(first);
+        assert_eq!(spy.drop_count("resource"), 0);
+
+        drop(second);
+        assert_eq!(spy.drop_count("resource"), 1);
+        assert_eq!(spy.drop_count("added"), 1);
+    }
+}
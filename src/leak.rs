@@ -0,0 +1,120 @@
+//! Detection of resources that are held for suspiciously long.
+//!
+//! With the `leak-detector` feature enabled, every use scope registers itself in a global
+//! registry while it holds its resource. [`leak_report`] lists the scopes that have been alive
+//! for longer than a threshold, which is invaluable for finding forgotten resources in
+//! long-running servers, e.g. async tasks that never complete while holding a connection.
+//!
+//! Each registered scope captures a [`Backtrace`] of its creation. As with
+//! [`Backtrace::capture`], backtraces are only resolved if the `RUST_BACKTRACE` or
+//! `RUST_LIB_BACKTRACE` environment variables enable them.
+//!
+//! # Examples
+//! ```rust
+//! use std::time::Duration;
+//! use use_with::{leak, Use};
+//!
+//! struct Connection;
+//!
+//! Connection.use_with(|_conn| {
+//!     std::thread::sleep(Duration::from_millis(20));
+//!     let leaks = leak::leak_report(Duration::from_millis(10));
+//!     assert_eq!(leaks.len(), 1);
+//!     assert!(leaks[0].resource_type().ends_with("Connection"));
+//! });
+//!
+//! assert!(leak::leak_report(Duration::ZERO).is_empty());
+//! ```
+
+use crate::registry;
+use crate::ScopeId;
+use std::backtrace::Backtrace;
+use std::panic::Location;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A use scope that has been holding its resource for longer than the requested threshold.
+#[derive(Debug, Clone)]
+pub struct LeakReport {
+    id: ScopeId,
+    resource_type: &'static str,
+    location: &'static Location<'static>,
+    held_for: Duration,
+    backtrace: Arc<Backtrace>,
+}
+
+impl LeakReport {
+    /// Returns the unique identifier of the use scope.
+    pub fn id(&self) -> ScopeId {
+        self.id
+    }
+
+    /// Returns the type name of the held resource.
+    pub fn resource_type(&self) -> &'static str {
+        self.resource_type
+    }
+
+    /// Returns the source location at which the use scope was entered.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns for how long the resource has been held.
+    pub fn held_for(&self) -> Duration {
+        self.held_for
+    }
+
+    /// Returns the backtrace captured when the use scope was entered.
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+}
+
+/// Returns all use scopes that have been holding their resource for longer than `threshold`,
+/// ordered from the oldest to the youngest scope.
+pub fn leak_report(threshold: Duration) -> Vec<LeakReport> {
+    registry::live()
+        .iter()
+        .filter_map(|(id, entry)| {
+            let held_for = entry.acquired.elapsed();
+            (held_for > threshold).then(|| LeakReport {
+                id: *id,
+                resource_type: entry.resource_type,
+                location: entry.location,
+                held_for,
+                backtrace: entry.backtrace.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+
+    #[tokio::test]
+    async fn test_pending_scope_is_reported() {
+        struct LeakyResource;
+
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(LeakyResource.use_with_async(|_res| async move {
+            rx.await.ok();
+        }));
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let leaks = leak_report(Duration::from_millis(10));
+        let leak = leaks
+            .iter()
+            .find(|leak| leak.resource_type().ends_with("LeakyResource"))
+            .expect("leaky resource was not reported");
+        assert_eq!(leak.location().file(), file!());
+        assert!(leak.held_for() >= Duration::from_millis(10));
+
+        tx.send(()).unwrap();
+        task.await.unwrap();
+        assert!(leak_report(Duration::ZERO)
+            .iter()
+            .all(|leak| !leak.resource_type().ends_with("LeakyResource")));
+    }
+}
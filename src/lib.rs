@@ -4,9 +4,11 @@
 //! and subsequently dropped, similar to patterns found in other programming languages like Kotlin's `use` function
 //! and C#'s `using` block.
 //!
-//! This module offers two primary functions:
+//! This module offers four primary functions:
 //! - `use_with`: Executes a closure synchronously, consuming the resource.
 //! - `use_with_async`: Executes an asynchronous closure, consuming the resource.
+//! - `use_close`: Executes a closure and explicitly closes the resource afterwards, reporting close failures.
+//! - `use_close_async`: Executes an asynchronous closure and explicitly closes the resource afterwards.
 //!
 //! These functions facilitate safe and efficient resource handling, ensuring that resources are properly utilized
 //! and dropped, even in asynchronous contexts.
@@ -18,6 +20,107 @@
 //! - **Asynchronous Resource Management:** The `use_with_async` function facilitates asynchronous operations on resources,
 //!   ensuring that resources are properly utilized and dropped after the asynchronous operation completes.
 //!
+//! - **Fallible Teardown:** The `use_close` and `use_close_async` functions run the [`Close`] or [`AsyncClose`]
+//!   implementation of a resource after the operation completes, so that errors during teardown are reported
+//!   instead of being swallowed by `Drop`. The [`io`] module provides such implementations for
+//!   standard library types, such as files that are flushed and synced to disk when closed, and the
+//!   [`net`], [`process`] and [`fs`] modules for TCP streams that are shut down gracefully, child
+//!   processes that are reaped, and temporary files and directories that are removed. The [`graceful`]
+//!   module shuts down connection futures, such as those of hyper, gracefully and within a timeout,
+//!   without depending on hyper.
+//!
+//! - **Observability:** A [`UseObserver`](observer::UseObserver) can be installed globally or per call
+//!   to hook custom telemetry, auditing, or leak tracking into every use scope.
+//!
+//! - **Zero Overhead When Unobserved:** The `Use` methods are inlined, and scopes that no observer,
+//!   profiler, panic hook or instrumentation feature consumes neither allocate nor take an identifier.
+//!   Without the `std` and `log` features, `x.use_with(f)` compiles to the same code as `f(x)`;
+//!   otherwise, the only remaining cost is a check of a process-wide flag. The future of
+//!   `use_with_async` is the future of its body plus, with those features, a pointer.
+//!
+//! - **Profiling:** A callback installed via [`profiling::set_profiler`] receives the body runtime of
+//!   every use scope and the teardown runtime of scopes that tear down their resource themselves,
+//!   helping to find resources whose teardown dominates latency.
+//!
+//! - **Panic Handling:** The `*_catch_unwind` variants turn panics of the body into errors while still
+//!   tearing down the resource, [`Poisonable`] refuses further use of resources whose scope panicked,
+//!   and [`QuietDrop`] keeps panicking destructors from escalating into aborts. [`panic_hook::install`]
+//!   adds the active use scopes to panic messages.
+//!
+//! - **Scoped Locking:** [`LockUseExt`] and [`RwLockUseExt`] run critical sections on a `Mutex` or
+//!   `RwLock` as use scopes that cannot leak the guard, with a [`PoisonPolicy`]
+//!   deciding how poisoned locks are treated. [`RefCellUseExt`] does the same for `RefCell` borrows.
+//!
+//! - **Scoped Process State:** The [`env`](mod@env) module sets environment variables or changes the
+//!   working directory for the duration of a closure and restores the previous state afterwards, even
+//!   if the closure panics. Its scopes are serialized with each other, but not with other code, so
+//!   changing variables is only sound while no other thread reads the environment through C's `getenv`.
+//!
+//! - **Scope Functions:** The [`Scope`] trait provides Kotlin's `also`, `apply`, `let` and `run`,
+//!   as well as `tap`, `pipe`, `take_if` and `take_unless`, for fluent configuration, transformation
+//!   and conditional consumption of values.
+//!
+//! - **Scope Guards:** The `scope` module provides C++-style `scope_exit`, `scope_fail` and
+//!   `scope_success` guards that can be dismissed, and the [`errdefer!`] macro registers cleanup within
+//!   `scope::errdefer_scope` that only runs if the scope returns an error or panics, rolling back
+//!   partial multi-step initialization.
+//!
+//! # Crate Features
+//! - `std` (default): Enables everything that depends on the standard library. Without it, the crate
+//!   is `no_std` and only requires `alloc`: the `Use` trait and `UseScope`, the `Close` and `AsyncClose`
+//!   traits, scope guards and `ExitStack`, the `using!`, `async_using!` and `errdefer!` macros,
+//!   `ResourceSet`, the `Scope` functions, and the extensions for `Box`, `Cow` and `Weak` remain
+//!   available. Panics are neither caught nor detected there, so the
+//!   `*_catch_unwind` variants and `use_critical` require `std`, as do heartbeats, timing thresholds,
+//!   global observers and the profiler. All other features apart from `log` enable `std`.
+//! - `log`: Emits [`log`](https://docs.rs/log) records when use scopes are entered and left,
+//!   and when closing a resource fails. Without this feature, no logging code is compiled in.
+//! - `metrics`: Records [`metrics`](https://docs.rs/metrics) histograms for the body duration
+//!   (`use_with.body.duration`) and close duration (`use_with.close.duration`) of use scopes, as well as
+//!   a `use_with.failures` counter for failed closes and panicking bodies. All metrics are labeled
+//!   with the `resource` type name.
+//! - `leak-detector`: Tracks every live use scope in a global registry and exposes
+//!   `leak::leak_report` to list resources that have been held for longer than a threshold.
+//! - `diagnostics`: Exposes `diagnostics::dump` to list all currently held resources, e.g. for
+//!   a `/debug/resources` endpoint of a service.
+//! - `otel`: Emits an [OpenTelemetry](https://docs.rs/opentelemetry) span for every use scope through the
+//!   global tracer provider. Spans of nested use scopes are children of the enclosing scope's span and carry
+//!   the resource kind, call site and outcome as attributes.
+//! - `std-adapters`: Implements [`Close`] for standard library types: `File` is flushed and synced
+//!   to disk, `BufWriter` is flushed, `TcpStream` is shut down and `JoinHandle` is joined.
+//! - `testing`: Provides the `testing` module with utilities such as `DropSpy` and `DropCounter`
+//!   for asserting teardown behavior in tests.
+//! - `record`: Provides the `record` module for capturing the sequence of use scopes into a
+//!   machine-readable trace and replaying it against mock resources.
+//! - `proptest`: Provides the `proptest` module with a strategy adapter that closes generated
+//!   resources at the end of every test case, even when the case fails or is being shrunk.
+//! - `macros`: Provides the `#[use_fixture]` attribute, which wraps test functions into use scopes
+//!   of their fixtures, including explicit closing of fixtures taken by `&mut` reference.
+//! - `tokio`: Provides the `tokio` module with adapters such as `ShutdownWriter`, which flushes and
+//!   shuts down an `AsyncWrite` when it is closed, scoped critical sections on tokio's locks and
+//!   semaphores, and `use_detached` for spawned tasks that close their resource when aborted.
+//! - `futures`: Provides the `sink` module with `ClosingSink`, which flushes and closes a
+//!   [`futures`](https://docs.rs/futures) `Sink` when it is closed, and `CloseHandshake`, which
+//!   exchanges close frames with the peer of a message stream such as a WebSocket. It works with
+//!   any `Sink` and `Stream`, such as the `WebSocketStream` of `tokio-tungstenite`, so there is no
+//!   separate `tungstenite` feature.
+//! - `deadpool`: Provides the `deadpool` module, which makes `Acquired<R>` a deadpool `Manager` for
+//!   resources implementing `AcquireAsync`, lets deadpool pools serve as `AsyncResourceFactory`, and
+//!   retires pooled objects that are closed explicitly.
+//! - `bb8`: Provides the `bb8` module, which makes `Acquired<R>` a bb8 `ManageConnection` for
+//!   resources implementing `ValidateAsync`, and lets bb8 pools serve as `AsyncResourceFactory`.
+//! - `tower`: Provides the `tower` module with `UseLayer`, a middleware that acquires a resource
+//!   for each call of a service, injects it into the request, and closes it once the response
+//!   future completes or is dropped.
+//! - `axum`: Provides the `axum` module with `Scoped`, an extractor that checks a resource out of a
+//!   deadpool pool for the duration of a handler and returns it afterwards, or discards it if it was
+//!   poisoned, the handler panicked, or the client disconnected in the middle of a use scope. Enables
+//!   `deadpool`.
+//! - `either`: Implements `Close`, `AsyncClose`, `Enter`, `Exit`, `ConnectionLost` and `Durable` for
+//!   [`Either`](https://docs.rs/either), dispatching to whichever variant is held.
+//! - `parking_lot`: Backs the locks of the shared-use, global, environment, leak registry and
+//!   watchdog internals with [`parking_lot`](https://docs.rs/parking_lot). The API is unchanged.
+//!
 //! # Usage
 //!To use these functions, the `Use` trait is auto-implemented for your resource types; simply call the appropriate method:
 //!
@@ -40,15 +143,178 @@
 //! assert_eq!(result, 42);
 //! ```
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![forbid(unsafe_code)]
 
-use std::future::Future;
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod acquire;
+#[cfg(feature = "std-adapters")]
+mod adapters;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "bb8")]
+pub mod bb8;
+mod boxed;
+#[cfg(feature = "std")]
+mod cached;
+#[cfg(feature = "std")]
+mod cell;
+mod close;
+mod context;
+mod cow;
+#[cfg(feature = "deadpool")]
+pub mod deadpool;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+#[cfg(feature = "either")]
+mod either;
+#[cfg(feature = "std")]
+pub mod env;
+mod exit_stack;
+#[cfg(feature = "std")]
+mod factory;
+#[cfg(feature = "std")]
+mod fallback;
+#[cfg(feature = "std")]
+pub mod fs;
+#[cfg(feature = "std")]
+mod global;
+#[cfg(feature = "std")]
+pub mod graceful;
+mod instrument;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "std")]
+mod keep;
+#[cfg(feature = "std")]
+mod last_drop;
+#[cfg(feature = "leak-detector")]
+pub mod leak;
+#[cfg(feature = "std")]
+mod lock;
+#[cfg(feature = "std")]
+pub mod net;
+pub mod observer;
+#[cfg(feature = "std")]
+pub mod panic_hook;
+#[cfg(feature = "std")]
+mod poison;
+#[cfg(feature = "std")]
+pub mod process;
+#[cfg(feature = "std")]
+pub mod profiling;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+#[cfg(feature = "std")]
+mod quiet;
+#[cfg(feature = "std")]
+mod reconnect;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(any(feature = "leak-detector", feature = "diagnostics"))]
+mod registry;
+mod resource_set;
+pub mod scope;
+mod scope_fn;
+mod scoped;
+mod sealed;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "futures")]
+pub mod sink;
+#[cfg(feature = "std")]
+mod slot;
+mod small_vec;
+mod snapshot;
+mod split;
+#[cfg(any(feature = "std", test))]
+#[cfg_attr(
+    not(all(feature = "std", any(test, feature = "testing"))),
+    allow(unused_imports)
+)]
+mod sync;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod thread;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "tower")]
+pub mod tower;
+mod unwind;
+#[cfg(feature = "std")]
+mod watchdog;
+mod weak;
+mod yielding;
+
+#[cfg(feature = "std")]
+pub use acquire::{
+    acquire_close_async, acquire_use, acquire_use_async, Acquire, AcquireAsync, ValidateAsync,
+};
+pub use boxed::{AnyUseExt, BoxUseExt, DynAsyncClose, DynClose};
+#[cfg(feature = "std")]
+pub use cached::{Cached, KeyedCache};
+#[cfg(feature = "std")]
+pub use cell::{BorrowConflict, RefCellUseExt};
+pub use close::{AsyncClose, AutoClose, BoxFuture, Close};
+pub use context::{context, Context, Enter, Exit, Outcome};
+pub use cow::CowUseExt;
+pub use exit_stack::ExitStack;
+#[cfg(feature = "std")]
+pub use factory::{Acquired, AsyncResourceFactory, ResourceFactory};
+#[cfg(feature = "std")]
+pub use fallback::{FallbackError, ResultUseExt};
+#[cfg(feature = "std")]
+pub use global::Global;
+pub use instrument::ScopeId;
+#[cfg(feature = "std")]
+pub use keep::{Keeper, Lease};
+#[cfg(feature = "std")]
+pub use last_drop::{ArcUseExt, LastDrop, LocalUse, RcUseExt};
+#[cfg(feature = "std")]
+pub use lock::{LockUseExt, PoisonPolicy, RwLockUseExt, TryLockError};
+#[cfg(feature = "std")]
+pub use poison::{Poisonable, Poisoned};
+#[cfg(feature = "std")]
+pub use quiet::QuietDrop;
+#[cfg(feature = "std")]
+pub use reconnect::{ConnectionLost, ReconnectError, Reconnecting};
+pub use resource_set::ResourceSet;
+pub use scope_fn::Scope;
+pub use scoped::{UseScope, UseWithAsync};
+pub use sealed::Sealed;
+#[cfg(feature = "std")]
+pub use shared::{ClosedSignal, SharedUse};
+#[cfg(feature = "std")]
+pub use slot::{use_replace, use_replace_or, OptionUseExt};
+pub use snapshot::WithSnapshot;
+pub use split::Split;
+pub use unwind::{PanicPayload, UnwindError};
+#[cfg(feature = "macros")]
+pub use use_with_macros::use_fixture;
+pub use weak::WeakUseExt;
+pub use yielding::{Checkpoint, Yielder};
+
+use core::fmt::Debug;
+use core::future::Future;
+use core::ops::ControlFlow;
+#[cfg(feature = "std")]
+use core::panic::UnwindSafe;
+use core::pin::Pin;
+use instrument::{PlainSlot, ScopeOptions};
+use scoped::ScopeFuture;
+#[cfg(feature = "std")]
+use std::time::Duration;
 
 /// A trait that facilitates resource management by ensuring proper usage and subsequent dropping.
 ///
-/// This trait provides two methods:
+/// This trait provides four primary methods:
 /// - `use_with`: Executes a closure synchronously, consuming the resource.
 /// - `use_with_async`: Executes an asynchronous closure, consuming the resource.
+/// - `use_close`: Executes a closure and explicitly closes the resource afterwards.
+/// - `use_close_async`: Executes an asynchronous closure and explicitly closes the resource afterwards.
 ///
 /// Implementing this trait allows for safe and efficient resource handling, ensuring that resources
 /// are properly utilized and dropped, even in asynchronous contexts.
@@ -83,11 +349,684 @@ pub trait Use {
     ///
     /// assert_eq!(result, 42);
     /// ```
+    #[inline]
+    #[track_caller]
     fn use_with<U, F: FnOnce(Self) -> U>(self, f: F) -> U
     where
         Self: Sized,
     {
-        f(self)
+        UseScope::new(self).use_with(f)
+    }
+
+    /// Executes a closure synchronously, consuming the resource, without instantiating the scope
+    /// machinery for every closure type.
+    ///
+    /// Behaves like [`use_with`](Use::use_with), but the generic part only moves the resource into
+    /// a boxed closure and hands it to a non-generic function that runs the scope. With hundreds of
+    /// call sites, this keeps the instrumentation of use scopes from being compiled into each of
+    /// them, at the cost of one allocation per call.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that takes ownership of `self` and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A value of type `U`, which is the result of the closure `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// let len = String::from("resource").use_with_dyn(|res| res.len());
+    /// assert_eq!(len, 8);
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_with_dyn<U, F: FnOnce(Self) -> U>(self, f: F) -> U
+    where
+        Self: Sized,
+    {
+        UseScope::new(self).use_with_dyn(f)
+    }
+
+    /// Executes a closure on the resource and hands the resource back afterwards.
+    ///
+    /// This method takes ownership of `self` and lends it mutably to the provided closure `f`.
+    /// Instead of dropping the resource, it returns it together with the result of the closure,
+    /// so the caller can decide whether to keep it, e.g. to hold on to a connection only if a
+    /// health check succeeded. Since the resource outlives the scope, observers are notified
+    /// about the end of the body, but not about a close.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that borrows the resource mutably and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A tuple of the result of the closure `f` and the resource.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// struct Connection {
+    ///     healthy: bool,
+    /// }
+    ///
+    /// let (healthy, conn) = Connection { healthy: true }.use_and_return(|conn| conn.healthy);
+    /// let kept = healthy.then_some(conn);
+    ///
+    /// assert!(kept.is_some());
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_and_return<U, F: FnOnce(&mut Self) -> U>(self, f: F) -> (U, Self)
+    where
+        Self: Sized,
+    {
+        UseScope::new(self).use_and_return(f)
+    }
+
+    /// Executes a closure that either consumes the resource or hands it back to the caller.
+    ///
+    /// This method takes ownership of `self` and passes it to the provided closure `f`. The
+    /// closure returns [`ControlFlow::Break`] with its output once it is done with the resource,
+    /// which has been dropped by then, or [`ControlFlow::Continue`] with the resource to hand it
+    /// back to the caller. This encodes in the type system that the resource is only given up
+    /// when the work is complete, e.g. for paginated reads over a cursor.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that takes ownership of `self` and returns a `ControlFlow<U, Self>`.
+    ///
+    /// # Returns
+    /// - `ControlFlow::Break(U)` with the output of the closure if it consumed the resource.
+    /// - `ControlFlow::Continue(Self)` with the resource if the closure handed it back.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::ops::ControlFlow;
+    /// use use_with::Use;
+    ///
+    /// struct Cursor {
+    ///     remaining: u32,
+    /// }
+    ///
+    /// let mut cursor = Cursor { remaining: 3 };
+    /// let mut pages = 0;
+    /// let total = loop {
+    ///     let flow = cursor.use_with_flow(|mut cursor| {
+    ///         pages += 1;
+    ///         cursor.remaining -= 1;
+    ///         if cursor.remaining == 0 {
+    ///             ControlFlow::Break(pages)
+    ///         } else {
+    ///             ControlFlow::Continue(cursor)
+    ///         }
+    ///     });
+    ///     match flow {
+    ///         ControlFlow::Continue(next) => cursor = next,
+    ///         ControlFlow::Break(total) => break total,
+    ///     }
+    /// };
+    ///
+    /// assert_eq!(total, 3);
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_with_flow<U, F: FnOnce(Self) -> ControlFlow<U, Self>>(self, f: F) -> ControlFlow<U, Self>
+    where
+        Self: Sized,
+    {
+        UseScope::new(self).use_with_flow(f)
+    }
+
+    /// Splits the resource into halves, executes a closure on both and rejoins them afterwards.
+    ///
+    /// This method takes ownership of `self`, splits it through its [`Split`] implementation and
+    /// lends both halves mutably to the provided closure `f`. After the closure returns, the halves
+    /// are rejoined and the resource is dropped, so the halves never outlive the scope.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that borrows both halves mutably and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A value of type `U`, which is the result of the closure `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::sync::mpsc;
+    /// use use_with::Use;
+    ///
+    /// let received = mpsc::channel().use_split(|sender, receiver| {
+    ///     sender.send("ping").unwrap();
+    ///     receiver.recv().unwrap()
+    /// });
+    ///
+    /// assert_eq!(received, "ping");
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_split<U, F>(self, f: F) -> U
+    where
+        Self: Split,
+        F: FnOnce(&mut Self::ReadHalf, &mut Self::WriteHalf) -> U,
+    {
+        UseScope::new(self).use_split(f)
+    }
+
+    /// Executes a closure on two disjoint parts of the resource, dropping it afterwards.
+    ///
+    /// This method takes ownership of `self` and uses the projection `project` to borrow two
+    /// disjoint parts of it mutably, such as two fields, which are lent to the provided closure `f`.
+    /// Since the projection proves to the compiler that the parts do not overlap, the closure can
+    /// mutate both independently, e.g. from two scoped threads. After the closure returns, `self`
+    /// is dropped.
+    ///
+    /// # Parameters
+    /// - `project`: A function that borrows two disjoint parts of the resource mutably.
+    /// - `f`: A closure that borrows both parts mutably and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A value of type `U`, which is the result of the closure `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// struct Pipeline {
+    ///     parsed: Vec<u32>,
+    ///     errors: Vec<String>,
+    /// }
+    ///
+    /// let pipeline = Pipeline { parsed: Vec::new(), errors: Vec::new() };
+    /// let (parsed, errors) = pipeline.use_disjoint(
+    ///     |pipeline| (&mut pipeline.parsed, &mut pipeline.errors),
+    ///     |parsed, errors| {
+    ///         std::thread::scope(|scope| {
+    ///             scope.spawn(|| parsed.extend([1, 2, 3]));
+    ///             scope.spawn(|| errors.push(String::from("line 4: not a number")));
+    ///         });
+    ///         (parsed.len(), errors.len())
+    ///     },
+    /// );
+    ///
+    /// assert_eq!((parsed, errors), (3, 1));
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_disjoint<A, B, U, P, F>(self, project: P, f: F) -> U
+    where
+        Self: Sized,
+        A: ?Sized,
+        B: ?Sized,
+        P: for<'a> FnOnce(&'a mut Self) -> (&'a mut A, &'a mut B),
+        F: FnOnce(&mut A, &mut B) -> U,
+    {
+        UseScope::new(self).use_disjoint(project, f)
+    }
+
+    /// Converts the resource into another type and executes a closure on the converted resource.
+    ///
+    /// This method takes ownership of `self`, converts it into `T` via [`Into`] and passes the
+    /// converted resource to the provided closure `f`. This applies adapter types, such as
+    /// wrappers that add [`Close`] behavior, at the call site without an intermediate binding.
+    /// The use scope reported to observers is the one of the converted resource.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that takes ownership of the converted resource and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A value of type `U`, which is the result of the closure `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// struct Celsius(f64);
+    /// struct Fahrenheit(f64);
+    ///
+    /// impl From<Celsius> for Fahrenheit {
+    ///     fn from(celsius: Celsius) -> Self {
+    ///         Fahrenheit(celsius.0 * 9.0 / 5.0 + 32.0)
+    ///     }
+    /// }
+    ///
+    /// let reading = Celsius(100.0).use_into::<Fahrenheit, _>(|fahrenheit| fahrenheit.0);
+    /// assert_eq!(reading, 212.0);
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_into<T, U>(self, f: impl FnOnce(T) -> U) -> U
+    where
+        Self: Sized + Into<T>,
+    {
+        UseScope::new(self).use_into(f)
+    }
+
+    /// Tries to convert the resource into another type and executes a closure on the converted
+    /// resource.
+    ///
+    /// This is the fallible counterpart of [`use_into`](Use::use_into), converting via
+    /// [`TryInto`]. If the conversion fails, the closure does not run and the conversion error is
+    /// returned; the unconverted resource is consumed by the conversion and dropped along with it,
+    /// unless the error hands it back. This covers parse-and-use pipelines, such as validating
+    /// raw input into a configuration before using it.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that takes ownership of the converted resource and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(E)` with the error `E` of the conversion.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// struct Port(u16);
+    ///
+    /// impl TryFrom<&str> for Port {
+    ///     type Error = String;
+    ///
+    ///     fn try_from(raw: &str) -> Result<Self, Self::Error> {
+    ///         match raw.parse() {
+    ///             Ok(0) | Err(_) => Err(format!("invalid port: {raw}")),
+    ///             Ok(port) => Ok(Port(port)),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let address = "8080".try_use_into(|port: Port| format!("0.0.0.0:{}", port.0));
+    /// assert_eq!(address.as_deref(), Ok("0.0.0.0:8080"));
+    ///
+    /// let address = "http".try_use_into(|port: Port| format!("0.0.0.0:{}", port.0));
+    /// assert_eq!(address, Err(String::from("invalid port: http")));
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn try_use_into<T, U>(self, f: impl FnOnce(T) -> U) -> Result<U, <Self as TryInto<T>>::Error>
+    where
+        Self: Sized + TryInto<T>,
+    {
+        UseScope::new(self).try_use_into(f)
+    }
+
+    /// Executes a fallible closure on the resource, attaching a snapshot of the resource to errors.
+    ///
+    /// This method takes ownership of `self` and lends it mutably to the provided closure `f`.
+    /// If the closure returns an error, the [`Debug`] representation of the resource is captured
+    /// before the resource is dropped and attached to the error as a [`WithSnapshot`]. This
+    /// identifies the connection, file or session an error occurred on without having to thread
+    /// that information through every error type.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that borrows the resource mutably and returns a `Result<U, E>`.
+    ///
+    /// # Returns
+    /// - `Ok(U)` if the closure `f` succeeded.
+    /// - `Err(WithSnapshot<E>)` with the error and the snapshot if the closure failed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// #[derive(Debug)]
+    /// struct Connection {
+    ///     peer: &'static str,
+    /// }
+    ///
+    /// let result = Connection { peer: "10.0.0.7:5432" }
+    ///     .try_use_with_snapshot(|_conn| -> Result<(), &str> { Err("connection reset") });
+    ///
+    /// let error = result.unwrap_err();
+    /// assert_eq!(error.error(), &"connection reset");
+    /// assert_eq!(error.snapshot(), r#"Connection { peer: "10.0.0.7:5432" }"#);
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn try_use_with_snapshot<U, E, F>(self, f: F) -> Result<U, WithSnapshot<E>>
+    where
+        Self: Sized + Debug,
+        F: FnOnce(&mut Self) -> Result<U, E>,
+    {
+        UseScope::new(self).try_use_with_snapshot(f)
+    }
+
+    /// Executes a closure synchronously, consuming the resource and catching any panic.
+    ///
+    /// This method behaves like [`use_with`](Use::use_with), but runs the closure under
+    /// [`std::panic::catch_unwind`]. If the closure panics, the resource is dropped during
+    /// unwinding as usual, and the panic is returned as an error instead of unwinding further.
+    /// This allows callers that embed untrusted code, such as plugins, to recover from panics
+    /// without losing the resource's cleanup.
+    ///
+    /// As with [`std::panic::catch_unwind`], the closure and the resource must be
+    /// [`UnwindSafe`]: after a panic, the caller may observe whatever the closure left behind,
+    /// so capturing `&mut` references or types with interior mutability such as `RefCell` is
+    /// rejected. Owned captures, shared references to plain data and atomics are accepted. If the
+    /// caller makes sure that broken invariants cannot be observed, e.g. by discarding the
+    /// captured state after a panic, [`assert_unwind_safe_use`](Use::assert_unwind_safe_use)
+    /// lifts the restriction.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that takes ownership of `self` and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f` if it returned normally.
+    /// - `Err(PanicPayload)` with the panic payload if the closure panicked.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// struct Plugin;
+    ///
+    /// let result = Plugin.use_with_catch_unwind(|_plugin| -> u32 {
+    ///     panic!("plugin crashed");
+    /// });
+    ///
+    /// let payload = result.unwrap_err();
+    /// assert_eq!(payload.downcast_ref::<&str>(), Some(&"plugin crashed"));
+    /// ```
+    #[inline]
+    #[track_caller]
+    #[cfg(feature = "std")]
+    fn use_with_catch_unwind<U, F>(self, f: F) -> Result<U, PanicPayload>
+    where
+        Self: Sized + UnwindSafe,
+        F: FnOnce(Self) -> U + UnwindSafe,
+    {
+        UseScope::new(self).use_with_catch_unwind(f)
+    }
+
+    /// Executes a closure synchronously, consuming the resource and catching any panic, asserting
+    /// that the closure and the resource are unwind safe.
+    ///
+    /// This is the escape hatch of [`use_with_catch_unwind`](Use::use_with_catch_unwind) for
+    /// closures that capture `&mut` references or other state that is not [`UnwindSafe`], much like
+    /// wrapping the closure into [`AssertUnwindSafe`](std::panic::AssertUnwindSafe). By calling it,
+    /// the caller asserts that no state left inconsistent by a panic is observed afterwards.
+    ///
+    /// The asynchronous `*_catch_unwind` variants make the same assertion implicitly, since
+    /// futures borrowing their environment are rarely unwind safe.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that takes ownership of `self` and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f` if it returned normally.
+    /// - `Err(PanicPayload)` with the panic payload if the closure panicked.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// struct Plugin;
+    ///
+    /// let mut log = Vec::new();
+    /// let result = Plugin.assert_unwind_safe_use(|_plugin| {
+    ///     log.push("started");
+    ///     panic!("plugin crashed");
+    /// });
+    ///
+    /// assert!(result.is_err());
+    /// // The log is known to be consistent after every push.
+    /// assert_eq!(log, ["started"]);
+    /// ```
+    #[inline]
+    #[track_caller]
+    #[cfg(feature = "std")]
+    fn assert_unwind_safe_use<U, F>(self, f: F) -> Result<U, PanicPayload>
+    where
+        Self: Sized,
+        F: FnOnce(Self) -> U,
+    {
+        UseScope::new(self).assert_unwind_safe_use(f)
+    }
+
+    /// Executes a closure on the pinned resource, dropping it afterwards.
+    ///
+    /// This method takes ownership of `self`, pins it on the stack and lends it to the provided
+    /// closure `f` as a `Pin<&mut Self>`. This allows using resources that are `!Unpin`, such as
+    /// self-referential futures or intrusive list nodes, whose methods require a pinned receiver,
+    /// without boxing them. After the closure returns, `self` is dropped in place.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that receives the pinned resource and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A value of type `U`, which is the result of the closure `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::marker::PhantomPinned;
+    /// use std::pin::Pin;
+    /// use use_with::Use;
+    ///
+    /// struct Node {
+    ///     id: u32,
+    ///     _pinned: PhantomPinned,
+    /// }
+    ///
+    /// impl Node {
+    ///     fn id(self: Pin<&Self>) -> u32 {
+    ///         self.id
+    ///     }
+    /// }
+    ///
+    /// let node = Node { id: 7, _pinned: PhantomPinned };
+    /// let id = node.use_with_pinned(|node| node.as_ref().id());
+    ///
+    /// assert_eq!(id, 7);
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_with_pinned<U, F: FnOnce(Pin<&mut Self>) -> U>(self, f: F) -> U
+    where
+        Self: Sized,
+    {
+        UseScope::new(self).use_with_pinned(f)
+    }
+
+    /// Executes a closure synchronously, consuming the resource, while calling `heartbeat` at a
+    /// fixed interval.
+    ///
+    /// Leased resources such as database sessions or distributed locks expire unless they are
+    /// kept alive. The heartbeat runs on a separate thread every `interval` until the closure `f`
+    /// returns or panics, so that it keeps running while the body is busy. It does not receive the
+    /// resource, which the body owns; keep-alives typically go through a separate handle to the
+    /// lease. If the heartbeat panics, it stops, and its panic resumes with the original payload
+    /// once the body has returned. A panic of the body takes precedence over that of the heartbeat.
+    ///
+    /// # Parameters
+    /// - `interval`: The time between two heartbeats, the first of which is sent after one interval.
+    /// - `heartbeat`: The keep-alive callback.
+    /// - `f`: A closure that takes ownership of `self` and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A value of type `U`, which is the result of the closure `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::sync::mpsc;
+    /// use std::time::Duration;
+    /// use use_with::Use;
+    ///
+    /// let (renew, renewals) = mpsc::channel();
+    ///
+    /// let renewed = String::from("lock:report").use_with_heartbeat(
+    ///     Duration::from_millis(5),
+    ///     move || {
+    ///         let _ = renew.send(());
+    ///     },
+    ///     // Works until the lease has been renewed twice.
+    ///     |_lease| renewals.iter().take(2).count(),
+    /// );
+    ///
+    /// assert_eq!(renewed, 2);
+    /// ```
+    #[inline]
+    #[track_caller]
+    #[cfg(feature = "std")]
+    fn use_with_heartbeat<U, H, F>(self, interval: Duration, heartbeat: H, f: F) -> U
+    where
+        Self: Sized,
+        H: FnMut() + Send,
+        F: FnOnce(Self) -> U,
+    {
+        UseScope::new(self).use_with_heartbeat(interval, heartbeat, f)
+    }
+
+    /// Executes an asynchronous closure, consuming the resource, while calling `heartbeat` at a
+    /// fixed interval.
+    ///
+    /// This is the asynchronous counterpart of [`use_with_heartbeat`](Use::use_with_heartbeat).
+    /// The heartbeat is driven by the Tokio timer within the returned future, so it runs on the
+    /// task that awaits the body, and stops as soon as the body completes or the future is
+    /// dropped. Asynchronous keep-alives can be sent from the heartbeat through a channel.
+    ///
+    /// Only available with the `tokio` feature.
+    ///
+    /// # Parameters
+    /// - `interval`: The time between two heartbeats, the first of which is sent after one interval.
+    /// - `heartbeat`: The keep-alive callback.
+    /// - `f`: An asynchronous closure that takes ownership of `self` and returns a future.
+    ///
+    /// # Returns
+    /// - A future that resolves to a value of type `U`, which is the result of the asynchronous operation.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::time::Duration;
+    /// use use_with::Use;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (renew, mut renewals) = tokio::sync::mpsc::unbounded_channel();
+    ///
+    /// let renewed = String::from("session")
+    ///     .use_with_heartbeat_async(
+    ///         Duration::from_millis(5),
+    ///         move || {
+    ///             let _ = renew.send(());
+    ///         },
+    ///         // Works until the session has been renewed twice.
+    ///         |_session| async move {
+    ///             let mut renewed = 0;
+    ///             while renewed < 2 && renewals.recv().await.is_some() {
+    ///                 renewed += 1;
+    ///             }
+    ///             renewed
+    ///         },
+    ///     )
+    ///     .await;
+    ///
+    /// assert_eq!(renewed, 2);
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    #[inline]
+    #[track_caller]
+    fn use_with_heartbeat_async<F, Fut, U, H>(
+        self,
+        interval: Duration,
+        heartbeat: H,
+        f: F,
+    ) -> impl Future<Output = U> + Send
+    where
+        Self: Sized + Send,
+        H: FnMut() + Send,
+        F: FnOnce(Self) -> Fut + Send,
+        Fut: Future<Output = U> + Send,
+    {
+        UseScope::new(self).use_with_heartbeat_async(interval, heartbeat, f)
+    }
+
+    /// Executes a long-running asynchronous closure on the resource, yielding to the executor
+    /// at the checkpoints marked by the closure once their budget is exhausted.
+    ///
+    /// The closure receives the resource and a [`Yielder`]. Awaiting
+    /// [`Yielder::checkpoint`] between sections of CPU-heavy work yields to the executor after
+    /// every `budget` checkpoints, so that other tasks are not starved while the resource is held.
+    /// The resource is dropped once the future of the closure completes.
+    ///
+    /// # Parameters
+    /// - `budget`: The number of checkpoints after which the closure yields.
+    /// - `f`: A closure that borrows the resource mutably along with the [`Yielder`] and returns a
+    ///   boxed future.
+    ///
+    /// # Returns
+    /// - A future resolving to the output of the future returned by `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let checksum = vec![1u64; 10_000]
+    ///     .use_with_yielding(1_000, |records, yielder| {
+    ///         Box::pin(async move {
+    ///             let mut checksum = 0u64;
+    ///             for record in records.iter() {
+    ///                 checksum = checksum.wrapping_mul(31).wrapping_add(*record);
+    ///                 yielder.checkpoint().await;
+    ///             }
+    ///             checksum
+    ///         })
+    ///     })
+    ///     .await;
+    ///
+    /// assert_ne!(checksum, 0);
+    /// # }
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_with_yielding<U, F>(self, budget: u32, f: F) -> impl Future<Output = U> + Send
+    where
+        Self: Sized + Send,
+        F: for<'a> FnOnce(&'a mut Self, &'a mut Yielder) -> BoxFuture<'a, U> + Send,
+        U: Send,
+    {
+        UseScope::new(self).use_with_yielding(budget, f)
+    }
+
+    /// Executes an asynchronous closure on the pinned resource, dropping it afterwards.
+    ///
+    /// This is the asynchronous counterpart of [`use_with_pinned`](Use::use_with_pinned). The
+    /// resource is pinned within the returned future, so it is dropped once the future completes
+    /// or is dropped itself.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that receives the pinned resource and returns a boxed future.
+    ///
+    /// # Returns
+    /// - A future resolving to the output of the future returned by `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let ticks = tokio::time::sleep(std::time::Duration::from_millis(1))
+    ///     .use_with_pinned_async(|sleep| {
+    ///         Box::pin(async move {
+    ///             sleep.await;
+    ///             1
+    ///         })
+    ///     })
+    ///     .await;
+    ///
+    /// assert_eq!(ticks, 1);
+    /// # }
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_with_pinned_async<U, F>(self, f: F) -> impl Future<Output = U> + Send
+    where
+        Self: Sized + Send,
+        F: for<'a> FnOnce(Pin<&'a mut Self>) -> BoxFuture<'a, U> + Send,
+        U: Send,
+    {
+        UseScope::new(self).use_with_pinned_async(f)
     }
 
     /// Executes an asynchronous closure, consuming the resource.
@@ -95,6 +1034,10 @@ pub trait Use {
     /// This method takes ownership of `self` and applies the provided asynchronous closure `f` to it.
     /// After the asynchronous operation completes, `self` is dropped.
     ///
+    /// Like calling `f(self)` directly, the closure is called right away, and the returned future
+    /// drives the future of the closure. It is only larger than that future by a pointer to the
+    /// state of the scope with the `std` or `log` feature, and not at all without them.
+    ///
     /// # Parameters
     /// - `f`: An asynchronous closure that takes ownership of `self` and returns a future.
     ///
@@ -122,17 +1065,411 @@ pub trait Use {
     ///     42
     /// });
     ///
-    /// // Await the result
-    /// assert_eq!(future.await, 42);
-    /// # }
+    /// // Await the result
+    /// assert_eq!(future.await, 42);
+    /// # }
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_with_async<F, Fut, U>(self, f: F) -> impl Future<Output = U> + Send
+    where
+        Self: Sized + Send,
+        F: FnOnce(Self) -> Fut + Send,
+        Fut: Future<Output = U> + Send,
+    {
+        ScopeFuture::<_, _, PlainSlot>::enter(self, f, ScopeOptions::new())
+    }
+
+    /// Executes an asynchronous closure, consuming the resource, without instantiating the scope
+    /// machinery for every closure type.
+    ///
+    /// This is the asynchronous counterpart of [`use_with_dyn`](Use::use_with_dyn): the future of
+    /// the closure is boxed and awaited by a non-generic future that runs the scope.
+    ///
+    /// # Parameters
+    /// - `f`: An asynchronous closure that takes ownership of `self` and returns a future.
+    ///
+    /// # Returns
+    /// - A future that resolves to a value of type `U`, which is the result of the asynchronous operation.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use use_with::Use;
+    ///
+    /// let len = String::from("resource")
+    ///     .use_with_async_dyn(|res| async move { res.len() })
+    ///     .await;
+    /// assert_eq!(len, 8);
+    /// # }
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_with_async_dyn<F, Fut, U>(self, f: F) -> impl Future<Output = U> + Send
+    where
+        Self: Sized + Send,
+        F: FnOnce(Self) -> Fut + Send,
+        Fut: Future<Output = U> + Send,
+        U: Send,
+    {
+        UseScope::new(self).use_with_async_dyn(f)
+    }
+
+    /// Executes an asynchronous closure, consuming the resource and catching any panic.
+    ///
+    /// This is the asynchronous counterpart of [`use_with_catch_unwind`](Use::use_with_catch_unwind).
+    /// If creating or polling the future panics, the future and the resource it owns are dropped,
+    /// and the panic is returned as an error instead of unwinding through the executor.
+    ///
+    /// Unlike the synchronous variant, this method does not require [`UnwindSafe`]; the caller
+    /// asserts unwind safety as with [`assert_unwind_safe_use`](Use::assert_unwind_safe_use).
+    ///
+    /// # Parameters
+    /// - `f`: An asynchronous closure that takes ownership of `self` and returns a future.
+    ///
+    /// # Returns
+    /// - A future that resolves to `Ok(U)` if the asynchronous operation completed, or to
+    ///   `Err(PanicPayload)` if it panicked.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use use_with::Use;
+    ///
+    /// struct Request;
+    ///
+    /// let result = Request
+    ///     .use_with_async_catch_unwind(|_req| async {
+    ///         tokio::task::yield_now().await;
+    ///         if true {
+    ///             panic!("handler crashed");
+    ///         }
+    ///         200
+    ///     })
+    ///     .await;
+    ///
+    /// assert!(result.is_err());
+    /// # }
+    /// ```
+    #[inline]
+    #[track_caller]
+    #[cfg(feature = "std")]
+    fn use_with_async_catch_unwind<F, Fut, U>(
+        self,
+        f: F,
+    ) -> impl Future<Output = Result<U, PanicPayload>> + Send
+    where
+        Self: Sized + Send,
+        F: FnOnce(Self) -> Fut + Send,
+        Fut: Future<Output = U> + Send,
+    {
+        UseScope::new(self).use_with_async_catch_unwind(f)
+    }
+
+    /// Executes a closure on the resource and explicitly closes it afterwards.
+    ///
+    /// This method takes ownership of `self`, lends it mutably to the provided closure `f`
+    /// and calls [`Close::close`] once the closure returns. Unlike a failure in [`Drop`],
+    /// a failure to close the resource is returned to the caller.
+    ///
+    /// If the closure panics, the resource is closed nonetheless before the panic resumes, so the
+    /// teardown must cope with a resource whose mutation was interrupted. Should closing panic as
+    /// well, its panic is suppressed and reported to observers as
+    /// [`Failure::ClosePanic`](observer::Failure::ClosePanic) instead of aborting the process.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that borrows the resource mutably and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f` if the resource was closed successfully.
+    /// - `Err(Self::Error)` if closing the resource failed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::{Close, Use};
+    ///
+    /// struct Connection {
+    ///     sent: usize,
+    /// }
+    ///
+    /// impl Close for Connection {
+    ///     type Error = std::io::Error;
+    ///
+    ///     fn close(self) -> Result<(), Self::Error> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let result = Connection { sent: 0 }.use_close(|conn| {
+    ///     conn.sent += 1;
+    ///     conn.sent
+    /// });
+    ///
+    /// assert_eq!(result.unwrap(), 1);
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_close<U, F: FnOnce(&mut Self) -> U>(self, f: F) -> Result<U, Self::Error>
+    where
+        Self: Sized + Close,
+    {
+        UseScope::new(self).use_close(f)
+    }
+
+    /// Enters the resource, executes a fallible closure on its guard and exits the guard with the
+    /// outcome of the closure.
+    ///
+    /// This method takes ownership of `self`, turns it into its guard via [`Enter::enter`] and
+    /// lends the guard mutably to the provided closure `f`. Afterwards, [`Exit::exit`] is called
+    /// with [`Outcome::Success`] or [`Outcome::Error`], depending on the result of the closure, or
+    /// with [`Outcome::Panic`] if the closure panicked, after which the panic resumes. A panic
+    /// while exiting after a panicking body is suppressed.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that borrows the guard mutably and returns a `Result<U, E>`.
+    ///
+    /// # Returns
+    /// - The result of the closure `f`.
+    ///
+    /// # Examples
+    /// See [`Enter`].
+    #[inline]
+    #[track_caller]
+    fn use_context<U, E, F>(self, f: F) -> Result<U, E>
+    where
+        Self: Sized + Enter,
+        F: FnOnce(&mut Self::Guard) -> Result<U, E>,
+    {
+        UseScope::new(self).use_context(f)
+    }
+
+    /// Executes an asynchronous closure on the resource and explicitly closes it afterwards.
+    ///
+    /// This is the asynchronous counterpart of [`use_close`](Use::use_close). Since the closure
+    /// borrows the resource, it returns a [`BoxFuture`], which is most easily created by wrapping
+    /// an `async move` block in [`Box::pin`]. Once the future completes, [`AsyncClose::close_async`]
+    /// is awaited.
+    ///
+    /// As with [`use_close`](Use::use_close), the resource is closed even if the body panics,
+    /// and a panic while closing after a panicking body is suppressed.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that borrows the resource mutably and returns a boxed future.
+    ///
+    /// # Returns
+    /// - A future that resolves to `Ok(U)` with the result of the closure `f` if the resource was
+    ///   closed successfully, or to `Err(Self::Error)` if closing the resource failed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use use_with::{AsyncClose, Use};
+    ///
+    /// struct Session {
+    ///     requests: usize,
+    /// }
+    ///
+    /// impl AsyncClose for Session {
+    ///     type Error = std::io::Error;
+    ///
+    ///     async fn close_async(self) -> Result<(), Self::Error> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let result = Session { requests: 0 }
+    ///     .use_close_async(|session| {
+    ///         Box::pin(async move {
+    ///             session.requests += 1;
+    ///             session.requests
+    ///         })
+    ///     })
+    ///     .await;
+    ///
+    /// assert_eq!(result.unwrap(), 1);
+    /// # }
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_close_async<U, F>(self, f: F) -> impl Future<Output = Result<U, Self::Error>> + Send
+    where
+        Self: Sized + Send + AsyncClose,
+        F: for<'a> FnOnce(&'a mut Self) -> BoxFuture<'a, U> + Send,
+        U: Send,
+    {
+        scoped::use_close_async(self, ScopeOptions::new(), f)
+    }
+
+    /// Executes an asynchronous closure on the resource, catching any panic, and explicitly closes
+    /// the resource afterwards.
+    ///
+    /// This method behaves like [`use_close_async`](Use::use_close_async), but catches panics raised
+    /// while creating or polling the body's future. The resource is closed via
+    /// [`AsyncClose::close_async`] even if the body panicked, so that a single panicking request
+    /// does not take down a connection without its cleanup. The teardown must therefore cope with
+    /// a resource whose mutation was interrupted by the panic.
+    /// Unwind safety is asserted as with [`assert_unwind_safe_use`](Use::assert_unwind_safe_use).
+    ///
+    /// # Parameters
+    /// - `f`: A closure that borrows the resource mutably and returns a boxed future.
+    ///
+    /// # Returns
+    /// - A future that resolves to `Ok(U)` with the result of the closure `f` if it completed and
+    ///   the resource was closed successfully.
+    /// - [`UnwindError::Panic`] with the panic payload and the outcome of closing the resource if
+    ///   the body panicked.
+    /// - [`UnwindError::Close`] if the body completed, but closing the resource failed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use use_with::{AsyncClose, UnwindError, Use};
+    ///
+    /// struct Connection;
+    ///
+    /// impl AsyncClose for Connection {
+    ///     type Error = std::io::Error;
+    ///
+    ///     async fn close_async(self) -> Result<(), Self::Error> {
+    ///         // Still runs after the handler panicked.
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let result = Connection
+    ///     .use_close_async_catch_unwind(|_conn| -> use_with::BoxFuture<'_, ()> {
+    ///         Box::pin(async { panic!("handler crashed") })
+    ///     })
+    ///     .await;
+    ///
+    /// assert!(matches!(result, Err(UnwindError::Panic { close: Some(Ok(())), .. })));
+    /// # }
+    /// ```
+    #[inline]
+    #[track_caller]
+    #[cfg(feature = "std")]
+    fn use_close_async_catch_unwind<U, F>(
+        self,
+        f: F,
+    ) -> impl Future<Output = Result<U, UnwindError<Self::Error>>> + Send
+    where
+        Self: Sized + Send + AsyncClose,
+        F: for<'a> FnOnce(&'a mut Self) -> BoxFuture<'a, U> + Send,
+        U: Send,
+    {
+        scoped::use_close_async_catch_unwind(self, ScopeOptions::new(), f)
+    }
+
+    /// Executes a closure on the resource, aborting the process if the closure panics.
+    ///
+    /// Some resources must never be observed in the state a panic leaves behind, such as shared
+    /// memory or a wire protocol in the middle of a frame. Unwinding past them would run their
+    /// destructors, and those of their owners, on inconsistent state. `use_critical` lends the
+    /// resource mutably to the closure and aborts the process if the closure panics, before the
+    /// resource is dropped. Observers are notified about the panic before aborting.
+    ///
+    /// This is an explicit opt-in; prefer [`use_with`](Use::use_with) unless aborting is the
+    /// lesser evil. After the closure returns, `self` is dropped.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that borrows the resource mutably and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A value of type `U`, which is the result of the closure `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// struct Frame {
+    ///     header_written: bool,
+    /// }
+    ///
+    /// let written = Frame { header_written: false }.use_critical(|frame| {
+    ///     frame.header_written = true;
+    ///     // Writing the payload must not be interrupted by unwinding.
+    ///     frame.header_written
+    /// });
+    ///
+    /// assert!(written);
+    /// ```
+    #[inline]
+    #[track_caller]
+    #[cfg(feature = "std")]
+    fn use_critical<U, F: FnOnce(&mut Self) -> U>(self, f: F) -> U
+    where
+        Self: Sized,
+    {
+        UseScope::new(self).use_critical(f)
+    }
+
+    /// Executes a closure on a resource that cannot escape the closure, dropping it afterwards.
+    ///
+    /// This method takes ownership of `self` and lends it to the provided closure `f` as a
+    /// [`Sealed`] wrapper. The wrapper is branded with a lifetime that is unique to this call,
+    /// so the compiler rejects any attempt to return, store or send away the wrapper or
+    /// references derived from it. Contents moved out through the mutable reference, e.g. with
+    /// [`mem::take`](core::mem::take), are not confined, as [`Sealed`] explains. After the closure
+    /// returns, `self` is dropped.
+    ///
+    /// # Parameters
+    /// - `f`: A closure that receives the sealed resource and returns a value of type `U`.
+    ///
+    /// # Returns
+    /// - A value of type `U`, which is the result of the closure `f`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::Use;
+    ///
+    /// let length = String::from("secret").use_sealed(|mut sealed| {
+    ///     sealed.push('!');
+    ///     sealed.len()
+    /// });
+    ///
+    /// assert_eq!(length, 7);
+    /// ```
+    #[inline]
+    #[track_caller]
+    fn use_sealed<U, F>(self, f: F) -> U
+    where
+        Self: Sized,
+        F: for<'brand> FnOnce(Sealed<'brand, Self>) -> U,
+    {
+        UseScope::new(self).use_sealed(f)
+    }
+
+    /// Prepares a use scope with additional per-call configuration, such as an observer.
+    ///
+    /// The returned [`UseScope`] offers the same `use_*` methods as this trait.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::observer::{UseEvent, UseObserver};
+    /// use use_with::Use;
+    ///
+    /// struct AuditLog;
+    ///
+    /// impl UseObserver for AuditLog {
+    ///     fn on_acquire(&self, event: &UseEvent) {
+    ///         println!("acquired {}", event.resource_type());
+    ///     }
+    /// }
+    ///
+    /// let result = 21.scoped().observer(&AuditLog).use_with(|value| value * 2);
+    /// assert_eq!(result, 42);
     /// ```
-    fn use_with_async<F, Fut, U>(self, f: F) -> impl Future<Output = U> + Send
+    #[inline]
+    #[track_caller]
+    fn scoped<'o>(self) -> UseScope<'o, Self>
     where
-        Self: Sized + Send,
-        F: FnOnce(Self) -> Fut + Send,
-        Fut: Future<Output = U> + Send,
+        Self: Sized,
     {
-        async { f(self).await }
+        UseScope::new(self)
     }
 }
 
@@ -179,34 +1516,91 @@ macro_rules! using {
     }};
 }
 
+/// Executes an asynchronous block with resources and closes them afterwards, like C#'s
+/// `await using`.
+///
+/// Each resource is bound to a name that the block can borrow. The block must evaluate to a
+/// `Result`, so that `?` can be used inside it. After the block completes, the resources are
+/// closed via [`AsyncClose::close_async`] in reverse order of their declaration, whether the block
+/// succeeded or not. The macro evaluates to the result of the block, or to the first error of
+/// closing a resource if the block succeeded; close errors are converted with [`From`].
+///
+/// If the block panics, the resources are dropped without being closed.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{async_using, AsyncClose};
+///
+/// struct Connection(&'static str);
+///
+/// impl AsyncClose for Connection {
+///     type Error = std::io::Error;
+///
+///     async fn close_async(self) -> Result<(), Self::Error> {
+///         println!("closing {}", self.0);
+///         Ok(())
+///     }
+/// }
+///
+/// async fn transfer() -> std::io::Result<usize> {
+///     let copied = async_using!(source = Connection("source"), target = Connection("target") => {
+///         // Closes `target`, then `source`.
+///         let copied = source.0.len() + target.0.len();
+///         Ok::<_, std::io::Error>(copied)
+///     })?;
+///     Ok(copied)
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// assert_eq!(transfer().await.unwrap(), 12);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! async_using {
+    ($($param:ident = $resource:expr),+ => $body:block) => {{
+        $(
+            #[allow(unused_mut)]
+            let mut $param = $resource;
+        )+
+        let mut result = async $body.await;
+        $crate::async_using!(@close result; $($param),+);
+        result
+    }};
+    (@close $result:ident; $first:ident $(, $rest:ident)*) => {
+        $crate::async_using!(@close $result; $($rest),*);
+        match $crate::AsyncClose::close_async($first).await {
+            Err(error) if $result.is_ok() => $result = Err(::core::convert::From::from(error)),
+            _ => {}
+        }
+    };
+    (@close $result:ident;) => {};
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::observer::{UseEvent, UseObserver};
+    use crate::testing::{DropCounter, DropProbe, DropSpy, SpyProbe};
+    // Shadows the `tokio` module of the crate.
+    use ::tokio;
     use std::sync::{Arc, Mutex};
 
     #[test]
     fn test_resource_usage() {
-        let drop_flag = Arc::new(Mutex::new(false));
+        let counter = DropCounter::new();
 
         {
-            let drop_flag = drop_flag.clone();
-            struct TestResource(Arc<Mutex<bool>>);
-
-            impl Drop for TestResource {
-                fn drop(&mut self) {
-                    let mut flag = self.0.lock().unwrap();
-                    *flag = true;
-                    println!("TestResource is dropped!");
-                }
-            }
+            struct TestResource(#[allow(dead_code)] DropProbe);
 
-            TestResource(drop_flag).use_with(|_res| {
+            TestResource(counter.probe()).use_with(|_res| {
                 println!("Using the resource");
+                assert_eq!(counter.count(), 0, "Resource was dropped too early");
                 // `_res` is consumed here
             });
         }
 
-        assert!(*drop_flag.lock().unwrap(), "Resource was not dropped");
+        assert_eq!(counter.count(), 1, "Resource was not dropped");
     }
 
     #[test]
@@ -231,16 +1625,11 @@ mod tests {
 
     #[test]
     fn test_multiple_resources() {
-        struct Resource(&'static str);
-
-        impl Drop for Resource {
-            fn drop(&mut self) {
-                println!("{} is dropped!", self.0);
-            }
-        }
+        struct Resource(&'static str, #[allow(dead_code)] SpyProbe);
 
-        let res1 = Resource("Resource 1");
-        let res2 = Resource("Resource 2");
+        let spy = DropSpy::new();
+        let res1 = Resource("Resource 1", spy.probe("Resource 1"));
+        let res2 = Resource("Resource 2", spy.probe("Resource 2"));
 
         res1.use_with(|r1| {
             println!("Using {}", r1.0);
@@ -251,20 +1640,16 @@ mod tests {
         });
 
         // Both resources should be dropped after this point
+        assert_eq!(spy.order(), ["Resource 1", "Resource 2"]);
     }
 
     #[test]
     fn test_nested_use_with() {
-        struct Resource(&'static str);
+        struct Resource(&'static str, #[allow(dead_code)] SpyProbe);
 
-        impl Drop for Resource {
-            fn drop(&mut self) {
-                println!("{} is dropped!", self.0);
-            }
-        }
-
-        let outer = Resource("Outer Resource");
-        let inner = Resource("Inner Resource");
+        let spy = DropSpy::new();
+        let outer = Resource("Outer Resource", spy.probe("outer"));
+        let inner = Resource("Inner Resource", spy.probe("inner"));
 
         outer.use_with(|o| {
             println!("Using {}", o.0);
@@ -272,8 +1657,11 @@ mod tests {
                 println!("Using {}", i.0);
             });
             // Inner resource should be dropped here
+            assert!(spy.was_dropped("inner"));
+            assert!(!spy.was_dropped("outer"));
         });
         // Outer resource should be dropped after this point
+        assert_eq!(spy.order(), ["inner", "outer"]);
     }
 
     #[test]
@@ -351,13 +1739,12 @@ mod tests {
 
     #[test]
     fn test_use_with_modifies_external_state() {
-        #[derive(Default)]
         struct Resource;
 
         // External state that we want to modify
         let mut external_state = 0;
 
-        Resource::default().use_with(|_res| {
+        Resource.use_with(|_res| {
             external_state += 1;
         });
 
@@ -365,9 +1752,462 @@ mod tests {
         assert_eq!(external_state, 1);
     }
 
+    #[test]
+    fn test_use_close() {
+        struct Resource(Arc<Mutex<bool>>);
+
+        impl Close for Resource {
+            type Error = &'static str;
+
+            fn close(self) -> Result<(), Self::Error> {
+                *self.0.lock().unwrap() = true;
+                Ok(())
+            }
+        }
+
+        let closed = Arc::new(Mutex::new(false));
+        let result = Resource(closed.clone()).use_close(|res| {
+            assert!(!*res.0.lock().unwrap(), "Resource was closed too early");
+            42
+        });
+
+        assert_eq!(result, Ok(42));
+        assert!(*closed.lock().unwrap(), "Resource was not closed");
+    }
+
+    #[test]
+    fn test_use_close_reports_failure() {
+        struct Resource;
+
+        impl Close for Resource {
+            type Error = &'static str;
+
+            fn close(self) -> Result<(), Self::Error> {
+                Err("close failed")
+            }
+        }
+
+        let result = Resource.use_close(|_res| 42);
+        assert_eq!(result, Err("close failed"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_use_with_catch_unwind() {
+        let counter = DropCounter::new();
+        let result = counter.probe().use_with_catch_unwind(|_probe| -> u32 {
+            panic!("plugin crashed");
+        });
+
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"plugin crashed"));
+        assert_eq!(counter.count(), 1);
+
+        let result = counter.probe().use_with_catch_unwind(|_probe| 42);
+        assert_eq!(result.ok(), Some(42));
+        assert_eq!(counter.count(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_unwind_safety_of_captures() {
+        // Owned and shared captures of plain data are unwind safe.
+        let shared = String::from("shared");
+        let atomic = std::sync::atomic::AtomicUsize::new(0);
+        let result = DropCounter::new().probe().use_with_catch_unwind(|_probe| {
+            atomic.fetch_add(shared.len(), std::sync::atomic::Ordering::Relaxed)
+        });
+        assert_eq!(result.ok(), Some(0));
+
+        // Mutable captures require asserting unwind safety.
+        let mut log = Vec::new();
+        let result = DropCounter::new()
+            .probe()
+            .assert_unwind_safe_use(|_probe| -> () {
+                log.push("started");
+                panic!("interrupted");
+            });
+        assert!(result.is_err());
+        assert_eq!(log, ["started"]);
+    }
+
+    #[test]
+    fn test_use_and_return() {
+        struct Connection {
+            pings: u32,
+        }
+
+        let (pings, conn) = Connection { pings: 0 }.use_and_return(|conn| {
+            conn.pings += 1;
+            conn.pings
+        });
+        assert_eq!(pings, 1);
+        assert_eq!(conn.pings, 1);
+    }
+
+    #[test]
+    fn test_use_with_flow() {
+        let counter = DropCounter::new();
+
+        let flow = counter
+            .probe()
+            .use_with_flow(ControlFlow::<(), _>::Continue);
+        let ControlFlow::Continue(probe) = flow else {
+            panic!("resource was not handed back");
+        };
+        assert_eq!(counter.count(), 0);
+
+        let flow = probe.use_with_flow(|_probe| ControlFlow::Break(42));
+        assert!(matches!(flow, ControlFlow::Break(42)));
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[test]
+    fn test_use_split() {
+        let counter = DropCounter::new();
+        let written = (counter.probe(), Vec::new()).use_split(|_read, write| {
+            write.extend_from_slice(b"hello");
+            assert_eq!(counter.count(), 0);
+            write.len()
+        });
+        assert_eq!(written, 5);
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[test]
+    fn test_use_disjoint() {
+        struct Buffers {
+            front: Vec<u8>,
+            back: [u8; 4],
+            _probe: DropProbe,
+        }
+
+        let counter = DropCounter::new();
+        let buffers = Buffers {
+            front: vec![1, 2],
+            back: [0; 4],
+            _probe: counter.probe(),
+        };
+        let copied = buffers.use_disjoint(
+            |buffers| (buffers.front.as_mut_slice(), &mut buffers.back[..]),
+            |front, back| {
+                back[..front.len()].copy_from_slice(front);
+                front.fill(0);
+                back.to_vec()
+            },
+        );
+        assert_eq!(copied, [1, 2, 0, 0]);
+        assert_eq!(counter.count(), 1);
+    }
+
     #[tokio::test]
-    async fn test_use_with_async_modifies_external_state() {
+    async fn test_pinned_resource_is_dropped_after_scope() {
+        let counter = DropCounter::new();
+        counter.probe().use_with_pinned(|_probe| {
+            assert_eq!(counter.count(), 0);
+        });
+        assert_eq!(counter.count(), 1);
+
+        let count = counter
+            .probe()
+            .use_with_pinned_async(|_probe| {
+                let counter = counter.clone();
+                Box::pin(async move { counter.count() })
+            })
+            .await;
+        assert_eq!(count, 1);
+        assert_eq!(counter.count(), 2);
+    }
+
+    #[test]
+    fn test_use_into_reports_converted_resource() {
+        struct Raw(DropProbe);
+        struct Wrapped(#[allow(dead_code)] DropProbe);
+
+        impl From<Raw> for Wrapped {
+            fn from(raw: Raw) -> Self {
+                Wrapped(raw.0)
+            }
+        }
+
+        #[derive(Default)]
+        struct TypeRecorder(Mutex<Vec<&'static str>>);
+
+        impl UseObserver for TypeRecorder {
+            fn on_acquire(&self, event: &UseEvent) {
+                self.0.lock().unwrap().push(event.resource_type());
+            }
+        }
+
+        let counter = DropCounter::new();
+        let recorder = TypeRecorder::default();
+        Raw(counter.probe())
+            .scoped()
+            .observer(&recorder)
+            .use_into(|_wrapped: Wrapped| assert_eq!(counter.count(), 0));
+        assert_eq!(counter.count(), 1);
+        assert!(recorder.0.lock().unwrap()[0].ends_with("Wrapped"));
+    }
+
+    #[test]
+    fn test_try_use_into_drops_unconverted_resource() {
+        struct Raw(#[allow(dead_code)] DropProbe, bool);
+        struct Valid(#[allow(dead_code)] DropProbe);
+
+        impl TryFrom<Raw> for Valid {
+            type Error = &'static str;
+
+            fn try_from(raw: Raw) -> Result<Self, Self::Error> {
+                match raw.1 {
+                    true => Ok(Valid(raw.0)),
+                    false => Err("invalid"),
+                }
+            }
+        }
+
+        let counter = DropCounter::new();
+        let result = Raw(counter.probe(), false).try_use_into(|_valid: Valid| ());
+        assert_eq!(result, Err("invalid"));
+        assert_eq!(counter.count(), 1);
+
+        let result = Raw(counter.probe(), true).try_use_into(|_valid: Valid| counter.count());
+        assert_eq!(result, Ok(1));
+        assert_eq!(counter.count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_async_using_closes_in_reverse_order() {
+        struct Closing<'a>(&'static str, &'a Mutex<Vec<&'static str>>);
+
+        impl AsyncClose for Closing<'_> {
+            type Error = String;
+
+            async fn close_async(self) -> Result<(), Self::Error> {
+                self.1.lock().unwrap().push(self.0);
+                match self.0 {
+                    "failing" => Err(String::from("close failed")),
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        let closed = Mutex::new(Vec::new());
+        let result = async_using!(first = Closing("first", &closed), second = Closing("second", &closed) => {
+            Ok::<_, String>(first.0.len() + second.0.len())
+        });
+        assert_eq!(result, Ok(11));
+        assert_eq!(*closed.lock().unwrap(), ["second", "first"]);
+
+        let result = async_using!(failing = Closing("failing", &closed) => {
+            Ok::<_, String>(failing.0)
+        });
+        assert_eq!(result, Err(String::from("close failed")));
+
+        let result = async_using!(failing = Closing("failing", &closed) => {
+            Err::<(), _>(String::from("body failed"))
+        });
+        assert_eq!(result, Err(String::from("body failed")));
+        assert_eq!(closed.lock().unwrap().len(), 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_use_context_exits_with_outcome() {
+        #[derive(Default)]
+        struct Outcomes(Mutex<Vec<Outcome>>);
+        struct Recorded<'a>(&'a Outcomes);
+
+        impl<'a> Enter for &'a Outcomes {
+            type Guard = Recorded<'a>;
+
+            fn enter(self) -> Self::Guard {
+                Recorded(self)
+            }
+        }
+
+        impl Exit for Recorded<'_> {
+            fn exit(self, outcome: &Outcome) {
+                self.0 .0.lock().unwrap().push(*outcome);
+            }
+        }
+
+        let outcomes = Outcomes::default();
+        assert_eq!((&outcomes).use_context(|_| Ok::<_, ()>(1)), Ok(1));
+        assert_eq!(
+            (&outcomes).use_context(|_| Err::<(), _>("failed")),
+            Err("failed")
+        );
+        let panicked = std::panic::catch_unwind(|| {
+            (&outcomes).use_context(|_| -> Result<(), ()> { panic!("body panicked") })
+        });
+        assert!(panicked.is_err());
+        assert_eq!(
+            *outcomes.0.lock().unwrap(),
+            [Outcome::Success, Outcome::Error, Outcome::Panic]
+        );
+    }
+
+    #[test]
+    fn test_use_with_async_stores_the_body_once() {
+        fn overhead<const N: usize>(scoped: bool) -> usize {
+            let payload = [0u8; N];
+            let body = move |resource: u64| async move {
+                ::tokio::task::yield_now().await;
+                u64::from(payload[0]) + resource
+            };
+            let direct = std::mem::size_of_val(&body(1));
+            let future_size = if scoped {
+                std::mem::size_of_val(&1u64.scoped().use_with_async(body))
+            } else {
+                std::mem::size_of_val(&1u64.use_with_async(body))
+            };
+            future_size - direct
+        }
+
+        assert!(overhead::<16>(false) <= std::mem::size_of::<u64>());
+        assert_eq!(overhead::<16>(false), overhead::<4096>(false));
+        assert_eq!(overhead::<16>(true), overhead::<4096>(true));
+    }
+
+    #[tokio::test]
+    async fn test_dyn_scopes_are_observed() {
         #[derive(Default)]
+        struct Counting(Mutex<Vec<&'static str>>);
+
+        impl UseObserver for Counting {
+            fn on_acquire(&self, event: &UseEvent) {
+                self.0.lock().unwrap().push(event.resource_type());
+            }
+        }
+
+        let observer = Counting::default();
+        let counter = DropCounter::new();
+        let dropped_inside = counter
+            .probe()
+            .scoped()
+            .observer(&observer)
+            .use_with_dyn(|probe| {
+                drop(probe);
+                counter.count()
+            });
+        assert_eq!(dropped_inside, 1);
+
+        let len = String::from("async")
+            .scoped()
+            .observer(&observer)
+            .use_with_async_dyn(|res| async move { res.len() })
+            .await;
+        assert_eq!(len, 5);
+        assert_eq!(
+            *observer.0.lock().unwrap(),
+            [
+                std::any::type_name::<DropProbe>(),
+                std::any::type_name::<String>()
+            ]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_heartbeat_stops_with_the_scope() {
+        let beats = Arc::new(Mutex::new(0));
+        let counter = DropCounter::new();
+        let result = counter.probe().use_with_heartbeat(
+            Duration::from_millis(2),
+            || *beats.lock().unwrap() += 1,
+            |_probe| {
+                std::thread::sleep(Duration::from_millis(30));
+                7
+            },
+        );
+        assert_eq!((result, counter.count()), (7, 1));
+
+        let after_scope = *beats.lock().unwrap();
+        assert!(after_scope > 0);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(*beats.lock().unwrap(), after_scope);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_heartbeat_panic_resumes_after_the_body() {
+        let counter = DropCounter::new();
+        let (alive, stopped) = std::sync::mpsc::channel::<()>();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            counter.probe().use_with_heartbeat(
+                Duration::from_millis(1),
+                move || {
+                    let _alive = &alive;
+                    panic!("lease lost")
+                },
+                // Returns once the panicking heartbeat dropped its sender.
+                |_probe| stopped.recv().is_err(),
+            )
+        }));
+
+        let payload = panicked.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"lease lost"));
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_async_ticks_while_body_runs() {
+        let mut beats = 0;
+        let result = 7
+            .use_with_heartbeat_async(
+                Duration::from_millis(10),
+                || beats += 1,
+                |value| async move {
+                    tokio::time::sleep(Duration::from_millis(35)).await;
+                    value
+                },
+            )
+            .await;
+        assert_eq!((result, beats), (7, 3));
+    }
+
+    #[test]
+    fn test_try_use_with_snapshot() {
+        #[derive(Debug)]
+        struct Cursor {
+            position: usize,
+        }
+
+        let result = Cursor { position: 0 }.try_use_with_snapshot(|cursor| {
+            cursor.position = 7;
+            Err::<(), _>("unexpected end of input")
+        });
+
+        let error = result.unwrap_err();
+        assert_eq!(error.snapshot(), "Cursor { position: 7 }");
+        assert_eq!(
+            error.to_string(),
+            "unexpected end of input (resource: Cursor { position: 7 })"
+        );
+
+        let result = Cursor { position: 0 }.try_use_with_snapshot(|cursor| {
+            cursor.position += 1;
+            Ok::<_, &str>(cursor.position)
+        });
+        assert_eq!(result, Ok(1));
+    }
+
+    #[test]
+    fn test_use_sealed() {
+        let counter = DropCounter::new();
+        let result = counter.probe().use_sealed(|sealed| {
+            let _probe: &DropProbe = &sealed;
+            assert_eq!(counter.count(), 0);
+            42
+        });
+
+        assert_eq!(result, 42);
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_use_with_async_modifies_external_state() {
         struct Resource;
 
         // Shared state wrapped in Arc<Mutex<...>>
@@ -378,7 +2218,7 @@ mod tests {
             let state = Arc::clone(&shared_state);
 
             // Create a new Resource and use `use_with_async` to pass an async closure
-            Resource::default()
+            Resource
                 .use_with_async(|_res| async move {
                     let mut num = state.lock().await;
                     *num += 1;
@@ -393,4 +2233,189 @@ mod tests {
         // Verify that the shared state was modified
         assert_eq!(*shared_state.lock().await, 1);
     }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_use_with_async_catch_unwind() {
+        let counter = DropCounter::new();
+        let result = counter
+            .probe()
+            .use_with_async_catch_unwind(|_probe| async {
+                tokio::task::yield_now().await;
+                panic!("handler crashed");
+            })
+            .await;
+
+        let payload: PanicPayload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"handler crashed"));
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_use_close_async_catch_unwind_closes_after_panic() {
+        struct Resource(Arc<Mutex<bool>>, bool);
+
+        impl AsyncClose for Resource {
+            type Error = &'static str;
+
+            async fn close_async(self) -> Result<(), Self::Error> {
+                *self.0.lock().unwrap() = true;
+                if self.1 {
+                    Ok(())
+                } else {
+                    Err("close failed")
+                }
+            }
+        }
+
+        let closed = Arc::new(Mutex::new(false));
+        let result = Resource(closed.clone(), false)
+            .use_close_async_catch_unwind(|_res| -> BoxFuture<'_, ()> {
+                Box::pin(async {
+                    tokio::task::yield_now().await;
+                    panic!("handler crashed");
+                })
+            })
+            .await;
+
+        let error = result.unwrap_err();
+        assert!(error.payload().is_some());
+        assert_eq!(error.close_error(), Some(&"close failed"));
+        assert!(*closed.lock().unwrap(), "Resource was not closed");
+
+        let result = Resource(closed, true)
+            .use_close_async_catch_unwind(|_res| Box::pin(async { 42 }))
+            .await;
+        assert_eq!(result.ok(), Some(42));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_use_critical_aborts_on_panic() {
+        const CHILD: &str = "USE_WITH_CRITICAL_CHILD";
+        if std::env::var_os(CHILD).is_some() {
+            let counter = DropCounter::new();
+            counter.probe().use_critical(|_probe| panic!("torn frame"));
+            unreachable!("use_critical returned after a panic");
+        }
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "tests::test_use_critical_aborts_on_panic"])
+            .args(["--nocapture", "--test-threads=1"])
+            .env(CHILD, "1")
+            .output()
+            .unwrap();
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains("panicked in a critical section"),
+            "{stderr}"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_use_critical() {
+        let counter = DropCounter::new();
+        let result = counter.probe().use_critical(|_probe| {
+            assert_eq!(counter.count(), 0);
+            42
+        });
+
+        assert_eq!(result, 42);
+        assert_eq!(counter.count(), 1);
+    }
+
+    struct PanickingClose(Arc<Mutex<bool>>);
+
+    impl Close for PanickingClose {
+        type Error = &'static str;
+
+        fn close(self) -> Result<(), Self::Error> {
+            *self.0.lock().unwrap() = true;
+            panic!("close panicked");
+        }
+    }
+
+    impl AsyncClose for PanickingClose {
+        type Error = &'static str;
+
+        async fn close_async(self) -> Result<(), Self::Error> {
+            self.close()
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_use_close_closes_after_panic() {
+        struct Resource(Arc<Mutex<bool>>);
+
+        impl Close for Resource {
+            type Error = &'static str;
+
+            fn close(self) -> Result<(), Self::Error> {
+                *self.0.lock().unwrap() = true;
+                Ok(())
+            }
+        }
+
+        let closed = Arc::new(Mutex::new(false));
+        let result = std::panic::catch_unwind(|| {
+            Resource(closed.clone()).use_close(|_res| panic!("body panicked"))
+        });
+
+        assert!(result.is_err());
+        assert!(*closed.lock().unwrap(), "Resource was not closed");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_use_close_suppresses_close_panic_after_body_panic() {
+        let closed = Arc::new(Mutex::new(false));
+        let result = std::panic::catch_unwind(|| {
+            PanickingClose(closed.clone()).use_close(|_res| panic!("body panicked"))
+        });
+
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"body panicked"));
+        assert!(*closed.lock().unwrap(), "Resource was not closed");
+    }
+
+    #[test]
+    fn test_use_close_propagates_close_panic() {
+        let closed = Arc::new(Mutex::new(false));
+        let result =
+            std::panic::catch_unwind(|| PanickingClose(closed.clone()).use_close(|_res| 42));
+
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"close panicked"));
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_use_close_async_suppresses_close_panic_after_body_panic() {
+        let closed = Arc::new(Mutex::new(false));
+        let result = PanickingClose(closed.clone())
+            .use_close_async_catch_unwind(|_res| -> BoxFuture<'_, ()> {
+                Box::pin(async { panic!("body panicked") })
+            })
+            .await;
+
+        let Err(UnwindError::Panic { payload, close }) = result else {
+            panic!("expected the body panic to be reported");
+        };
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"body panicked"));
+        assert!(close.is_none());
+        assert!(*closed.lock().unwrap(), "Resource was not closed");
+
+        let closed = Arc::new(Mutex::new(false));
+        let task = tokio::spawn(PanickingClose(closed.clone()).use_close_async(
+            |_res| -> BoxFuture<'_, ()> { Box::pin(async { panic!("body panicked") }) },
+        ));
+        let payload = task.await.unwrap_err().into_panic();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"body panicked"));
+        assert!(*closed.lock().unwrap(), "Resource was not closed");
+    }
 }
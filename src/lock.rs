@@ -0,0 +1,338 @@
+//! Scoped critical sections on locks.
+
+use crate::{Poisoned, UseScope};
+use std::fmt;
+use std::sync::{self, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Determines how a scoped critical section treats a lock that was poisoned by a panic.
+///
+/// A lock is poisoned when a thread panics while holding it, which may leave the protected
+/// data in an inconsistent state.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Refuses to run the critical section and returns [`Poisoned`] instead.
+    #[default]
+    Fail,
+    /// Ignores the poison and runs the critical section on the data as it is.
+    Recover,
+}
+
+impl PoisonPolicy {
+    /// Applies the policy to the result of acquiring a lock.
+    fn acquire<G, T: ?Sized>(self, result: Result<G, PoisonError<G>>) -> Result<G, Poisoned> {
+        match (result, self) {
+            (Ok(guard), _) => Ok(guard),
+            (Err(poisoned), PoisonPolicy::Recover) => Ok(poisoned.into_inner()),
+            (Err(_), PoisonPolicy::Fail) => Err(Poisoned::of::<T>()),
+        }
+    }
+
+    /// Applies the policy to the result of attempting to acquire a lock without blocking.
+    fn try_acquire<G, T: ?Sized>(
+        self,
+        result: Result<G, sync::TryLockError<G>>,
+    ) -> Result<G, TryLockError> {
+        match result {
+            Ok(guard) => Ok(guard),
+            Err(sync::TryLockError::Poisoned(poisoned)) => self
+                .acquire::<G, T>(Err(poisoned))
+                .map_err(TryLockError::Poisoned),
+            Err(sync::TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+        }
+    }
+}
+
+/// The error returned by the non-blocking critical sections of [`RwLockUseExt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryLockError {
+    /// The lock is poisoned and the [`PoisonPolicy`] refused to run the critical section.
+    Poisoned(Poisoned),
+    /// The lock could not be acquired without blocking.
+    WouldBlock,
+}
+
+impl From<Poisoned> for TryLockError {
+    fn from(poisoned: Poisoned) -> Self {
+        TryLockError::Poisoned(poisoned)
+    }
+}
+
+impl fmt::Display for TryLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryLockError::Poisoned(poisoned) => poisoned.fmt(f),
+            TryLockError::WouldBlock => f.write_str("acquiring the lock would block"),
+        }
+    }
+}
+
+impl std::error::Error for TryLockError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TryLockError::Poisoned(poisoned) => Some(poisoned),
+            TryLockError::WouldBlock => None,
+        }
+    }
+}
+
+/// Runs critical sections on a [`Mutex`] as use scopes.
+///
+/// The closure receives the protected data rather than the guard, so the guard cannot escape the
+/// critical section and is guaranteed to be released once the closure returns or panics. The
+/// critical section is reported to observers like any other use scope, with the guard as its
+/// resource.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::Mutex;
+/// use use_with::{LockUseExt, PoisonPolicy};
+///
+/// let queue = Mutex::new(vec![1, 2]);
+///
+/// let len = queue.use_locked(PoisonPolicy::Fail, |queue| {
+///     queue.push(3);
+///     queue.len()
+/// });
+///
+/// assert_eq!(len, Ok(3));
+/// assert!(queue.try_lock().is_ok());
+/// ```
+pub trait LockUseExt<T: ?Sized> {
+    /// Locks the mutex, runs the closure `f` on the protected data and releases the lock.
+    ///
+    /// Blocks the current thread until the lock is acquired.
+    ///
+    /// # Parameters
+    /// - `policy`: How to treat a mutex that was poisoned by a panic.
+    /// - `f`: A closure that borrows the protected data mutably.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(Poisoned)` without running the closure if the mutex is poisoned and the policy is
+    ///   [`PoisonPolicy::Fail`].
+    fn use_locked<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, Poisoned>
+    where
+        F: FnOnce(&mut T) -> U;
+}
+
+impl<T: ?Sized> LockUseExt<T> for Mutex<T> {
+    #[track_caller]
+    fn use_locked<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, Poisoned>
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        let guard = policy.acquire::<_, Mutex<T>>(self.lock())?;
+        Ok(UseScope::new(guard).use_with(|mut guard: MutexGuard<'_, T>| f(&mut guard)))
+    }
+}
+
+/// Runs read and write critical sections on a [`RwLock`] as use scopes.
+///
+/// Like [`LockUseExt`], the closures receive the protected data rather than the guard, so the lock
+/// is guaranteed to be released once the closure returns or panics. The `try_*` variants return
+/// [`TryLockError::WouldBlock`] instead of blocking if the lock is currently held.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::RwLock;
+/// use use_with::{PoisonPolicy, RwLockUseExt, TryLockError};
+///
+/// let settings = RwLock::new(String::from("debug"));
+///
+/// settings
+///     .use_write(PoisonPolicy::Fail, |config| config.push_str(",trace"))
+///     .unwrap();
+///
+/// let len = settings.use_read(PoisonPolicy::Fail, |config| {
+///     // A reader does not block other readers, but it does block writers.
+///     assert_eq!(
+///         settings.try_use_read(PoisonPolicy::Fail, |config| config.len()),
+///         Ok(11)
+///     );
+///     assert_eq!(
+///         settings.try_use_write(PoisonPolicy::Fail, |_config| ()),
+///         Err(TryLockError::WouldBlock)
+///     );
+///     config.len()
+/// });
+/// assert_eq!(len, Ok(11));
+/// ```
+pub trait RwLockUseExt<T: ?Sized> {
+    /// Acquires shared read access, runs the closure `f` on the protected data and releases the lock.
+    ///
+    /// Blocks the current thread until read access is granted.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(Poisoned)` without running the closure if the lock is poisoned and the policy is
+    ///   [`PoisonPolicy::Fail`].
+    fn use_read<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, Poisoned>
+    where
+        F: FnOnce(&T) -> U;
+
+    /// Acquires exclusive write access, runs the closure `f` on the protected data and releases
+    /// the lock.
+    ///
+    /// Blocks the current thread until write access is granted.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(Poisoned)` without running the closure if the lock is poisoned and the policy is
+    ///   [`PoisonPolicy::Fail`].
+    fn use_write<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, Poisoned>
+    where
+        F: FnOnce(&mut T) -> U;
+
+    /// Like [`use_read`](Self::use_read), but returns [`TryLockError::WouldBlock`] instead of
+    /// blocking if a writer holds the lock.
+    fn try_use_read<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, TryLockError>
+    where
+        F: FnOnce(&T) -> U;
+
+    /// Like [`use_write`](Self::use_write), but returns [`TryLockError::WouldBlock`] instead of
+    /// blocking if a reader or writer holds the lock.
+    fn try_use_write<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, TryLockError>
+    where
+        F: FnOnce(&mut T) -> U;
+}
+
+impl<T: ?Sized> RwLockUseExt<T> for RwLock<T> {
+    #[track_caller]
+    fn use_read<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, Poisoned>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        let guard = policy.acquire::<_, RwLock<T>>(self.read())?;
+        Ok(read_scope(guard, f))
+    }
+
+    #[track_caller]
+    fn use_write<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, Poisoned>
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        let guard = policy.acquire::<_, RwLock<T>>(self.write())?;
+        Ok(write_scope(guard, f))
+    }
+
+    #[track_caller]
+    fn try_use_read<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, TryLockError>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        let guard = policy.try_acquire::<_, RwLock<T>>(self.try_read())?;
+        Ok(read_scope(guard, f))
+    }
+
+    #[track_caller]
+    fn try_use_write<U, F>(&self, policy: PoisonPolicy, f: F) -> Result<U, TryLockError>
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        let guard = policy.try_acquire::<_, RwLock<T>>(self.try_write())?;
+        Ok(write_scope(guard, f))
+    }
+}
+
+#[track_caller]
+fn read_scope<T: ?Sized, U>(guard: RwLockReadGuard<'_, T>, f: impl FnOnce(&T) -> U) -> U {
+    UseScope::new(guard).use_with(|guard| f(&guard))
+}
+
+#[track_caller]
+fn write_scope<T: ?Sized, U>(guard: RwLockWriteGuard<'_, T>, f: impl FnOnce(&mut T) -> U) -> U {
+    UseScope::new(guard).use_with(|mut guard| f(&mut guard))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    fn poisoned_mutex() -> Mutex<Vec<u8>> {
+        let mutex = Mutex::new(Vec::new());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            mutex.use_locked(PoisonPolicy::Fail, |data| {
+                data.push(1);
+                panic!("interrupted");
+            })
+        }));
+        assert!(result.is_err());
+        mutex
+    }
+
+    #[test]
+    fn test_use_locked_releases_lock() {
+        let mutex = Mutex::new(0);
+        assert_eq!(mutex.use_locked(PoisonPolicy::Fail, |n| *n += 1), Ok(()));
+        assert_eq!(*mutex.try_lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_panic_releases_and_poisons_lock() {
+        let mutex = poisoned_mutex();
+        assert!(mutex.is_poisoned());
+
+        let error = mutex
+            .use_locked(PoisonPolicy::Fail, |_data| unreachable!())
+            .unwrap_err();
+        assert!(error.resource_type().contains("Mutex<alloc::vec::Vec<u8>>"));
+    }
+
+    #[test]
+    fn test_recover_ignores_poison() {
+        let mutex = poisoned_mutex();
+        let len = mutex.use_locked(PoisonPolicy::Recover, |data| data.len());
+        assert_eq!(len, Ok(1));
+    }
+
+    #[test]
+    fn test_use_read_and_write() {
+        let lock = RwLock::new(vec![1]);
+        assert_eq!(
+            lock.use_write(PoisonPolicy::Fail, |data| data.push(2)),
+            Ok(())
+        );
+
+        let sum = lock.use_read(PoisonPolicy::Fail, |data| {
+            assert_eq!(
+                lock.try_use_read(PoisonPolicy::Fail, |data| data.len()),
+                Ok(2)
+            );
+            data.iter().sum::<i32>()
+        });
+        assert_eq!(sum, Ok(3));
+        assert!(lock.try_write().is_ok());
+    }
+
+    #[test]
+    fn test_try_use_write_would_block() {
+        let lock = RwLock::new(0);
+        let result = lock.use_read(PoisonPolicy::Fail, |_data| {
+            lock.try_use_write(PoisonPolicy::Fail, |data| *data += 1)
+        });
+        assert_eq!(result, Ok(Err(TryLockError::WouldBlock)));
+        assert_eq!(
+            lock.try_use_write(PoisonPolicy::Fail, |data| *data += 1),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_poisoned_rwlock() {
+        let lock = RwLock::new(0);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            lock.use_write(PoisonPolicy::Fail, |_data| panic!("interrupted"))
+        }));
+        assert!(result.is_err());
+
+        assert!(matches!(
+            lock.try_use_read(PoisonPolicy::Fail, |data| *data),
+            Err(TryLockError::Poisoned(_))
+        ));
+        assert_eq!(
+            lock.try_use_read(PoisonPolicy::Recover, |data| *data),
+            Ok(0)
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Adapters giving `std::net` types an explicit, fallible teardown.
+
+use crate::Close;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+/// A TCP stream that is shut down gracefully when it is closed.
+///
+/// Dropping a [`TcpStream`] closes the socket without a protocol-level goodbye; unread data may
+/// even cause the connection to be reset. Closing a `GracefulStream` shuts down both directions
+/// of the connection instead and reports errors of the shutdown. With
+/// [`wait_for_fin`](GracefulStream::wait_for_fin), it first shuts down the writing half only and
+/// waits for the peer to close its half, discarding any data that is still received.
+///
+/// # Examples
+/// ```rust
+/// use std::io::{Read, Write};
+/// use std::net::{TcpListener, TcpStream};
+/// use std::time::Duration;
+/// use use_with::net::GracefulStream;
+/// use use_with::Use;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0")?;
+/// let server_addr = listener.local_addr()?;
+/// let server = std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+///     let (mut stream, _) = listener.accept()?;
+///     let mut request = Vec::new();
+///     stream.read_to_end(&mut request)?;
+///     Ok(request)
+/// });
+///
+/// let stream = TcpStream::connect(server_addr)?;
+/// GracefulStream::new(stream)
+///     .wait_for_fin(Duration::from_secs(5))
+///     .use_close(|stream| stream.write_all(b"PING"))??;
+///
+/// assert_eq!(server.join().unwrap()?, b"PING");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct GracefulStream {
+    stream: TcpStream,
+    fin_timeout: Option<Duration>,
+}
+
+impl GracefulStream {
+    /// Wraps a connected stream that is shut down in both directions when closed.
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            fin_timeout: None,
+        }
+    }
+
+    /// Waits up to `timeout` for the peer to close its half of the connection when closed.
+    ///
+    /// Closing fails with [`io::ErrorKind::TimedOut`] if the peer keeps its half open for longer.
+    pub fn wait_for_fin(mut self, timeout: Duration) -> Self {
+        self.fin_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the wrapped stream without shutting it down.
+    pub fn into_inner(self) -> TcpStream {
+        self.stream
+    }
+
+    /// Discards incoming data until the peer closes its half of the connection.
+    fn drain(&mut self, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0; 1024];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(fin_timed_out(timeout));
+            }
+            self.stream.set_read_timeout(Some(remaining))?;
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Err(fin_timed_out(timeout))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+fn fin_timed_out(timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("the peer did not close the connection within {timeout:?}"),
+    )
+}
+
+impl Deref for GracefulStream {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl DerefMut for GracefulStream {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.stream
+    }
+}
+
+impl Read for GracefulStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for GracefulStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Close for GracefulStream {
+    type Error = io::Error;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        match self.fin_timeout {
+            Some(timeout) => {
+                self.stream.shutdown(Shutdown::Write)?;
+                self.drain(timeout)
+            }
+            None => self.stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+    use std::net::TcpListener;
+
+    fn connect() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (client, server)
+    }
+
+    #[test]
+    fn test_close_shuts_down_connection() {
+        let (client, mut server) = connect();
+        GracefulStream::new(client)
+            .use_close(|stream| stream.write_all(b"bye"))
+            .unwrap()
+            .unwrap();
+
+        let mut received = Vec::new();
+        server.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"bye");
+    }
+
+    #[test]
+    fn test_wait_for_fin_times_out() {
+        let (client, _server) = connect();
+        let error = GracefulStream::new(client)
+            .wait_for_fin(Duration::from_millis(50))
+            .use_close(|_stream| ())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,521 @@
+//! Pluggable observation of resource lifecycles.
+//!
+//! A [`UseObserver`] is notified whenever a use scope acquires a resource, finishes its body,
+//! closes the resource, or fails. Observers can be installed process-wide via
+//! [`set_global_observer`] or attached to a single call via [`Use::scoped`](crate::Use::scoped).
+//! Observers attached with [`UseScope::static_observer`](crate::UseScope::static_observer) are
+//! called without dynamic dispatch.
+//!
+//! # Examples
+//! ```rust
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use use_with::observer::{UseEvent, UseObserver};
+//! use use_with::Use;
+//!
+//! #[derive(Default)]
+//! struct CountingObserver(AtomicUsize);
+//!
+//! impl UseObserver for CountingObserver {
+//!     fn on_acquire(&self, _event: &UseEvent) {
+//!         self.0.fetch_add(1, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! let observer = CountingObserver::default();
+//! let result = 40.scoped().observer(&observer).use_with(|value| value + 2);
+//!
+//! assert_eq!(result, 42);
+//! assert_eq!(observer.0.load(Ordering::Relaxed), 1);
+//! ```
+
+#[cfg(feature = "std")]
+use crate::instrument::{self, Consumer};
+use crate::ScopeId;
+use alloc::sync::Arc;
+use core::panic::Location;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+
+/// Receives notifications about the lifecycle of resources in use scopes.
+///
+/// All methods have empty default implementations, so implementors only need to
+/// override the events they are interested in.
+pub trait UseObserver: Send + Sync {
+    /// Called when a use scope has acquired its resource, before the body runs.
+    fn on_acquire(&self, _event: &UseEvent) {}
+
+    /// Called when the body of a use scope has returned.
+    fn on_body_end(&self, _event: &UseEvent) {}
+
+    /// Called when the resource of a use scope has been closed or dropped successfully.
+    fn on_close(&self, _event: &UseEvent) {}
+
+    /// Called when a use scope failed, either because the body panicked or closing failed.
+    ///
+    /// Also called with [`Failure::DropPanic`] when dropping a [`QuietDrop`](crate::QuietDrop)
+    /// panicked; such events are not preceded by an acquisition.
+    fn on_error(&self, _event: &UseEvent, _failure: Failure) {}
+
+    /// Called when closing or dropping the resource took longer than the threshold configured
+    /// via [`UseScope::slow_teardown`](crate::UseScope::slow_teardown).
+    ///
+    /// Called before [`on_close`](Self::on_close) or [`on_error`](Self::on_error) report the
+    /// outcome of the teardown.
+    fn on_slow_teardown(&self, _event: &UseEvent, _teardown: Duration) {}
+
+    /// Called when a use scope held its resource for longer than the threshold configured via
+    /// [`UseScope::warn_if_held_longer_than`](crate::UseScope::warn_if_held_longer_than).
+    ///
+    /// The resource is held from the acquisition until it has been closed or dropped, or until
+    /// the body panicked. Global observers are called by a watchdog thread as soon as the
+    /// threshold passes, while the resource is still held, so that stuck or deadlocked scopes are
+    /// reported too. All other observers are called once the resource is released, before
+    /// [`on_close`](Self::on_close) or [`on_error`](Self::on_error) report the outcome of the
+    /// teardown, with the total hold time. Each observer is called at most once per scope.
+    fn on_long_hold(&self, _event: &UseEvent, _held: Duration) {}
+
+    /// Returns whether the observer ignores all notifications, so that scopes observed by nothing
+    /// else need not be tracked.
+    #[doc(hidden)]
+    fn is_noop(&self) -> bool {
+        false
+    }
+}
+
+/// An observer that ignores all notifications.
+///
+/// The default observer of a [`UseScope`](crate::UseScope). Observers attached via
+/// [`UseScope::static_observer`](crate::UseScope::static_observer) take its place and are called
+/// without dynamic dispatch; with `NoopObserver`, the calls compile to nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoopObserver;
+
+impl UseObserver for NoopObserver {
+    fn is_noop(&self) -> bool {
+        true
+    }
+}
+
+impl<O: UseObserver + ?Sized> UseObserver for &O {
+    fn on_acquire(&self, event: &UseEvent) {
+        (**self).on_acquire(event);
+    }
+
+    fn on_body_end(&self, event: &UseEvent) {
+        (**self).on_body_end(event);
+    }
+
+    fn on_close(&self, event: &UseEvent) {
+        (**self).on_close(event);
+    }
+
+    fn on_error(&self, event: &UseEvent, failure: Failure) {
+        (**self).on_error(event, failure);
+    }
+
+    fn on_slow_teardown(&self, event: &UseEvent, teardown: Duration) {
+        (**self).on_slow_teardown(event, teardown);
+    }
+
+    fn on_long_hold(&self, event: &UseEvent, held: Duration) {
+        (**self).on_long_hold(event, held);
+    }
+
+    fn is_noop(&self) -> bool {
+        (**self).is_noop()
+    }
+}
+
+/// Describes the use scope an observer notification belongs to.
+#[derive(Debug, Clone)]
+pub struct UseEvent {
+    id: ScopeId,
+    resource_type: &'static str,
+    location: &'static Location<'static>,
+}
+
+impl UseEvent {
+    pub(crate) fn new(
+        id: ScopeId,
+        resource_type: &'static str,
+        location: &'static Location<'static>,
+    ) -> Self {
+        Self {
+            id,
+            resource_type,
+            location,
+        }
+    }
+
+    /// Returns the unique identifier of the use scope.
+    ///
+    /// All notifications belonging to the same scope carry the same identifier.
+    pub fn id(&self) -> ScopeId {
+        self.id
+    }
+
+    /// Returns the type name of the resource used in the scope.
+    pub fn resource_type(&self) -> &'static str {
+        self.resource_type
+    }
+
+    /// Returns the source location at which the use scope was entered.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+/// The kind of failure reported to [`UseObserver::on_error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Failure {
+    /// The body of the use scope panicked.
+    Panic,
+    /// Closing the resource returned an error.
+    Close,
+    /// Closing the resource panicked after the body had panicked already.
+    ///
+    /// The panic of the closing is suppressed; the panic of the body continues to unwind.
+    ClosePanic,
+    /// Dropping a [`QuietDrop`](crate::QuietDrop) resource panicked, and the panic was suppressed.
+    DropPanic,
+}
+
+#[cfg(feature = "std")]
+static GLOBAL_ACTIVE: AtomicBool = AtomicBool::new(false);
+#[cfg(feature = "std")]
+static GLOBAL: RwLock<Option<Arc<dyn UseObserver>>> = RwLock::new(None);
+
+/// Installs an observer that is notified about every use scope in the process.
+///
+/// Replaces any previously installed global observer.
+#[cfg(feature = "std")]
+pub fn set_global_observer(observer: impl UseObserver + 'static) {
+    let mut global = GLOBAL.write().unwrap_or_else(|e| e.into_inner());
+    *global = Some(Arc::new(observer));
+    GLOBAL_ACTIVE.store(true, Ordering::Release);
+    instrument::set_consumer(Consumer::GlobalObserver, true);
+}
+
+/// Removes the global observer, if any.
+#[cfg(feature = "std")]
+pub fn clear_global_observer() {
+    let mut global = GLOBAL.write().unwrap_or_else(|e| e.into_inner());
+    GLOBAL_ACTIVE.store(false, Ordering::Release);
+    instrument::set_consumer(Consumer::GlobalObserver, false);
+    *global = None;
+}
+
+/// Returns the currently installed global observer.
+///
+/// Checks an atomic flag first so that scopes do not contend on the lock
+/// while no global observer is installed.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn global_observer() -> Option<Arc<dyn UseObserver>> {
+    if !GLOBAL_ACTIVE.load(Ordering::Acquire) {
+        return None;
+    }
+    GLOBAL.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Returns `None`, since global observers require the `std` feature.
+#[cfg(not(feature = "std"))]
+#[inline(always)]
+pub(crate) fn global_observer() -> Option<Arc<dyn UseObserver>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Close, Use};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>, Mutex<Option<ScopeId>>);
+
+    impl UseObserver for Recorder {
+        fn on_acquire(&self, event: &UseEvent) {
+            self.push("acquire", event);
+        }
+
+        fn on_body_end(&self, event: &UseEvent) {
+            self.push("body_end", event);
+        }
+
+        fn on_close(&self, event: &UseEvent) {
+            self.push("close", event);
+        }
+
+        fn on_error(&self, event: &UseEvent, failure: Failure) {
+            self.push(&format!("error({failure:?})"), event);
+        }
+
+        fn on_slow_teardown(&self, event: &UseEvent, _teardown: Duration) {
+            self.push("slow_teardown", event);
+        }
+
+        fn on_long_hold(&self, event: &UseEvent, _held: Duration) {
+            self.push("long_hold", event);
+        }
+    }
+
+    impl Recorder {
+        fn push(&self, what: &str, event: &UseEvent) {
+            assert!(event.resource_type().ends_with("Resource"));
+            assert_eq!(event.location().file(), file!());
+            assert_eq!(
+                *self.1.lock().unwrap().get_or_insert(event.id()),
+                event.id()
+            );
+            self.0.lock().unwrap().push(what.to_string());
+        }
+
+        fn events(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    struct Resource(bool);
+
+    impl Close for Resource {
+        type Error = ();
+
+        fn close(self) -> Result<(), Self::Error> {
+            if self.0 {
+                Ok(())
+            } else {
+                Err(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_per_call_observer() {
+        let observer = Recorder::default();
+        let result = Resource(true)
+            .scoped()
+            .observer(&observer)
+            .use_with(|_res| 42);
+
+        assert_eq!(result, 42);
+        assert_eq!(observer.events(), ["acquire", "body_end", "close"]);
+    }
+
+    #[test]
+    fn test_static_observer() {
+        let observer = Recorder::default();
+        let dynamic = Recorder::default();
+        let result = Resource(false)
+            .scoped()
+            .static_observer(&observer)
+            .observer(&dynamic)
+            .use_close(|_res| 42);
+
+        assert_eq!(result, Err(()));
+        assert_eq!(observer.events(), ["acquire", "body_end", "error(Close)"]);
+        assert_eq!(observer.events(), dynamic.events());
+    }
+
+    #[test]
+    fn test_close_failure_is_reported() {
+        let observer = Recorder::default();
+        let result = Resource(false)
+            .scoped()
+            .observer(&observer)
+            .use_close(|_res| 42);
+
+        assert_eq!(result, Err(()));
+        assert_eq!(observer.events(), ["acquire", "body_end", "error(Close)"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_panic_is_reported() {
+        let observer = Recorder::default();
+        let result = std::panic::catch_unwind(|| {
+            Resource(true)
+                .scoped()
+                .observer(&observer)
+                .use_with(|_res| panic!("Intentional panic"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(observer.events(), ["acquire", "error(Panic)"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_close_after_panic_is_reported() {
+        let observer = Recorder::default();
+        let result = std::panic::catch_unwind(|| {
+            Resource(false)
+                .scoped()
+                .observer(&observer)
+                .use_close(|_res| panic!("Intentional panic"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(
+            observer.events(),
+            ["acquire", "error(Panic)", "error(Close)"]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_slow_teardown_is_reported() {
+        struct SlowResource;
+
+        impl Close for SlowResource {
+            type Error = ();
+
+            fn close(self) -> Result<(), Self::Error> {
+                std::thread::sleep(Duration::from_millis(20));
+                Ok(())
+            }
+        }
+
+        let observer = Recorder::default();
+        SlowResource
+            .scoped()
+            .observer(&observer)
+            .slow_teardown(Duration::from_millis(5))
+            .use_close(|_res| ())
+            .unwrap();
+        assert_eq!(
+            observer.events(),
+            ["acquire", "body_end", "slow_teardown", "close"]
+        );
+
+        let observer = Recorder::default();
+        SlowResource
+            .scoped()
+            .observer(&observer)
+            .slow_teardown(Duration::from_secs(3600))
+            .use_close(|_res| ())
+            .unwrap();
+        assert_eq!(observer.events(), ["acquire", "body_end", "close"]);
+    }
+
+    #[cfg(all(feature = "std", debug_assertions))]
+    #[test]
+    #[should_panic(expected = "cannot time the teardown")]
+    fn test_slow_teardown_is_rejected_when_the_body_owns_the_resource() {
+        let observer = Recorder::default();
+        Resource(true)
+            .scoped()
+            .observer(&observer)
+            .slow_teardown(Duration::from_millis(5))
+            .use_with(drop);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_long_hold_is_reported() {
+        let observer = Recorder::default();
+        Resource(true)
+            .scoped()
+            .observer(&observer)
+            .warn_if_held_longer_than(Duration::from_millis(5))
+            .use_with(|_res| std::thread::sleep(Duration::from_millis(20)));
+        assert_eq!(
+            observer.events(),
+            ["acquire", "body_end", "long_hold", "close"]
+        );
+
+        let observer = Recorder::default();
+        Resource(true)
+            .scoped()
+            .observer(&observer)
+            .warn_if_held_longer_than(Duration::from_secs(3600))
+            .use_close(|_res| ())
+            .unwrap();
+        assert_eq!(observer.events(), ["acquire", "body_end", "close"]);
+
+        let observer = Recorder::default();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            Resource(true)
+                .scoped()
+                .observer(&observer)
+                .warn_if_held_longer_than(Duration::from_millis(5))
+                .use_with(|_res| {
+                    std::thread::sleep(Duration::from_millis(20));
+                    panic!("Intentional panic")
+                })
+        }));
+        assert!(result.is_err());
+        assert_eq!(observer.events(), ["acquire", "long_hold", "error(Panic)"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_caught_panic_is_reported() {
+        let observer = Recorder::default();
+        let result = Resource(true)
+            .scoped()
+            .observer(&observer)
+            .use_with_catch_unwind(|_res| panic!("Intentional panic"));
+
+        assert!(result.is_err());
+        assert_eq!(observer.events(), ["acquire", "error(Panic)"]);
+    }
+
+    #[test]
+    fn test_scopes_have_distinct_ids() {
+        let first = Recorder::default();
+        let second = Recorder::default();
+        Resource(true).scoped().observer(&first).use_with(|_res| ());
+        Resource(true)
+            .scoped()
+            .observer(&second)
+            .use_with(|_res| ());
+
+        let first = first.1.lock().unwrap().unwrap();
+        let second = second.1.lock().unwrap().unwrap();
+        assert!(first < second);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_global_observer() {
+        use std::sync::atomic::AtomicUsize;
+
+        struct GlobalOnlyResource;
+        struct Counter(Arc<AtomicUsize>);
+
+        impl UseObserver for Counter {
+            fn on_close(&self, event: &UseEvent) {
+                if event.resource_type().ends_with("GlobalOnlyResource") {
+                    assert_eq!(event.location().file(), file!());
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        set_global_observer(Counter(count.clone()));
+        GlobalOnlyResource.use_with(|_res| ());
+        clear_global_observer();
+        GlobalOnlyResource.use_with(|_res| ());
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_async_observer() {
+        let observer = Recorder::default();
+        let result = Resource(true)
+            .scoped()
+            .observer(&observer)
+            .use_with_async(|_res| async { 42 })
+            .await;
+
+        assert_eq!(result, 42);
+        assert_eq!(observer.events(), ["acquire", "body_end", "close"]);
+    }
+}
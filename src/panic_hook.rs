@@ -0,0 +1,162 @@
+//! Resource context for panic messages.
+//!
+//! A panic message names the code that panicked, but not the resources it was working on. After
+//! [`install`] has been called, every thread keeps track of the use scopes whose bodies it is
+//! currently running, and the installed panic hook appends them to the output of the previous
+//! hook. Asynchronous scopes are tracked while their body is being polled, so the context is
+//! correct even if tasks move between threads.
+//!
+//! ```text
+//! thread 'main' panicked at src/main.rs:12:9:
+//! connection reset
+//! note: panicked in use scope #7 for `app::Connection` entered at src/main.rs:10:5
+//! note: within use scope #6 for `app::Pool` entered at src/main.rs:8:1
+//! ```
+//!
+//! Custom panic hooks can query the same information through [`active_scopes`].
+//!
+//! # Examples
+//! ```rust
+//! use use_with::{panic_hook, Use};
+//!
+//! struct Connection;
+//!
+//! panic_hook::install();
+//!
+//! Connection.use_with(|_conn| {
+//!     let scopes = panic_hook::active_scopes();
+//!     assert!(scopes[0].resource_type().ends_with("Connection"));
+//! });
+//! ```
+
+use crate::instrument::{self, Consumer};
+use crate::observer::UseEvent;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static ACTIVE: RefCell<Vec<UseEvent>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Installs a panic hook that reports the use scopes active on the panicking thread.
+///
+/// The hook runs the previously installed hook first, so it composes with custom hooks that
+/// were installed before. Installing the hook more than once has no further effect.
+pub fn install() {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    instrument::set_consumer(Consumer::PanicHook, true);
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+
+        let scopes = active_scopes();
+        for (depth, scope) in scopes.iter().enumerate() {
+            let relation = if depth == 0 { "panicked in" } else { "within" };
+            eprintln!(
+                "note: {relation} use scope {} for `{}` entered at {}",
+                scope.id(),
+                scope.resource_type(),
+                scope.location()
+            );
+        }
+    }));
+}
+
+/// Returns the use scopes whose bodies are currently running on this thread, innermost first.
+///
+/// Scopes are only tracked after [`install`] has been called.
+pub fn active_scopes() -> Vec<UseEvent> {
+    ACTIVE
+        .try_with(|active| {
+            active
+                .try_borrow()
+                .map(|active| active.iter().rev().cloned().collect())
+                .unwrap_or_default()
+        })
+        .unwrap_or_default()
+}
+
+/// Marks the body of a use scope as running on the current thread until dropped.
+pub(crate) struct ActiveScope(());
+
+impl ActiveScope {
+    /// Tracks the scope described by `event`, if the panic hook is installed.
+    #[inline(always)]
+    pub(crate) fn enter(event: &UseEvent) -> Option<Self> {
+        if !INSTALLED.load(Ordering::Acquire) {
+            return None;
+        }
+        ACTIVE.with(|active| active.borrow_mut().push(event.clone()));
+        Some(Self(()))
+    }
+}
+
+impl Drop for ActiveScope {
+    fn drop(&mut self) {
+        // Ignore thread-local destruction at thread exit.
+        let _ = ACTIVE.try_with(|active| active.borrow_mut().pop());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+
+    struct Outer;
+    struct Inner;
+
+    #[test]
+    fn test_nested_scopes_are_tracked() {
+        install();
+
+        Outer.use_with(|_outer| {
+            Inner.use_with(|_inner| {
+                let scopes = active_scopes();
+                assert_eq!(scopes.len(), 2);
+                assert!(scopes[0].resource_type().ends_with("Inner"));
+                assert!(scopes[1].resource_type().ends_with("Outer"));
+            });
+            assert_eq!(active_scopes().len(), 1);
+        });
+        assert!(active_scopes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_async_scopes_are_tracked_while_polled() {
+        install();
+
+        let scopes = Outer
+            .use_with_async(|_outer| async {
+                tokio::task::yield_now().await;
+                active_scopes()
+            })
+            .await;
+
+        assert_eq!(scopes.len(), 1);
+        assert!(scopes[0].resource_type().ends_with("Outer"));
+        assert!(active_scopes().is_empty());
+    }
+
+    #[test]
+    fn test_scopes_are_visible_to_panic_hooks() {
+        install();
+
+        let seen = std::panic::catch_unwind(|| {
+            Outer.use_with(|_outer| {
+                // Emulate what a panic hook observes at the time of the panic.
+                let scopes = active_scopes();
+                assert_eq!(scopes.len(), 1);
+                panic!("observed {} scope", scopes.len());
+            })
+        });
+
+        assert!(seen.is_err());
+        assert!(active_scopes().is_empty());
+    }
+}
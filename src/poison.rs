@@ -0,0 +1,172 @@
+//! Poisoning of resources whose use scope panicked.
+
+use crate::UseScope;
+use std::fmt;
+
+/// A resource that is poisoned when a use scope panics while holding it.
+///
+/// A panic in the middle of a use scope can leave the resource in an inconsistent state, such as
+/// a half-written frame of a wire protocol. Mirroring [`std::sync::Mutex`], `Poisonable` remembers
+/// such panics and refuses further use of the resource until the poison is cleared explicitly
+/// with [`clear_poison`](Poisonable::clear_poison).
+///
+/// The use method is called [`use_mut`](Poisonable::use_mut) rather than `use_with`, since
+/// [`Use::use_with`](crate::Use::use_with) consumes the wrapper itself.
+///
+/// # Examples
+/// ```rust
+/// use use_with::Poisonable;
+///
+/// let mut buffer = Poisonable::new(Vec::new());
+///
+/// let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+///     buffer.use_mut(|buffer| {
+///         buffer.push(1);
+///         panic!("interrupted");
+///     })
+/// }));
+/// assert!(result.is_err());
+///
+/// assert!(buffer.is_poisoned());
+/// assert!(buffer.use_mut(|buffer| buffer.len()).is_err());
+///
+/// buffer.clear_poison();
+/// assert_eq!(buffer.use_mut(|buffer| buffer.len()), Ok(1));
+/// ```
+#[derive(Debug, Default)]
+pub struct Poisonable<T> {
+    resource: T,
+    poisoned: bool,
+}
+
+impl<T> Poisonable<T> {
+    /// Wraps a resource that is not poisoned.
+    pub fn new(resource: T) -> Self {
+        Self {
+            resource,
+            poisoned: false,
+        }
+    }
+
+    /// Executes a closure on the resource, unless it is poisoned.
+    ///
+    /// If the closure panics, the resource is poisoned and the panic continues to unwind.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(Poisoned)` without running the closure if the resource is poisoned.
+    #[track_caller]
+    pub fn use_mut<U, F: FnOnce(&mut T) -> U>(&mut self, f: F) -> Result<U, Poisoned> {
+        if self.poisoned {
+            return Err(Poisoned::of::<T>());
+        }
+
+        let _guard = PoisonOnUnwind(&mut self.poisoned);
+        Ok(UseScope::new(&mut self.resource).use_with(f))
+    }
+
+    /// Returns whether a use scope panicked while holding the resource.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Clears the poison, allowing the resource to be used again.
+    ///
+    /// The caller is responsible for restoring the resource to a consistent state beforehand,
+    /// e.g. through [`get_mut`](Poisonable::get_mut).
+    pub fn clear_poison(&mut self) {
+        self.poisoned = false;
+    }
+
+    /// Returns a mutable reference to the resource, regardless of poisoning.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.resource
+    }
+
+    /// Returns the resource, regardless of poisoning.
+    pub fn into_inner(self) -> T {
+        self.resource
+    }
+}
+
+/// Poisons the resource if dropped during unwinding.
+struct PoisonOnUnwind<'a>(&'a mut bool);
+
+impl Drop for PoisonOnUnwind<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            *self.0 = true;
+        }
+    }
+}
+
+/// The error returned when using a resource that was poisoned by a panic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poisoned {
+    resource_type: &'static str,
+}
+
+impl Poisoned {
+    pub(crate) fn of<T: ?Sized>() -> Self {
+        Self {
+            resource_type: std::any::type_name::<T>(),
+        }
+    }
+
+    /// Returns the type name of the poisoned resource.
+    pub fn resource_type(&self) -> &'static str {
+        self.resource_type
+    }
+}
+
+impl fmt::Display for Poisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` was poisoned by a panic in an earlier use scope",
+            self.resource_type
+        )
+    }
+}
+
+impl std::error::Error for Poisoned {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    fn poison(resource: &mut Poisonable<Vec<u8>>) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            resource.use_mut(|buffer| {
+                buffer.push(1);
+                panic!("interrupted");
+            })
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_panic_poisons_resource() {
+        let mut resource = Poisonable::new(Vec::new());
+        assert_eq!(resource.use_mut(|buffer| buffer.push(0)), Ok(()));
+        assert!(!resource.is_poisoned());
+
+        poison(&mut resource);
+        assert!(resource.is_poisoned());
+
+        let error = resource.use_mut(|_buffer| unreachable!()).unwrap_err();
+        assert!(error.resource_type().contains("Vec<u8>"));
+        assert_eq!(resource.into_inner(), [0, 1]);
+    }
+
+    #[test]
+    fn test_clear_poison() {
+        let mut resource = Poisonable::new(Vec::new());
+        poison(&mut resource);
+
+        resource.get_mut().clear();
+        resource.clear_poison();
+        assert_eq!(resource.use_mut(|buffer| buffer.len()), Ok(0));
+    }
+}
@@ -0,0 +1,171 @@
+//! Child processes that are reaped when their use scope ends.
+
+use crate::{Close, Use};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The interval at which the exit of a child is polled during its grace period.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A child process that is killed and waited for when it is closed or dropped.
+///
+/// Dropping a [`Child`] neither terminates the process nor reaps it, which leaves stray processes
+/// behind when a scope returns early or panics, and zombies once they exit. Closing a
+/// `ScopedChild` kills the process and waits for it to exit, reporting any error. With
+/// [`wait_then_kill`](ScopedChild::wait_then_kill), the process is first given a grace period to
+/// exit on its own. Dropping a `ScopedChild` without closing it kills and reaps the process as
+/// well, ignoring errors.
+///
+/// See [`use_child`] for running a closure on a freshly spawned child process.
+#[derive(Debug)]
+pub struct ScopedChild {
+    child: Option<Child>,
+    grace: Option<Duration>,
+}
+
+impl ScopedChild {
+    /// Spawns `command` as a scoped child process.
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        command.spawn().map(Self::new)
+    }
+
+    /// Wraps a running child process.
+    pub fn new(child: Child) -> Self {
+        Self {
+            child: Some(child),
+            grace: None,
+        }
+    }
+
+    /// Waits up to `timeout` for the process to exit on its own before it is killed.
+    pub fn wait_then_kill(mut self, timeout: Duration) -> Self {
+        self.grace = Some(timeout);
+        self
+    }
+
+    /// Returns the wrapped child process without killing or waiting for it.
+    pub fn into_inner(mut self) -> Child {
+        self.child.take().expect("child is present until closed")
+    }
+
+    /// Waits for the grace period, then kills the process and waits for it to exit.
+    fn reap(child: &mut Child, grace: Option<Duration>) -> io::Result<()> {
+        if let Some(grace) = grace {
+            let deadline = Instant::now() + grace;
+            while Instant::now() < deadline {
+                if child.try_wait()?.is_some() {
+                    return Ok(());
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+
+        if child.try_wait()?.is_none() {
+            child.kill()?;
+        }
+        child.wait().map(drop)
+    }
+}
+
+impl Deref for ScopedChild {
+    type Target = Child;
+
+    fn deref(&self) -> &Self::Target {
+        self.child.as_ref().expect("child is present until closed")
+    }
+}
+
+impl DerefMut for ScopedChild {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.child.as_mut().expect("child is present until closed")
+    }
+}
+
+impl Close for ScopedChild {
+    type Error = io::Error;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        let mut child = self.child.take().expect("child is present until closed");
+        Self::reap(&mut child, self.grace)
+    }
+}
+
+impl Drop for ScopedChild {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = Self::reap(&mut child, None);
+        }
+    }
+}
+
+/// Spawns `command`, executes a closure on the child process and reaps it afterwards.
+///
+/// The process is killed and waited for once the closure returns or panics, so that it cannot
+/// outlive the scope. See [`ScopedChild`] for giving it a grace period to exit on its own instead.
+///
+/// # Returns
+/// - `Ok(U)` with the result of the closure `f`.
+/// - `Err(io::Error)` if spawning, killing or waiting for the process failed.
+///
+/// # Examples
+/// ```rust,no_run
+/// use std::io::Read;
+/// use std::process::{Command, Stdio};
+/// use use_with::process::use_child;
+///
+/// let output = use_child(Command::new("yes").stdout(Stdio::piped()), |child| {
+///     let mut line = [0; 2];
+///     child.stdout.as_mut().unwrap().read_exact(&mut line)?;
+///     Ok::<_, std::io::Error>(line)
+/// })??;
+///
+/// assert_eq!(&output, b"y\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[track_caller]
+pub fn use_child<U, F>(command: &mut Command, f: F) -> io::Result<U>
+where
+    F: FnOnce(&mut ScopedChild) -> U,
+{
+    ScopedChild::spawn(command)?.use_close(f)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_kills_child() {
+        let mut child = None;
+        use_child(Command::new("sleep").arg("60"), |scoped| {
+            child = Some(scoped.id());
+        })
+        .unwrap();
+
+        let pid = child.unwrap().to_string();
+        let status = Command::new("kill").args(["-0", &pid]).status().unwrap();
+        assert!(!status.success(), "process {pid} is still running");
+    }
+
+    #[test]
+    fn test_wait_then_kill_lets_child_exit() {
+        let started = Instant::now();
+        ScopedChild::spawn(Command::new("sleep").arg("0.05"))
+            .unwrap()
+            .wait_then_kill(Duration::from_secs(30))
+            .close()
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(30));
+
+        let started = Instant::now();
+        ScopedChild::spawn(Command::new("sleep").arg("60"))
+            .unwrap()
+            .wait_then_kill(Duration::from_millis(50))
+            .close()
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(60));
+    }
+}
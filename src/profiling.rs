@@ -0,0 +1,201 @@
+//! Lightweight profiling of use scopes.
+//!
+//! When a profiler is installed via [`set_profiler`], every use scope measures the runtime of
+//! its body and of the teardown of its resource separately and reports both as a [`Profile`].
+//! This helps identifying resources whose teardown dominates latency, such as files that
+//! `fsync` when being closed. While no profiler is installed, scopes do not read the clock.
+//!
+//! The teardown is measured for scopes that tear down the resource themselves after the body
+//! returned: [`use_close`](crate::Use::use_close), [`use_close_async`](crate::Use::use_close_async)
+//! and [`use_context`](crate::Use::use_context), which close or exit it, as well as
+//! [`use_split`](crate::Use::use_split), [`use_disjoint`](crate::Use::use_disjoint),
+//! [`try_use_with_snapshot`](crate::Use::try_use_with_snapshot),
+//! [`use_critical`](crate::Use::use_critical), [`use_sealed`](crate::Use::use_sealed),
+//! [`use_with_pinned`](crate::Use::use_with_pinned),
+//! [`use_with_pinned_async`](crate::Use::use_with_pinned_async) and
+//! [`use_with_yielding`](crate::Use::use_with_yielding), which drop it, and the scopes of
+//! [`SharedUse`](crate::SharedUse), which release their access. Combinators that hand ownership
+//! of the resource to the body, such as [`use_with`](crate::Use::use_with),
+//! [`use_with_flow`](crate::Use::use_with_flow), [`use_into`](crate::Use::use_into) or
+//! [`use_with_async`](crate::Use::use_with_async), leave its teardown to the body and report
+//! none; neither does [`use_and_return`](crate::Use::use_and_return), which hands it back.
+//!
+//! # Examples
+//! ```rust
+//! use use_with::{profiling, Close, Use};
+//!
+//! struct File;
+//!
+//! impl Close for File {
+//!     type Error = std::io::Error;
+//!
+//!     fn close(self) -> Result<(), Self::Error> {
+//!         std::thread::sleep(std::time::Duration::from_millis(5));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! profiling::set_profiler(|profile| {
+//!     println!(
+//!         "{}: body took {:?}, teardown took {:?}",
+//!         profile.resource_type(),
+//!         profile.body(),
+//!         profile.teardown()
+//!     );
+//! });
+//!
+//! File.use_close(|_file| ()).unwrap();
+//! profiling::clear_profiler();
+//! ```
+
+use crate::instrument::{self, Consumer};
+use crate::ScopeId;
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// The measured runtimes of a single use scope.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub(crate) id: ScopeId,
+    pub(crate) resource_type: &'static str,
+    pub(crate) location: &'static Location<'static>,
+    pub(crate) body: Duration,
+    pub(crate) teardown: Option<Duration>,
+}
+
+impl Profile {
+    /// Returns the unique identifier of the use scope.
+    pub fn id(&self) -> ScopeId {
+        self.id
+    }
+
+    /// Returns the type name of the resource used in the scope.
+    pub fn resource_type(&self) -> &'static str {
+        self.resource_type
+    }
+
+    /// Returns the source location at which the use scope was entered.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns the runtime of the body of the scope.
+    pub fn body(&self) -> Duration {
+        self.body
+    }
+
+    /// Returns the runtime of the teardown of the resource, from the end of the body until the
+    /// resource was closed, exited, dropped or released by the scope.
+    ///
+    /// This is `None` for combinators such as [`Use::use_with`](crate::Use::use_with) that hand
+    /// ownership of the resource to the body, since the resource is dropped as part of the body.
+    /// The [module documentation](self) lists the combinators that measure the teardown.
+    pub fn teardown(&self) -> Option<Duration> {
+        self.teardown
+    }
+}
+
+pub(crate) type Profiler = Arc<dyn Fn(&Profile) + Send + Sync>;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static PROFILER: RwLock<Option<Profiler>> = RwLock::new(None);
+
+/// Installs a callback that receives the [`Profile`] of every completed use scope in the process.
+///
+/// Replaces any previously installed profiler.
+pub fn set_profiler(profiler: impl Fn(&Profile) + Send + Sync + 'static) {
+    let mut current = PROFILER.write().unwrap_or_else(|e| e.into_inner());
+    *current = Some(Arc::new(profiler));
+    ACTIVE.store(true, Ordering::Release);
+    instrument::set_consumer(Consumer::Profiler, true);
+}
+
+/// Removes the installed profiler, if any.
+pub fn clear_profiler() {
+    let mut current = PROFILER.write().unwrap_or_else(|e| e.into_inner());
+    ACTIVE.store(false, Ordering::Release);
+    instrument::set_consumer(Consumer::Profiler, false);
+    *current = None;
+}
+
+/// Returns the currently installed profiler.
+#[inline]
+pub(crate) fn profiler() -> Option<Profiler> {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return None;
+    }
+    PROFILER.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Close, Use};
+    use std::sync::Mutex;
+
+    struct ProfiledResource;
+
+    impl Close for ProfiledResource {
+        type Error = ();
+
+        fn close(self) -> Result<(), Self::Error> {
+            std::thread::sleep(Duration::from_millis(20));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_body_and_teardown_are_measured_separately() {
+        let profiles = Arc::new(Mutex::new(Vec::new()));
+        let sink = profiles.clone();
+        set_profiler(move |profile| {
+            if profile.resource_type().ends_with("ProfiledResource") {
+                sink.lock().unwrap().push(profile.clone());
+            }
+        });
+
+        ProfiledResource
+            .use_close(|_res| std::thread::sleep(Duration::from_millis(10)))
+            .unwrap();
+        ProfiledResource.use_with(|_res| ());
+        clear_profiler();
+        ProfiledResource.use_with(|_res| ());
+
+        let profiles = profiles.lock().unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert!(profiles[0].body() >= Duration::from_millis(10));
+        assert!(profiles[0].teardown().unwrap() >= Duration::from_millis(20));
+        assert_eq!(profiles[1].teardown(), None);
+    }
+
+    #[test]
+    fn test_dropping_the_resource_is_measured_as_teardown() {
+        struct SlowDrop;
+
+        impl Drop for SlowDrop {
+            fn drop(&mut self) {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+
+        let profiles = Arc::new(Mutex::new(Vec::new()));
+        let sink = profiles.clone();
+        set_profiler(move |profile| {
+            if profile.resource_type().ends_with("SlowDrop") {
+                sink.lock().unwrap().push(profile.clone());
+            }
+        });
+
+        SlowDrop.use_sealed(|_res| ());
+        SlowDrop.use_with(|_res| ());
+        clear_profiler();
+
+        let profiles = profiles.lock().unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert!(profiles[0].teardown().unwrap() >= Duration::from_millis(20));
+        assert!(profiles[1].body() >= Duration::from_millis(20));
+        assert_eq!(profiles[1].teardown(), None);
+    }
+}
@@ -0,0 +1,249 @@
+//! Per-case teardown of resources generated by [`proptest`](https://docs.rs/proptest) strategies.
+//!
+//! Property tests run the same test body many times, and failing cases are re-run over and over
+//! while shrinking. When a case fails, whether by panicking or by returning an error, the explicit
+//! teardown of its resources is skipped, which leaks temporary files, connections or database
+//! rows into later cases. Wrapping a strategy with [`closing`] makes every generated resource
+//! close itself when its case ends, no matter how the case ends, and fails the case if closing
+//! fails.
+//!
+//! # Examples
+//! ```rust
+//! use proptest::prelude::*;
+//! use use_with::proptest::{closing, Closing};
+//! use use_with::Close;
+//!
+//! #[derive(Debug)]
+//! struct Scratch(Vec<u8>);
+//!
+//! impl Close for Scratch {
+//!     type Error = std::io::Error;
+//!
+//!     fn close(self) -> Result<(), Self::Error> {
+//!         // Remove the scratch area, ...
+//!         Ok(())
+//!     }
+//! }
+//!
+//! proptest!(|(mut scratch in closing(any::<Vec<u8>>().prop_map(Scratch)))| {
+//!     scratch.0.push(42);
+//!     prop_assert!(!scratch.0.is_empty());
+//! });
+//! ```
+
+use crate::Close;
+use ::proptest::strategy::{NewTree, Strategy, ValueTree};
+use ::proptest::test_runner::TestRunner;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// Wraps `strategy` so that every generated resource is closed when its test case ends.
+pub fn closing<S>(strategy: S) -> ClosingStrategy<S>
+where
+    S: Strategy,
+    S::Value: Close,
+    <S::Value as Close>::Error: fmt::Debug,
+{
+    ClosingStrategy(strategy)
+}
+
+/// A strategy generating [`Closing`] resources. Created by [`closing`].
+#[derive(Debug, Clone)]
+#[must_use = "strategies do nothing unless used"]
+pub struct ClosingStrategy<S>(S);
+
+impl<S> Strategy for ClosingStrategy<S>
+where
+    S: Strategy,
+    S::Value: Close,
+    <S::Value as Close>::Error: fmt::Debug,
+{
+    type Tree = ClosingValueTree<S::Tree>;
+    type Value = Closing<S::Value>;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        self.0.new_tree(runner).map(ClosingValueTree)
+    }
+}
+
+/// The value tree of a [`ClosingStrategy`], shrinking like the wrapped tree.
+#[derive(Debug, Clone)]
+pub struct ClosingValueTree<T>(T);
+
+impl<T> ValueTree for ClosingValueTree<T>
+where
+    T: ValueTree,
+    T::Value: Close,
+    <T::Value as Close>::Error: fmt::Debug,
+{
+    type Value = Closing<T::Value>;
+
+    fn current(&self) -> Self::Value {
+        Closing {
+            resource: Some(self.0.current()),
+        }
+    }
+
+    fn simplify(&mut self) -> bool {
+        self.0.simplify()
+    }
+
+    fn complicate(&mut self) -> bool {
+        self.0.complicate()
+    }
+}
+
+/// A generated resource that is closed when it goes out of scope.
+///
+/// If closing fails, dropping the resource panics and thereby fails the test case, unless the
+/// case is already failing with a panic. Use [`close`](Closing::close) to handle the failure
+/// explicitly instead.
+pub struct Closing<T: Close>
+where
+    T::Error: fmt::Debug,
+{
+    resource: Option<T>,
+}
+
+impl<T: Close> Closing<T>
+where
+    T::Error: fmt::Debug,
+{
+    /// Closes the resource, returning the outcome instead of panicking.
+    pub fn close(mut self) -> Result<(), T::Error> {
+        self.resource
+            .take()
+            .expect("resource already closed")
+            .close()
+    }
+}
+
+impl<T: Close> Deref for Closing<T>
+where
+    T::Error: fmt::Debug,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.resource.as_ref().expect("resource already closed")
+    }
+}
+
+impl<T: Close> DerefMut for Closing<T>
+where
+    T::Error: fmt::Debug,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.resource.as_mut().expect("resource already closed")
+    }
+}
+
+impl<T: Close + fmt::Debug> fmt::Debug for Closing<T>
+where
+    T::Error: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.resource {
+            Some(resource) => resource.fmt(f),
+            None => f.write_str("<closed>"),
+        }
+    }
+}
+
+impl<T: Close> Drop for Closing<T>
+where
+    T::Error: fmt::Debug,
+{
+    fn drop(&mut self) {
+        let Some(resource) = self.resource.take() else {
+            return;
+        };
+        if let Err(error) = resource.close() {
+            if !std::thread::panicking() {
+                panic!(
+                    "closing generated `{}` failed: {error:?}",
+                    std::any::type_name::<T>()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::proptest::prelude::*;
+    use ::proptest::test_runner::{Config, TestError};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug)]
+    struct Resource {
+        value: u8,
+        open: &'static AtomicUsize,
+    }
+
+    impl Resource {
+        fn new(value: u8, open: &'static AtomicUsize) -> Self {
+            open.fetch_add(1, Ordering::SeqCst);
+            Self { value, open }
+        }
+    }
+
+    impl Close for Resource {
+        type Error = u8;
+
+        fn close(self) -> Result<(), Self::Error> {
+            self.open.fetch_sub(1, Ordering::SeqCst);
+            if self.value == u8::MAX {
+                Err(self.value)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn runner(cases: u32) -> TestRunner {
+        TestRunner::new(Config {
+            cases,
+            failure_persistence: None,
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn test_failing_and_shrinking_cases_are_closed() {
+        static OPEN: AtomicUsize = AtomicUsize::new(0);
+
+        let strategy = closing((0u8..100).prop_map(|value| Resource::new(value, &OPEN)));
+        let result = runner(256).run(&strategy, |res| {
+            // Every case sees only its own resource.
+            prop_assert_eq!(OPEN.load(Ordering::SeqCst), 1);
+            prop_assert!(res.value < 50);
+            Ok(())
+        });
+
+        // The runner reports the minimal failing value, which is closed when the error is dropped.
+        let Err(TestError::Fail(_, minimal)) = result else {
+            panic!("expected the property to fail");
+        };
+        assert_eq!(minimal.value, 50);
+        assert_eq!(OPEN.load(Ordering::SeqCst), 1);
+        drop(minimal);
+        assert_eq!(OPEN.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_close_failure_fails_case() {
+        static OPEN: AtomicUsize = AtomicUsize::new(0);
+
+        let strategy = closing(Just(u8::MAX).prop_map(|value| Resource::new(value, &OPEN)));
+        let result = runner(1).run(&strategy, |_res| Ok(()));
+
+        let Err(TestError::Fail(reason, minimal)) = result else {
+            panic!("expected the close failure to fail the case");
+        };
+        assert!(reason.message().contains("closing generated"));
+        assert_eq!(minimal.close(), Err(u8::MAX));
+        assert_eq!(OPEN.load(Ordering::SeqCst), 0);
+    }
+}
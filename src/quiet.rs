@@ -0,0 +1,135 @@
+//! Suppression of panics raised while dropping resources.
+
+use crate::observer::{self, Failure, UseEvent};
+use crate::ScopeId;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe, Location};
+
+/// A resource whose destructor is prevented from unwinding.
+///
+/// A destructor that panics while the thread is already unwinding from another panic aborts
+/// the process. `QuietDrop` runs the destructor of the wrapped value under
+/// [`std::panic::catch_unwind`] instead, and reports a caught panic to the global
+/// [`UseObserver`](crate::observer::UseObserver) as a [`Failure::DropPanic`] and, with the
+/// `log` feature, as a warning. The event carries the location at which the `QuietDrop`
+/// was created.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{QuietDrop, Use};
+///
+/// struct Flaky;
+///
+/// impl Drop for Flaky {
+///     fn drop(&mut self) {
+///         panic!("failed to flush");
+///     }
+/// }
+///
+/// // Does not panic.
+/// QuietDrop::new(Flaky).use_with(|_flaky| ());
+/// ```
+pub struct QuietDrop<T> {
+    resource: Option<T>,
+    location: &'static Location<'static>,
+}
+
+impl<T> QuietDrop<T> {
+    /// Wraps a resource, recording the caller's location for reports.
+    #[track_caller]
+    pub fn new(resource: T) -> Self {
+        Self {
+            resource: Some(resource),
+            location: Location::caller(),
+        }
+    }
+
+    /// Returns the resource, whose destructor is no longer guarded.
+    pub fn into_inner(mut self) -> T {
+        self.resource.take().expect("resource already dropped")
+    }
+}
+
+impl<T> Deref for QuietDrop<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.resource.as_ref().expect("resource already dropped")
+    }
+}
+
+impl<T> DerefMut for QuietDrop<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.resource.as_mut().expect("resource already dropped")
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for QuietDrop<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("QuietDrop").field(&self.resource).finish()
+    }
+}
+
+impl<T> Drop for QuietDrop<T> {
+    fn drop(&mut self) {
+        let Some(resource) = self.resource.take() else {
+            return;
+        };
+        if let Err(_payload) = panic::catch_unwind(AssertUnwindSafe(move || drop(resource))) {
+            let event = UseEvent::new(ScopeId::next(), std::any::type_name::<T>(), self.location);
+
+            #[cfg(feature = "log")]
+            log::warn!(
+                "suppressed panic while dropping `{}` created at {}: {}",
+                event.resource_type(),
+                event.location(),
+                _payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| _payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("<non-string payload>")
+            );
+
+            if let Some(observer) = observer::global_observer() {
+                observer.on_error(&event, Failure::DropPanic);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+
+    struct Flaky;
+
+    impl Drop for Flaky {
+        fn drop(&mut self) {
+            panic!("failed to flush");
+        }
+    }
+
+    #[test]
+    fn test_drop_panic_is_suppressed() {
+        QuietDrop::new(Flaky).use_with(|_flaky| ());
+    }
+
+    #[test]
+    fn test_drop_panic_during_unwinding_does_not_abort() {
+        let result = panic::catch_unwind(|| {
+            let _flaky = QuietDrop::new(Flaky);
+            panic!("body failed");
+        });
+
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"body failed"));
+    }
+
+    #[test]
+    fn test_into_inner_disarms() {
+        let value = QuietDrop::new(vec![1, 2]).into_inner();
+        assert_eq!(value, [1, 2]);
+    }
+}
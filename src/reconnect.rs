@@ -0,0 +1,405 @@
+//! Resources that are rebuilt transparently after losing their connection.
+
+#[cfg(feature = "tokio")]
+use crate::instrument::ScopeOptions;
+use crate::{ResourceFactory, UseScope};
+use std::fmt;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Blocks the thread for the given duration.
+type Sleep = Arc<dyn Fn(Duration) + Send + Sync>;
+
+/// Sleeps asynchronously for the given duration.
+#[cfg(feature = "tokio")]
+type AsyncSleep = Arc<dyn Fn(Duration) -> crate::BoxFuture<'static, ()> + Send + Sync>;
+
+/// Classifies errors that indicate a lost connection, after which a resource must be rebuilt.
+///
+/// Implemented for [`io::Error`], where broken pipes, reset, aborted or missing connections and
+/// unexpected ends of file count as lost connections. Implement it for the error types of your
+/// own clients to use them with [`Reconnecting`].
+pub trait ConnectionLost {
+    /// Returns whether the error indicates that the connection of the resource was lost.
+    fn is_connection_lost(&self) -> bool;
+}
+
+impl ConnectionLost for io::Error {
+    fn is_connection_lost(&self) -> bool {
+        matches!(
+            self.kind(),
+            io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::UnexpectedEof
+        )
+    }
+}
+
+/// The error returned by the use scopes of [`Reconnecting`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectError<E, C> {
+    /// The body failed, either with an error that does not indicate a lost connection, or after
+    /// the rebuild budget was exhausted.
+    Use(E),
+    /// Creating the resource failed.
+    Create(C),
+}
+
+impl<E: fmt::Display, C: fmt::Display> fmt::Display for ReconnectError<E, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReconnectError::Use(error) => error.fmt(f),
+            ReconnectError::Create(error) => write!(f, "failed to create the resource: {error}"),
+        }
+    }
+}
+
+impl<E, C> std::error::Error for ReconnectError<E, C>
+where
+    E: std::error::Error + 'static,
+    C: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReconnectError::Use(error) => Some(error),
+            ReconnectError::Create(error) => Some(error),
+        }
+    }
+}
+
+/// A resource that is rebuilt from its factory whenever its connection is lost.
+///
+/// The resource is created lazily on first use and kept between use scopes. If a body fails with
+/// an error classified as [`ConnectionLost`], the resource is dropped, a fresh one is created after
+/// a backoff delay, and the body is retried. Each retry doubles the delay, and at most
+/// [`max_rebuilds`](Self::max_rebuilds) rebuilds are attempted per use scope before the error is
+/// returned. Since bodies may run more than once, they must be safe to retry.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use std::io::{self, ErrorKind};
+/// use std::time::Duration;
+/// use use_with::Reconnecting;
+///
+/// struct Client {
+///     alive: bool,
+/// }
+///
+/// let connects = Cell::new(0);
+/// let mut client = Reconnecting::new(|| {
+///     connects.set(connects.get() + 1);
+///     Ok::<_, io::Error>(Client { alive: connects.get() > 1 })
+/// })
+/// .max_rebuilds(3)
+/// .backoff(Duration::from_millis(1));
+///
+/// let response = client.use_connected(|client| {
+///     if client.alive {
+///         Ok("pong")
+///     } else {
+///         Err(io::Error::from(ErrorKind::ConnectionReset))
+///     }
+/// });
+///
+/// assert_eq!(response.ok(), Some("pong"));
+/// ```
+pub struct Reconnecting<T, C> {
+    factory: C,
+    resource: Option<T>,
+    max_rebuilds: usize,
+    backoff: Duration,
+    sleep: Sleep,
+    #[cfg(feature = "tokio")]
+    sleep_async: AsyncSleep,
+}
+
+impl<T, C> Reconnecting<T, C> {
+    /// Wraps a factory, rebuilding its resource up to three times per use scope, with an initial
+    /// backoff delay of 100 milliseconds.
+    pub fn new(factory: C) -> Self {
+        Self {
+            factory,
+            resource: None,
+            max_rebuilds: 3,
+            backoff: Duration::from_millis(100),
+            sleep: Arc::new(thread::sleep),
+            #[cfg(feature = "tokio")]
+            sleep_async: Arc::new(|delay| Box::pin(::tokio::time::sleep(delay))),
+        }
+    }
+
+    /// Sets the maximum number of rebuilds per use scope.
+    pub fn max_rebuilds(mut self, max_rebuilds: usize) -> Self {
+        self.max_rebuilds = max_rebuilds;
+        self
+    }
+
+    /// Sets the delay before the first rebuild of a use scope, which doubles with each rebuild.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets the function used by [`use_connected`](Self::use_connected) to wait out the backoff
+    /// delay, which defaults to [`thread::sleep`].
+    ///
+    /// Passing a function that records the delays instead keeps tests of the backoff fast.
+    pub fn sleep_with<S>(mut self, sleep: S) -> Self
+    where
+        S: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.sleep = Arc::new(sleep);
+        self
+    }
+
+    /// Sets the function used by [`use_connected_async`](Self::use_connected_async) to wait out
+    /// the backoff delay, which defaults to `tokio::time::sleep`.
+    ///
+    /// Only available with the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub fn sleep_async_with<S, F>(mut self, sleep: S) -> Self
+    where
+        S: Fn(Duration) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.sleep_async = Arc::new(move |delay| Box::pin(sleep(delay)));
+        self
+    }
+
+    /// Returns whether a resource is currently held.
+    pub fn is_connected(&self) -> bool {
+        self.resource.is_some()
+    }
+
+    /// Drops the current resource, if any, so that the next use scope creates a fresh one.
+    pub fn invalidate(&mut self) {
+        self.resource = None;
+    }
+}
+
+impl<T, C: ResourceFactory<T>> Reconnecting<T, C> {
+    /// Executes a closure on the resource, rebuilding it and retrying the closure whenever it
+    /// fails with a lost connection.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - [`ReconnectError::Use`] if the closure failed with an error that does not indicate a
+    ///   lost connection, or once the rebuild budget is exhausted.
+    /// - [`ReconnectError::Create`] if creating the resource failed.
+    #[track_caller]
+    pub fn use_connected<U, E, F>(&mut self, mut f: F) -> Result<U, ReconnectError<E, C::Error>>
+    where
+        E: ConnectionLost,
+        F: FnMut(&mut T) -> Result<U, E>,
+    {
+        let mut rebuilds = 0;
+        let mut delay = self.backoff;
+        loop {
+            let resource = match self.resource.take() {
+                Some(resource) => resource,
+                None => self.factory.create().map_err(ReconnectError::Create)?,
+            };
+            let (result, resource) = UseScope::new(resource).use_and_return(&mut f);
+            match result {
+                Err(error) if error.is_connection_lost() => {
+                    drop(resource);
+                    if rebuilds == self.max_rebuilds {
+                        return Err(ReconnectError::Use(error));
+                    }
+                    rebuilds += 1;
+                    (self.sleep)(delay);
+                    delay = delay.saturating_mul(2);
+                }
+                result => {
+                    self.resource = Some(resource);
+                    return result.map_err(ReconnectError::Use);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T, C: crate::AsyncResourceFactory<T>> Reconnecting<T, C> {
+    /// Executes an asynchronous closure on the resource, rebuilding it and retrying the closure
+    /// whenever it fails with a lost connection.
+    ///
+    /// This is the asynchronous counterpart of [`use_connected`](Self::use_connected), which waits
+    /// for the backoff delay using the Tokio timer, or the function passed to
+    /// [`sleep_async_with`](Self::sleep_async_with).
+    ///
+    /// Only available with the `tokio` feature.
+    #[track_caller]
+    pub fn use_connected_async<'s, U, E, F>(
+        &'s mut self,
+        mut f: F,
+    ) -> impl Future<Output = Result<U, ReconnectError<E, C::Error>>> + 's
+    where
+        E: ConnectionLost + 's,
+        F: for<'a> FnMut(&'a mut T) -> crate::BoxFuture<'a, Result<U, E>> + 's,
+        U: 's,
+    {
+        let options = ScopeOptions::new();
+        async move {
+            let mut rebuilds = 0;
+            let mut delay = self.backoff;
+            loop {
+                let mut resource = match self.resource.take() {
+                    Some(resource) => resource,
+                    None => self
+                        .factory
+                        .create_async()
+                        .await
+                        .map_err(ReconnectError::Create)?,
+                };
+                let result = UseScope::with_options(&mut resource, options)
+                    .use_with_async(&mut f)
+                    .await;
+                match result {
+                    Err(error) if error.is_connection_lost() => {
+                        drop(resource);
+                        if rebuilds == self.max_rebuilds {
+                            return Err(ReconnectError::Use(error));
+                        }
+                        rebuilds += 1;
+                        (self.sleep_async)(delay).await;
+                        delay = delay.saturating_mul(2);
+                    }
+                    result => {
+                        self.resource = Some(resource);
+                        return result.map_err(ReconnectError::Use);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug, C> fmt::Debug for Reconnecting<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reconnecting")
+            .field("resource", &self.resource)
+            .field("max_rebuilds", &self.max_rebuilds)
+            .field("backoff", &self.backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropCounter;
+    use std::cell::Cell;
+
+    fn lost() -> io::Error {
+        io::Error::from(io::ErrorKind::BrokenPipe)
+    }
+
+    #[test]
+    fn test_rebuild_budget_is_enforced() {
+        let counter = DropCounter::new();
+        let created = Cell::new(0);
+        let mut client = Reconnecting::new(|| {
+            created.set(created.get() + 1);
+            Ok::<_, io::Error>(counter.probe())
+        })
+        .max_rebuilds(2)
+        .backoff(Duration::ZERO);
+
+        let result = client.use_connected(|_probe| Err::<(), _>(lost()));
+        assert!(matches!(result, Err(ReconnectError::Use(e)) if e.is_connection_lost()));
+        assert_eq!(created.get(), 3);
+        assert_eq!(counter.count(), 3);
+        assert!(!client.is_connected());
+    }
+
+    #[test]
+    fn test_backoff_doubles_with_each_rebuild() {
+        let delays = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut client = Reconnecting::new(|| Ok::<_, io::Error>(()))
+            .max_rebuilds(3)
+            .backoff(Duration::from_millis(10))
+            .sleep_with({
+                let delays = Arc::clone(&delays);
+                move |delay| delays.lock().unwrap().push(delay)
+            });
+
+        let result = client.use_connected(|()| Err::<(), _>(lost()));
+        assert!(matches!(result, Err(ReconnectError::Use(_))));
+        assert_eq!(
+            *delays.lock().unwrap(),
+            [10, 20, 40].map(Duration::from_millis)
+        );
+    }
+
+    #[test]
+    fn test_resource_is_kept_on_other_errors() {
+        let created = Cell::new(0);
+        let mut client = Reconnecting::new(|| {
+            created.set(created.get() + 1);
+            Ok::<_, io::Error>(())
+        });
+
+        let result = client.use_connected(|()| Err::<(), _>(io::Error::other("bad request")));
+        assert!(matches!(result, Err(ReconnectError::Use(e)) if !e.is_connection_lost()));
+        assert_eq!(
+            client.use_connected(|()| Ok::<_, io::Error>(1)).ok(),
+            Some(1)
+        );
+        assert_eq!(created.get(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[::tokio::test(start_paused = true)]
+    async fn test_async_rebuilds_after_backoff() {
+        let mut attempts = 0;
+        let mut client = Reconnecting::new(|| async { Ok::<_, io::Error>(0) });
+
+        let started = ::tokio::time::Instant::now();
+        let result = client
+            .use_connected_async(|failures: &mut i32| {
+                attempts += 1;
+                Box::pin(async move {
+                    *failures += 1;
+                    if attempts < 3 {
+                        Err(lost())
+                    } else {
+                        Ok(*failures)
+                    }
+                })
+            })
+            .await;
+
+        assert_eq!(result.ok(), Some(1));
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[::tokio::test]
+    async fn test_async_backoff_is_waited_out_through_the_injected_sleep() {
+        let delays = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut client = Reconnecting::new(|| async { Ok::<_, io::Error>(()) })
+            .max_rebuilds(2)
+            .backoff(Duration::from_secs(60))
+            .sleep_async_with({
+                let delays = Arc::clone(&delays);
+                move |delay| {
+                    delays.lock().unwrap().push(delay);
+                    async {}
+                }
+            });
+
+        let result = client
+            .use_connected_async(|()| Box::pin(async { Err::<(), _>(lost()) }))
+            .await;
+        assert!(matches!(result, Err(ReconnectError::Use(_))));
+        assert_eq!(*delays.lock().unwrap(), [60, 120].map(Duration::from_secs));
+    }
+}
@@ -0,0 +1,721 @@
+//! Recording and replaying the sequence of use scopes.
+//!
+//! A [`Recorder`] is a [`UseObserver`] that captures every lifecycle event of the use scopes it
+//! observes into a [`Trace`]. Traces render to a line-based text format and can be parsed back,
+//! so a trace captured in production can be attached to a bug report and checked into a test.
+//!
+//! A [`Replayer`] re-executes the schedule of a trace against mock resources, reproducing the
+//! exact interleaving of acquisitions, body completions and teardowns of concurrent scopes,
+//! including panics and failed closes. Hooks and observers attached to the replay see the events
+//! in the recorded order, which helps reproducing teardown-ordering bugs deterministically.
+//!
+//! # Examples
+//! ```rust
+//! use use_with::record::{Recorder, Replayer, Trace};
+//! use use_with::Use;
+//!
+//! struct Connection;
+//! struct Transaction;
+//!
+//! let recorder = Recorder::new();
+//! Connection.scoped().observer(&recorder).use_with(|_conn| {
+//!     Transaction.scoped().observer(&recorder).use_with(|_tx| ());
+//! });
+//!
+//! let text = recorder.trace().to_string();
+//! let trace: Trace = text.parse().unwrap();
+//! assert_eq!(trace.events().len(), 6);
+//!
+//! let replayed = Recorder::new();
+//! Replayer::new(&trace).observer(&replayed).run().unwrap();
+//! assert!(replayed.trace().same_schedule(&trace));
+//! ```
+
+use crate::observer::{Failure, UseEvent, UseObserver};
+use crate::{Close, Use};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The kind of a [`TraceEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceEventKind {
+    /// The scope acquired its resource.
+    Acquire,
+    /// The body of the scope returned.
+    BodyEnd,
+    /// The resource was closed or dropped successfully.
+    Close,
+    /// The body of the scope panicked.
+    Panic,
+    /// Closing the resource failed.
+    CloseError,
+    /// Closing the resource panicked after the body had panicked already.
+    ClosePanic,
+    /// Dropping a [`QuietDrop`](crate::QuietDrop) resource panicked.
+    ///
+    /// These events do not belong to a use scope and are not replayed.
+    DropPanic,
+}
+
+impl TraceEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            TraceEventKind::Acquire => "acquire",
+            TraceEventKind::BodyEnd => "body_end",
+            TraceEventKind::Close => "close",
+            TraceEventKind::Panic => "panic",
+            TraceEventKind::CloseError => "close_error",
+            TraceEventKind::ClosePanic => "close_panic",
+            TraceEventKind::DropPanic => "drop_panic",
+        }
+    }
+
+    /// Returns whether the event ends its scope.
+    fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            TraceEventKind::Close
+                | TraceEventKind::Panic
+                | TraceEventKind::CloseError
+                | TraceEventKind::ClosePanic
+        )
+    }
+}
+
+impl fmt::Display for TraceEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TraceEventKind {
+    type Err = ParseTraceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "acquire" => TraceEventKind::Acquire,
+            "body_end" => TraceEventKind::BodyEnd,
+            "close" => TraceEventKind::Close,
+            "panic" => TraceEventKind::Panic,
+            "close_error" => TraceEventKind::CloseError,
+            "close_panic" => TraceEventKind::ClosePanic,
+            "drop_panic" => TraceEventKind::DropPanic,
+            _ => return Err(ParseTraceError::new(format!("unknown event kind `{s}`"))),
+        })
+    }
+}
+
+/// A single lifecycle event of a recorded use scope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    offset: Duration,
+    scope: u64,
+    kind: TraceEventKind,
+    resource_type: String,
+}
+
+impl TraceEvent {
+    /// Returns the time at which the event occurred, relative to the start of the recording.
+    pub fn offset(&self) -> Duration {
+        self.offset
+    }
+
+    /// Returns the identifier of the scope the event belongs to.
+    ///
+    /// Identifiers are only unique within a single trace.
+    pub fn scope(&self) -> u64 {
+        self.scope
+    }
+
+    /// Returns the kind of the event.
+    pub fn kind(&self) -> TraceEventKind {
+        self.kind
+    }
+
+    /// Returns the type name of the resource used in the scope.
+    pub fn resource_type(&self) -> &str {
+        &self.resource_type
+    }
+}
+
+/// A recorded sequence of use scope events, ordered by their occurrence.
+///
+/// Traces render to a machine-readable text format with one event per line, listing the offset
+/// in microseconds, the scope identifier, the event kind and the resource type:
+///
+/// ```text
+/// # use-with trace v1
+/// 0 1 acquire my_app::Connection
+/// 15 1 body_end my_app::Connection
+/// 21 1 close my_app::Connection
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    /// Returns the recorded events.
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Returns whether both traces describe the same schedule.
+    ///
+    /// Two schedules are the same if their events have the same kinds and occur in the same
+    /// order for the same scopes. Offsets, resource types and the concrete scope identifiers are
+    /// not compared.
+    pub fn same_schedule(&self, other: &Trace) -> bool {
+        fn normalize(trace: &Trace) -> Vec<(usize, TraceEventKind)> {
+            let mut scopes = BTreeMap::new();
+            trace
+                .events
+                .iter()
+                .map(|event| {
+                    let next = scopes.len();
+                    (*scopes.entry(event.scope).or_insert(next), event.kind)
+                })
+                .collect()
+        }
+
+        normalize(self) == normalize(other)
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# use-with trace v1")?;
+        for event in &self.events {
+            writeln!(
+                f,
+                "{} {} {} {}",
+                event.offset.as_micros(),
+                event.scope,
+                event.kind,
+                event.resource_type
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = ParseTraceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |what: &str| ParseTraceError::new(format!("line {}: {what}", number + 1));
+            let mut fields = line.splitn(4, ' ');
+            let offset = fields
+                .next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| error("invalid offset"))?;
+            let scope = fields
+                .next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| error("invalid scope identifier"))?;
+            let kind = fields
+                .next()
+                .ok_or_else(|| error("missing event kind"))?
+                .parse()
+                .map_err(|e: ParseTraceError| error(&e.0))?;
+            let resource_type = fields
+                .next()
+                .ok_or_else(|| error("missing resource type"))?;
+
+            events.push(TraceEvent {
+                offset: Duration::from_micros(offset),
+                scope,
+                kind,
+                resource_type: resource_type.to_owned(),
+            });
+        }
+        Ok(Self { events })
+    }
+}
+
+/// The error returned when parsing a [`Trace`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTraceError(String);
+
+impl ParseTraceError {
+    fn new(message: String) -> Self {
+        Self(message)
+    }
+}
+
+impl fmt::Display for ParseTraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid trace: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTraceError {}
+
+/// The error returned when a [`Trace`] cannot be replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayError(String);
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot replay trace: {}", self.0)
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// An observer recording the events of the use scopes it observes into a [`Trace`].
+///
+/// Clones share the same recording, so a clone can be installed as the global observer while
+/// the original is used to retrieve the trace.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    inner: Arc<RecorderInner>,
+}
+
+#[derive(Debug)]
+struct RecorderInner {
+    start: Instant,
+    events: Mutex<Vec<TraceEvent>>,
+}
+
+impl Recorder {
+    /// Starts a new, empty recording.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(RecorderInner {
+                start: Instant::now(),
+                events: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Returns a snapshot of the events recorded so far.
+    pub fn trace(&self) -> Trace {
+        let events = self.inner.events.lock().unwrap_or_else(|e| e.into_inner());
+        Trace {
+            events: events.clone(),
+        }
+    }
+
+    fn record(&self, event: &UseEvent, kind: TraceEventKind) {
+        let event = TraceEvent {
+            offset: self.inner.start.elapsed(),
+            scope: event.id().get(),
+            kind,
+            resource_type: event.resource_type().to_owned(),
+        };
+        let mut events = self.inner.events.lock().unwrap_or_else(|e| e.into_inner());
+        events.push(event);
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UseObserver for Recorder {
+    fn on_acquire(&self, event: &UseEvent) {
+        self.record(event, TraceEventKind::Acquire);
+    }
+
+    fn on_body_end(&self, event: &UseEvent) {
+        self.record(event, TraceEventKind::BodyEnd);
+    }
+
+    fn on_close(&self, event: &UseEvent) {
+        self.record(event, TraceEventKind::Close);
+    }
+
+    fn on_error(&self, event: &UseEvent, failure: Failure) {
+        let kind = match failure {
+            Failure::Panic => TraceEventKind::Panic,
+            Failure::Close => TraceEventKind::CloseError,
+            Failure::ClosePanic => TraceEventKind::ClosePanic,
+            Failure::DropPanic => TraceEventKind::DropPanic,
+        };
+        self.record(event, kind);
+    }
+}
+
+/// A hook invoked for every replayed event.
+type Hook<'h> = Box<dyn Fn(&TraceEvent) + Send + Sync + 'h>;
+
+/// Re-executes the schedule of a [`Trace`] against mock resources.
+///
+/// Every recorded scope is replayed on its own thread, in a use scope of a mock resource. A
+/// scope's thread is started when its acquisition is due and ends with the scope, so only the
+/// scopes that were alive at the same time run concurrently. The threads are sequenced so that
+/// all events occur in exactly the recorded order, and every event wakes only the thread that
+/// replays the next one. Scopes whose body panicked panic again, and scopes whose close failed
+/// fail to close again.
+///
+/// Scopes that were still alive when the trace was captured are not replayed. Timing is not
+/// reproduced; only the order of events is.
+pub struct Replayer<'a> {
+    trace: &'a Trace,
+    observer: Option<&'a dyn UseObserver>,
+    hook: Option<Hook<'a>>,
+}
+
+impl<'a> Replayer<'a> {
+    /// Prepares the replay of `trace`.
+    pub fn new(trace: &'a Trace) -> Self {
+        Self {
+            trace,
+            observer: None,
+            hook: None,
+        }
+    }
+
+    /// Attaches an observer to every replayed use scope.
+    pub fn observer(mut self, observer: &'a dyn UseObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Installs a hook that is invoked right before every replayed event occurs.
+    ///
+    /// The hook runs on the thread replaying the event's scope, which allows reproducing side
+    /// effects of the original resources, e.g. by calling into the code under test.
+    pub fn on_event(mut self, hook: impl Fn(&TraceEvent) + Send + Sync + 'a) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Runs the replay, returning once all replayed scopes have completed.
+    ///
+    /// # Errors
+    /// Returns an error without replaying anything if a completed scope of the trace did not
+    /// record a full use scope, such as a scope whose `acquire` event is missing.
+    pub fn run(self) -> Result<(), ReplayError> {
+        let schedule = self.schedule()?;
+        let sequencer = Sequencer::new(&schedule);
+        let hook = self.hook.as_deref();
+        let observer = self.observer;
+
+        std::thread::scope(|threads| {
+            for steps in &schedule {
+                // Scopes are ordered by their acquisition, which starts their thread.
+                sequencer.wait_for(steps[0].0);
+                let sequencer = &sequencer;
+                threads.spawn(move || {
+                    let resource = ReplayResource {
+                        sequencer,
+                        steps,
+                        hook,
+                    };
+                    // The acquisition is reported when the scope is entered.
+                    resource.await_event(TraceEventKind::Acquire);
+                    let scope = resource.scoped();
+                    let scope = match observer {
+                        Some(observer) => scope.observer(observer),
+                        None => scope,
+                    };
+
+                    // Scopes that panicked without closing their resource did not use `use_close`.
+                    let closes = steps.iter().any(|(_, event)| {
+                        matches!(
+                            event.kind,
+                            TraceEventKind::Close
+                                | TraceEventKind::CloseError
+                                | TraceEventKind::ClosePanic
+                        )
+                    });
+
+                    // Replayed panics are expected; they are caught to keep the schedule going.
+                    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        if closes {
+                            let _ = scope.use_close(|resource| resource.body());
+                        } else {
+                            scope.use_with(|resource| resource.body());
+                        }
+                    }));
+                    sequencer.advance();
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Collects the steps of all complete scopes, numbering them in trace order, and orders the
+    /// scopes by their acquisition.
+    fn schedule(&self) -> Result<Vec<Vec<(usize, &'a TraceEvent)>>, ReplayError> {
+        let complete: Vec<u64> = self
+            .trace
+            .events
+            .iter()
+            .filter(|event| event.kind.is_terminal())
+            .map(|event| event.scope)
+            .collect();
+
+        let mut scopes: BTreeMap<u64, Vec<(usize, &TraceEvent)>> = BTreeMap::new();
+        let events = self.trace.events.iter().filter(|event| {
+            event.kind != TraceEventKind::DropPanic && complete.contains(&event.scope)
+        });
+        for (step, event) in events.enumerate() {
+            scopes.entry(event.scope).or_default().push((step, event));
+        }
+
+        for (scope, steps) in &scopes {
+            check_sequence(*scope, steps)?;
+        }
+        let mut schedule: Vec<_> = scopes.into_values().collect();
+        schedule.sort_by_key(|steps| steps[0].0);
+        Ok(schedule)
+    }
+}
+
+/// Checks that the events of a completed scope form a full use scope, so that every replayed
+/// event has a counterpart in the recorded schedule.
+fn check_sequence(scope: u64, steps: &[(usize, &TraceEvent)]) -> Result<(), ReplayError> {
+    use TraceEventKind::{Acquire, BodyEnd, Close, CloseError, ClosePanic, Panic};
+
+    let kinds: Vec<_> = steps.iter().map(|(_, event)| event.kind).collect();
+    match kinds.as_slice() {
+        [Acquire, BodyEnd, Close | CloseError]
+        | [Acquire, Panic]
+        | [Acquire, Panic, Close | CloseError | ClosePanic] => Ok(()),
+        [first, ..] if *first != Acquire => Err(ReplayError(format!(
+            "scope {scope} is missing its `acquire` event before `{first}`"
+        ))),
+        _ => {
+            let kinds: Vec<_> = kinds.iter().map(|kind| kind.as_str()).collect();
+            Err(ReplayError(format!(
+                "scope {scope} recorded the incomplete event sequence `{}`",
+                kinds.join(" ")
+            )))
+        }
+    }
+}
+
+impl fmt::Debug for Replayer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replayer")
+            .field("trace", &self.trace)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Orders the events of concurrently replayed scopes.
+///
+/// Every step is awaited by a single thread, which is identified by its slot: slot 0 starts the
+/// scope threads at their acquisitions, and the remaining slots replay one scope each.
+struct Sequencer {
+    step: Mutex<usize>,
+    /// The slot awaiting each step.
+    slots: Vec<usize>,
+    /// The wakeups of the threads, indexed by their slot.
+    wakeups: Vec<Condvar>,
+}
+
+impl Sequencer {
+    fn new(schedule: &[Vec<(usize, &TraceEvent)>]) -> Self {
+        let steps = schedule.iter().map(Vec::len).sum();
+        let mut slots = vec![0; steps];
+        for (scope, scope_steps) in schedule.iter().enumerate() {
+            // The acquisition is awaited by slot 0, which starts the scope's thread.
+            for (step, _) in &scope_steps[1..] {
+                slots[*step] = scope + 1;
+            }
+        }
+        Self {
+            step: Mutex::new(0),
+            slots,
+            wakeups: (0..=schedule.len()).map(|_| Condvar::new()).collect(),
+        }
+    }
+
+    /// Blocks until all events before `step` have occurred.
+    fn wait_for(&self, step: usize) {
+        let current = self.step.lock().unwrap_or_else(|e| e.into_inner());
+        let _current = self.wakeups[self.slots[step]]
+            .wait_while(current, |current| *current < step)
+            .unwrap_or_else(|e| e.into_inner());
+    }
+
+    /// Marks the current event as having occurred and wakes the thread awaiting the next one.
+    fn advance(&self) {
+        let mut step = self.step.lock().unwrap_or_else(|e| e.into_inner());
+        *step += 1;
+        if let Some(&slot) = self.slots.get(*step) {
+            self.wakeups[slot].notify_one();
+        }
+    }
+}
+
+/// The mock resource standing in for a recorded resource during a replay.
+struct ReplayResource<'a> {
+    sequencer: &'a Sequencer,
+    steps: &'a [(usize, &'a TraceEvent)],
+    hook: Option<&'a (dyn Fn(&TraceEvent) + Send + Sync)>,
+}
+
+impl ReplayResource<'_> {
+    /// Returns the type name of the recorded resource this resource stands in for.
+    fn resource_type(&self) -> &str {
+        self.steps
+            .first()
+            .map_or("", |(_, event)| event.resource_type())
+    }
+
+    /// Waits until the recorded event of the given kind is due.
+    ///
+    /// Returns `false` if the scope has no such event.
+    fn await_event(&self, kind: TraceEventKind) -> bool {
+        let Some((step, event)) = self.steps.iter().find(|(_, event)| event.kind == kind) else {
+            return false;
+        };
+        self.sequencer.wait_for(*step);
+        if let Some(hook) = self.hook {
+            hook(event);
+        }
+        true
+    }
+
+    fn body(&self) {
+        self.sequencer.advance();
+
+        if self.await_event(TraceEventKind::Panic) {
+            panic!("replayed panic of `{}`", self.resource_type());
+        }
+        self.await_event(TraceEventKind::BodyEnd);
+    }
+}
+
+impl fmt::Debug for ReplayResource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplayResource")
+            .field("resource_type", &self.resource_type())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Close for ReplayResource<'_> {
+    type Error = ReplayedCloseError;
+
+    fn close(self) -> Result<(), Self::Error> {
+        self.sequencer.advance();
+        if self.await_event(TraceEventKind::ClosePanic) {
+            panic!("replayed close panic of `{}`", self.resource_type());
+        }
+        if self.await_event(TraceEventKind::CloseError) {
+            return Err(ReplayedCloseError);
+        }
+        self.await_event(TraceEventKind::Close);
+        Ok(())
+    }
+}
+
+/// The error returned by a [`ReplayResource`] whose recorded close failed.
+#[derive(Debug)]
+struct ReplayedCloseError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+
+    struct Outer;
+    struct Inner;
+
+    #[test]
+    fn test_trace_round_trip() {
+        let recorder = Recorder::new();
+        Outer.scoped().observer(&recorder).use_with(|_outer| {
+            Inner.scoped().observer(&recorder).use_with(|_inner| ());
+        });
+
+        let trace = recorder.trace();
+        let kinds: Vec<_> = trace.events().iter().map(TraceEvent::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TraceEventKind::Acquire,
+                TraceEventKind::Acquire,
+                TraceEventKind::BodyEnd,
+                TraceEventKind::Close,
+                TraceEventKind::BodyEnd,
+                TraceEventKind::Close,
+            ]
+        );
+        assert!(trace.events()[0].resource_type().ends_with("Outer"));
+        let parsed: Trace = trace.to_string().parse().unwrap();
+        assert_eq!(parsed.to_string(), trace.to_string());
+        assert!(parsed.same_schedule(&trace));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("1 2 acquire".parse::<Trace>().is_err());
+        assert!("x 2 acquire Foo".parse::<Trace>().is_err());
+        assert!("1 2 explode Foo".parse::<Trace>().is_err());
+        assert_eq!("# comment\n\n".parse::<Trace>(), Ok(Trace::default()));
+    }
+
+    #[test]
+    fn test_replay_interleaved_scopes() {
+        // Two scopes on different threads whose lifetimes overlap without nesting,
+        // including a panic and a failed close.
+        let trace: Trace = "\
+            0 1 acquire a::Connection\n\
+            1 2 acquire a::Transaction\n\
+            2 1 body_end a::Connection\n\
+            3 2 panic a::Transaction\n\
+            4 1 close_error a::Connection\n\
+            5 3 acquire a::Orphan\n"
+            .parse()
+            .unwrap();
+
+        let seen = Mutex::new(Vec::new());
+        let replayed = Recorder::new();
+        Replayer::new(&trace)
+            .observer(&replayed)
+            .on_event(|event| {
+                seen.lock()
+                    .unwrap()
+                    .push((event.scope(), event.resource_type().to_owned()));
+            })
+            .run()
+            .unwrap();
+
+        let complete = Trace {
+            events: trace.events()[..5].to_vec(),
+        };
+        assert!(replayed.trace().same_schedule(&complete));
+        assert_eq!(seen.into_inner().unwrap()[3], (2, "a::Transaction".into()));
+    }
+
+    #[test]
+    fn test_replay_rejects_incomplete_scopes() {
+        let trace: Trace = "\
+            0 1 acquire a::Connection\n\
+            1 2 body_end a::Transaction\n\
+            2 1 body_end a::Connection\n\
+            3 2 close a::Transaction\n\
+            4 1 close a::Connection\n"
+            .parse()
+            .unwrap();
+        let error = Replayer::new(&trace).run().unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("scope 2 is missing its `acquire` event"));
+
+        let trace: Trace = "0 1 acquire a::Connection\n1 1 close a::Connection\n"
+            .parse()
+            .unwrap();
+        let error = Replayer::new(&trace).run().unwrap_err();
+        assert!(error.to_string().contains("`acquire close`"));
+    }
+}
@@ -0,0 +1,60 @@
+//! Internal registry of the use scopes that are currently alive.
+
+use crate::sync::{StaticMutex, StaticMutexGuard};
+use crate::ScopeId;
+#[cfg(feature = "leak-detector")]
+use std::backtrace::Backtrace;
+use std::collections::BTreeMap;
+use std::panic::Location;
+#[cfg(feature = "leak-detector")]
+use std::sync::Arc;
+#[cfg(feature = "diagnostics")]
+use std::thread::{self, ThreadId};
+use std::time::Instant;
+
+/// A use scope that is currently holding its resource.
+pub(crate) struct Entry {
+    pub(crate) resource_type: &'static str,
+    pub(crate) location: &'static Location<'static>,
+    pub(crate) acquired: Instant,
+    #[cfg(feature = "diagnostics")]
+    pub(crate) thread_id: ThreadId,
+    #[cfg(feature = "diagnostics")]
+    pub(crate) thread_name: Option<String>,
+    #[cfg(feature = "leak-detector")]
+    pub(crate) backtrace: Arc<Backtrace>,
+}
+
+static LIVE: StaticMutex<BTreeMap<ScopeId, Entry>> = StaticMutex::new(BTreeMap::new());
+
+/// Returns the live scopes, ordered by their identifier.
+pub(crate) fn live() -> StaticMutexGuard<'static, BTreeMap<ScopeId, Entry>> {
+    LIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Registers a scope that just acquired its resource.
+pub(crate) fn register(
+    id: ScopeId,
+    resource_type: &'static str,
+    location: &'static Location<'static>,
+) {
+    #[cfg(feature = "diagnostics")]
+    let thread = thread::current();
+    let entry = Entry {
+        resource_type,
+        location,
+        acquired: Instant::now(),
+        #[cfg(feature = "diagnostics")]
+        thread_id: thread.id(),
+        #[cfg(feature = "diagnostics")]
+        thread_name: thread.name().map(str::to_owned),
+        #[cfg(feature = "leak-detector")]
+        backtrace: Arc::new(Backtrace::capture()),
+    };
+    live().insert(id, entry);
+}
+
+/// Removes a scope that released its resource.
+pub(crate) fn unregister(id: ScopeId) {
+    live().remove(&id);
+}
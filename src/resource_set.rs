@@ -0,0 +1,206 @@
+//! A fixed-capacity set of resources that does not allocate.
+
+use crate::Close;
+use core::fmt;
+
+/// A set of up to `N` resources that are closed or dropped together, in reverse order.
+///
+/// The resources are stored inline, so the set never allocates, which suits embedded and
+/// latency-critical code. Resources of different types are stored as variants of an enum that
+/// implements [`Close`] by closing the respective resource; where allocations are acceptable,
+/// `Box<dyn DynClose<Error = E>>` works as well.
+///
+/// Sets built from an array with [`from_array`](Self::from_array) check the capacity at compile
+/// time; [`try_push`](Self::try_push) hands the resource back if the set is full.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{Close, ResourceSet, Use};
+///
+/// enum Peripheral {
+///     Uart(u8),
+///     Spi(u8),
+/// }
+///
+/// impl Close for Peripheral {
+///     type Error = &'static str;
+///
+///     fn close(self) -> Result<(), Self::Error> {
+///         match self {
+///             Peripheral::Uart(_) => Ok(()),
+///             Peripheral::Spi(_) => Err("SPI bus busy"),
+///         }
+///     }
+/// }
+///
+/// let mut peripherals = ResourceSet::<_, 4>::from_array([Peripheral::Uart(0)]);
+/// assert!(peripherals.try_push(Peripheral::Spi(1)).is_ok());
+///
+/// let result = peripherals.use_close(|peripherals| peripherals.len());
+/// assert_eq!(result, Err("SPI bus busy"));
+/// ```
+///
+/// Exceeding the capacity with an array fails to compile:
+///
+/// ```compile_fail
+/// use use_with::ResourceSet;
+///
+/// let set = ResourceSet::<u8, 2>::from_array([1, 2, 3]);
+/// ```
+pub struct ResourceSet<T, const N: usize> {
+    resources: [Option<T>; N],
+    len: usize,
+}
+
+/// Fails to evaluate, and thus to compile, if an array of `M` elements exceeds the capacity `N`.
+struct AssertCapacity<const M: usize, const N: usize>;
+
+impl<const M: usize, const N: usize> AssertCapacity<M, N> {
+    const OK: () = assert!(M <= N, "the array exceeds the capacity of the resource set");
+}
+
+impl<T, const N: usize> ResourceSet<T, N> {
+    const EMPTY: Option<T> = None;
+
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        Self {
+            resources: [Self::EMPTY; N],
+            len: 0,
+        }
+    }
+
+    /// Creates a set from an array of resources.
+    ///
+    /// Fails to compile if the array has more than `N` elements.
+    pub fn from_array<const M: usize>(resources: [T; M]) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = AssertCapacity::<M, N>::OK;
+        let mut set = Self::new();
+        for resource in resources {
+            set.resources[set.len] = Some(resource);
+            set.len += 1;
+        }
+        set
+    }
+
+    /// Adds a resource to the set, or returns it if the set is full.
+    pub fn try_push(&mut self, resource: T) -> Result<(), T> {
+        match self.resources.get_mut(self.len) {
+            Some(slot) => {
+                *slot = Some(resource);
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(resource),
+        }
+    }
+
+    /// Returns the number of resources in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the set contains no resources.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the maximum number of resources in the set.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns an iterator over the resources, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.resources.iter().flatten()
+    }
+
+    /// Returns an iterator that allows modifying the resources, in the order they were added.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.resources.iter_mut().flatten()
+    }
+
+    /// Removes the most recently added resource.
+    fn pop(&mut self) -> Option<T> {
+        let last = self.len.checked_sub(1)?;
+        self.len = last;
+        self.resources[last].take()
+    }
+}
+
+impl<T, const N: usize> Default for ResourceSet<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for ResourceSet<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Drops the resources in reverse order of their adding.
+impl<T, const N: usize> Drop for ResourceSet<T, N> {
+    fn drop(&mut self) {
+        while let Some(resource) = self.pop() {
+            drop(resource);
+        }
+    }
+}
+
+/// Closes all resources in reverse order of their adding, returning the first error.
+///
+/// A failure to close one resource does not keep the remaining ones from being closed.
+impl<T: Close, const N: usize> Close for ResourceSet<T, N> {
+    type Error = T::Error;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        let mut first_error = None;
+        while let Some(resource) = self.pop() {
+            if let Err(error) = resource.close() {
+                first_error.get_or_insert(error);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropSpy;
+    use crate::Use;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_resources_are_released_in_reverse_order() {
+        let spy = DropSpy::new();
+        let mut set = ResourceSet::<_, 2>::from_array([spy.probe("first")]);
+        assert!(set.try_push(spy.probe("second")).is_ok());
+        let rejected = set.try_push(spy.probe("third")).unwrap_err();
+        drop(rejected);
+        assert_eq!(spy.drop_count("third"), 1);
+
+        let order = RefCell::new(Vec::new());
+        struct Recorded<'a>(u8, &'a RefCell<Vec<u8>>);
+
+        impl Close for Recorded<'_> {
+            type Error = u8;
+
+            fn close(self) -> Result<(), Self::Error> {
+                self.1.borrow_mut().push(self.0);
+                Err(self.0)
+            }
+        }
+
+        let closed = ResourceSet::<_, 3>::from_array([Recorded(1, &order), Recorded(2, &order)])
+            .use_close(|set| set.len());
+        assert_eq!(closed, Err(2));
+        assert_eq!(*order.borrow(), [2, 1]);
+
+        drop(set);
+        assert_eq!((spy.drop_count("first"), spy.drop_count("second")), (1, 1));
+    }
+}
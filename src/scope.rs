@@ -0,0 +1,335 @@
+//! Cleanup actions that run depending on how a scope ends.
+//!
+//! [`scope_exit`], [`scope_fail`] and [`scope_success`] create guards with the semantics of the
+//! C++ Library Fundamentals TS: their action runs when the guard is dropped always, only during a
+//! panic, or only without a panic. [`errdefer_scope`] and the [`errdefer!`](crate::errdefer) macro
+//! register rollback actions that only run if the scope fails, in the style of Zig's `errdefer`.
+
+use crate::unwind::panic;
+use crate::ExitStack;
+use core::fmt;
+use core::panic::AssertUnwindSafe;
+
+/// When the action of a [`ScopeGuard`] runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum When {
+    Always,
+    Fail,
+    Success,
+}
+
+/// A guard that runs an action when it is dropped, created by [`scope_exit`], [`scope_fail`] or
+/// [`scope_success`].
+///
+/// A panic counts as the failure of the guarded scope if it started after the guard was created,
+/// so a guard created inside a [`Drop`] implementation that runs during unwinding still behaves as
+/// if its own scope succeeded. If the action panics while the thread is already panicking, that
+/// panic is discarded rather than aborting the process.
+///
+/// Without the `std` feature, panics cannot be detected, so every scope counts as successful.
+#[must_use = "a scope guard runs its action as soon as it is dropped"]
+pub struct ScopeGuard<F: FnOnce()> {
+    action: Option<F>,
+    when: When,
+    panicking_on_creation: bool,
+}
+
+impl<F: FnOnce()> ScopeGuard<F> {
+    fn new(action: F, when: When) -> Self {
+        Self {
+            action: Some(action),
+            when,
+            panicking_on_creation: panic::panicking(),
+        }
+    }
+
+    /// Disarms the guard, so that its action never runs.
+    pub fn dismiss(&mut self) {
+        self.action = None;
+    }
+
+    /// Returns whether the guard has been dismissed.
+    pub fn is_dismissed(&self) -> bool {
+        self.action.is_none()
+    }
+}
+
+impl<F: FnOnce()> fmt::Debug for ScopeGuard<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopeGuard")
+            .field("when", &self.when)
+            .field("dismissed", &self.is_dismissed())
+            .finish()
+    }
+}
+
+impl<F: FnOnce()> Drop for ScopeGuard<F> {
+    fn drop(&mut self) {
+        let Some(action) = self.action.take() else {
+            return;
+        };
+        let panicking = panic::panicking();
+        let failed = panicking && !self.panicking_on_creation;
+        let run = match self.when {
+            When::Always => true,
+            When::Fail => failed,
+            When::Success => !failed,
+        };
+        if !run {
+            return;
+        }
+        if panicking {
+            // A second panic would abort the process.
+            let _ = panic::catch_unwind(AssertUnwindSafe(action));
+        } else {
+            action();
+        }
+    }
+}
+
+/// Creates a guard that runs the action whenever it is dropped, like C++'s `scope_exit`.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use use_with::scope::scope_exit;
+///
+/// let depth = Cell::new(0);
+/// {
+///     depth.set(depth.get() + 1);
+///     let _restore = scope_exit(|| depth.set(depth.get() - 1));
+///     assert_eq!(depth.get(), 1);
+/// }
+/// assert_eq!(depth.get(), 0);
+/// ```
+pub fn scope_exit<F: FnOnce()>(action: F) -> ScopeGuard<F> {
+    ScopeGuard::new(action, When::Always)
+}
+
+/// Creates a guard that runs the action only if it is dropped by a panic, like C++'s
+/// `scope_fail`.
+///
+/// Errors returned from a function are not panics. To roll back on `Err` as well, use
+/// [`errdefer_scope`], or [`dismiss`](ScopeGuard::dismiss) a [`scope_exit`] guard on success.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::RefCell;
+/// use std::panic::{self, AssertUnwindSafe};
+/// use use_with::scope::scope_fail;
+///
+/// let staged = RefCell::new(vec!["committed"]);
+///
+/// let result = panic::catch_unwind(AssertUnwindSafe(|| {
+///     staged.borrow_mut().push("pending");
+///     let _rollback = scope_fail(|| {
+///         staged.borrow_mut().pop();
+///     });
+///     panic!("validation failed");
+/// }));
+///
+/// assert!(result.is_err());
+/// # #[cfg(feature = "std")]
+/// assert_eq!(*staged.borrow(), ["committed"]);
+/// ```
+pub fn scope_fail<F: FnOnce()>(action: F) -> ScopeGuard<F> {
+    ScopeGuard::new(action, When::Fail)
+}
+
+/// Creates a guard that runs the action only if it is dropped without a panic, like C++'s
+/// `scope_success`.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use use_with::scope::scope_success;
+///
+/// let completed = Cell::new(0);
+/// {
+///     let _count = scope_success(|| completed.set(completed.get() + 1));
+/// }
+/// assert_eq!(completed.get(), 1);
+/// ```
+pub fn scope_success<F: FnOnce()>(action: F) -> ScopeGuard<F> {
+    ScopeGuard::new(action, When::Success)
+}
+
+/// The marker of a scope created by [`errdefer_scope`], on which rollback actions are registered.
+#[derive(Debug)]
+pub struct ErrDefer<'a> {
+    stack: ExitStack<'a>,
+}
+
+impl<'a> ErrDefer<'a> {
+    /// Registers an action that runs only if the scope fails.
+    ///
+    /// Actions run in reverse order of their registration.
+    pub fn errdefer<F: FnOnce() + 'a>(&mut self, f: F) {
+        self.stack.callback(f);
+    }
+
+    /// Returns the number of registered actions.
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns whether no actions are registered.
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+}
+
+/// Runs a fallible closure, rolling back the actions registered on its scope marker if it fails.
+///
+/// Multi-step initialization often has to undo the steps that succeeded when a later one fails.
+/// Each step registers its rollback on the [`ErrDefer`] marker, usually through the
+/// [`errdefer!`](crate::errdefer) macro, right after it succeeded. If the closure returns `Err` or
+/// panics, the registered actions run in reverse order; if it returns `Ok`, they are discarded.
+///
+/// Rollback actions may only borrow values that outlive the scope; values created within the
+/// closure must be moved into them.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::RefCell;
+/// use use_with::errdefer;
+/// use use_with::scope::errdefer_scope;
+///
+/// let provisioned = RefCell::new(Vec::new());
+/// let provisioned = &provisioned;
+///
+/// let provision = |name: &'static str| -> Result<&'static str, String> {
+///     if name == "dns" {
+///         return Err(format!("failed to provision {name}"));
+///     }
+///     provisioned.borrow_mut().push(name);
+///     Ok(name)
+/// };
+///
+/// let result = errdefer_scope(|scope| {
+///     let vm = provision("vm")?;
+///     errdefer!(scope, provisioned.borrow_mut().retain(|&item| item != vm));
+///
+///     let disk = provision("disk")?;
+///     errdefer!(scope, provisioned.borrow_mut().retain(|&item| item != disk));
+///
+///     provision("dns")
+/// });
+///
+/// assert_eq!(result, Err(String::from("failed to provision dns")));
+/// assert!(provisioned.borrow().is_empty());
+/// ```
+pub fn errdefer_scope<'a, T, E, F>(f: F) -> Result<T, E>
+where
+    F: FnOnce(&mut ErrDefer<'a>) -> Result<T, E>,
+{
+    let mut scope = ErrDefer {
+        stack: ExitStack::new(),
+    };
+    let result = f(&mut scope);
+    if result.is_ok() {
+        scope.stack.dismiss();
+    }
+    result
+}
+
+/// Registers a rollback action on an [`ErrDefer`] scope marker, like Zig's `errdefer`.
+///
+/// `errdefer!(scope, expression)` registers the expression as an action that runs only if the
+/// scope created by [`errdefer_scope`](crate::scope::errdefer_scope) fails. The expression is
+/// wrapped into a `move` closure, so values created in the scope, such as identifiers of the
+/// resources to roll back, are moved into it.
+///
+/// # Examples
+/// See [`errdefer_scope`](crate::scope::errdefer_scope).
+#[macro_export]
+macro_rules! errdefer {
+    ($scope:expr, $($body:tt)+) => {
+        $crate::scope::ErrDefer::errdefer($scope, move || {
+            $($body)+;
+        })
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_guards_run_depending_on_panics() {
+        let log = RefCell::new(Vec::new());
+        let guards = || {
+            (
+                scope_exit(|| log.borrow_mut().push("exit")),
+                scope_fail(|| log.borrow_mut().push("fail")),
+                scope_success(|| log.borrow_mut().push("success")),
+            )
+        };
+
+        drop(guards());
+        assert_eq!(log.take(), ["exit", "success"]);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guards = guards();
+            panic!("scope failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(log.take(), ["exit", "fail"]);
+
+        let mut dismissed = guards();
+        dismissed.0.dismiss();
+        dismissed.2.dismiss();
+        drop(dismissed);
+        assert!(log.borrow().is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_panicking_action_during_unwind_is_discarded() {
+        let result = panic::catch_unwind(|| {
+            let _guard = scope_fail(|| panic!("rollback failed"));
+            panic!("scope failed");
+        });
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"scope failed"));
+    }
+
+    #[test]
+    fn test_rollback_runs_only_on_failure() {
+        let log = RefCell::new(Vec::new());
+        let log = &log;
+
+        let result = errdefer_scope(|scope| {
+            errdefer!(scope, log.borrow_mut().push("rolled back"));
+            Ok::<_, ()>(1)
+        });
+        assert_eq!(result, Ok(1));
+        assert!(log.borrow().is_empty());
+
+        let result = errdefer_scope(|scope| {
+            for step in ["first", "second"] {
+                errdefer!(scope, log.borrow_mut().push(step));
+            }
+            Err::<(), _>("failed")
+        });
+        assert_eq!(result, Err("failed"));
+        assert_eq!(*log.borrow(), ["second", "first"]);
+
+        #[cfg(feature = "std")]
+        {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                errdefer_scope(|scope| {
+                    errdefer!(scope, log.borrow_mut().push("unwound"));
+                    if scope.len() == 1 {
+                        panic!("step panicked");
+                    }
+                    Ok::<_, ()>(())
+                })
+            }));
+            assert!(result.is_err());
+            assert_eq!(log.borrow().last(), Some(&"unwound"));
+        }
+    }
+}
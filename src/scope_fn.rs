@@ -0,0 +1,161 @@
+//! Kotlin-style scope functions.
+
+/// The scope functions of Kotlin's standard library, for every type.
+///
+/// Kotlin offers five closely related functions to run a block in the context of a value. They
+/// differ in whether the block receives the value as `it` or as `this`, and in whether they return
+/// the value itself or the result of the block. In Rust, receiving the value as `this` maps to a
+/// mutable borrow:
+///
+/// | Kotlin         | `Scope`            | Block receives | Returns  |
+/// |----------------|--------------------|----------------|----------|
+/// | `x.also { }`   | [`also`](Self::also)   | `&Self`        | `Self`   |
+/// | `x.apply { }`  | [`apply`](Self::apply) | `&mut Self`    | `Self`   |
+/// | `x.let { }`    | [`let_`](Self::let_)   | `Self`         | `U`      |
+/// | `x.run { }`    | [`run`](Self::run)     | `&mut Self`    | `U`      |
+///
+/// Kotlin's `takeIf` and `takeUnless` are available as [`take_if`](Self::take_if) and
+/// [`take_unless`](Self::take_unless), which feed conditional consumption into [`Option`]-aware
+/// code. With `Scope` imported, they take precedence over [`Option::take_if`] on `Option` values,
+/// which can still be called as `Option::take_if(&mut option, predicate)`.
+///
+/// [`tap`](Self::tap) and [`pipe`](Self::pipe) are the same as `also` and `let_` under the names
+/// commonly used for fluent pipelines in Rust.
+///
+/// Kotlin's `use` corresponds to [`Use::use_with`](crate::Use::use_with), which additionally
+/// reports the scope to observers. `Scope` is a separate trait so that its short method names only
+/// come into scope where they are imported.
+///
+/// # Examples
+/// ```rust
+/// use use_with::Scope;
+///
+/// let total = Vec::new()
+///     .apply(|numbers| numbers.extend([3, 1, 2]))
+///     .apply(|numbers| numbers.sort())
+///     .also(|numbers| assert_eq!(numbers, &[1, 2, 3]))
+///     .let_(|numbers| numbers.into_iter().sum::<i32>());
+///
+/// assert_eq!(total, 6);
+/// ```
+pub trait Scope: Sized {
+    /// Runs a side effect on the value and returns the value, like Kotlin's `also`.
+    fn also<F: FnOnce(&Self)>(self, f: F) -> Self {
+        f(&self);
+        self
+    }
+
+    /// Configures the value in place and returns it, like Kotlin's `apply`.
+    fn apply<F: FnOnce(&mut Self)>(mut self, f: F) -> Self {
+        f(&mut self);
+        self
+    }
+
+    /// Transforms the value into the result of the closure, like Kotlin's `let`.
+    ///
+    /// The trailing underscore avoids the `let` keyword.
+    fn let_<U, F: FnOnce(Self) -> U>(self, f: F) -> U {
+        f(self)
+    }
+
+    /// Runs the closure on the value and returns its result, like Kotlin's `run`.
+    ///
+    /// The value is dropped once the closure returns.
+    fn run<U, F: FnOnce(&mut Self) -> U>(mut self, f: F) -> U {
+        f(&mut self)
+    }
+
+    /// Runs a side effect on the value and returns the value, such as logging an intermediate
+    /// step of a pipeline.
+    ///
+    /// Same as [`also`](Self::also).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::{Scope, Use};
+    ///
+    /// let words = "use with scope"
+    ///     .split(' ')
+    ///     .collect::<Vec<_>>()
+    ///     .tap(|words| println!("{} words", words.len()))
+    ///     .pipe(|words| words.join("_"))
+    ///     .use_with(|joined| joined.to_uppercase());
+    ///
+    /// assert_eq!(words, "USE_WITH_SCOPE");
+    /// ```
+    fn tap<F: FnOnce(&Self)>(self, f: F) -> Self {
+        self.also(f)
+    }
+
+    /// Transforms the value into the result of the closure.
+    ///
+    /// Same as [`let_`](Self::let_).
+    fn pipe<U, F: FnOnce(Self) -> U>(self, f: F) -> U {
+        self.let_(f)
+    }
+
+    /// Returns the value if it satisfies the predicate, or drops it and returns `None` otherwise,
+    /// like Kotlin's `takeIf`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::{Scope, Use};
+    ///
+    /// struct Connection {
+    ///     healthy: bool,
+    /// }
+    ///
+    /// impl Connection {
+    ///     fn is_healthy(&self) -> bool {
+    ///         self.healthy
+    ///     }
+    /// }
+    ///
+    /// let status = Connection { healthy: false }
+    ///     .take_if(Connection::is_healthy)
+    ///     .map(|conn| conn.use_with(|_conn| 200));
+    ///
+    /// assert_eq!(status, None);
+    /// ```
+    fn take_if<P: FnOnce(&Self) -> bool>(self, predicate: P) -> Option<Self> {
+        if predicate(&self) {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value unless it satisfies the predicate, in which case it is dropped and `None`
+    /// is returned, like Kotlin's `takeUnless`.
+    fn take_unless<P: FnOnce(&Self) -> bool>(self, predicate: P) -> Option<Self> {
+        self.take_if(|value| !predicate(value))
+    }
+}
+
+impl<T> Scope for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropCounter;
+
+    #[test]
+    fn test_run_drops_the_value() {
+        let counter = DropCounter::new();
+        let kept = counter.probe().apply(|_| ()).also(|_| ());
+        assert_eq!(counter.count(), 0);
+
+        let dropped_inside = kept.run(|_probe| counter.count());
+        assert_eq!((dropped_inside, counter.count()), (0, 1));
+    }
+
+    #[test]
+    fn test_rejected_values_are_dropped() {
+        let counter = DropCounter::new();
+        assert!(counter.probe().take_if(|_| true).is_some());
+        assert_eq!(counter.count(), 1);
+
+        assert!(counter.probe().take_unless(|_| true).is_none());
+        assert_eq!(counter.count(), 2);
+    }
+}
@@ -0,0 +1,899 @@
+//! Per-call configuration of use scopes.
+
+use crate::instrument::{Probe, ScopeOptions, Slot, Tracking};
+use crate::observer::{NoopObserver, UseObserver};
+use crate::unwind::{catch_unwind, panic};
+#[cfg(feature = "std")]
+use crate::PanicPayload;
+use crate::{
+    AsyncClose, BoxFuture, Close, Enter, Exit, Outcome, Sealed, Split, UnwindError, WithSnapshot,
+    Yielder,
+};
+use alloc::boxed::Box;
+use alloc::format;
+use core::fmt;
+use core::future::Future;
+use core::ops::ControlFlow;
+use core::panic::AssertUnwindSafe;
+#[cfg(feature = "std")]
+use core::panic::UnwindSafe;
+use core::pin::{pin, Pin};
+use core::task::{ready, Context, Poll};
+use pin_project_lite::pin_project;
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, RecvTimeoutError};
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+/// A resource together with the configuration of the use scope it is about to enter.
+///
+/// Created by [`Use::scoped`](crate::Use::scoped). The terminal methods mirror the ones of
+/// the [`Use`](crate::Use) trait and behave identically, apart from applying the configuration.
+/// The call site reported to observers is the one that created the `UseScope`.
+///
+/// # Examples
+/// ```rust
+/// use use_with::observer::{UseEvent, UseObserver};
+/// use use_with::Use;
+///
+/// struct PrintingObserver;
+///
+/// impl UseObserver for PrintingObserver {
+///     fn on_close(&self, event: &UseEvent) {
+///         println!("{} was released", event.resource_type());
+///     }
+/// }
+///
+/// let result = String::from("resource")
+///     .scoped()
+///     .observer(&PrintingObserver)
+///     .use_with(|res| res.len());
+///
+/// assert_eq!(result, 8);
+/// ```
+#[must_use = "a scoped resource does nothing unless one of its `use_*` methods is called"]
+pub struct UseScope<'o, T, O = NoopObserver> {
+    resource: T,
+    options: ScopeOptions<'o, O>,
+}
+
+impl<'o, T> UseScope<'o, T> {
+    #[inline]
+    #[track_caller]
+    pub(crate) fn new(resource: T) -> Self {
+        Self::with_options(resource, ScopeOptions::new())
+    }
+}
+
+impl<'o, T, O: UseObserver> UseScope<'o, T, O> {
+    #[inline]
+    pub(crate) fn with_options(resource: T, options: ScopeOptions<'o, O>) -> Self {
+        Self { resource, options }
+    }
+
+    /// Attaches an observer that is notified about this scope only,
+    /// in addition to the global observer.
+    pub fn observer(mut self, observer: &'o dyn UseObserver) -> Self {
+        self.options.observer = Some(observer);
+        self
+    }
+
+    /// Attaches an observer that is notified about this scope only and called without dynamic
+    /// dispatch, in addition to the global observer.
+    ///
+    /// Unlike [`observer`](Self::observer), the observer is part of the scope's type, so its
+    /// notifications are direct calls that the compiler can inline; an observer whose methods do
+    /// nothing compiles away entirely. Attaching a second observer this way replaces the first.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use use_with::observer::{UseEvent, UseObserver};
+    /// use use_with::Use;
+    ///
+    /// struct BodyCounter<'a>(&'a AtomicUsize);
+    ///
+    /// impl UseObserver for BodyCounter<'_> {
+    ///     fn on_body_end(&self, _event: &UseEvent) {
+    ///         self.0.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// }
+    ///
+    /// let bodies = AtomicUsize::new(0);
+    /// let len = String::from("resource")
+    ///     .scoped()
+    ///     .static_observer(BodyCounter(&bodies))
+    ///     .use_with(|res| res.len());
+    ///
+    /// assert_eq!(len, 8);
+    /// assert_eq!(bodies.into_inner(), 1);
+    /// ```
+    pub fn static_observer<P: UseObserver>(self, observer: P) -> UseScope<'o, T, P> {
+        UseScope {
+            resource: self.resource,
+            options: self.options.with_local(observer),
+        }
+    }
+
+    /// Reports a teardown of the resource that takes longer than `threshold`.
+    ///
+    /// If closing or dropping the resource after the body returned exceeds the threshold,
+    /// observers are notified through [`UseObserver::on_slow_teardown`] with the measured duration,
+    /// and a warning is logged with the `log` feature. Slow destructors, such as network close
+    /// handshakes or files that `fsync` when closed, are a common source of hidden latency.
+    ///
+    /// Only the teardown performed by the scope itself is measured, which the
+    /// [`profiling`](crate::profiling) module lists. Combinators such as
+    /// [`use_with`](Self::use_with) hand ownership of the resource to the body, which drops it
+    /// as part of the body. Since their teardown cannot be timed, they log a warning with the
+    /// `log` feature and panic in debug builds when a threshold is set.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::time::Duration;
+    /// use use_with::observer::{UseEvent, UseObserver};
+    /// use use_with::{Close, Use};
+    ///
+    /// struct Connection;
+    ///
+    /// impl Close for Connection {
+    ///     type Error = std::io::Error;
+    ///
+    ///     fn close(self) -> Result<(), Self::Error> {
+    ///         std::thread::sleep(Duration::from_millis(20));
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// struct SlowTeardownObserver;
+    ///
+    /// impl UseObserver for SlowTeardownObserver {
+    ///     fn on_slow_teardown(&self, event: &UseEvent, teardown: Duration) {
+    ///         eprintln!("closing {} at {} took {teardown:?}", event.resource_type(), event.location());
+    ///     }
+    /// }
+    ///
+    /// Connection
+    ///     .scoped()
+    ///     .observer(&SlowTeardownObserver)
+    ///     .slow_teardown(Duration::from_millis(5))
+    ///     .use_close(|_conn| ())
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn slow_teardown(mut self, threshold: Duration) -> Self {
+        self.options.slow_teardown = Some(threshold);
+        self
+    }
+
+    /// Reports a resource that is held for longer than `threshold`.
+    ///
+    /// Once the resource has been held, measured from its acquisition, for longer than the
+    /// threshold, a warning naming the call site is logged with the `log` feature and the
+    /// global observer is notified through [`UseObserver::on_long_hold`]. Both happen on a shared
+    /// watchdog thread while the resource is still held, so a stuck or deadlocked critical
+    /// section is reported even if it never ends. Observers attached to this scope are borrowed
+    /// by it and are notified after the resource has been closed or dropped, or the body
+    /// panicked, with the total hold time. This catches critical sections that accidentally grew
+    /// long in production, such as a lock or a pooled connection held across a slow request.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::sync::Mutex;
+    /// use std::time::Duration;
+    /// use use_with::observer::{UseEvent, UseObserver};
+    /// use use_with::Use;
+    ///
+    /// struct HoldTimeObserver;
+    ///
+    /// impl UseObserver for HoldTimeObserver {
+    ///     fn on_long_hold(&self, event: &UseEvent, held: Duration) {
+    ///         eprintln!("{} held `{}` for {held:?}", event.location(), event.resource_type());
+    ///     }
+    /// }
+    ///
+    /// let counter = Mutex::new(0);
+    /// counter
+    ///     .lock()
+    ///     .unwrap()
+    ///     .scoped()
+    ///     .observer(&HoldTimeObserver)
+    ///     .warn_if_held_longer_than(Duration::from_millis(100))
+    ///     .use_with(|mut count| *count += 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn warn_if_held_longer_than(mut self, threshold: Duration) -> Self {
+        self.options.long_hold = Some(threshold);
+        self
+    }
+
+    /// Executes a closure synchronously, consuming the resource.
+    ///
+    /// See [`Use::use_with`](crate::Use::use_with).
+    #[inline]
+    pub fn use_with<U, F: FnOnce(T) -> U>(self, f: F) -> U {
+        let mut probe = Probe::enter::<T>(self.options);
+        let resource = self.resource;
+        let result = probe.run(|| f(resource));
+        probe.body_end();
+        probe.handed_over();
+        result
+    }
+
+    /// Executes a closure synchronously, consuming the resource, without instantiating the scope
+    /// machinery for every closure type.
+    ///
+    /// See [`Use::use_with_dyn`](crate::Use::use_with_dyn).
+    pub fn use_with_dyn<U, F: FnOnce(T) -> U>(self, f: F) -> U {
+        let resource = self.resource;
+        let (options, local) = self.options.split_local();
+        let mut result = None;
+        use_with_erased(
+            options.with_local(&local),
+            core::any::type_name::<T>(),
+            Box::new(|| result = Some(f(resource))),
+        );
+        result.expect("the body ran to completion")
+    }
+
+    /// Executes a closure on the resource and hands the resource back afterwards.
+    ///
+    /// See [`Use::use_and_return`](crate::Use::use_and_return).
+    pub fn use_and_return<U, F: FnOnce(&mut T) -> U>(self, f: F) -> (U, T) {
+        let mut resource = self.resource;
+        let mut probe = Probe::enter::<T>(self.options);
+        let result = probe.run(|| f(&mut resource));
+        probe.body_end();
+        (result, resource)
+    }
+
+    /// Executes a closure on the resource and hands the resource back afterwards, passing it to
+    /// `retire` instead of dropping it if the closure panics.
+    #[cfg(feature = "std")]
+    pub(crate) fn use_and_return_or_retire<U, F, R>(self, f: F, retire: R) -> (U, T)
+    where
+        F: FnOnce(&mut T) -> U,
+        R: FnOnce(T),
+    {
+        let mut probe = Probe::enter::<T>(self.options);
+        let mut guard = RetireOnUnwind {
+            resource: Some(self.resource),
+            retire: Some(retire),
+        };
+        let result = probe.run(|| f(guard.resource.as_mut().expect("the resource is held")));
+        probe.body_end();
+        let resource = guard.resource.take().expect("the resource is held");
+        (result, resource)
+    }
+
+    /// Executes a closure that either consumes the resource or hands it back.
+    ///
+    /// See [`Use::use_with_flow`](crate::Use::use_with_flow).
+    pub fn use_with_flow<U, F: FnOnce(T) -> ControlFlow<U, T>>(self, f: F) -> ControlFlow<U, T> {
+        let mut probe = Probe::enter::<T>(self.options);
+        let resource = self.resource;
+        let flow = probe.run(|| f(resource));
+        probe.body_end();
+        if flow.is_break() {
+            probe.handed_over();
+        }
+        flow
+    }
+
+    /// Splits the resource, executes a closure on both halves and rejoins them afterwards.
+    ///
+    /// See [`Use::use_split`](crate::Use::use_split).
+    pub fn use_split<U, F>(self, f: F) -> U
+    where
+        T: Split,
+        F: FnOnce(&mut T::ReadHalf, &mut T::WriteHalf) -> U,
+    {
+        let mut probe = Probe::enter::<T>(self.options);
+        let (mut read, mut write) = self.resource.split();
+        let result = probe.run(|| f(&mut read, &mut write));
+        probe.body_end();
+        drop(T::unsplit(read, write));
+        probe.released();
+        result
+    }
+
+    /// Executes a closure on two disjoint parts of the resource, dropping it afterwards.
+    ///
+    /// See [`Use::use_disjoint`](crate::Use::use_disjoint).
+    pub fn use_disjoint<A, B, U, P, F>(self, project: P, f: F) -> U
+    where
+        A: ?Sized,
+        B: ?Sized,
+        P: for<'a> FnOnce(&'a mut T) -> (&'a mut A, &'a mut B),
+        F: FnOnce(&mut A, &mut B) -> U,
+    {
+        let mut resource = self.resource;
+        let mut probe = Probe::enter::<T>(self.options);
+        let result = probe.run(|| {
+            let (a, b) = project(&mut resource);
+            f(a, b)
+        });
+        probe.body_end();
+        drop(resource);
+        probe.released();
+        result
+    }
+
+    /// Converts the resource into `V` and executes a closure on the converted resource.
+    ///
+    /// See [`Use::use_into`](crate::Use::use_into).
+    pub fn use_into<V, U>(self, f: impl FnOnce(V) -> U) -> U
+    where
+        T: Into<V>,
+    {
+        UseScope {
+            resource: self.resource.into(),
+            options: self.options,
+        }
+        .use_with(f)
+    }
+
+    /// Tries to convert the resource into `V` and executes a closure on the converted resource.
+    ///
+    /// See [`Use::try_use_into`](crate::Use::try_use_into).
+    pub fn try_use_into<V, U>(self, f: impl FnOnce(V) -> U) -> Result<U, <T as TryInto<V>>::Error>
+    where
+        T: TryInto<V>,
+    {
+        let resource = self.resource.try_into()?;
+        Ok(UseScope {
+            resource,
+            options: self.options,
+        }
+        .use_with(f))
+    }
+
+    /// Executes a fallible closure on the resource, attaching a snapshot of the resource to errors.
+    ///
+    /// See [`Use::try_use_with_snapshot`](crate::Use::try_use_with_snapshot).
+    pub fn try_use_with_snapshot<U, E, F>(self, f: F) -> Result<U, WithSnapshot<E>>
+    where
+        T: fmt::Debug,
+        F: FnOnce(&mut T) -> Result<U, E>,
+    {
+        let mut resource = self.resource;
+        let mut probe = Probe::enter::<T>(self.options);
+        let result = probe
+            .run(|| f(&mut resource))
+            .map_err(|error| WithSnapshot::new(error, format!("{resource:?}")));
+        probe.body_end();
+        drop(resource);
+        probe.released();
+        result
+    }
+
+    /// Executes a closure synchronously, consuming the resource and catching any panic.
+    ///
+    /// See [`Use::use_with_catch_unwind`](crate::Use::use_with_catch_unwind).
+    #[cfg(feature = "std")]
+    pub fn use_with_catch_unwind<U, F>(self, f: F) -> Result<U, PanicPayload>
+    where
+        T: UnwindSafe,
+        F: FnOnce(T) -> U + UnwindSafe,
+    {
+        self.assert_unwind_safe_use(f)
+    }
+
+    /// Executes a closure synchronously, consuming the resource and catching any panic, without
+    /// requiring the closure or the resource to be unwind safe.
+    ///
+    /// See [`Use::assert_unwind_safe_use`](crate::Use::assert_unwind_safe_use).
+    #[cfg(feature = "std")]
+    pub fn assert_unwind_safe_use<U, F>(self, f: F) -> Result<U, PanicPayload>
+    where
+        F: FnOnce(T) -> U,
+    {
+        let mut probe = Probe::enter::<T>(self.options);
+        let resource = self.resource;
+        match probe.run(|| panic::catch_unwind(AssertUnwindSafe(move || f(resource)))) {
+            Ok(result) => {
+                probe.body_end();
+                probe.handed_over();
+                Ok(result)
+            }
+            Err(payload) => {
+                probe.panicked();
+                Err(payload)
+            }
+        }
+    }
+
+    /// Executes a closure on the resource, aborting the process if the closure panics.
+    ///
+    /// See [`Use::use_critical`](crate::Use::use_critical).
+    #[cfg(feature = "std")]
+    pub fn use_critical<U, F: FnOnce(&mut T) -> U>(self, f: F) -> U {
+        let mut resource = self.resource;
+        let location = self.options.location;
+        let mut probe = Probe::enter::<T>(self.options);
+        let critical = AbortOnUnwind {
+            probe: &mut probe,
+            resource_type: core::any::type_name::<T>(),
+            location,
+        };
+        let result = critical.probe.run(|| f(&mut resource));
+        std::mem::forget(critical);
+        probe.body_end();
+        drop(resource);
+        probe.released();
+        result
+    }
+
+    /// Executes a closure on a resource that cannot escape the closure, dropping it afterwards.
+    ///
+    /// See [`Use::use_sealed`](crate::Use::use_sealed).
+    pub fn use_sealed<U, F>(self, f: F) -> U
+    where
+        F: for<'brand> FnOnce(Sealed<'brand, T>) -> U,
+    {
+        let mut resource = self.resource;
+        let mut probe = Probe::enter::<T>(self.options);
+        let result = probe.run(|| f(Sealed::new(&mut resource)));
+        probe.body_end();
+        drop(resource);
+        probe.released();
+        result
+    }
+
+    /// Executes a closure on the pinned resource, dropping it afterwards.
+    ///
+    /// See [`Use::use_with_pinned`](crate::Use::use_with_pinned).
+    pub fn use_with_pinned<U, F: FnOnce(Pin<&mut T>) -> U>(self, f: F) -> U {
+        let mut probe = Probe::enter::<T>(self.options);
+        let result = {
+            let resource = pin!(self.resource);
+            let result = probe.run(|| f(resource));
+            probe.body_end();
+            result
+        };
+        probe.released();
+        result
+    }
+
+    /// Executes an asynchronous closure, consuming the resource.
+    ///
+    /// See [`Use::use_with_async`](crate::Use::use_with_async).
+    #[inline]
+    pub fn use_with_async<F, Fut, U>(self, f: F) -> UseWithAsync<'o, Fut, O>
+    where
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = U>,
+    {
+        UseWithAsync {
+            scope: ScopeFuture::enter(self.resource, f, self.options),
+        }
+    }
+
+    /// Executes an asynchronous closure, consuming the resource, without instantiating the scope
+    /// machinery for every closure type.
+    ///
+    /// See [`Use::use_with_async_dyn`](crate::Use::use_with_async_dyn).
+    pub async fn use_with_async_dyn<F, Fut, U>(self, f: F) -> U
+    where
+        T: Send,
+        F: FnOnce(T) -> Fut + Send,
+        Fut: Future<Output = U> + Send,
+        U: Send,
+    {
+        let resource = self.resource;
+        let (options, local) = self.options.split_local();
+        let mut result = None;
+        let body = Box::pin(async {
+            result = Some(f(resource).await);
+        });
+        let options = options.with_local::<&dyn UseObserver>(&local);
+        use_with_async_erased(options, core::any::type_name::<T>(), body).await;
+        result.expect("the body ran to completion")
+    }
+
+    /// Executes an asynchronous closure, consuming the resource and catching any panic.
+    ///
+    /// See [`Use::use_with_async_catch_unwind`](crate::Use::use_with_async_catch_unwind).
+    #[cfg(feature = "std")]
+    pub async fn use_with_async_catch_unwind<F, Fut, U>(self, f: F) -> Result<U, PanicPayload>
+    where
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = U>,
+    {
+        let mut probe = Probe::enter::<T>(self.options);
+        let resource = self.resource;
+        let body = pin!(catch_unwind(|| f(resource)));
+        match probe.run_async(body).await {
+            Ok(result) => {
+                probe.body_end();
+                probe.handed_over();
+                Ok(result)
+            }
+            Err(payload) => {
+                probe.panicked();
+                Err(payload)
+            }
+        }
+    }
+
+    /// Executes a closure synchronously, consuming the resource, while calling `heartbeat` at a
+    /// fixed interval.
+    ///
+    /// See [`Use::use_with_heartbeat`](crate::Use::use_with_heartbeat).
+    #[cfg(feature = "std")]
+    pub fn use_with_heartbeat<U, H, F>(self, interval: Duration, heartbeat: H, f: F) -> U
+    where
+        H: FnMut() + Send,
+        F: FnOnce(T) -> U,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            let beating = scope.spawn(move || {
+                let mut heartbeat = heartbeat;
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    heartbeat();
+                }
+            });
+            let result = {
+                // Disconnecting the channel stops the heartbeat, also when the body panics.
+                let _stop = stop;
+                self.use_with(f)
+            };
+            // Joining explicitly resumes the heartbeat's own panic instead of a generic one.
+            if let Err(payload) = beating.join() {
+                panic::resume_unwind(payload);
+            }
+            result
+        })
+    }
+
+    /// Executes an asynchronous closure, consuming the resource, while calling `heartbeat` at a
+    /// fixed interval.
+    ///
+    /// See [`Use::use_with_heartbeat_async`](crate::Use::use_with_heartbeat_async).
+    #[cfg(feature = "tokio")]
+    pub async fn use_with_heartbeat_async<F, Fut, U, H>(
+        self,
+        interval: Duration,
+        mut heartbeat: H,
+        f: F,
+    ) -> U
+    where
+        H: FnMut(),
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = U>,
+    {
+        use ::tokio::time::{interval_at, Instant, MissedTickBehavior};
+        use std::task::Poll;
+
+        let mut ticks = interval_at(Instant::now() + interval, interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut body = pin!(self.use_with_async(f));
+        std::future::poll_fn(|cx| {
+            if let Poll::Ready(result) = body.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+            while ticks.poll_tick(cx).is_ready() {
+                heartbeat();
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Executes a long-running asynchronous closure on the resource, yielding to the executor
+    /// at the checkpoints marked by the closure once their budget is exhausted.
+    ///
+    /// See [`Use::use_with_yielding`](crate::Use::use_with_yielding).
+    pub async fn use_with_yielding<U, F>(self, budget: u32, f: F) -> U
+    where
+        F: for<'a> FnOnce(&'a mut T, &'a mut Yielder) -> BoxFuture<'a, U>,
+    {
+        let mut probe = Probe::enter::<T>(self.options);
+        let mut resource = self.resource;
+        let mut yielder = Yielder::new(budget);
+        let result = probe
+            .run_async(f(&mut resource, &mut yielder).as_mut())
+            .await;
+        probe.body_end();
+        drop(resource);
+        probe.released();
+        result
+    }
+
+    /// Executes an asynchronous closure on the pinned resource, dropping it afterwards.
+    ///
+    /// See [`Use::use_with_pinned_async`](crate::Use::use_with_pinned_async).
+    pub async fn use_with_pinned_async<U, F>(self, f: F) -> U
+    where
+        F: for<'a> FnOnce(Pin<&'a mut T>) -> BoxFuture<'a, U>,
+    {
+        let mut probe = Probe::enter::<T>(self.options);
+        let result = {
+            let resource = pin!(self.resource);
+            let body = pin!(f(resource));
+            let result = probe.run_async(body).await;
+            probe.body_end();
+            result
+        };
+        probe.released();
+        result
+    }
+
+    /// Executes a closure on the resource and explicitly closes it afterwards.
+    ///
+    /// See [`Use::use_close`](crate::Use::use_close).
+    pub fn use_close<U, F: FnOnce(&mut T) -> U>(self, f: F) -> Result<U, T::Error>
+    where
+        T: Close,
+    {
+        let mut resource = self.resource;
+        let mut probe = Probe::enter::<T>(self.options);
+        // The resource is closed even if the body panics, so its state is not asserted to be
+        // consistent; closing must cope with an interrupted body.
+        match probe.run(|| panic::catch_unwind(AssertUnwindSafe(|| f(&mut resource)))) {
+            Ok(result) => {
+                probe.body_end();
+                let closed = resource.close();
+                probe.closed(closed.is_ok());
+                closed.map(|()| result)
+            }
+            Err(payload) => {
+                probe.panicked();
+                match panic::catch_unwind(AssertUnwindSafe(|| resource.close())) {
+                    Ok(closed) => probe.closed(closed.is_ok()),
+                    Err(_suppressed) => probe.close_panicked(),
+                }
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+
+    /// Enters the resource, executes a fallible closure on its guard and exits it with the outcome.
+    ///
+    /// See [`Use::use_context`](crate::Use::use_context).
+    pub fn use_context<U, E, F>(self, f: F) -> Result<U, E>
+    where
+        T: Enter,
+        F: FnOnce(&mut T::Guard) -> Result<U, E>,
+    {
+        let mut probe = Probe::enter::<T>(self.options);
+        let mut guard = self.resource.enter();
+        match probe.run(|| panic::catch_unwind(AssertUnwindSafe(|| f(&mut guard)))) {
+            Ok(result) => {
+                probe.body_end();
+                guard.exit(match result {
+                    Ok(_) => &Outcome::Success,
+                    Err(_) => &Outcome::Error,
+                });
+                probe.released();
+                result
+            }
+            Err(payload) => {
+                probe.panicked();
+                // A panic while exiting is suppressed in favor of the body's panic.
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| guard.exit(&Outcome::Panic)));
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+
+    /// Executes an asynchronous closure on the resource and explicitly closes it afterwards.
+    ///
+    /// See [`Use::use_close_async`](crate::Use::use_close_async).
+    pub async fn use_close_async<U, F>(self, f: F) -> Result<U, T::Error>
+    where
+        T: AsyncClose,
+        F: for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, U>,
+    {
+        use_close_async(self.resource, self.options, f).await
+    }
+}
+
+pin_project! {
+    /// The future returned by [`UseScope::use_with_async`].
+    ///
+    /// The closure is called and the scope entered when the future is created, so the future
+    /// holds the future returned by the closure, the statically dispatched observer, and a
+    /// pointer to the state of the scope for the case that anything observes it.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct UseWithAsync<'o, Fut, O: UseObserver = NoopObserver> {
+        #[pin]
+        scope: ScopeFuture<'o, Fut, O>,
+    }
+}
+
+impl<Fut: Future, O: UseObserver> Future for UseWithAsync<'_, Fut, O> {
+    type Output = Fut::Output;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().scope.poll(cx)
+    }
+}
+
+pin_project! {
+    /// An asynchronous use scope whose body has been created.
+    ///
+    /// Shared by [`UseWithAsync`] and [`Use::use_with_async`](crate::Use::use_with_async), whose
+    /// scopes have no options and thus need no state at all in builds without global consumers
+    /// of scope events.
+    pub(crate) struct ScopeFuture<'o, Fut, O: UseObserver = NoopObserver, S: Slot<'o> = Tracking<'o>> {
+        // The body is declared first, so that a cancelled body drops the resource before the
+        // scope is left.
+        #[pin]
+        body: Fut,
+        probe: Probe<'o, O, S>,
+    }
+}
+
+impl<'o, Fut: Future, O: UseObserver, S: Slot<'o>> ScopeFuture<'o, Fut, O, S> {
+    /// Enters the scope and creates its body by calling `f` with the resource.
+    #[inline(always)]
+    pub(crate) fn enter<T, F>(resource: T, f: F, options: ScopeOptions<'o, O>) -> Self
+    where
+        F: FnOnce(T) -> Fut,
+    {
+        let probe = Probe::new(options, core::any::type_name::<T>());
+        let body = probe.run(|| f(resource));
+        Self { body, probe }
+    }
+}
+
+impl<'o, Fut: Future, O: UseObserver, S: Slot<'o>> Future for ScopeFuture<'o, Fut, O, S> {
+    type Output = Fut::Output;
+
+    /// Drives the body to completion and leaves the scope.
+    ///
+    /// Polling the future again after it completed polls the body again, which panics for the
+    /// futures of `async` blocks and functions, but is no longer reported to observers.
+    #[inline]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.probe.poll_body(this.body, cx));
+        this.probe.body_end();
+        this.probe.handed_over();
+        this.probe.leave();
+        Poll::Ready(result)
+    }
+}
+
+/// Retires the resource of a scope whose body panicked, instead of dropping it.
+#[cfg(feature = "std")]
+struct RetireOnUnwind<T, R: FnOnce(T)> {
+    resource: Option<T>,
+    retire: Option<R>,
+}
+
+#[cfg(feature = "std")]
+impl<T, R: FnOnce(T)> Drop for RetireOnUnwind<T, R> {
+    fn drop(&mut self) {
+        if let (Some(resource), Some(retire)) = (self.resource.take(), self.retire.take()) {
+            // A second panic would abort the process.
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| retire(resource)));
+        }
+    }
+}
+
+/// Aborts the process if dropped during unwinding, before the resource can be dropped.
+#[cfg(feature = "std")]
+struct AbortOnUnwind<'p, 'o, O: UseObserver> {
+    probe: &'p mut Probe<'o, O>,
+    resource_type: &'static str,
+    location: &'static core::panic::Location<'static>,
+}
+
+#[cfg(feature = "std")]
+impl<O: UseObserver> Drop for AbortOnUnwind<'_, '_, O> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.probe.panicked();
+            let id = self
+                .probe
+                .event()
+                .map(|event| format!(" {}", event.id()))
+                .unwrap_or_default();
+            eprintln!(
+                "use scope{id} for `{}` at {} panicked in a critical section, aborting",
+                self.resource_type, self.location
+            );
+            std::process::abort();
+        }
+    }
+}
+
+/// Runs a use scope around a type-erased body.
+///
+/// The non-generic core of [`UseScope::use_with_dyn`], which is compiled once instead of once per
+/// closure type.
+#[inline(never)]
+fn use_with_erased(
+    options: ScopeOptions<'_, &dyn UseObserver>,
+    type_name: &'static str,
+    body: Box<dyn FnOnce() + '_>,
+) {
+    let mut probe = Probe::enter_named(options, type_name);
+    probe.run(body);
+    probe.body_end();
+    probe.handed_over();
+}
+
+/// Runs an asynchronous use scope around a type-erased body.
+///
+/// The non-generic core of [`UseScope::use_with_async_dyn`].
+async fn use_with_async_erased(
+    options: ScopeOptions<'_, &dyn UseObserver>,
+    type_name: &'static str,
+    mut body: BoxFuture<'_, ()>,
+) {
+    let mut probe = Probe::enter_named(options, type_name);
+    probe.run_async(body.as_mut()).await;
+    probe.body_end();
+    probe.handed_over();
+}
+
+/// Runs an asynchronous use scope that closes its resource explicitly.
+///
+/// The resource is closed even if the body panics, after which the panic resumes.
+pub(crate) async fn use_close_async<T, O, F, U>(
+    resource: T,
+    options: ScopeOptions<'_, O>,
+    f: F,
+) -> Result<U, T::Error>
+where
+    T: AsyncClose,
+    O: UseObserver,
+    F: for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, U>,
+{
+    match use_close_async_catch_unwind(resource, options, f).await {
+        Ok(result) => Ok(result),
+        Err(UnwindError::Close(error)) => Err(error),
+        Err(UnwindError::Panic { payload, .. }) => panic::resume_unwind(payload),
+    }
+}
+
+/// Runs an asynchronous use scope that catches panics of its body and closes its resource
+/// explicitly, even after a panic.
+pub(crate) async fn use_close_async_catch_unwind<T, O, F, U>(
+    mut resource: T,
+    options: ScopeOptions<'_, O>,
+    f: F,
+) -> Result<U, UnwindError<T::Error>>
+where
+    T: AsyncClose,
+    O: UseObserver,
+    F: for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, U>,
+{
+    let mut probe = Probe::enter::<T>(options);
+    let outcome = {
+        let body = pin!(catch_unwind(|| f(&mut resource)));
+        probe.run_async(body).await
+    };
+    match outcome {
+        Ok(result) => {
+            probe.body_end();
+            let closed = probe.run_async(pin!(resource.close_async())).await;
+            probe.closed(closed.is_ok());
+            closed.map(|()| result).map_err(UnwindError::Close)
+        }
+        Err(payload) => {
+            probe.panicked();
+            let close = match probe
+                .run_async(pin!(catch_unwind(|| resource.close_async())))
+                .await
+            {
+                Ok(closed) => {
+                    probe.closed(closed.is_ok());
+                    Some(closed)
+                }
+                Err(_suppressed) => {
+                    probe.close_panicked();
+                    None
+                }
+            };
+            Err(UnwindError::Panic { payload, close })
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Resources branded with the lifetime of their use scope.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+/// A resource that is sealed into the use scope it was lent to.
+///
+/// Passed to the closure of [`Use::use_sealed`](crate::Use::use_sealed). The `'brand` lifetime
+/// is invariant and chosen anew for every scope, so the closure can neither return the
+/// wrapper nor any reference obtained from it, nor store either of them outside the scope.
+/// The compiler checks this for the wrapper and for references, which turns the convention of
+/// not letting them escape the scope into a guarantee.
+///
+/// ```compile_fail
+/// use use_with::Use;
+///
+/// let leaked = String::from("secret").use_sealed(|sealed| sealed.into_mut());
+/// ```
+///
+/// The guarantee does not extend to the contents of the resource. Through [`DerefMut`], the
+/// closure can move them out by value, e.g. with [`mem::take`](core::mem::take),
+/// [`mem::replace`](core::mem::replace) or [`mem::swap`](core::mem::swap), and return or store
+/// them like any other owned value. Resources whose contents must not escape either should not
+/// be lent as `Sealed`, or should not allow replacing their contents through `&mut T`.
+///
+/// ```rust
+/// use use_with::Use;
+///
+/// let taken = String::from("secret").use_sealed(|mut sealed| std::mem::take(&mut *sealed));
+/// assert_eq!(taken, "secret");
+/// ```
+pub struct Sealed<'brand, T> {
+    resource: &'brand mut T,
+    _brand: PhantomData<fn(&'brand ()) -> &'brand ()>,
+}
+
+impl<'brand, T> Sealed<'brand, T> {
+    pub(crate) fn new(resource: &'brand mut T) -> Self {
+        Self {
+            resource,
+            _brand: PhantomData,
+        }
+    }
+
+    /// Converts the wrapper into a reference branded with the scope's lifetime.
+    ///
+    /// The reference is just as confined to the scope as the wrapper itself.
+    pub fn into_mut(self) -> &'brand mut T {
+        self.resource
+    }
+}
+
+impl<T> Deref for Sealed<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.resource
+    }
+}
+
+impl<T> DerefMut for Sealed<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.resource
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Sealed<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Sealed").field(&self.resource).finish()
+    }
+}
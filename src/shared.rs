@@ -0,0 +1,753 @@
+//! Resources shared by concurrent use scopes and torn down by the last of them.
+
+use crate::instrument::{Probe, ScopeOptions};
+use crate::sync::{Arc, Condvar, Mutex, MutexGuard};
+use crate::{AsyncClose, BoxFuture, Close, UseScope};
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+
+/// A resource that is used by concurrent scopes and dropped once the last of them has finished.
+///
+/// `SharedUse` is the primary handle to the resource. Every use scope keeps the resource alive
+/// while it runs, so the resource is dropped only when the primary handle has been dropped and
+/// the last active scope has exited, whichever happens last. The futures returned by
+/// [`use_read_async`](Self::use_read_async) and [`use_write_async`](Self::use_write_async) do not
+/// borrow the handle and can be spawned as tasks. [`closed`](Self::closed) returns a signal that
+/// completes once the resource has been dropped. Resources implementing [`Close`] or
+/// [`AsyncClose`] are closed instead by releasing the primary handle through
+/// [`close`](Self::close) or [`close_async`](Self::close_async), which wait for the scopes to
+/// finish and return the result of closing the resource.
+///
+/// Access follows the rules of a reader-writer lock: read scopes run concurrently, while write
+/// scopes wait for all active read scopes to exit and then get exclusive access. Waiting writers
+/// take precedence over new readers, so that occasional writes are not starved by a steady
+/// stream of reads. A scope that waits for access to a resource that its own thread or task
+/// already uses deadlocks.
+///
+/// The methods are not named `use_with` and `use_with_async`, since the blanket implementation
+/// of [`Use`](crate::Use) would take precedence and consume the handle.
+///
+/// # Examples
+/// ```rust
+/// use use_with::SharedUse;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let shared = SharedUse::new(vec![String::from("debug")]);
+///
+/// let tasks: Vec<_> = (0..3)
+///     .map(|_| {
+///         tokio::spawn(shared.use_read_async(|config| {
+///             Box::pin(async move {
+///                 tokio::task::yield_now().await;
+///                 config.len()
+///             })
+///         }))
+///     })
+///     .collect();
+///
+/// shared
+///     .use_write_async(|config| Box::pin(async move { config.push(String::from("trace")) }))
+///     .await;
+///
+/// let closed = shared.closed();
+/// drop(shared);
+/// for task in tasks {
+///     let len = task.await.unwrap();
+///     assert!(len == 1 || len == 2);
+/// }
+///
+/// // The last task dropped the configuration.
+/// closed.await;
+/// # }
+/// ```
+pub struct SharedUse<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The shared state of a [`SharedUse`].
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// Notifies threads blocked in synchronous scopes about released access.
+    released: Condvar,
+    closed: Arc<Signal>,
+}
+
+/// The access state of a [`SharedUse`].
+struct State<T> {
+    /// The resource, or `None` while a write scope holds it.
+    resource: Option<Arc<Entry<T>>>,
+    /// The number of write scopes waiting for or holding the resource.
+    writers: usize,
+    /// The number of scopes that have been started and not yet finished, including waiting ones.
+    scopes: usize,
+    /// The tasks waiting for access to the resource.
+    wakers: Vec<Waker>,
+}
+
+/// The resource of a [`SharedUse`], which sets the closed signal when dropped.
+struct Entry<T> {
+    value: T,
+    // Declared after the value, so that it is dropped after it.
+    _closed: NotifyOnDrop,
+}
+
+impl<T> Entry<T> {
+    /// Takes the value out of an entry that no scope references anymore, together with the
+    /// notification that sets the closed signal.
+    fn unwrap(entry: Arc<Self>) -> (T, NotifyOnDrop) {
+        let Self { value, _closed } = Arc::try_unwrap(entry)
+            .unwrap_or_else(|_| unreachable!("finished scopes hold no reference to the resource"));
+        (value, _closed)
+    }
+}
+
+/// Grants access to the resource, or returns `None` if the scope has to wait.
+type Acquire<T> = fn(&mut State<T>) -> Option<Arc<Entry<T>>>;
+
+impl<T> SharedUse<T> {
+    /// Wraps a resource to be shared by concurrent use scopes.
+    pub fn new(value: T) -> Self {
+        let closed = Arc::new(Signal::default());
+        let entry = Entry {
+            value,
+            _closed: NotifyOnDrop(Arc::clone(&closed)),
+        };
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    resource: Some(Arc::new(entry)),
+                    writers: 0,
+                    scopes: 0,
+                    wakers: Vec::new(),
+                }),
+                released: Condvar::new(),
+                closed,
+            }),
+        }
+    }
+
+    /// Executes a closure with shared access to the resource, blocking while a write scope is
+    /// active or waiting.
+    ///
+    /// The resource is kept alive until the closure returns, even if the primary handle is
+    /// dropped by another thread in the meantime.
+    #[track_caller]
+    pub fn use_read<U, F: FnOnce(&T) -> U>(&self, f: F) -> U {
+        let options = ScopeOptions::new();
+        let _started = Started::register(Arc::clone(&self.shared));
+        let guard = ReadGuard {
+            entry: Some(self.shared.wait(State::read)),
+            shared: Arc::clone(&self.shared),
+        };
+
+        let mut probe = Probe::enter::<T>(options);
+        let result = probe.run(|| f(guard.value()));
+        probe.body_end();
+        drop(guard);
+        probe.released();
+        result
+    }
+
+    /// Executes a closure with exclusive access to the resource, blocking until all other scopes
+    /// have exited.
+    ///
+    /// The resource is kept alive until the closure returns, even if the primary handle is
+    /// dropped by another thread in the meantime.
+    #[track_caller]
+    pub fn use_write<U, F: FnOnce(&mut T) -> U>(&self, f: F) -> U {
+        let options = ScopeOptions::new();
+        let _started = Started::register(Arc::clone(&self.shared));
+        let mut guard = WriteGuard::register(Arc::clone(&self.shared));
+        guard.entry = Some(self.shared.wait(State::write));
+
+        let mut probe = Probe::enter::<T>(options);
+        let result = probe.run(|| f(guard.value_mut()));
+        probe.body_end();
+        drop(guard);
+        probe.released();
+        result
+    }
+
+    /// Executes an asynchronous closure with shared access to the resource, waiting while a write
+    /// scope is active or waiting.
+    ///
+    /// The returned future does not borrow the handle. The resource is kept alive until the
+    /// future completes or is dropped, even if the primary handle is dropped in the meantime.
+    #[track_caller]
+    pub fn use_read_async<U, F>(&self, f: F) -> impl Future<Output = U> + Send + 'static
+    where
+        T: Send + Sync + 'static,
+        F: for<'a> FnOnce(&'a T) -> BoxFuture<'a, U> + Send + 'static,
+        U: Send + 'static,
+    {
+        let shared = Arc::clone(&self.shared);
+        let options = ScopeOptions::new();
+        let started = Started::register(Arc::clone(&shared));
+        async move {
+            let _started = started;
+            let entry = poll_fn(|cx| shared.poll(cx, State::read)).await;
+            let guard = ReadGuard {
+                shared,
+                entry: Some(entry),
+            };
+
+            let mut probe = Probe::enter::<T>(options);
+            let result = probe.run_async(pin!(f(guard.value()))).await;
+            probe.body_end();
+            drop(guard);
+            probe.released();
+            result
+        }
+    }
+
+    /// Executes an asynchronous closure with exclusive access to the resource, waiting until all
+    /// other scopes have exited.
+    ///
+    /// The returned future does not borrow the handle. The resource is kept alive until the
+    /// future completes or is dropped, even if the primary handle is dropped in the meantime.
+    #[track_caller]
+    pub fn use_write_async<U, F>(&self, f: F) -> impl Future<Output = U> + Send + 'static
+    where
+        T: Send + Sync + 'static,
+        F: for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, U> + Send + 'static,
+        U: Send + 'static,
+    {
+        let shared = Arc::clone(&self.shared);
+        let options = ScopeOptions::new();
+        let started = Started::register(Arc::clone(&shared));
+        async move {
+            let _started = started;
+            // Registered before waiting, so that new read scopes queue up behind this one.
+            let mut guard = WriteGuard::register(shared);
+            let entry = poll_fn(|cx| guard.shared.poll(cx, State::write)).await;
+            guard.entry = Some(entry);
+
+            let mut probe = Probe::enter::<T>(options);
+            let result = probe.run_async(pin!(f(guard.value_mut()))).await;
+            probe.body_end();
+            drop(guard);
+            probe.released();
+            result
+        }
+    }
+
+    /// Returns a signal that completes once the resource has been dropped or closed.
+    pub fn closed(&self) -> ClosedSignal {
+        ClosedSignal {
+            signal: Arc::clone(&self.shared.closed),
+        }
+    }
+
+    /// Releases the primary handle and closes the resource once all scopes have finished,
+    /// blocking until then.
+    ///
+    /// Scopes that were started through the handle and still wait for access run before the
+    /// resource is closed. Returns the result of [`Close::close`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::{Close, SharedUse};
+    ///
+    /// struct Log(Vec<&'static str>);
+    ///
+    /// impl Close for Log {
+    ///     type Error = String;
+    ///
+    ///     fn close(self) -> Result<(), Self::Error> {
+    ///         Err(format!("lost {} lines", self.0.len()))
+    ///     }
+    /// }
+    ///
+    /// let shared = SharedUse::new(Log(Vec::new()));
+    /// std::thread::scope(|scope| {
+    ///     for line in ["first", "second"] {
+    ///         let shared = &shared;
+    ///         scope.spawn(move || shared.use_write(|log| log.0.push(line)));
+    ///     }
+    /// });
+    ///
+    /// // Reports the failure to persist the log instead of dropping it silently.
+    /// assert_eq!(shared.close(), Err(String::from("lost 2 lines")));
+    /// ```
+    #[track_caller]
+    pub fn close(self) -> Result<(), T::Error>
+    where
+        T: Close,
+    {
+        let options = ScopeOptions::new();
+        let (value, closed) = Entry::unwrap(self.shared.wait(State::unused));
+        let result = UseScope::with_options(value, options).use_close(|_| ());
+        drop(closed);
+        result
+    }
+
+    /// Releases the primary handle and closes the resource asynchronously once all scopes have
+    /// finished.
+    ///
+    /// The asynchronous counterpart of [`close`](Self::close), which waits for the scopes without
+    /// blocking and returns the result of [`AsyncClose::close_async`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::{AsyncClose, SharedUse};
+    ///
+    /// struct Session;
+    ///
+    /// impl AsyncClose for Session {
+    ///     type Error = &'static str;
+    ///
+    ///     async fn close_async(self) -> Result<(), Self::Error> {
+    ///         Err("logout failed")
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let shared = SharedUse::new(Session);
+    /// let task = tokio::spawn(shared.use_read_async(|_session| Box::pin(async { 42 })));
+    ///
+    /// assert_eq!(shared.close_async().await, Err("logout failed"));
+    /// assert_eq!(task.await.unwrap(), 42);
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn close_async(self) -> impl Future<Output = Result<(), T::Error>> + Send + 'static
+    where
+        T: AsyncClose + Send + Sync + 'static,
+    {
+        let shared = self.shared;
+        let options = ScopeOptions::new();
+        async move {
+            let entry = poll_fn(|cx| shared.poll(cx, State::unused)).await;
+            let (value, closed) = Entry::unwrap(entry);
+            let result = UseScope::with_options(value, options)
+                .use_close_async(|_| Box::pin(async {}))
+                .await;
+            drop(closed);
+            result
+        }
+    }
+}
+
+impl<T> fmt::Debug for SharedUse<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("SharedUse")
+            .field("writing", &state.resource.is_none())
+            .field("writers", &state.writers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().expect("shared state lock poisoned")
+    }
+
+    /// Blocks until `acquire` grants access to the resource.
+    fn wait(&self, acquire: Acquire<T>) -> Arc<Entry<T>> {
+        let mut state = self.lock();
+        loop {
+            if let Some(entry) = acquire(&mut state) {
+                return entry;
+            }
+            state = self
+                .released
+                .wait(state)
+                .expect("shared state lock poisoned");
+        }
+    }
+
+    /// Polls whether `acquire` grants access to the resource, registering the task otherwise.
+    fn poll(&self, cx: &mut Context<'_>, acquire: Acquire<T>) -> Poll<Arc<Entry<T>>> {
+        let mut state = self.lock();
+        match acquire(&mut state) {
+            Some(entry) => Poll::Ready(entry),
+            None => {
+                if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    state.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Wakes all scopes waiting for access, which check again whether they can proceed.
+    fn notify(&self, mut state: MutexGuard<'_, State<T>>) {
+        let wakers = std::mem::take(&mut state.wakers);
+        drop(state);
+        self.released.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl<T> State<T> {
+    /// Grants shared access unless a write scope is active or waiting.
+    fn read(&mut self) -> Option<Arc<Entry<T>>> {
+        match self.writers {
+            0 => self.resource.clone(),
+            _ => None,
+        }
+    }
+
+    /// Hands out the resource for closing once all scopes have finished.
+    fn unused(&mut self) -> Option<Arc<Entry<T>>> {
+        match self.scopes {
+            0 => self.resource.take(),
+            _ => None,
+        }
+    }
+
+    /// Grants exclusive access once no other scope holds the resource.
+    fn write(&mut self) -> Option<Arc<Entry<T>>> {
+        match &self.resource {
+            Some(entry) if Arc::strong_count(entry) == 1 => self.resource.take(),
+            _ => None,
+        }
+    }
+}
+
+/// A scope that has been started on a [`SharedUse`] and has not finished yet.
+///
+/// Dropped after the access guard of the scope, so that a finished scope no longer references
+/// the resource.
+struct Started<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Started<T> {
+    fn register(shared: Arc<Shared<T>>) -> Self {
+        shared.lock().scopes += 1;
+        Self { shared }
+    }
+}
+
+impl<T> Drop for Started<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.scopes -= 1;
+        self.shared.notify(state);
+    }
+}
+
+/// Shared access to the resource of a [`SharedUse`], released when dropped.
+struct ReadGuard<T> {
+    shared: Arc<Shared<T>>,
+    entry: Option<Arc<Entry<T>>>,
+}
+
+impl<T> ReadGuard<T> {
+    fn value(&self) -> &T {
+        &self
+            .entry
+            .as_ref()
+            .expect("entry is present until dropped")
+            .value
+    }
+}
+
+impl<T> Drop for ReadGuard<T> {
+    fn drop(&mut self) {
+        let state = self.shared.lock();
+        // Released under the lock, so that writers observe the updated reference count.
+        drop(self.entry.take());
+        self.shared.notify(state);
+    }
+}
+
+/// Exclusive access to the resource of a [`SharedUse`], or a registered wait for it.
+struct WriteGuard<T> {
+    shared: Arc<Shared<T>>,
+    entry: Option<Arc<Entry<T>>>,
+}
+
+impl<T> WriteGuard<T> {
+    fn register(shared: Arc<Shared<T>>) -> Self {
+        shared.lock().writers += 1;
+        Self {
+            shared,
+            entry: None,
+        }
+    }
+
+    fn value_mut(&mut self) -> &mut T {
+        let entry = self.entry.as_mut().expect("entry is present once granted");
+        &mut Arc::get_mut(entry)
+            .expect("write scopes hold the only reference")
+            .value
+    }
+}
+
+impl<T> Drop for WriteGuard<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.writers -= 1;
+        if let Some(entry) = self.entry.take() {
+            state.resource = Some(entry);
+        }
+        self.shared.notify(state);
+    }
+}
+
+/// A future that completes once the resource of a [`SharedUse`] has been dropped.
+///
+/// Created by [`SharedUse::closed`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct ClosedSignal {
+    signal: Arc<Signal>,
+}
+
+impl ClosedSignal {
+    /// Returns whether the resource has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.signal
+            .state
+            .lock()
+            .expect("signal lock poisoned")
+            .closed
+    }
+}
+
+impl Future for ClosedSignal {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.signal.state.lock().expect("signal lock poisoned");
+        if state.closed {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Records that a resource has been dropped and wakes the tasks waiting for it.
+#[derive(Debug, Default)]
+struct Signal {
+    state: Mutex<SignalState>,
+}
+
+#[derive(Debug, Default)]
+struct SignalState {
+    closed: bool,
+    wakers: Vec<Waker>,
+}
+
+/// Sets its signal when dropped.
+struct NotifyOnDrop(Arc<Signal>);
+
+impl Drop for NotifyOnDrop {
+    fn drop(&mut self) {
+        let wakers = {
+            let mut state = self.0.state.lock().expect("signal lock poisoned");
+            state.closed = true;
+            std::mem::take(&mut state.wakers)
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{DropCounter, DropProbe};
+    use ::tokio::sync::oneshot;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[cfg(use_with_loom)]
+    #[test]
+    fn loom_scopes_race_with_release_of_the_primary_handle() {
+        loom::model(|| {
+            let counter = DropCounter::new();
+            let shared = SharedUse::new((counter.probe(), 0));
+            let closed = shared.closed();
+
+            let read = shared.use_read_async(|value| Box::pin(async move { value.1 }));
+            let write = shared.use_write_async(|value| Box::pin(async move { value.1 += 1 }));
+            let reader = loom::thread::spawn(move || loom::future::block_on(read));
+            let writer = loom::thread::spawn(move || loom::future::block_on(write));
+            drop(shared);
+
+            let read = reader.join().unwrap();
+            writer.join().unwrap();
+            assert!(read == 0 || read == 1);
+            assert_eq!(counter.count(), 1);
+            assert!(closed.is_closed());
+        });
+    }
+
+    #[cfg(use_with_loom)]
+    #[test]
+    fn loom_blocking_scopes_exclude_each_other_until_the_last_handle_is_released() {
+        loom::model(|| {
+            let counter = DropCounter::new();
+            let shared = Arc::new(SharedUse::new((counter.probe(), 0)));
+            let closed = shared.closed();
+
+            let writer = {
+                let shared = Arc::clone(&shared);
+                loom::thread::spawn(move || shared.use_write(|value| value.1 += 1))
+            };
+            let read = shared.use_read(|value| value.1);
+            drop(shared);
+            writer.join().unwrap();
+
+            assert!(read == 0 || read == 1);
+            assert_eq!(counter.count(), 1);
+            assert!(closed.is_closed());
+        });
+    }
+
+    #[cfg(use_with_loom)]
+    #[test]
+    fn loom_close_waits_for_started_scopes() {
+        struct Closing {
+            _probe: DropProbe,
+            closed: Arc<Mutex<bool>>,
+        }
+
+        impl Close for Closing {
+            type Error = ();
+
+            fn close(self) -> Result<(), Self::Error> {
+                *self.closed.lock().unwrap() = true;
+                Ok(())
+            }
+        }
+
+        loom::model(|| {
+            let counter = DropCounter::new();
+            let closes = Arc::new(Mutex::new(false));
+            let shared = SharedUse::new(Closing {
+                _probe: counter.probe(),
+                closed: Arc::clone(&closes),
+            });
+
+            let closes_seen = Arc::clone(&closes);
+            let read = shared.use_read_async(move |_value| {
+                Box::pin(async move { *closes_seen.lock().unwrap() })
+            });
+            let reader = loom::thread::spawn(move || loom::future::block_on(read));
+            assert_eq!(shared.close(), Ok(()));
+
+            assert!(
+                !reader.join().unwrap(),
+                "closed while a started scope was running"
+            );
+            assert!(*closes.lock().unwrap());
+            assert_eq!(counter.count(), 1);
+        });
+    }
+
+    #[::tokio::test]
+    async fn test_resource_outlives_primary_handle_while_in_use() {
+        let counter = DropCounter::new();
+        let shared = SharedUse::new(counter.probe());
+        let closed = shared.closed();
+
+        let user = shared.use_read_async(|_probe: &DropProbe| Box::pin(async { 42 }));
+        drop(shared);
+        assert_eq!(counter.count(), 0);
+        assert!(!closed.is_closed());
+
+        assert_eq!(user.await, 42);
+        assert!(closed.is_closed());
+        closed.await;
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[test]
+    fn test_resource_is_dropped_with_primary_handle_when_unused() {
+        let counter = DropCounter::new();
+        let shared = SharedUse::new(counter.probe());
+        assert_eq!(shared.use_read(|_probe| 7), 7);
+        assert_eq!(counter.count(), 0);
+
+        let closed = shared.closed();
+        drop(shared);
+        assert!(closed.is_closed());
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[test]
+    fn test_write_waits_for_active_reads() {
+        let shared = SharedUse::new(Vec::new());
+        let (reading, read) = mpsc::channel();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                shared.use_read(|values| {
+                    reading.send(()).unwrap();
+                    thread::sleep(Duration::from_millis(20));
+                    assert!(values.is_empty(), "write scope ran during a read scope");
+                });
+            });
+
+            read.recv().unwrap();
+            shared.use_write(|values| values.push(1));
+        });
+
+        assert_eq!(shared.use_read(Vec::len), 1);
+    }
+
+    #[::tokio::test]
+    async fn test_close_waits_for_started_scopes_and_reports_the_result() {
+        struct Journal(Arc<Mutex<usize>>);
+
+        impl Close for Journal {
+            type Error = &'static str;
+
+            fn close(self) -> Result<(), Self::Error> {
+                *self.0.lock().unwrap() += 1;
+                Err("flush failed")
+            }
+        }
+
+        let closes = Arc::new(Mutex::new(0));
+        let shared = SharedUse::new(Journal(Arc::clone(&closes)));
+        let closed = shared.closed();
+        let reader = shared.use_read_async(|_journal| Box::pin(async { 7 }));
+
+        let closer = thread::spawn(move || shared.close());
+        thread::sleep(Duration::from_millis(20));
+        assert!(!closer.is_finished(), "closed before a started scope ran");
+        assert_eq!(*closes.lock().unwrap(), 0);
+
+        assert_eq!(reader.await, 7);
+        assert_eq!(closer.join().unwrap(), Err("flush failed"));
+        assert_eq!(*closes.lock().unwrap(), 1);
+        assert!(closed.is_closed());
+    }
+
+    #[::tokio::test(start_paused = true)]
+    async fn test_cancelled_write_unblocks_reads() {
+        let shared = SharedUse::new(0);
+        let (finish, finished) = oneshot::channel::<()>();
+
+        let reader = ::tokio::spawn(shared.use_read_async(|value| {
+            Box::pin(async move {
+                finished.await.unwrap();
+                *value
+            })
+        }));
+        ::tokio::task::yield_now().await;
+
+        // The writer waits for the active reader and gives up.
+        let write = shared.use_write_async(|value| Box::pin(async move { *value = 1 }));
+        let timed_out = ::tokio::time::timeout(Duration::from_secs(1), write).await;
+        assert!(timed_out.is_err());
+
+        let value = shared.use_read_async(|value| Box::pin(async move { *value }));
+        assert_eq!(value.await, 0);
+        finish.send(()).unwrap();
+        assert_eq!(reader.await.unwrap(), 0);
+    }
+}
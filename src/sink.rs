@@ -0,0 +1,487 @@
+//! Closing of [`Sink`]s at the end of their use scope.
+//!
+//! [`ClosingSink`] closes any sink, while [`CloseHandshake`] additionally exchanges close frames
+//! with the peer of a message-based protocol such as WebSocket.
+//!
+//! The module is available with the `futures` feature and only depends on the [`Sink`] and
+//! [`Stream`] traits of the `futures` crates. There is no `tungstenite` feature: the
+//! `WebSocketStream` of `tokio-tungstenite` implements both traits and works with
+//! [`CloseHandshake`] as is.
+
+use crate::{AsyncClose, BoxFuture};
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::{pin, Pin};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A [`Sink`] that is flushed and closed when it is closed.
+///
+/// Dropping a sink discards items that are still buffered and skips any closing handshake of the
+/// underlying protocol. Closing a `ClosingSink` through
+/// [`Use::use_close_async`](crate::Use::use_close_async) calls [`Sink::poll_close`] instead,
+/// which flushes pending items and closes the sink, and reports its error.
+///
+/// The wrapper dereferences to the wrapped sink, and implements [`Sink`] itself if the wrapped
+/// sink is [`Unpin`].
+///
+/// # Examples
+/// ```rust
+/// use use_with::sink::ClosingSink;
+/// use use_with::Use;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let result = ClosingSink::new(Vec::<u8>::new())
+///     .use_close_async(|sink| Box::pin(async move { sink.push(42) }))
+///     .await;
+///
+/// assert!(result.is_ok());
+/// # }
+/// ```
+pub struct ClosingSink<S, Item> {
+    sink: S,
+    _item: PhantomData<fn(Item)>,
+}
+
+impl<S: Sink<Item>, Item> ClosingSink<S, Item> {
+    /// Wraps a sink that is closed at the end of its use scope.
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the wrapped sink without closing it.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: fmt::Debug, Item> fmt::Debug for ClosingSink<S, Item> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClosingSink")
+            .field("sink", &self.sink)
+            .finish()
+    }
+}
+
+impl<S, Item> Deref for ClosingSink<S, Item> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.sink
+    }
+}
+
+impl<S, Item> DerefMut for ClosingSink<S, Item> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.sink
+    }
+}
+
+impl<S: Sink<Item> + Unpin, Item> Sink<Item> for ClosingSink<S, Item> {
+    type Error = S::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        Pin::new(&mut self.sink).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sink).poll_close(cx)
+    }
+}
+
+impl<S: Sink<Item> + Send, Item> AsyncClose for ClosingSink<S, Item> {
+    type Error = S::Error;
+
+    async fn close_async(self) -> Result<(), Self::Error> {
+        let mut sink = pin!(self.sink);
+        poll_fn(|cx| sink.as_mut().poll_close(cx)).await
+    }
+}
+
+/// Sleeps asynchronously for the given duration.
+type Sleep = Arc<dyn Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// A duplex message stream that performs a closing handshake with its peer when it is closed.
+///
+/// Protocols such as WebSocket end a connection by exchanging close frames: one side sends a
+/// close frame and waits for the peer to answer with its own before tearing down the transport.
+/// Dropping the stream instead ends the connection abruptly. Closing a `CloseHandshake` through
+/// [`Use::use_close_async`](crate::Use::use_close_async) sends the configured close frame, flushes
+/// it, and discards incoming messages until the peer's close frame arrives or the stream ends.
+/// Finally, it closes the sink through [`Sink::poll_close`], which shuts down the transport.
+///
+/// The stream must implement both [`Sink`] and [`Stream`] with the same error type, as the
+/// `WebSocketStream` of `tokio-tungstenite` does:
+///
+/// ```rust,ignore
+/// use std::time::Duration;
+/// use tokio_tungstenite::tungstenite::Message;
+/// use use_with::sink::CloseHandshake;
+/// use use_with::Use;
+///
+/// let (ws, _) = tokio_tungstenite::connect_async("wss://example.com/socket").await?;
+///
+/// CloseHandshake::new(ws, Message::Close(None), Message::is_close)
+///     .timeout_with(Duration::from_secs(5), tokio::time::sleep)
+///     .use_close_async(|ws| Box::pin(async move { ws.send(Message::text("bye")).await }))
+///     .await??;
+/// ```
+pub struct CloseHandshake<S, M> {
+    stream: S,
+    close_frame: M,
+    is_close: fn(&M) -> bool,
+    timeout: Option<(Duration, Sleep)>,
+}
+
+impl<S, M, E> CloseHandshake<S, M>
+where
+    S: Sink<M, Error = E> + Stream<Item = Result<M, E>>,
+{
+    /// Wraps a message stream that sends `close_frame` when it is closed, and recognizes the
+    /// close frame of the peer through `is_close`.
+    pub fn new(stream: S, close_frame: M, is_close: fn(&M) -> bool) -> Self {
+        Self {
+            stream,
+            close_frame,
+            is_close,
+            timeout: None,
+        }
+    }
+
+    /// Limits the closing handshake to `timeout`, waited out through `sleep`.
+    ///
+    /// The timeout covers the whole handshake, including closing the sink after the peer answered.
+    /// If the handshake does not complete within the timeout, closing fails with
+    /// [`HandshakeError::TimedOut`]. The `sleep` function decouples the adapter from a particular
+    /// runtime; with tokio, pass `tokio::time::sleep`.
+    pub fn timeout_with<T, F>(mut self, timeout: Duration, sleep: T) -> Self
+    where
+        T: Fn(Duration) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.timeout = Some((timeout, Arc::new(move |delay| Box::pin(sleep(delay)))));
+        self
+    }
+
+    /// Returns the wrapped stream without performing the closing handshake.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: fmt::Debug, M> fmt::Debug for CloseHandshake<S, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CloseHandshake")
+            .field("stream", &self.stream)
+            .field(
+                "timeout",
+                &self.timeout.as_ref().map(|(timeout, _)| timeout),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, M> Deref for CloseHandshake<S, M> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl<S, M> DerefMut for CloseHandshake<S, M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.stream
+    }
+}
+
+impl<S, M, E> AsyncClose for CloseHandshake<S, M>
+where
+    S: Sink<M, Error = E> + Stream<Item = Result<M, E>> + Send,
+    M: Send,
+    E: Send,
+{
+    type Error = HandshakeError<E>;
+
+    async fn close_async(self) -> Result<(), Self::Error> {
+        let Self {
+            stream,
+            close_frame,
+            is_close,
+            timeout,
+        } = self;
+
+        let mut stream = pin!(stream);
+        let handshake = async move {
+            poll_fn(|cx| stream.as_mut().poll_ready(cx)).await?;
+            stream.as_mut().start_send(close_frame)?;
+            poll_fn(|cx| stream.as_mut().poll_flush(cx)).await?;
+            loop {
+                match poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+                    Some(Ok(message)) if is_close(&message) => break,
+                    Some(Ok(_)) => {}
+                    Some(Err(error)) => return Err(error),
+                    None => break,
+                }
+            }
+            poll_fn(|cx| stream.as_mut().poll_close(cx)).await
+        };
+
+        let mut handshake = pin!(handshake);
+        let Some((timeout, sleep)) = timeout else {
+            return handshake.await.map_err(HandshakeError::Transport);
+        };
+        let mut expired = sleep(timeout);
+        poll_fn(|cx| {
+            if let Poll::Ready(result) = handshake.as_mut().poll(cx) {
+                return Poll::Ready(result.map_err(HandshakeError::Transport));
+            }
+            expired
+                .as_mut()
+                .poll(cx)
+                .map(|()| Err(HandshakeError::TimedOut))
+        })
+        .await
+    }
+}
+
+/// The error returned when the closing handshake of a [`CloseHandshake`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandshakeError<E> {
+    /// Sending the close frame, receiving the answer of the peer or closing the sink failed.
+    Transport(E),
+    /// The handshake did not complete within the timeout.
+    TimedOut,
+}
+
+impl<E: fmt::Display> fmt::Display for HandshakeError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandshakeError::Transport(error) => write!(f, "closing handshake failed: {error}"),
+            HandshakeError::TimedOut => {
+                f.write_str("the closing handshake did not complete in time")
+            }
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for HandshakeError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HandshakeError::Transport(error) => Some(error),
+            HandshakeError::TimedOut => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    /// A sink that buffers items until it is flushed, and fails to close if it holds too many.
+    struct BufferingSink {
+        buffer: Vec<u32>,
+        flushed: Arc<AtomicBool>,
+    }
+
+    impl Sink<u32> for BufferingSink {
+        type Error = String;
+
+        fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: u32) -> Result<(), String> {
+            self.buffer.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            if self.buffer.len() > 2 {
+                return Poll::Ready(Err(format!("{} items rejected", self.buffer.len())));
+            }
+            self.buffer.clear();
+            self.flushed.store(true, Ordering::SeqCst);
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    fn sink(flushed: &Arc<AtomicBool>) -> ClosingSink<BufferingSink, u32> {
+        ClosingSink::new(BufferingSink {
+            buffer: Vec::new(),
+            flushed: flushed.clone(),
+        })
+    }
+
+    #[::tokio::test]
+    async fn test_pending_items_are_flushed_on_close() {
+        let flushed = Arc::new(AtomicBool::new(false));
+        sink(&flushed)
+            .use_close_async(|sink| Box::pin(async move { Pin::new(sink).start_send(1).unwrap() }))
+            .await
+            .unwrap();
+        assert!(flushed.load(Ordering::SeqCst));
+    }
+
+    #[::tokio::test]
+    async fn test_close_error_is_reported() {
+        let flushed = Arc::new(AtomicBool::new(false));
+        let error = sink(&flushed)
+            .use_close_async(|sink| {
+                Box::pin(async move {
+                    for item in 0..3 {
+                        Pin::new(&mut *sink).start_send(item).unwrap();
+                    }
+                })
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error, "3 items rejected");
+        assert!(!flushed.load(Ordering::SeqCst));
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Frame {
+        Text(&'static str),
+        Close,
+    }
+
+    /// A message socket whose peer answers close frames if `responsive`.
+    struct Socket {
+        sent: Arc<Mutex<Vec<Frame>>>,
+        closed: Arc<AtomicBool>,
+        incoming: VecDeque<Frame>,
+        responsive: bool,
+        ended: bool,
+    }
+
+    impl Socket {
+        fn new(responsive: bool, sent: &Arc<Mutex<Vec<Frame>>>, closed: &Arc<AtomicBool>) -> Self {
+            Self {
+                sent: sent.clone(),
+                closed: closed.clone(),
+                incoming: VecDeque::from([Frame::Text("pending")]),
+                responsive,
+                ended: false,
+            }
+        }
+
+        /// A socket whose peer ends the stream instead of answering close frames.
+        fn ending(sent: &Arc<Mutex<Vec<Frame>>>, closed: &Arc<AtomicBool>) -> Self {
+            Self {
+                ended: true,
+                ..Self::new(false, sent, closed)
+            }
+        }
+    }
+
+    impl Sink<Frame> for Socket {
+        type Error = String;
+
+        fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, frame: Frame) -> Result<(), String> {
+            if frame == Frame::Close && self.responsive {
+                self.incoming.push_back(Frame::Close);
+            }
+            self.sent.lock().unwrap().push(frame);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            self.closed.store(true, Ordering::SeqCst);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Stream for Socket {
+        type Item = Result<Frame, String>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            match self.incoming.pop_front() {
+                Some(frame) => Poll::Ready(Some(Ok(frame))),
+                None if self.ended => Poll::Ready(None),
+                // An unresponsive peer never answers; the timer wakes the task instead.
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    fn is_close(frame: &Frame) -> bool {
+        *frame == Frame::Close
+    }
+
+    #[::tokio::test(start_paused = true)]
+    async fn test_close_frames_are_exchanged() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        CloseHandshake::new(Socket::new(true, &sent, &closed), Frame::Close, is_close)
+            .timeout_with(Duration::from_secs(5), ::tokio::time::sleep)
+            .use_close_async(|socket| {
+                Box::pin(async move { Pin::new(&mut **socket).start_send(Frame::Text("bye")) })
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*sent.lock().unwrap(), [Frame::Text("bye"), Frame::Close]);
+        assert!(closed.load(Ordering::SeqCst));
+    }
+
+    #[::tokio::test(start_paused = true)]
+    async fn test_sink_is_closed_when_the_peer_ends_the_stream() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        CloseHandshake::new(Socket::ending(&sent, &closed), Frame::Close, is_close)
+            .timeout_with(Duration::from_secs(5), ::tokio::time::sleep)
+            .use_close_async(|_socket| Box::pin(async {}))
+            .await
+            .unwrap();
+        assert_eq!(*sent.lock().unwrap(), [Frame::Close]);
+        assert!(closed.load(Ordering::SeqCst));
+    }
+
+    #[::tokio::test(start_paused = true)]
+    async fn test_unanswered_close_frame_times_out() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        let error = CloseHandshake::new(Socket::new(false, &sent, &closed), Frame::Close, is_close)
+            .timeout_with(Duration::from_secs(5), ::tokio::time::sleep)
+            .use_close_async(|_socket| Box::pin(async {}))
+            .await
+            .unwrap_err();
+        assert_eq!(error, HandshakeError::TimedOut);
+        assert_eq!(*sent.lock().unwrap(), [Frame::Close]);
+        assert!(!closed.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,177 @@
+//! Scoped use of resources stored in slots owned by someone else.
+
+use crate::UseScope;
+use std::mem;
+
+/// Runs closures on resources taken out of an [`Option`] slot.
+///
+/// Struct fields and state machines often hold a resource in an `Option` that is consumed exactly
+/// once, e.g. a pending request or a one-shot callback. [`use_taken`](Self::use_taken) takes the
+/// resource out of the slot, leaving `None`, and drops it at the end of the scope, so the slot
+/// cannot be consumed twice.
+///
+/// # Examples
+/// ```rust
+/// use use_with::OptionUseExt;
+///
+/// struct Job {
+///     pending: Option<Vec<u8>>,
+/// }
+///
+/// let mut job = Job { pending: Some(b"payload".to_vec()) };
+///
+/// assert_eq!(job.pending.use_taken(|payload| payload.len()), Some(7));
+/// assert_eq!(job.pending.use_taken(|payload| payload.len()), None);
+/// ```
+pub trait OptionUseExt<T> {
+    /// Takes the resource out of the slot, if any, and executes a closure on it.
+    ///
+    /// The slot is left empty and the resource is dropped once the closure returns.
+    ///
+    /// # Returns
+    /// - `Some(U)` with the result of the closure `f`.
+    /// - `None` without running the closure if the slot is empty.
+    fn use_taken<U, F>(&mut self, f: F) -> Option<U>
+    where
+        F: FnOnce(&mut T) -> U;
+
+    /// Takes the resource out of the slot, if any, and executes a closure that may refill the slot.
+    ///
+    /// The closure returns its result together with an optional replacement. The taken resource
+    /// is dropped before the replacement is put into the slot. If the closure panics, the slot is
+    /// left empty.
+    ///
+    /// # Returns
+    /// - `Some(U)` with the result of the closure `f`.
+    /// - `None` without running the closure if the slot is empty.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use use_with::OptionUseExt;
+    ///
+    /// let mut token = Some(String::from("expired"));
+    ///
+    /// let refreshed = token.use_taken_and_replace(|old| {
+    ///     let fresh = format!("{old}-renewed");
+    ///     (true, Some(fresh))
+    /// });
+    ///
+    /// assert_eq!(refreshed, Some(true));
+    /// assert_eq!(token.as_deref(), Some("expired-renewed"));
+    /// ```
+    fn use_taken_and_replace<U, F>(&mut self, f: F) -> Option<U>
+    where
+        F: FnOnce(&mut T) -> (U, Option<T>);
+}
+
+impl<T> OptionUseExt<T> for Option<T> {
+    #[track_caller]
+    fn use_taken<U, F>(&mut self, f: F) -> Option<U>
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        let resource = self.take()?;
+        Some(UseScope::new(resource).use_with(|mut resource| f(&mut resource)))
+    }
+
+    #[track_caller]
+    fn use_taken_and_replace<U, F>(&mut self, f: F) -> Option<U>
+    where
+        F: FnOnce(&mut T) -> (U, Option<T>),
+    {
+        let resource = self.take()?;
+        let (result, replacement) =
+            UseScope::new(resource).use_with(|mut resource| f(&mut resource));
+        *self = replacement;
+        Some(result)
+    }
+}
+
+/// Replaces the value in `slot` with the result of a closure consuming the old value.
+///
+/// This lets state-machine transitions that take the old state by value run in the scoped style on
+/// a field they only borrow. While the closure runs, the slot holds `T::default()`, which remains
+/// in place if the closure panics, so the slot is never left in a moved-from state.
+///
+/// # Examples
+/// ```rust
+/// use use_with::use_replace;
+///
+/// #[derive(Debug, Default, PartialEq)]
+/// enum Connection {
+///     #[default]
+///     Closed,
+///     Connecting(String),
+///     Open { address: String, retries: u32 },
+/// }
+///
+/// let mut state = Connection::Connecting(String::from("db:5432"));
+///
+/// use_replace(&mut state, |state| match state {
+///     Connection::Connecting(address) => Connection::Open { address, retries: 0 },
+///     other => other,
+/// });
+///
+/// assert_eq!(
+///     state,
+///     Connection::Open { address: String::from("db:5432"), retries: 0 }
+/// );
+/// ```
+#[track_caller]
+pub fn use_replace<T, F>(slot: &mut T, f: F)
+where
+    T: Default,
+    F: FnOnce(T) -> T,
+{
+    use_replace_or(slot, T::default(), f);
+}
+
+/// Replaces the value in `slot` with the result of a closure consuming the old value.
+///
+/// This is like [`use_replace`], for types that do not implement [`Default`]: the slot holds
+/// `placeholder` while the closure runs, which remains in place if the closure panics.
+#[track_caller]
+pub fn use_replace_or<T, F>(slot: &mut T, placeholder: T, f: F)
+where
+    F: FnOnce(T) -> T,
+{
+    let old = mem::replace(slot, placeholder);
+    *slot = UseScope::new(old).use_with(f);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropSpy;
+
+    #[test]
+    fn test_taken_resource_is_dropped_before_replacement_is_stored() {
+        let spy = DropSpy::new();
+        let mut slot = Some(spy.probe("old"));
+
+        let result = slot.use_taken_and_replace(|_old| (42, Some(spy.probe("new"))));
+        assert_eq!(result, Some(42));
+        assert_eq!(spy.drop_count("old"), 1);
+        assert_eq!(spy.drop_count("new"), 0);
+
+        assert_eq!(slot.use_taken(|_new| ()), Some(()));
+        assert_eq!(spy.drop_count("new"), 1);
+        assert!(slot.is_none());
+    }
+
+    #[test]
+    fn test_use_replace_leaves_placeholder_on_panic() {
+        let mut state = String::from("running");
+
+        use_replace(&mut state, |state| state + "!");
+        assert_eq!(state, "running!");
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            use_replace_or(&mut state, String::from("failed"), |_| {
+                panic!("transition failed")
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(state, "failed");
+    }
+}
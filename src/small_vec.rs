@@ -0,0 +1,107 @@
+//! A vector that stores its first elements inline.
+
+use alloc::vec::Vec;
+
+/// A vector that keeps up to `N` elements inline and spills further elements onto the heap.
+///
+/// Only supports the stack operations needed for cleanup lists, without any `unsafe` code: the
+/// inline elements are kept as `Option`s and the spilled ones in a regular [`Vec`], which does not
+/// allocate until it is first pushed to.
+pub(crate) struct SmallVec<T, const N: usize> {
+    inline: [Option<T>; N],
+    inline_len: usize,
+    spilled: Vec<T>,
+}
+
+impl<T, const N: usize> SmallVec<T, N> {
+    const EMPTY: Option<T> = None;
+
+    /// Creates an empty vector.
+    pub(crate) const fn new() -> Self {
+        Self {
+            inline: [Self::EMPTY; N],
+            inline_len: 0,
+            spilled: Vec::new(),
+        }
+    }
+
+    /// Appends an element.
+    pub(crate) fn push(&mut self, value: T) {
+        if self.inline_len < N {
+            self.inline[self.inline_len] = Some(value);
+            self.inline_len += 1;
+        } else {
+            self.spilled.push(value);
+        }
+    }
+
+    /// Removes the last element.
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        if let Some(value) = self.spilled.pop() {
+            return Some(value);
+        }
+        let last = self.inline_len.checked_sub(1)?;
+        self.inline_len = last;
+        self.inline[last].take()
+    }
+
+    /// Returns the number of elements.
+    pub(crate) fn len(&self) -> usize {
+        self.inline_len + self.spilled.len()
+    }
+
+    /// Returns whether the vector contains no elements.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether elements have been spilled onto the heap.
+    #[cfg(test)]
+    pub(crate) fn spilled(&self) -> bool {
+        self.spilled.capacity() > 0
+    }
+
+    /// Drops all elements, keeping the heap storage for reuse.
+    pub(crate) fn clear(&mut self) {
+        self.spilled.clear();
+        for slot in &mut self.inline[..self.inline_len] {
+            *slot = None;
+        }
+        self.inline_len = 0;
+    }
+}
+
+impl<T, const N: usize> Default for SmallVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropCounter;
+
+    #[test]
+    fn test_spills_beyond_inline_capacity() {
+        let counter = DropCounter::new();
+        let mut vec = SmallVec::<_, 2>::new();
+        for value in 0..2 {
+            vec.push((value, counter.probe()));
+        }
+        assert!(!vec.spilled());
+
+        vec.push((2, counter.probe()));
+        assert!(vec.spilled());
+        assert_eq!(vec.len(), 3);
+
+        let popped: Vec<_> = std::iter::from_fn(|| vec.pop().map(|(value, _)| value)).collect();
+        assert_eq!(popped, [2, 1, 0]);
+        assert!(vec.is_empty());
+        assert_eq!(counter.count(), 3);
+
+        vec.push((3, counter.probe()));
+        vec.clear();
+        assert_eq!(counter.count(), 4);
+    }
+}
@@ -0,0 +1,50 @@
+//! Debug snapshots of resources attached to errors.
+
+use alloc::string::String;
+use core::fmt;
+
+/// An error of a use scope's body, together with a snapshot of the resource.
+///
+/// Returned by [`Use::try_use_with_snapshot`](crate::Use::try_use_with_snapshot). The snapshot
+/// is the [`Debug`](fmt::Debug) representation of the resource, taken after the body failed and
+/// before the resource was dropped, which answers the question of which connection or file
+/// an error occurred on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithSnapshot<E> {
+    error: E,
+    snapshot: String,
+}
+
+impl<E> WithSnapshot<E> {
+    pub(crate) fn new(error: E, snapshot: String) -> Self {
+        Self { error, snapshot }
+    }
+
+    /// Returns the error of the body.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the `Debug` representation of the resource at the time the body failed.
+    pub fn snapshot(&self) -> &str {
+        &self.snapshot
+    }
+
+    /// Returns the error of the body, discarding the snapshot.
+    pub fn into_error(self) -> E {
+        self.error
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for WithSnapshot<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (resource: {})", self.error, self.snapshot)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for WithSnapshot<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,60 @@
+//! Resources that split into halves for the duration of a use scope.
+
+/// A resource that can be split into two halves and rejoined afterwards.
+///
+/// Streams are commonly split into a reading and a writing half, so that both directions can be
+/// used independently, and channels consist of a sending and a receiving end. Used by
+/// [`Use::use_split`](crate::Use::use_split), which lends both halves to a closure and rejoins
+/// them afterwards, so the halves cannot outlive the scope of the resource they were split from.
+///
+/// Pairs implement `Split` by handing out their elements, which covers channels such as the
+/// `(Sender, Receiver)` pair returned by [`std::sync::mpsc::channel`].
+///
+/// # Examples
+/// ```rust
+/// use use_with::Split;
+///
+/// struct Pipe {
+///     inbound: Vec<u8>,
+///     outbound: Vec<u8>,
+/// }
+///
+/// impl Split for Pipe {
+///     type ReadHalf = Vec<u8>;
+///     type WriteHalf = Vec<u8>;
+///
+///     fn split(self) -> (Self::ReadHalf, Self::WriteHalf) {
+///         (self.inbound, self.outbound)
+///     }
+///
+///     fn unsplit(inbound: Self::ReadHalf, outbound: Self::WriteHalf) -> Self {
+///         Pipe { inbound, outbound }
+///     }
+/// }
+/// ```
+pub trait Split: Sized {
+    /// The half used for reading or receiving.
+    type ReadHalf;
+
+    /// The half used for writing or sending.
+    type WriteHalf;
+
+    /// Splits the resource into its halves.
+    fn split(self) -> (Self::ReadHalf, Self::WriteHalf);
+
+    /// Rejoins the halves into the resource they were split from.
+    fn unsplit(read: Self::ReadHalf, write: Self::WriteHalf) -> Self;
+}
+
+impl<A, B> Split for (A, B) {
+    type ReadHalf = A;
+    type WriteHalf = B;
+
+    fn split(self) -> (A, B) {
+        self
+    }
+
+    fn unsplit(read: A, write: B) -> Self {
+        (read, write)
+    }
+}
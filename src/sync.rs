@@ -0,0 +1,147 @@
+//! Synchronization primitives for the crate's concurrent internals.
+//!
+//! Internal state that is shared between threads uses the primitives re-exported here instead of
+//! `std::sync` directly, so that it can be model-checked with [`loom`](https://docs.rs/loom) by
+//! building with `RUSTFLAGS="--cfg use_with_loom"`. Since loom's primitives cannot be constructed
+//! in `const` contexts, process-wide statics either keep using `std::sync`, or are declared with
+//! [`loom::lazy_static!`](https://docs.rs/loom/latest/loom/macro.lazy_static.html) when
+//! model-checked. Loom cannot model poisoned locks either, so recovering from them is tested
+//! with `std::sync` only.
+//!
+//! With the `parking_lot` feature, the locks and condition variables are backed by
+//! [`parking_lot`](https://docs.rs/parking_lot) instead, which are smaller and faster when
+//! uncontended, and never poisoned. Thin wrappers keep the `std::sync` signatures, so that call
+//! sites do not change: locking always succeeds, and recovering from poison becomes a no-op.
+//! Process-wide statics use the `Static*` aliases, which switch to parking_lot as well but are
+//! never replaced by loom.
+
+#[cfg(use_with_loom)]
+pub(crate) use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(use_with_loom)]
+pub(crate) use loom::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
+#[cfg(use_with_loom)]
+pub(crate) use loom::thread::{self, ThreadId};
+
+#[cfg(all(not(use_with_loom), feature = "parking_lot"))]
+pub(crate) use parking::{Condvar, Mutex, MutexGuard, RwLock};
+#[cfg(not(use_with_loom))]
+pub(crate) use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(use_with_loom))]
+pub(crate) use std::sync::Arc;
+#[cfg(all(not(use_with_loom), not(feature = "parking_lot")))]
+pub(crate) use std::sync::{Condvar, Mutex, MutexGuard, RwLock};
+#[cfg(not(use_with_loom))]
+pub(crate) use std::thread::{self, ThreadId};
+
+#[cfg(feature = "parking_lot")]
+pub(crate) use parking::{
+    Condvar as StaticCondvar, Mutex as StaticMutex, MutexGuard as StaticMutexGuard,
+};
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) use std::sync::{
+    Condvar as StaticCondvar, Mutex as StaticMutex, MutexGuard as StaticMutexGuard,
+};
+
+/// Wrappers giving parking_lot's primitives the signatures of `std::sync`.
+///
+/// Under loom, only the `Static*` aliases use them.
+#[cfg(feature = "parking_lot")]
+#[cfg_attr(use_with_loom, allow(dead_code))]
+mod parking {
+    use std::fmt;
+    use std::sync::{LockResult, TryLockError, TryLockResult};
+    use std::time::Duration;
+
+    pub(crate) use ::parking_lot::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+
+    /// A [`parking_lot::Mutex`] with the signatures of [`std::sync::Mutex`].
+    pub(crate) struct Mutex<T: ?Sized>(::parking_lot::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            Self(::parking_lot::Mutex::new(value))
+        }
+    }
+
+    impl<T: ?Sized> Mutex<T> {
+        pub(crate) fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+            Ok(self.0.lock())
+        }
+    }
+
+    impl<T: Default> Default for Mutex<T> {
+        fn default() -> Self {
+            Self::new(T::default())
+        }
+    }
+
+    impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+
+    /// A [`parking_lot::Condvar`] with the signatures of [`std::sync::Condvar`].
+    #[derive(Debug, Default)]
+    pub(crate) struct Condvar(::parking_lot::Condvar);
+
+    impl Condvar {
+        pub(crate) const fn new() -> Self {
+            Self(::parking_lot::Condvar::new())
+        }
+
+        pub(crate) fn wait<'a, T>(
+            &self,
+            mut guard: MutexGuard<'a, T>,
+        ) -> LockResult<MutexGuard<'a, T>> {
+            self.0.wait(&mut guard);
+            Ok(guard)
+        }
+
+        pub(crate) fn wait_timeout<'a, T>(
+            &self,
+            mut guard: MutexGuard<'a, T>,
+            timeout: Duration,
+        ) -> LockResult<(MutexGuard<'a, T>, ::parking_lot::WaitTimeoutResult)> {
+            let result = self.0.wait_for(&mut guard, timeout);
+            Ok((guard, result))
+        }
+
+        pub(crate) fn notify_one(&self) {
+            self.0.notify_one();
+        }
+
+        pub(crate) fn notify_all(&self) {
+            self.0.notify_all();
+        }
+    }
+
+    /// A [`parking_lot::RwLock`] with the signatures of [`std::sync::RwLock`].
+    pub(crate) struct RwLock<T: ?Sized>(::parking_lot::RwLock<T>);
+
+    impl<T> RwLock<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            Self(::parking_lot::RwLock::new(value))
+        }
+    }
+
+    impl<T: ?Sized> RwLock<T> {
+        pub(crate) fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+            Ok(self.0.read())
+        }
+
+        pub(crate) fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+            Ok(self.0.write())
+        }
+
+        pub(crate) fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+            self.0.try_read().ok_or(TryLockError::WouldBlock)
+        }
+    }
+
+    impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+}
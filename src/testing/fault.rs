@@ -0,0 +1,454 @@
+//! Fault injection for chaos testing.
+
+use crate::{AsyncClose, BoxFuture, Close};
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A fault injected at a single injection point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Let the operation pass unchanged.
+    Pass,
+    /// Delay the operation by the given duration.
+    Delay(Duration),
+    /// Fail the operation with an [`InjectedFault`].
+    Fail,
+}
+
+/// Decides which [`Fault`] to inject each time an injection point is reached.
+#[derive(Debug, Clone)]
+pub struct FaultPlan(Plan);
+
+#[derive(Debug, Clone)]
+enum Plan {
+    Never,
+    Script(VecDeque<Fault>),
+    Random {
+        probability: f64,
+        fault: Fault,
+        state: u64,
+    },
+}
+
+impl FaultPlan {
+    /// Never injects any fault.
+    pub fn never() -> Self {
+        Self(Plan::Never)
+    }
+
+    /// Injects the given faults in order, then lets all further operations pass.
+    pub fn script(faults: impl IntoIterator<Item = Fault>) -> Self {
+        Self(Plan::Script(faults.into_iter().collect()))
+    }
+
+    /// Injects `fault` with the given `probability` between `0.0` and `1.0`.
+    ///
+    /// The plan is seeded from the system clock; use [`with_seed`](Self::with_seed)
+    /// for reproducible runs.
+    pub fn random(probability: f64, fault: Fault) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self(Plan::Random {
+            probability: probability.clamp(0.0, 1.0),
+            fault,
+            state: seed,
+        })
+        .with_seed(seed)
+    }
+
+    /// Seeds a random plan, making the sequence of injected faults reproducible.
+    ///
+    /// Has no effect on other plans.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        if let Plan::Random { state, .. } = &mut self.0 {
+            // xorshift must not be seeded with zero.
+            *state = seed | 1;
+        }
+        self
+    }
+
+    fn next(&mut self) -> Fault {
+        match &mut self.0 {
+            Plan::Never => Fault::Pass,
+            Plan::Script(faults) => faults.pop_front().unwrap_or(Fault::Pass),
+            Plan::Random {
+                probability,
+                fault,
+                state,
+            } => {
+                // xorshift64*
+                *state ^= *state >> 12;
+                *state ^= *state << 25;
+                *state ^= *state >> 27;
+                let sample = state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+                let sample = sample as f64 / (1u64 << 53) as f64;
+                if sample < *probability {
+                    *fault
+                } else {
+                    Fault::Pass
+                }
+            }
+        }
+    }
+}
+
+/// The error returned by an injection point that was configured to fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InjectedFault;
+
+impl fmt::Display for InjectedFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("injected fault")
+    }
+}
+
+impl std::error::Error for InjectedFault {}
+
+/// The error returned when closing a [`FaultInjecting`] resource fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaultError<E> {
+    /// The failure was injected; the wrapped resource was dropped without being closed.
+    Injected(InjectedFault),
+    /// Closing the wrapped resource failed.
+    Inner(E),
+}
+
+impl<E: fmt::Display> fmt::Display for FaultError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaultError::Injected(fault) => fault.fmt(f),
+            FaultError::Inner(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for FaultError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FaultError::Injected(fault) => Some(fault),
+            FaultError::Inner(error) => Some(error),
+        }
+    }
+}
+
+/// Wraps a resource to inject latencies, body errors and close failures.
+///
+/// The wrapper dereferences to the wrapped resource. Bodies call [`inject`](Self::inject) or
+/// [`inject_async`](Self::inject_async) wherever a fault should be able to occur, and closing
+/// through [`Close`] or [`AsyncClose`] consults the close plan before closing the wrapped resource.
+/// This allows testing retry, backoff and error handling configurations end to end.
+///
+/// # Examples
+/// ```rust
+/// use use_with::testing::{Fault, FaultError, FaultInjecting, FaultPlan, InjectedFault};
+/// use use_with::{Close, Use};
+///
+/// struct Connection;
+///
+/// impl Close for Connection {
+///     type Error = std::io::Error;
+///
+///     fn close(self) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// let conn = FaultInjecting::new(Connection)
+///     .body_faults(FaultPlan::script([Fault::Pass, Fault::Fail]))
+///     .close_faults(FaultPlan::random(0.5, Fault::Fail).with_seed(42));
+///
+/// let result = conn.use_close(|conn| {
+///     conn.inject()?;
+///     conn.inject()
+/// });
+///
+/// match result {
+///     Ok(body) => assert_eq!(body, Err(InjectedFault)),
+///     Err(error) => assert!(matches!(error, FaultError::Injected(_))),
+/// }
+/// ```
+///
+/// # Virtual time
+/// By default, asynchronously injected delays are timed by a helper thread and therefore take
+/// real time. Tests driving retry, backoff or deadline logic can route delays through their
+/// runtime's timer instead with [`sleep_with`](Self::sleep_with); combined with
+/// `tokio::time::pause`, injected latencies then elapse instantly and deterministically.
+///
+/// ```rust
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use std::time::Duration;
+/// use use_with::testing::{Fault, FaultInjecting, FaultPlan};
+///
+/// tokio::time::pause();
+/// let start = tokio::time::Instant::now();
+///
+/// let mut conn = FaultInjecting::new(())
+///     .body_faults(FaultPlan::script([Fault::Delay(Duration::from_secs(30))]))
+///     .sleep_with(tokio::time::sleep);
+/// conn.inject_async().await.unwrap();
+///
+/// assert!(start.elapsed() >= Duration::from_secs(30));
+/// # }
+/// ```
+pub struct FaultInjecting<T> {
+    inner: T,
+    body: FaultPlan,
+    close: FaultPlan,
+    sleep: Option<Sleep>,
+}
+
+/// Sleeps asynchronously for the given duration.
+type Sleep = Arc<dyn Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync>;
+
+impl<T> FaultInjecting<T> {
+    /// Wraps `inner` without injecting any faults yet.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            body: FaultPlan::never(),
+            close: FaultPlan::never(),
+            sleep: None,
+        }
+    }
+
+    /// Sets the plan consulted by [`inject`](Self::inject) and [`inject_async`](Self::inject_async).
+    pub fn body_faults(mut self, plan: FaultPlan) -> Self {
+        self.body = plan;
+        self
+    }
+
+    /// Sets the plan consulted when the resource is closed.
+    pub fn close_faults(mut self, plan: FaultPlan) -> Self {
+        self.close = plan;
+        self
+    }
+
+    /// Sets the function used to wait out asynchronously injected delays.
+    ///
+    /// Passing a runtime timer such as `tokio::time::sleep` makes injected latencies follow the
+    /// runtime's (possibly paused) clock.
+    pub fn sleep_with<S, F>(mut self, sleep: S) -> Self
+    where
+        S: Fn(Duration) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.sleep = Some(Arc::new(move |delay| Box::pin(sleep(delay))));
+        self
+    }
+
+    /// Reaches a body injection point, blocking the thread if a delay is injected.
+    pub fn inject(&mut self) -> Result<(), InjectedFault> {
+        apply_blocking(self.body.next())
+    }
+
+    /// Reaches a body injection point, without blocking the executor if a delay is injected.
+    pub async fn inject_async(&mut self) -> Result<(), InjectedFault> {
+        apply_async(self.body.next(), self.sleep.as_ref()).await
+    }
+
+    /// Returns the wrapped resource.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for FaultInjecting<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultInjecting")
+            .field("inner", &self.inner)
+            .field("body", &self.body)
+            .field("close", &self.close)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Deref for FaultInjecting<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for FaultInjecting<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T: Close> Close for FaultInjecting<T> {
+    type Error = FaultError<T::Error>;
+
+    fn close(mut self) -> Result<(), Self::Error> {
+        apply_blocking(self.close.next()).map_err(FaultError::Injected)?;
+        self.inner.close().map_err(FaultError::Inner)
+    }
+}
+
+impl<T: AsyncClose + Send> AsyncClose for FaultInjecting<T> {
+    type Error = FaultError<T::Error>;
+
+    async fn close_async(mut self) -> Result<(), Self::Error> {
+        apply_async(self.close.next(), self.sleep.as_ref())
+            .await
+            .map_err(FaultError::Injected)?;
+        self.inner.close_async().await.map_err(FaultError::Inner)
+    }
+}
+
+fn apply_blocking(fault: Fault) -> Result<(), InjectedFault> {
+    match fault {
+        Fault::Pass => Ok(()),
+        Fault::Delay(delay) => {
+            std::thread::sleep(delay);
+            Ok(())
+        }
+        Fault::Fail => Err(InjectedFault),
+    }
+}
+
+async fn apply_async(fault: Fault, sleep: Option<&Sleep>) -> Result<(), InjectedFault> {
+    match fault {
+        Fault::Pass => Ok(()),
+        Fault::Delay(delay) => {
+            match sleep {
+                Some(sleep) => sleep(delay).await,
+                None => Delay::new(delay).await,
+            }
+            Ok(())
+        }
+        Fault::Fail => Err(InjectedFault),
+    }
+}
+
+/// Whether a [`Delay`] has elapsed, and the waker to notify when it does.
+type TimerState = Arc<Mutex<(bool, Option<Waker>)>>;
+
+/// A runtime-agnostic timer future backed by a sleeping helper thread.
+struct Delay {
+    delay: Duration,
+    state: Option<TimerState>,
+}
+
+impl Delay {
+    fn new(delay: Duration) -> Self {
+        Self { delay, state: None }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let delay = self.delay;
+        let state = self.state.get_or_insert_with(|| {
+            let state: TimerState = Arc::new(Mutex::new((false, None)));
+            let timer = state.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                let mut timer = timer.lock().unwrap_or_else(|e| e.into_inner());
+                timer.0 = true;
+                if let Some(waker) = timer.1.take() {
+                    waker.wake();
+                }
+            });
+            state
+        });
+
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.0 {
+            Poll::Ready(())
+        } else {
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockError, MockResource};
+    use crate::Use;
+    use std::time::Instant;
+
+    #[test]
+    fn test_scripted_body_faults() {
+        let mut resource = FaultInjecting::new(()).body_faults(FaultPlan::script([
+            Fault::Fail,
+            Fault::Pass,
+            Fault::Fail,
+        ]));
+
+        assert_eq!(resource.inject(), Err(InjectedFault));
+        assert_eq!(resource.inject(), Ok(()));
+        assert_eq!(resource.inject(), Err(InjectedFault));
+        assert_eq!(resource.inject(), Ok(()));
+    }
+
+    #[test]
+    fn test_random_faults_are_reproducible() {
+        let sample = |seed| {
+            let mut plan = FaultPlan::random(0.5, Fault::Fail).with_seed(seed);
+            (0..64).map(|_| plan.next()).collect::<Vec<_>>()
+        };
+
+        let faults = sample(7);
+        assert_eq!(faults, sample(7));
+        assert!(faults.contains(&Fault::Fail));
+        assert!(faults.contains(&Fault::Pass));
+    }
+
+    #[test]
+    fn test_injected_close_failure_skips_inner_close() {
+        // The mock expects no close, so closing it would panic.
+        let mock = MockResource::builder().build();
+        let resource = FaultInjecting::new(mock).close_faults(FaultPlan::script([Fault::Fail]));
+
+        let result = resource.use_close(|_res| ());
+        assert_eq!(result, Err(FaultError::Injected(InjectedFault)));
+    }
+
+    #[tokio::test]
+    async fn test_async_close_with_latency() {
+        let mock = MockResource::builder()
+            .close_fails_with(MockError::new("reset"))
+            .build();
+        let resource = FaultInjecting::new(mock)
+            .close_faults(FaultPlan::script([Fault::Delay(Duration::from_millis(20))]));
+
+        let start = Instant::now();
+        let result = resource
+            .use_close_async(|res| Box::pin(async move { res.inject_async().await }))
+            .await;
+
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(result, Err(FaultError::Inner(MockError::new("reset"))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_virtual_time_delays() {
+        let mut resource = FaultInjecting::new(())
+            .body_faults(FaultPlan::script(
+                [Fault::Delay(Duration::from_secs(60)); 3],
+            ))
+            .sleep_with(tokio::time::sleep);
+
+        let real = Instant::now();
+        let virt = tokio::time::Instant::now();
+        for _ in 0..3 {
+            resource.inject_async().await.unwrap();
+        }
+
+        assert!(virt.elapsed() >= Duration::from_secs(180));
+        assert!(real.elapsed() < Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,271 @@
+//! Mock resources with scripted expectations.
+
+use crate::{AsyncClose, Close};
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+
+/// An error injected by a [`MockResource`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockError(String);
+
+impl MockError {
+    /// Creates an error with the given message.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+
+    /// Returns the message of the error.
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for MockError {}
+
+/// A single scripted step of a [`MockResource`].
+#[derive(Debug)]
+enum Expectation {
+    Call(String, Result<(), MockError>),
+    Close(Result<(), MockError>),
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::Call(name, _) => write!(f, "call to `{name}`"),
+            Expectation::Close(_) => f.write_str("close"),
+        }
+    }
+}
+
+/// Builds a [`MockResource`] from a script of expected method calls and closing.
+#[derive(Debug, Default)]
+#[must_use]
+pub struct MockResourceBuilder {
+    name: Option<String>,
+    script: VecDeque<Expectation>,
+}
+
+impl MockResourceBuilder {
+    /// Names the mock in failure messages.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Expects the next step to be a successful call of the method `name`.
+    pub fn expect_call(mut self, name: impl Into<String>) -> Self {
+        self.script
+            .push_back(Expectation::Call(name.into(), Ok(())));
+        self
+    }
+
+    /// Expects the next step to be a call of the method `name`, which fails with `error`.
+    pub fn expect_call_failing(mut self, name: impl Into<String>, error: MockError) -> Self {
+        self.script
+            .push_back(Expectation::Call(name.into(), Err(error)));
+        self
+    }
+
+    /// Expects the next step to be closing the resource, which succeeds.
+    pub fn expect_close(mut self) -> Self {
+        self.script.push_back(Expectation::Close(Ok(())));
+        self
+    }
+
+    /// Expects the next step to be closing the resource, which fails with `error`.
+    pub fn close_fails_with(mut self, error: MockError) -> Self {
+        self.script.push_back(Expectation::Close(Err(error)));
+        self
+    }
+
+    /// Builds the mock.
+    #[track_caller]
+    pub fn build(self) -> MockResource {
+        MockResource {
+            name: self.name.unwrap_or_else(|| "MockResource".to_owned()),
+            script: self.script,
+            created: Location::caller(),
+        }
+    }
+}
+
+/// A resource that verifies a scripted sequence of method calls and closing.
+///
+/// Code under test reports its interactions through [`call`](MockResource::call) and closes the
+/// mock through [`Close`] or [`AsyncClose`]. Failures can be injected into both. The mock panics
+/// as soon as an interaction deviates from the script, and when it is dropped or closed while
+/// scripted steps remain.
+///
+/// # Examples
+/// ```rust
+/// use use_with::testing::{MockError, MockResource};
+/// use use_with::Use;
+///
+/// let mock = MockResource::builder()
+///     .expect_call("begin")
+///     .expect_call("commit")
+///     .close_fails_with(MockError::new("connection reset"))
+///     .build();
+///
+/// let result = mock.use_close(|conn| {
+///     conn.call("begin")?;
+///     conn.call("commit")
+/// });
+///
+/// assert_eq!(result, Err(MockError::new("connection reset")));
+/// ```
+#[derive(Debug)]
+pub struct MockResource {
+    name: String,
+    script: VecDeque<Expectation>,
+    created: &'static Location<'static>,
+}
+
+impl MockResource {
+    /// Starts building a mock.
+    pub fn builder() -> MockResourceBuilder {
+        MockResourceBuilder::default()
+    }
+
+    /// Records a call of the method `name`, returning the scripted result.
+    ///
+    /// # Panics
+    /// Panics if the next scripted step is not a call of `name`.
+    #[track_caller]
+    pub fn call(&mut self, name: &str) -> Result<(), MockError> {
+        match self.script.pop_front() {
+            Some(Expectation::Call(expected, result)) if expected == name => result,
+            Some(expectation) => self.fail(format_args!(
+                "expected {expectation}, but `{name}` was called"
+            )),
+            None => self.fail(format_args!(
+                "expected no further interaction, but `{name}` was called"
+            )),
+        }
+    }
+
+    /// Returns whether all scripted steps have been performed.
+    pub fn is_satisfied(&self) -> bool {
+        self.script.is_empty()
+    }
+
+    #[track_caller]
+    fn close_mock(mut self) -> Result<(), MockError> {
+        match self.script.pop_front() {
+            Some(Expectation::Close(result)) => {
+                if let Some(expectation) = self.script.pop_front() {
+                    self.fail(format_args!(
+                        "expected {expectation} after closing, but the resource was closed"
+                    ));
+                }
+                result
+            }
+            Some(expectation) => {
+                self.fail(format_args!("expected {expectation}, but it was closed"))
+            }
+            None => self.fail(format_args!(
+                "expected no further interaction, but it was closed"
+            )),
+        }
+    }
+
+    #[track_caller]
+    fn fail(&mut self, message: fmt::Arguments) -> ! {
+        // Prevent the drop check from panicking again while unwinding.
+        self.script.clear();
+        panic!("{} created at {}: {message}", self.name, self.created)
+    }
+}
+
+impl Close for MockResource {
+    type Error = MockError;
+
+    #[track_caller]
+    fn close(self) -> Result<(), Self::Error> {
+        self.close_mock()
+    }
+}
+
+impl AsyncClose for MockResource {
+    type Error = MockError;
+
+    #[track_caller]
+    fn close_async(self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        std::future::ready(self.close_mock())
+    }
+}
+
+impl Drop for MockResource {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(expectation) = self.script.front() {
+            panic!(
+                "{} created at {}: expected {expectation}, but it was dropped",
+                self.name, self.created
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+
+    #[test]
+    fn test_mock_resource_script() {
+        let mock = MockResource::builder()
+            .expect_call("query")
+            .expect_call_failing("query", MockError::new("timeout"))
+            .expect_close()
+            .build();
+
+        let result = mock.use_close(|conn| {
+            assert!(conn.call("query").is_ok());
+            conn.call("query")
+        });
+
+        assert_eq!(result, Ok(Err(MockError::new("timeout"))));
+    }
+
+    #[tokio::test]
+    async fn test_mock_resource_async_close_failure() {
+        let mock = MockResource::builder()
+            .close_fails_with(MockError::new("reset"))
+            .build();
+
+        let result = mock
+            .use_close_async(|conn| Box::pin(async move { conn.is_satisfied() }))
+            .await;
+
+        assert_eq!(result, Err(MockError::new("reset")));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected call to `commit`, but it was dropped")]
+    fn test_mock_resource_detects_missing_calls() {
+        let mut mock = MockResource::builder()
+            .expect_call("begin")
+            .expect_call("commit")
+            .build();
+        mock.call("begin").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected close, but `rollback` was called")]
+    fn test_mock_resource_detects_unexpected_calls() {
+        let mut mock = MockResource::builder().expect_close().build();
+        let _ = mock.call("rollback");
+    }
+}
@@ -0,0 +1,370 @@
+//! Utilities for asserting teardown behavior in tests.
+//!
+//! [`DropCounter`] counts how many of its probes have been dropped, while [`DropSpy`] additionally
+//! records the order and time at which each labeled probe was dropped. Probes are plain values that
+//! can be embedded into test resources or passed into use scopes directly.
+//!
+//! [`MockResource`] verifies scripted interactions of code using the close combinators, and
+//! [`FaultInjecting`] wraps real resources to inject latencies and failures for chaos testing.
+//!
+//! This module is available with the `testing` feature, which is typically enabled for
+//! `dev-dependencies` only.
+//!
+//! # Examples
+//! ```rust
+//! use use_with::testing::DropSpy;
+//! use use_with::Use;
+//!
+//! let spy = DropSpy::new();
+//! let outer = spy.probe("outer");
+//! let inner = spy.probe("inner");
+//!
+//! outer.use_with(|_outer| {
+//!     inner.use_with(|_inner| ());
+//! });
+//!
+//! assert_eq!(spy.order(), ["inner", "outer"]);
+//! ```
+
+mod fault;
+mod mock;
+
+pub use fault::{Fault, FaultError, FaultInjecting, FaultPlan, InjectedFault};
+pub use mock::{MockError, MockResource, MockResourceBuilder};
+
+use crate::sync::{Arc, AtomicUsize, Mutex, Ordering};
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::time::Instant;
+
+/// Counts how many of its [`DropProbe`]s have been dropped.
+///
+/// Clones share the same count.
+///
+/// # Examples
+/// ```rust
+/// use use_with::testing::DropCounter;
+/// use use_with::Use;
+///
+/// let counter = DropCounter::new();
+/// counter.probe().use_with(|_probe| {
+///     assert_eq!(counter.count(), 0);
+/// });
+/// assert_eq!(counter.count(), 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DropCounter(Arc<AtomicUsize>);
+
+impl DropCounter {
+    /// Creates a new counter with a count of zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a probe that increments this counter when it is dropped.
+    pub fn probe(&self) -> DropProbe {
+        DropProbe(self.clone())
+    }
+
+    /// Returns how many probes have been dropped so far.
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A value that increments its [`DropCounter`] when dropped.
+#[derive(Debug)]
+pub struct DropProbe(DropCounter);
+
+impl Drop for DropProbe {
+    fn drop(&mut self) {
+        self.0 .0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Records the order and time at which its labeled [`SpyProbe`]s are dropped.
+///
+/// Clones share the same record.
+#[derive(Debug, Clone, Default)]
+pub struct DropSpy(Arc<Mutex<Vec<DropEvent>>>);
+
+impl DropSpy {
+    /// Creates a new spy without any recorded drops.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a probe with the given label that is recorded by this spy when it is dropped.
+    pub fn probe(&self, label: impl Into<String>) -> SpyProbe {
+        SpyProbe {
+            label: label.into(),
+            spy: self.clone(),
+        }
+    }
+
+    /// Returns the labels of all dropped probes, in the order they were dropped.
+    pub fn order(&self) -> Vec<String> {
+        self.events().into_iter().map(|event| event.label).collect()
+    }
+
+    /// Returns all recorded drops, in the order they happened.
+    pub fn events(&self) -> Vec<DropEvent> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Returns how many times a probe with the given label has been dropped.
+    pub fn drop_count(&self, label: &str) -> usize {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|event| event.label == label)
+            .count()
+    }
+
+    /// Returns whether a probe with the given label has been dropped.
+    pub fn was_dropped(&self, label: &str) -> bool {
+        self.drop_count(label) > 0
+    }
+}
+
+/// A single drop recorded by a [`DropSpy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropEvent {
+    label: String,
+    at: Instant,
+}
+
+impl DropEvent {
+    /// Returns the label of the dropped probe.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns the time at which the probe was dropped.
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+}
+
+/// A labeled value that is recorded by its [`DropSpy`] when dropped.
+#[derive(Debug)]
+pub struct SpyProbe {
+    label: String,
+    spy: DropSpy,
+}
+
+impl SpyProbe {
+    /// Returns the label of this probe.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl Drop for SpyProbe {
+    fn drop(&mut self) {
+        let event = DropEvent {
+            label: std::mem::take(&mut self.label),
+            at: Instant::now(),
+        };
+        self.spy
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event);
+    }
+}
+
+/// Asserts that a value is dropped exactly once by the end of a block.
+///
+/// The value is wrapped into a [`Tracked`] that dereferences to it and bound to the given
+/// pattern. The optional second binding is a [`DropCheck`] whose [`checkpoint`](DropCheck::checkpoint)
+/// asserts that the value has not been dropped yet at a marked point. Failure messages include
+/// the location at which the value was created. The macro evaluates to the value of the block.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{assert_dropped, Use};
+///
+/// struct Connection;
+///
+/// let result = assert_dropped!(Connection, |conn, check| {
+///     check.checkpoint();
+///     conn.use_with(|_conn| 42)
+/// });
+/// assert_eq!(result, 42);
+/// ```
+///
+/// A value that outlives the block fails the assertion:
+/// ```rust,should_panic
+/// use use_with::assert_dropped;
+///
+/// let mut keep = Vec::new();
+/// assert_dropped!(String::from("leaked"), |value| {
+///     keep.push(value);
+/// });
+/// ```
+#[macro_export]
+macro_rules! assert_dropped {
+    ($value:expr, |$binding:pat_param, $check:ident| $body:block) => {{
+        let ($binding, $check) = $crate::testing::DropCheck::track($value);
+        let result = $body;
+        $check.assert_dropped_once();
+        result
+    }};
+    ($value:expr, |$binding:pat_param| $body:block) => {
+        $crate::assert_dropped!($value, |$binding, check| $body)
+    };
+}
+
+/// A value whose drop is observed by a [`DropCheck`].
+///
+/// Dereferences to the wrapped value. Created by [`DropCheck::track`], usually through
+/// the [`assert_dropped!`](crate::assert_dropped) macro.
+#[derive(Debug)]
+pub struct Tracked<T> {
+    value: T,
+    _probe: DropProbe,
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+/// Observes whether a [`Tracked`] value has been dropped.
+#[derive(Debug)]
+pub struct DropCheck {
+    counter: DropCounter,
+    created: &'static Location<'static>,
+}
+
+impl DropCheck {
+    /// Wraps `value` into a [`Tracked`] and returns it together with a check observing its drop.
+    #[track_caller]
+    pub fn track<T>(value: T) -> (Tracked<T>, DropCheck) {
+        let counter = DropCounter::new();
+        let tracked = Tracked {
+            value,
+            _probe: counter.probe(),
+        };
+        let check = DropCheck {
+            counter,
+            created: Location::caller(),
+        };
+        (tracked, check)
+    }
+
+    /// Returns the location at which the tracked value was created.
+    pub fn created(&self) -> &'static Location<'static> {
+        self.created
+    }
+
+    /// Returns how many times the tracked value has been dropped.
+    pub fn drop_count(&self) -> usize {
+        self.counter.count()
+    }
+
+    /// Asserts that the tracked value has not been dropped yet.
+    #[track_caller]
+    pub fn checkpoint(&self) {
+        assert_eq!(
+            self.drop_count(),
+            0,
+            "value created at {} was dropped before the checkpoint at {}",
+            self.created,
+            Location::caller()
+        );
+    }
+
+    /// Asserts that the tracked value has been dropped exactly once.
+    #[track_caller]
+    pub fn assert_dropped_once(&self) {
+        let count = self.drop_count();
+        assert_eq!(
+            count, 1,
+            "value created at {} was dropped {count} time(s), expected exactly once",
+            self.created
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(use_with_loom)]
+    #[test]
+    fn loom_concurrent_drops_are_all_recorded() {
+        loom::model(|| {
+            let counter = DropCounter::new();
+            let spy = DropSpy::new();
+            let probes = (counter.probe(), spy.probe("thread"));
+            let handle = loom::thread::spawn(move || drop(probes));
+            drop((counter.probe(), spy.probe("main")));
+            handle.join().unwrap();
+
+            assert_eq!(counter.count(), 2);
+            assert_eq!(spy.drop_count("main") + spy.drop_count("thread"), 2);
+        });
+    }
+
+    #[test]
+    fn test_drop_counter() {
+        let counter = DropCounter::new();
+        let probes = vec![counter.probe(), counter.probe()];
+        assert_eq!(counter.count(), 0);
+        drop(probes);
+        assert_eq!(counter.count(), 2);
+    }
+
+    #[test]
+    fn test_drop_spy_records_order_and_time() {
+        let spy = DropSpy::new();
+        let first = spy.probe("first");
+        let second = spy.probe("second");
+
+        drop(second);
+        drop(first);
+
+        let events = spy.events();
+        assert_eq!(spy.order(), ["second", "first"]);
+        assert!(events[0].at() <= events[1].at());
+        assert_eq!(spy.drop_count("first"), 1);
+        assert!(!spy.was_dropped("third"));
+    }
+
+    #[test]
+    fn test_assert_dropped() {
+        let value = assert_dropped!(vec![1, 2, 3], |values, check| {
+            check.checkpoint();
+            let sum = values.iter().sum::<i32>();
+            drop(values);
+            sum
+        });
+        assert_eq!(value, 6);
+    }
+
+    #[test]
+    fn test_assert_dropped_reports_early_drop() {
+        let result = std::panic::catch_unwind(|| {
+            assert_dropped!(String::new(), |value, check| {
+                drop(value);
+                check.checkpoint();
+            })
+        });
+
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("dropped before the checkpoint"));
+        assert!(message.contains(file!()));
+    }
+}
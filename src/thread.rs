@@ -0,0 +1,75 @@
+//! Loaning resources to other threads.
+
+use crate::PanicPayload;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+/// Loans a resource to a worker thread for the duration of a closure and takes it back afterwards.
+///
+/// The resource is moved to a new thread, which runs `f` on it, and returned to the caller once
+/// the closure completes. The call blocks until then, so the closure may borrow from the caller's
+/// stack like one passed to [`std::thread::scope`]. This keeps `!Sync` resources, such as
+/// connections that must not be shared, usable from threads with dedicated requirements, e.g.
+/// large stacks or blocking calls, without giving up their ownership.
+///
+/// If the closure panics, the panic is caught on the worker and the resource is returned all the
+/// same; it is never left behind on the worker. The closure may have left the resource in an
+/// inconsistent state, which the caller has to consider before using it again.
+///
+/// # Returns
+/// - A tuple of the result of the closure `f`, or the panic payload if it panicked, and the resource.
+///
+/// # Examples
+/// ```rust
+/// use use_with::thread::loan_to_thread;
+///
+/// let (sum, numbers) = loan_to_thread(vec![1, 2, 3], |numbers| {
+///     numbers.push(4);
+///     numbers.iter().sum::<i32>()
+/// });
+///
+/// assert_eq!(sum.unwrap(), 10);
+/// assert_eq!(numbers, [1, 2, 3, 4]);
+/// ```
+pub fn loan_to_thread<T, U, F>(resource: T, f: F) -> (Result<U, PanicPayload>, T)
+where
+    T: Send,
+    U: Send,
+    F: FnOnce(&mut T) -> U + Send,
+{
+    thread::scope(|scope| {
+        let worker = scope.spawn(move || {
+            let mut resource = resource;
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut resource)));
+            (result, resource)
+        });
+        worker
+            .join()
+            .expect("the worker catches panics of the closure")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropCounter;
+
+    #[test]
+    fn test_resource_returns_after_panic() {
+        let counter = DropCounter::new();
+        let caller = thread::current().id();
+
+        let (result, probe) = loan_to_thread(counter.probe(), |_probe| {
+            assert_ne!(thread::current().id(), caller);
+            panic!("interrupted");
+        });
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<&str>(),
+            Some(&"interrupted")
+        );
+        assert_eq!(counter.count(), 0);
+
+        drop(probe);
+        assert_eq!(counter.count(), 1);
+    }
+}
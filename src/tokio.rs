@@ -0,0 +1,653 @@
+//! Integrations with [`tokio`](https://docs.rs/tokio).
+//!
+//! Provides adapters giving tokio's I/O types an explicit, asynchronous teardown, scoped
+//! critical sections on tokio's locks and semaphores, and detached tasks that close their resource.
+
+use crate::instrument::ScopeOptions;
+use crate::{scoped, AsyncClose, BoxFuture, UnwindError, UseScope};
+use ::tokio::io::{AsyncWrite, AsyncWriteExt};
+use ::tokio::runtime::Handle;
+use ::tokio::sync::{oneshot, Mutex, RwLock, Semaphore, SemaphorePermit};
+use ::tokio::task::JoinHandle;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// An asynchronous writer that is flushed and shut down when it is closed.
+///
+/// Dropping an [`AsyncWrite`] cannot perform asynchronous work, so buffered data is lost and
+/// protocols such as TLS or TCP do not complete their shutdown. Closing a `ShutdownWriter` through
+/// [`Use::use_close_async`](crate::Use::use_close_async) flushes the writer and calls
+/// [`AsyncWriteExt::shutdown`], reporting the first error that occurs.
+///
+/// # Examples
+/// ```rust
+/// use tokio::io::AsyncWriteExt;
+/// use use_with::tokio::ShutdownWriter;
+/// use use_with::Use;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let (client, mut server) = tokio::io::duplex(64);
+///
+/// ShutdownWriter::new(client)
+///     .use_close_async(|client| Box::pin(async move { client.write_all(b"hello").await }))
+///     .await??;
+///
+/// let mut received = Vec::new();
+/// tokio::io::AsyncReadExt::read_to_end(&mut server, &mut received).await?;
+/// assert_eq!(received, b"hello");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ShutdownWriter<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> ShutdownWriter<W> {
+    /// Wraps a writer that is shut down when closed.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Returns the wrapped writer without shutting it down.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> Deref for ShutdownWriter<W> {
+    type Target = W;
+
+    fn deref(&self) -> &Self::Target {
+        &self.writer
+    }
+}
+
+impl<W> DerefMut for ShutdownWriter<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.writer
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for ShutdownWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.writer.is_write_vectored()
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send> AsyncClose for ShutdownWriter<W> {
+    type Error = io::Error;
+
+    async fn close_async(mut self) -> Result<(), Self::Error> {
+        self.writer.flush().await?;
+        self.writer.shutdown().await
+    }
+}
+
+/// Runs critical sections on a tokio [`Mutex`] as asynchronous use scopes.
+///
+/// This is the asynchronous counterpart of [`LockUseExt`](crate::LockUseExt). The closure receives
+/// the protected data rather than the guard, so the lock is guaranteed to be released once the
+/// returned future completes or is dropped. The critical section is reported to observers like any
+/// other use scope, with the guard as its resource; waiting for the lock is not part of the scope.
+///
+/// # Examples
+/// ```rust
+/// use tokio::sync::Mutex;
+/// use use_with::tokio::AsyncLockUseExt;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let queue = Mutex::new(vec![1, 2]);
+///
+/// let len = queue
+///     .use_locked(|queue| {
+///         Box::pin(async move {
+///             tokio::task::yield_now().await;
+///             queue.push(3);
+///             queue.len()
+///         })
+///     })
+///     .await;
+///
+/// assert_eq!(len, 3);
+/// # }
+/// ```
+pub trait AsyncLockUseExt<T: ?Sized> {
+    /// Locks the mutex, runs the asynchronous closure `f` on the protected data and releases the
+    /// lock.
+    fn use_locked<U, F>(&self, f: F) -> impl Future<Output = U> + Send
+    where
+        F: for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, U> + Send,
+        U: Send;
+}
+
+impl<T: ?Sized + Send> AsyncLockUseExt<T> for Mutex<T> {
+    #[track_caller]
+    fn use_locked<U, F>(&self, f: F) -> impl Future<Output = U> + Send
+    where
+        F: for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, U> + Send,
+        U: Send,
+    {
+        let options = ScopeOptions::new();
+        async move {
+            let guard = self.lock().await;
+            UseScope::with_options(guard, options)
+                .use_with_async(|mut guard| async move { f(&mut guard).await })
+                .await
+        }
+    }
+}
+
+/// Runs read and write critical sections on a tokio [`RwLock`] as asynchronous use scopes.
+///
+/// This is the asynchronous counterpart of [`RwLockUseExt`](crate::RwLockUseExt), see
+/// [`AsyncLockUseExt`] for how the critical sections behave.
+///
+/// # Examples
+/// ```rust
+/// use tokio::sync::RwLock;
+/// use use_with::tokio::AsyncRwLockUseExt;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let settings = RwLock::new(String::from("debug"));
+///
+/// settings
+///     .use_write(|config| Box::pin(async move { config.push_str(",trace") }))
+///     .await;
+///
+/// let len = settings
+///     .use_read(|config| Box::pin(async move { config.len() }))
+///     .await;
+/// assert_eq!(len, 11);
+/// # }
+/// ```
+pub trait AsyncRwLockUseExt<T: ?Sized> {
+    /// Acquires shared read access, runs the asynchronous closure `f` on the protected data and
+    /// releases the lock.
+    fn use_read<U, F>(&self, f: F) -> impl Future<Output = U> + Send
+    where
+        F: for<'a> FnOnce(&'a T) -> BoxFuture<'a, U> + Send,
+        U: Send;
+
+    /// Acquires exclusive write access, runs the asynchronous closure `f` on the protected data
+    /// and releases the lock.
+    fn use_write<U, F>(&self, f: F) -> impl Future<Output = U> + Send
+    where
+        F: for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, U> + Send,
+        U: Send;
+}
+
+impl<T: ?Sized + Send + Sync> AsyncRwLockUseExt<T> for RwLock<T> {
+    #[track_caller]
+    fn use_read<U, F>(&self, f: F) -> impl Future<Output = U> + Send
+    where
+        F: for<'a> FnOnce(&'a T) -> BoxFuture<'a, U> + Send,
+        U: Send,
+    {
+        let options = ScopeOptions::new();
+        async move {
+            let guard = self.read().await;
+            UseScope::with_options(guard, options)
+                .use_with_async(|guard| async move { f(&guard).await })
+                .await
+        }
+    }
+
+    #[track_caller]
+    fn use_write<U, F>(&self, f: F) -> impl Future<Output = U> + Send
+    where
+        F: for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, U> + Send,
+        U: Send,
+    {
+        let options = ScopeOptions::new();
+        async move {
+            let guard = self.write().await;
+            UseScope::with_options(guard, options)
+                .use_with_async(|mut guard| async move { f(&mut guard).await })
+                .await
+        }
+    }
+}
+
+/// The error returned when the permits of a [`Semaphore`] could not be acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PermitError {
+    /// The semaphore was closed.
+    Closed,
+    /// The permits were not granted within the timeout.
+    TimedOut,
+}
+
+impl fmt::Display for PermitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermitError::Closed => f.write_str("the semaphore was closed"),
+            PermitError::TimedOut => f.write_str("acquiring the permits timed out"),
+        }
+    }
+}
+
+impl std::error::Error for PermitError {}
+
+/// Runs asynchronous closures while holding permits of a tokio [`Semaphore`].
+///
+/// The permits are held by the scope until the future returned by the closure completes, so that
+/// they cannot be released early by accident, e.g. by a closure that ignores them. The closure
+/// receives a reference to the permits, which lets it inspect but not release them. Acquiring the
+/// permits is cancel safe: dropping the returned future while it waits for permits gives up the
+/// wait without holding any permit.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use tokio::sync::Semaphore;
+/// use use_with::tokio::{PermitError, SemaphoreUseExt};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), PermitError> {
+/// let connections = Semaphore::new(4);
+///
+/// let response = connections
+///     .use_permits(2, |permit| {
+///         let connections = permit.num_permits();
+///         async move {
+///             // Use two of the four connections, ...
+///             assert_eq!(connections, 2);
+///             200
+///         }
+///     })
+///     .await?;
+/// assert_eq!(response, 200);
+///
+/// let result = connections
+///     .use_permits(1, |_permit| async {
+///         connections
+///             .use_permits_timeout(4, Duration::from_millis(10), |_permit| async {})
+///             .await
+///     })
+///     .await?;
+/// assert_eq!(result, Err(PermitError::TimedOut));
+/// # Ok(())
+/// # }
+/// ```
+pub trait SemaphoreUseExt {
+    /// Acquires `n` permits, runs the asynchronous closure `f` with a reference to them while
+    /// holding them and releases them.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(PermitError::Closed)` without running the closure if the semaphore is closed.
+    fn use_permits<F, Fut, U>(
+        &self,
+        n: u32,
+        f: F,
+    ) -> impl Future<Output = Result<U, PermitError>> + Send
+    where
+        F: FnOnce(&SemaphorePermit<'_>) -> Fut + Send,
+        Fut: Future<Output = U> + Send;
+
+    /// Like [`use_permits`](Self::use_permits), but gives up waiting for the permits after
+    /// `timeout`.
+    ///
+    /// Only the acquisition of the permits is subject to the timeout, not the closure `f`. The
+    /// timeout requires the tokio runtime to have its time driver enabled.
+    ///
+    /// # Returns
+    /// - `Ok(U)` with the result of the closure `f`.
+    /// - `Err(PermitError::TimedOut)` without running the closure if the permits were not granted
+    ///   within the timeout.
+    /// - `Err(PermitError::Closed)` without running the closure if the semaphore is closed.
+    fn use_permits_timeout<F, Fut, U>(
+        &self,
+        n: u32,
+        timeout: Duration,
+        f: F,
+    ) -> impl Future<Output = Result<U, PermitError>> + Send
+    where
+        F: FnOnce(&SemaphorePermit<'_>) -> Fut + Send,
+        Fut: Future<Output = U> + Send;
+}
+
+impl SemaphoreUseExt for Semaphore {
+    #[track_caller]
+    fn use_permits<F, Fut, U>(
+        &self,
+        n: u32,
+        f: F,
+    ) -> impl Future<Output = Result<U, PermitError>> + Send
+    where
+        F: FnOnce(&SemaphorePermit<'_>) -> Fut + Send,
+        Fut: Future<Output = U> + Send,
+    {
+        let options = ScopeOptions::new();
+        async move {
+            let permits = self
+                .acquire_many(n)
+                .await
+                .map_err(|_| PermitError::Closed)?;
+            Ok(hold(permits, options, f).await)
+        }
+    }
+
+    #[track_caller]
+    fn use_permits_timeout<F, Fut, U>(
+        &self,
+        n: u32,
+        timeout: Duration,
+        f: F,
+    ) -> impl Future<Output = Result<U, PermitError>> + Send
+    where
+        F: FnOnce(&SemaphorePermit<'_>) -> Fut + Send,
+        Fut: Future<Output = U> + Send,
+    {
+        let options = ScopeOptions::new();
+        async move {
+            let permits = ::tokio::time::timeout(timeout, self.acquire_many(n))
+                .await
+                .map_err(|_| PermitError::TimedOut)?
+                .map_err(|_| PermitError::Closed)?;
+            Ok(hold(permits, options, f).await)
+        }
+    }
+}
+
+/// Runs asynchronous closures in tasks that own their resource and close it even when aborted.
+///
+/// Aborting a tokio task drops its future, so a resource owned by the task is dropped without
+/// running its asynchronous teardown. [`use_detached`](Self::use_detached) spawns a task that
+/// instead stops the body when the returned [`DetachedHandle`] is aborted or dropped, closes the
+/// resource through [`AsyncClose`] and only then completes. Awaiting the handle waits for the
+/// task and returns its outcome.
+///
+/// # Examples
+/// ```rust
+/// use tokio::runtime::Handle;
+/// use use_with::tokio::{DetachedUseExt, ShutdownWriter};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let (client, mut server) = tokio::io::duplex(64);
+///
+/// let mut handle = ShutdownWriter::new(client).use_detached(&Handle::current(), |_client| {
+///     Box::pin(async move {
+///         // Streams until aborted.
+///         std::future::pending::<()>().await
+///     })
+/// });
+///
+/// handle.abort();
+/// assert!(matches!(handle.await, Ok(None)));
+///
+/// // The writer was shut down, so the reader sees the end of the stream.
+/// let mut received = Vec::new();
+/// tokio::io::AsyncReadExt::read_to_end(&mut server, &mut received).await.unwrap();
+/// assert!(received.is_empty());
+/// # }
+/// ```
+pub trait DetachedUseExt: AsyncClose + Sized {
+    /// Spawns a task on `runtime` that owns the resource, runs the asynchronous closure `f` on it
+    /// and closes it afterwards.
+    ///
+    /// The body is stopped at its next suspension point when the returned handle is aborted or
+    /// dropped; the resource is closed in either case.
+    fn use_detached<U, F>(self, runtime: &Handle, f: F) -> DetachedHandle<U, Self::Error>
+    where
+        F: for<'a> FnOnce(&'a mut Self) -> BoxFuture<'a, U> + Send + 'static,
+        U: Send + 'static;
+}
+
+impl<T> DetachedUseExt for T
+where
+    T: AsyncClose + Send + 'static,
+    T::Error: Send + 'static,
+{
+    #[track_caller]
+    fn use_detached<U, F>(self, runtime: &Handle, f: F) -> DetachedHandle<U, Self::Error>
+    where
+        F: for<'a> FnOnce(&'a mut Self) -> BoxFuture<'a, U> + Send + 'static,
+        U: Send + 'static,
+    {
+        let options = ScopeOptions::new();
+        let (abort, mut aborted) = oneshot::channel::<()>();
+        let task = runtime.spawn(scoped::use_close_async_catch_unwind(
+            self,
+            options,
+            move |resource| {
+                let mut body = f(resource);
+                // Dropping the handle closes the channel, which aborts the body as well.
+                Box::pin(poll_fn(move |cx| match body.as_mut().poll(cx) {
+                    Poll::Ready(result) => Poll::Ready(Some(result)),
+                    Poll::Pending => Pin::new(&mut aborted).poll(cx).map(|_| None),
+                }))
+            },
+        ));
+        DetachedHandle {
+            task,
+            abort: Some(abort),
+        }
+    }
+}
+
+/// A handle to a task spawned by [`DetachedUseExt::use_detached`].
+///
+/// Dropping the handle aborts the task like [`abort`](Self::abort), but without waiting for it.
+/// Awaiting the handle returns
+/// - `Ok(Some(U))` with the result of the body if it completed and the resource was closed,
+/// - `Ok(None)` if the body was aborted and the resource was closed, or the runtime shut down,
+///   in which case the resource was dropped without being closed,
+/// - `Err(UnwindError)` if the body panicked or closing the resource failed.
+#[derive(Debug)]
+#[must_use = "dropping the handle aborts the task"]
+pub struct DetachedHandle<U, E> {
+    task: JoinHandle<Result<Option<U>, UnwindError<E>>>,
+    abort: Option<oneshot::Sender<()>>,
+}
+
+impl<U, E> DetachedHandle<U, E> {
+    /// Stops the body of the task at its next suspension point, after which the resource is closed.
+    ///
+    /// Has no effect if the body already completed.
+    pub fn abort(&mut self) {
+        if let Some(abort) = self.abort.take() {
+            let _ = abort.send(());
+        }
+    }
+
+    /// Returns whether the task has completed, including the closing of the resource.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl<U, E> Future for DetachedHandle<U, E> {
+    type Output = Result<Option<U>, UnwindError<E>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.task)
+            .poll(cx)
+            .map(|joined| match joined {
+                Ok(outcome) => outcome,
+                // Panics of the body are caught by the scope, so the task only fails if closing the
+                // resource panicked after the body had completed.
+                Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+                Err(_cancelled) => Ok(None),
+            })
+    }
+}
+
+/// Runs `f` in a use scope of `resource`, holding the resource until the future of `f` completes.
+async fn hold<R, F, Fut, U>(resource: R, options: ScopeOptions<'_>, f: F) -> U
+where
+    F: FnOnce(&R) -> Fut,
+    Fut: Future<Output = U>,
+{
+    UseScope::with_options(resource, options)
+        .use_with_async(|resource| async move {
+            let result = f(&resource).await;
+            drop(resource);
+            result
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Use;
+    use ::tokio::io::{AsyncReadExt, BufWriter};
+    use std::sync::Arc;
+
+    #[::tokio::test]
+    async fn test_buffered_data_is_flushed_on_close() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+
+        ShutdownWriter::new(BufWriter::new(client))
+            .use_close_async(|client| Box::pin(async move { client.write_all(b"buffered").await }))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut received = Vec::new();
+        server.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"buffered");
+    }
+
+    #[::tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_use_locked_serializes_critical_sections() {
+        let counter = Arc::new(Mutex::new(0));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let counter = counter.clone();
+                ::tokio::spawn(async move {
+                    counter
+                        .use_locked(|counter| {
+                            Box::pin(async move {
+                                let value = *counter;
+                                ::tokio::task::yield_now().await;
+                                *counter = value + 1;
+                            })
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(*counter.try_lock().unwrap(), 8);
+    }
+
+    #[::tokio::test]
+    async fn test_rwlock_is_released_after_scope() {
+        let lock = RwLock::new(vec![1]);
+        lock.use_write(|data| Box::pin(async move { data.push(2) }))
+            .await;
+
+        let sum = lock
+            .use_read(|data| Box::pin(async move { data.iter().sum::<i32>() }))
+            .await;
+        assert_eq!(sum, 3);
+        assert!(lock.try_write().is_ok());
+    }
+
+    #[::tokio::test]
+    async fn test_permits_are_released_after_scope() {
+        let semaphore = Semaphore::new(3);
+        let shared = &semaphore;
+
+        let available = semaphore
+            .use_permits(2, |permit| {
+                let held = permit.num_permits();
+                async move { (held, shared.available_permits()) }
+            })
+            .await;
+        assert_eq!(available, Ok((2, 1)));
+        assert_eq!(semaphore.available_permits(), 3);
+
+        semaphore.close();
+        let closed = semaphore.use_permits(1, |_permit| async {}).await;
+        assert_eq!(closed, Err(PermitError::Closed));
+    }
+
+    #[::tokio::test(start_paused = true)]
+    async fn test_use_permits_timeout() {
+        let semaphore = Semaphore::new(1);
+
+        let result = semaphore
+            .use_permits(1, |_permit| async {
+                semaphore
+                    .use_permits_timeout(1, Duration::from_secs(5), |_permit| async {})
+                    .await
+            })
+            .await;
+        assert_eq!(result, Ok(Err(PermitError::TimedOut)));
+
+        let result = semaphore
+            .use_permits_timeout(1, Duration::from_secs(5), |_permit| async { 42 })
+            .await;
+        assert_eq!(result, Ok(42));
+    }
+
+    #[::tokio::test]
+    async fn test_dropped_detached_handle_closes_resource() {
+        struct Connection(oneshot::Sender<&'static str>);
+
+        impl AsyncClose for Connection {
+            type Error = io::Error;
+
+            async fn close_async(self) -> Result<(), Self::Error> {
+                let _ = self.0.send("closed");
+                Ok(())
+            }
+        }
+
+        let (closed, closing) = oneshot::channel();
+        let handle = Connection(closed).use_detached(&Handle::current(), |_conn| {
+            Box::pin(std::future::pending::<()>())
+        });
+        drop(handle);
+        assert_eq!(closing.await, Ok("closed"));
+
+        let (closed, closing) = oneshot::channel();
+        let result = Connection(closed)
+            .use_detached(&Handle::current(), |_conn| Box::pin(async { 42 }))
+            .await;
+        assert!(matches!(result, Ok(Some(42))));
+        assert_eq!(closing.await, Ok("closed"));
+    }
+}
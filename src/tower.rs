@@ -0,0 +1,295 @@
+//! Integrations with [`tower`](https://docs.rs/tower).
+//!
+//! Provides [`UseLayer`], a middleware that scopes a resource to each call of the service it wraps.
+
+use crate::{AsyncResourceFactory, BoxFuture, Close, UseScope};
+use ::tower::{BoxError, Layer, Service};
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// A [`Layer`] that acquires a resource of type `T` for each call of the inner service.
+///
+/// Before each call, the resource is created through the [`AsyncResourceFactory`] and handed to
+/// the inject function, which attaches it to the request, for example as an extension or as part
+/// of a tuple. Once the response future of the inner service completes, the resource is closed
+/// and a close error fails the call if the inner service succeeded. If the response future is
+/// dropped before, for example because the client went away, the resource is closed on drop and
+/// its close error is reported to the observers of the scope.
+///
+/// The resource is shared with the request through an [`Arc`]. If the inner service keeps a clone
+/// beyond the call, the resource cannot be closed and is dropped with the last clone instead.
+///
+/// Errors of the factory, the inner service and closing the resource are boxed into a
+/// [`BoxError`].
+///
+/// # Examples
+/// ```rust
+/// use std::sync::Arc;
+/// use tower::{service_fn, BoxError, ServiceBuilder, ServiceExt};
+/// use use_with::tower::UseLayer;
+/// use use_with::Close;
+///
+/// struct Transaction(u32);
+///
+/// impl Close for Transaction {
+///     type Error = std::io::Error;
+///
+///     fn close(self) -> Result<(), Self::Error> {
+///         // Commit, ...
+///         Ok(())
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), BoxError> {
+/// let begin = || async { Ok::<_, std::io::Error>(Transaction(7)) };
+/// let service = ServiceBuilder::new()
+///     .layer(UseLayer::new(begin, |request: u32, tx: Arc<Transaction>| (request, tx)))
+///     .service(service_fn(|(request, tx): (u32, Arc<Transaction>)| async move {
+///         Ok::<_, BoxError>(request + tx.0)
+///     }));
+///
+/// assert_eq!(service.oneshot(35).await?, 42);
+/// # Ok(())
+/// # }
+/// ```
+pub struct UseLayer<T, F, I> {
+    factory: Arc<F>,
+    inject: Arc<I>,
+    _resource: PhantomData<fn() -> T>,
+}
+
+impl<T, F, I> UseLayer<T, F, I>
+where
+    F: AsyncResourceFactory<T>,
+{
+    /// Creates a layer acquiring resources through `factory`, which `inject` attaches to the
+    /// requests.
+    pub fn new(factory: F, inject: I) -> Self {
+        Self {
+            factory: Arc::new(factory),
+            inject: Arc::new(inject),
+            _resource: PhantomData,
+        }
+    }
+}
+
+impl<T, F, I> Clone for UseLayer<T, F, I> {
+    fn clone(&self) -> Self {
+        Self {
+            factory: Arc::clone(&self.factory),
+            inject: Arc::clone(&self.inject),
+            _resource: PhantomData,
+        }
+    }
+}
+
+impl<T, F, I> fmt::Debug for UseLayer<T, F, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("UseLayer")
+            .field(&std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<S, T, F, I> Layer<S> for UseLayer<T, F, I> {
+    type Service = UseService<S, T, F, I>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        UseService {
+            inner,
+            factory: Arc::clone(&self.factory),
+            inject: Arc::clone(&self.inject),
+            _resource: PhantomData,
+        }
+    }
+}
+
+/// The service created by [`UseLayer`], which acquires a resource for each call of `S`.
+pub struct UseService<S, T, F, I> {
+    inner: S,
+    factory: Arc<F>,
+    inject: Arc<I>,
+    _resource: PhantomData<fn() -> T>,
+}
+
+impl<S: Clone, T, F, I> Clone for UseService<S, T, F, I> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            factory: Arc::clone(&self.factory),
+            inject: Arc::clone(&self.inject),
+            _resource: PhantomData,
+        }
+    }
+}
+
+impl<S: fmt::Debug, T, F, I> fmt::Debug for UseService<S, T, F, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UseService")
+            .field("inner", &self.inner)
+            .field("resource", &std::any::type_name::<T>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, T, F, I, Request, Inner> Service<Request> for UseService<S, T, F, I>
+where
+    S: Service<Inner> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+    T: Close + Send + Sync + 'static,
+    T::Error: Into<BoxError>,
+    F: AsyncResourceFactory<T> + Send + Sync + 'static,
+    F::Error: Into<BoxError>,
+    I: Fn(Request, Arc<T>) -> Inner + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<S::Response, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // The clone has not been driven to readiness, so the ready service handles the call.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let factory = Arc::clone(&self.factory);
+        let inject = Arc::clone(&self.inject);
+        Box::pin(async move {
+            let resource = factory.create_async().await.map_err(Into::into)?;
+            let mut guard = CloseOnDrop(Some(Arc::new(resource)));
+            let response = {
+                let resource = guard.0.as_ref().map(Arc::clone).expect("resource is held");
+                inner.call(inject(request, resource))
+            }
+            .await
+            .map_err(Into::into);
+            let closed = guard.close().map_err(Into::into);
+            let response = response?;
+            closed?;
+            Ok(response)
+        })
+    }
+}
+
+/// Closes the resource of a call when the call completes or its future is dropped.
+struct CloseOnDrop<T: Close>(Option<Arc<T>>);
+
+impl<T: Close> CloseOnDrop<T> {
+    fn close(&mut self) -> Result<(), T::Error> {
+        match self.0.take().map(Arc::try_unwrap) {
+            Some(Ok(resource)) => UseScope::new(resource).use_close(|_| ()),
+            Some(Err(_)) | None => Ok(()),
+        }
+    }
+}
+
+impl<T: Close> Drop for CloseOnDrop<T> {
+    fn drop(&mut self) {
+        // Close errors reach the observers of the scope.
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::tower::{service_fn, ServiceExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// A resource that counts how often it was closed, and fails to close if `broken`.
+    struct Connection {
+        closed: Arc<AtomicUsize>,
+        broken: bool,
+    }
+
+    impl Close for Connection {
+        type Error = String;
+
+        fn close(self) -> Result<(), Self::Error> {
+            self.closed.fetch_add(1, Ordering::SeqCst);
+            if self.broken {
+                return Err(String::from("connection broke"));
+            }
+            Ok(())
+        }
+    }
+
+    /// Creates connections sharing a close counter.
+    struct Connect {
+        closed: Arc<AtomicUsize>,
+        broken: bool,
+    }
+
+    impl AsyncResourceFactory<Connection> for Connect {
+        type Error = String;
+
+        async fn create_async(&self) -> Result<Connection, Self::Error> {
+            Ok(Connection {
+                closed: Arc::clone(&self.closed),
+                broken: self.broken,
+            })
+        }
+    }
+
+    type Inject = fn(u32, Arc<Connection>) -> (u32, Arc<Connection>);
+
+    fn layer(closed: &Arc<AtomicUsize>, broken: bool) -> UseLayer<Connection, Connect, Inject> {
+        let connect = Connect {
+            closed: Arc::clone(closed),
+            broken,
+        };
+        UseLayer::new(connect, |request, connection| (request, connection))
+    }
+
+    #[::tokio::test]
+    async fn test_resource_is_closed_after_each_call() {
+        let closed = Arc::new(AtomicUsize::new(0));
+        let service = layer(&closed, false).layer(service_fn(
+            |(request, _connection): (u32, Arc<Connection>)| async move {
+                Ok::<_, BoxError>(request * 2)
+            },
+        ));
+
+        assert_eq!(service.clone().oneshot(1).await.unwrap(), 2);
+        assert_eq!(service.oneshot(2).await.unwrap(), 4);
+        assert_eq!(closed.load(Ordering::SeqCst), 2);
+    }
+
+    #[::tokio::test]
+    async fn test_close_error_fails_the_call() {
+        let closed = Arc::new(AtomicUsize::new(0));
+        let service =
+            layer(&closed, true).layer(service_fn(
+                |(request, _connection): (u32, Arc<Connection>)| async move {
+                    Ok::<_, BoxError>(request)
+                },
+            ));
+
+        let error = service.oneshot(1).await.unwrap_err();
+        assert_eq!(error.to_string(), "connection broke");
+        assert_eq!(closed.load(Ordering::SeqCst), 1);
+    }
+
+    #[::tokio::test(start_paused = true)]
+    async fn test_resource_is_closed_when_the_call_is_dropped() {
+        let closed = Arc::new(AtomicUsize::new(0));
+        let service = layer(&closed, false).layer(service_fn(
+            |(_request, _connection): (u32, Arc<Connection>)| {
+                std::future::pending::<Result<u32, BoxError>>()
+            },
+        ));
+
+        let call = service.oneshot(1);
+        let timed_out = ::tokio::time::timeout(Duration::from_secs(1), call).await;
+        assert!(timed_out.is_err());
+        assert_eq!(closed.load(Ordering::SeqCst), 1);
+    }
+}
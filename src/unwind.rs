@@ -0,0 +1,137 @@
+//! Recovering from panics inside use scopes.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::any::Any;
+use core::fmt;
+use core::future::{poll_fn, Future};
+use core::panic::AssertUnwindSafe;
+use core::pin::pin;
+use core::task::Poll;
+
+/// The payload of a caught panic, as returned by [`std::panic::catch_unwind`].
+///
+/// Returned by [`Use::use_with_catch_unwind`](crate::Use::use_with_catch_unwind) when the body
+/// panicked. The payload is usually a `&'static str` or a `String` with the panic message.
+pub type PanicPayload = Box<dyn Any + Send + 'static>;
+
+/// The error returned by [`Use::use_close_async_catch_unwind`](crate::Use::use_close_async_catch_unwind).
+#[derive(Debug)]
+pub enum UnwindError<E> {
+    /// The body panicked. The resource was closed nonetheless, with the given outcome.
+    Panic {
+        /// The payload of the panic.
+        payload: PanicPayload,
+        /// The outcome of closing the resource after the panic, or `None` if closing panicked
+        /// as well. The panic of the closing is suppressed in favor of the body's panic.
+        close: Option<Result<(), E>>,
+    },
+    /// The body completed, but closing the resource failed.
+    Close(E),
+}
+
+impl<E> UnwindError<E> {
+    /// Returns the panic payload, if the body panicked.
+    pub fn payload(&self) -> Option<&PanicPayload> {
+        match self {
+            UnwindError::Panic { payload, .. } => Some(payload),
+            UnwindError::Close(_) => None,
+        }
+    }
+
+    /// Returns the error of closing the resource, if closing failed.
+    pub fn close_error(&self) -> Option<&E> {
+        match self {
+            UnwindError::Panic { close, .. } => {
+                close.as_ref().and_then(|close| close.as_ref().err())
+            }
+            UnwindError::Close(error) => Some(error),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for UnwindError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnwindError::Panic { payload, close } => {
+                f.write_str("use scope panicked")?;
+                if let Some(message) = panic_message(payload) {
+                    write!(f, ": {message}")?;
+                }
+                match close {
+                    Some(Ok(())) => Ok(()),
+                    Some(Err(error)) => {
+                        write!(f, " (closing the resource failed as well: {error})")
+                    }
+                    None => f.write_str(" (closing the resource panicked as well)"),
+                }
+            }
+            UnwindError::Close(error) => write!(f, "closing the resource failed: {error}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for UnwindError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.close_error().map(|error| error as _)
+    }
+}
+
+/// Returns the message of a panic payload created by `panic!`, if any.
+fn panic_message(payload: &PanicPayload) -> Option<&str> {
+    payload
+        .downcast_ref::<&'static str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+/// The panic primitives used by use scopes.
+///
+/// Without the `std` feature, panics cannot be caught or detected: `catch_unwind` runs the
+/// closure directly, and a panicking body leaves its scope by unwinding or aborting, depending on
+/// the panic strategy, without the resource being closed explicitly.
+pub(crate) mod panic {
+    #[cfg(feature = "std")]
+    pub(crate) use std::panic::{catch_unwind, resume_unwind};
+    #[cfg(feature = "std")]
+    pub(crate) use std::thread::panicking;
+
+    #[cfg(not(feature = "std"))]
+    use super::PanicPayload;
+    #[cfg(not(feature = "std"))]
+    use core::panic::UnwindSafe;
+
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn catch_unwind<F: FnOnce() -> R + UnwindSafe, R>(f: F) -> Result<R, PanicPayload> {
+        Ok(f())
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn resume_unwind(_payload: PanicPayload) -> ! {
+        unreachable!("panics are not caught without the `std` feature")
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn panicking() -> bool {
+        false
+    }
+}
+
+/// Drives a future to completion, catching panics raised while creating or polling it.
+///
+/// Unwind safety is asserted: the future is never polled again after it panicked.
+pub(crate) async fn catch_unwind<Fut: Future>(
+    create: impl FnOnce() -> Fut,
+) -> Result<Fut::Output, PanicPayload> {
+    let future = panic::catch_unwind(AssertUnwindSafe(create))?;
+    let mut future = pin!(future);
+    poll_fn(
+        move |cx| match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        },
+    )
+    .await
+}
@@ -0,0 +1,168 @@
+//! A shared thread that reports use scopes which still hold their resource past a threshold.
+//!
+//! Scopes configured with [`UseScope::warn_if_held_longer_than`](crate::UseScope::warn_if_held_longer_than)
+//! register a deadline here when they are entered. If a scope is still running when its deadline
+//! passes, e.g. because its body is stuck or deadlocked, the watchdog reports it right away instead
+//! of waiting for a release that may never happen. Process-wide state uses the `Static*`
+//! primitives, see [`sync`](crate::sync).
+
+use crate::observer::{UseEvent, UseObserver};
+use crate::sync::{StaticCondvar, StaticMutex, StaticMutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A scope whose hold time is watched.
+struct Watched {
+    event: UseEvent,
+    entered: Instant,
+    #[cfg_attr(not(feature = "log"), allow(dead_code))]
+    threshold: Duration,
+    global: Option<Arc<dyn UseObserver>>,
+    reported: AtomicBool,
+}
+
+impl Watched {
+    /// Reports the scope as still holding its resource, unless the long hold was reported already.
+    fn report_still_held(&self) {
+        if self.reported.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let held = self.entered.elapsed();
+
+        #[cfg(feature = "log")]
+        log::warn!(
+            "use scope {} at {} has held `{}` for {held:?}, longer than {:?}, and still holds it",
+            self.event.id(),
+            self.event.location(),
+            self.event.resource_type(),
+            self.threshold
+        );
+        #[cfg(feature = "metrics")]
+        metrics::counter!("use_with.long_holds", "resource" => self.event.resource_type())
+            .increment(1);
+
+        if let Some(global) = &self.global {
+            global.on_long_hold(&self.event, held);
+        }
+    }
+}
+
+/// The deadlines of all watched scopes, in no particular order.
+static PENDING: StaticMutex<Vec<(Instant, Arc<Watched>)>> = StaticMutex::new(Vec::new());
+static CHANGED: StaticCondvar = StaticCondvar::new();
+static START: Once = Once::new();
+
+fn pending() -> StaticMutexGuard<'static, Vec<(Instant, Arc<Watched>)>> {
+    PENDING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The registration of a watched scope, which ends the watch when dropped.
+pub(crate) struct Watch(Arc<Watched>);
+
+impl Watch {
+    /// Claims the report of a long hold, returning `false` if the watchdog has reported it already.
+    pub(crate) fn claim(&self) -> bool {
+        !self.0.reported.swap(true, Ordering::AcqRel)
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        pending().retain(|(_, watched)| !Arc::ptr_eq(watched, &self.0));
+    }
+}
+
+/// Watches a scope entered at `entered`, reporting it to the log, the metrics and the global
+/// observer once it holds its resource for longer than `threshold`.
+pub(crate) fn watch(
+    event: UseEvent,
+    entered: Instant,
+    #[cfg_attr(not(feature = "log"), allow(dead_code))] threshold: Duration,
+    global: Option<Arc<dyn UseObserver>>,
+) -> Watch {
+    START.call_once(|| {
+        // Without the thread, long holds are still reported once the resource is released.
+        let _ = thread::Builder::new()
+            .name("use-with-watchdog".into())
+            .spawn(run);
+    });
+    let watched = Arc::new(Watched {
+        event,
+        entered,
+        threshold,
+        global,
+        reported: AtomicBool::new(false),
+    });
+    let deadline = entered.checked_add(threshold).unwrap_or(entered);
+    pending().push((deadline, Arc::clone(&watched)));
+    CHANGED.notify_one();
+    Watch(watched)
+}
+
+/// Sleeps until the next deadline and reports the scopes that are overdue.
+fn run() {
+    let mut pending = pending();
+    loop {
+        let now = Instant::now();
+        let (due, waiting) = pending
+            .drain(..)
+            .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+        *pending = waiting;
+
+        if !due.is_empty() {
+            // Observers may enter watched scopes themselves, which registers them.
+            drop(pending);
+            for (_, watched) in due {
+                watched.report_still_held();
+            }
+            pending = self::pending();
+            continue;
+        }
+
+        pending = match pending.iter().map(|(deadline, _)| *deadline).min() {
+            Some(next) => {
+                CHANGED
+                    .wait_timeout(pending, next - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+            None => CHANGED.wait(pending).unwrap_or_else(|e| e.into_inner()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScopeId;
+    use std::panic::Location;
+    use std::sync::mpsc::{self, Sender};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_stuck_scope_is_reported_while_held() {
+        struct Reported(Mutex<Sender<Duration>>);
+
+        impl UseObserver for Reported {
+            fn on_long_hold(&self, _event: &UseEvent, held: Duration) {
+                let _ = self.0.lock().unwrap().send(held);
+            }
+        }
+
+        let (sender, reports) = mpsc::channel();
+        let event = UseEvent::new(ScopeId::next(), "StuckResource", Location::caller());
+        let threshold = Duration::from_millis(10);
+        let watch = watch(
+            event,
+            Instant::now(),
+            threshold,
+            Some(Arc::new(Reported(Mutex::new(sender)))),
+        );
+
+        let held = reports.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(held >= threshold);
+        assert!(!watch.claim());
+    }
+}
@@ -0,0 +1,79 @@
+//! Scoped access to resources through weak references.
+
+use crate::UseScope;
+use alloc::rc;
+use alloc::sync;
+
+/// Runs closures on the target of a weak reference, if it is still alive.
+///
+/// The weak reference is upgraded for the duration of the closure only, as a use scope with the
+/// strong reference as its resource. The strong reference cannot escape the closure, so the
+/// closure does not keep the resource alive beyond its scope by accident.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::Arc;
+/// use use_with::WeakUseExt;
+///
+/// let session = Arc::new(String::from("session"));
+/// let weak = Arc::downgrade(&session);
+///
+/// assert_eq!(weak.use_if_alive(|session| session.len()), Some(7));
+///
+/// drop(session);
+/// assert_eq!(weak.use_if_alive(|session| session.len()), None);
+/// ```
+pub trait WeakUseExt<T: ?Sized> {
+    /// Upgrades the weak reference and runs the closure `f` on the resource.
+    ///
+    /// # Returns
+    /// - `Some(U)` with the result of the closure `f`.
+    /// - `None` without running the closure if the resource has been dropped.
+    fn use_if_alive<U, F>(&self, f: F) -> Option<U>
+    where
+        F: FnOnce(&T) -> U;
+}
+
+impl<T: ?Sized> WeakUseExt<T> for sync::Weak<T> {
+    #[track_caller]
+    fn use_if_alive<U, F>(&self, f: F) -> Option<U>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        let strong = self.upgrade()?;
+        Some(UseScope::new(strong).use_with(|strong| f(&strong)))
+    }
+}
+
+impl<T: ?Sized> WeakUseExt<T> for rc::Weak<T> {
+    #[track_caller]
+    fn use_if_alive<U, F>(&self, f: F) -> Option<U>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        let strong = self.upgrade()?;
+        Some(UseScope::new(strong).use_with(|strong| f(&strong)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::DropCounter;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_upgrade_does_not_outlive_scope() {
+        let counter = DropCounter::new();
+        let strong = Rc::new(counter.probe());
+        let weak = Rc::downgrade(&strong);
+
+        let count = weak.use_if_alive(|_probe| Rc::strong_count(&strong));
+        assert_eq!(count, Some(2));
+        assert_eq!(Rc::strong_count(&strong), 1);
+
+        drop(strong);
+        assert_eq!(counter.count(), 1);
+        assert_eq!(weak.use_if_alive(|_probe| ()), None);
+    }
+}
@@ -0,0 +1,110 @@
+//! Cooperative yielding within long-running asynchronous use scopes.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Hands control back to the executor once the body of a use scope exhausted its budget.
+///
+/// Passed to the closure of [`Use::use_with_yielding`](crate::Use::use_with_yielding). A body
+/// that performs CPU-heavy work between awaits starves the other tasks of its executor thread.
+/// Awaiting [`checkpoint`](Self::checkpoint) between sections of such work consumes one unit of
+/// the budget, and yields to the executor whenever the budget is exhausted, so that the body
+/// does not have to decide on its own when to call e.g. `tokio::task::yield_now`.
+///
+/// Yielding only wakes the current task again, so it works with every executor.
+#[derive(Debug)]
+pub struct Yielder {
+    budget: u32,
+    remaining: u32,
+    yields: u64,
+}
+
+impl Yielder {
+    /// Creates a yielder that yields after every `budget` checkpoints.
+    ///
+    /// A budget of zero is treated as one, which yields at every checkpoint.
+    pub(crate) fn new(budget: u32) -> Self {
+        let budget = budget.max(1);
+        Self {
+            budget,
+            remaining: budget,
+            yields: 0,
+        }
+    }
+
+    /// Marks the end of a section of work, yielding to the executor if the budget is exhausted.
+    pub fn checkpoint(&mut self) -> Checkpoint<'_> {
+        self.remaining -= 1;
+        let yield_now = self.remaining == 0;
+        if yield_now {
+            self.remaining = self.budget;
+            self.yields += 1;
+        }
+        Checkpoint {
+            _yielder: self,
+            yield_now,
+        }
+    }
+
+    /// Returns how often the body has yielded so far.
+    pub fn yields(&self) -> u64 {
+        self.yields
+    }
+}
+
+/// The future returned by [`Yielder::checkpoint`].
+#[must_use = "a checkpoint only yields when it is awaited"]
+#[derive(Debug)]
+pub struct Checkpoint<'a> {
+    _yielder: &'a mut Yielder,
+    yield_now: bool,
+}
+
+impl Future for Checkpoint<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.yield_now {
+            self.yield_now = false;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Use;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_other_tasks_run_while_resource_is_held() {
+        let ran = Arc::new(AtomicBool::new(false));
+        let other = ::tokio::spawn({
+            let ran = Arc::clone(&ran);
+            async move { ran.store(true, Ordering::SeqCst) }
+        });
+
+        let (observed, yields) = vec![1u64; 6]
+            .use_with_yielding(4, |numbers, yielder| {
+                Box::pin(async move {
+                    let mut observed = Vec::new();
+                    for number in numbers.iter() {
+                        std::hint::black_box(number);
+                        yielder.checkpoint().await;
+                        observed.push(ran.load(Ordering::SeqCst));
+                    }
+                    (observed, yielder.yields())
+                })
+            })
+            .await;
+
+        assert_eq!(yields, 1);
+        assert_eq!(observed, [false, false, false, true, true, true]);
+        other.await.unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+//! Compares the machine code of plain use scopes with the code of calling their body directly.
+//!
+//! Builds a small crate against this one in release mode and inspects the emitted assembly, so it
+//! only runs on x86-64 Linux, where the output format is known.
+
+#![cfg(all(target_arch = "x86_64", target_os = "linux"))]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FIXTURE: &str = r#"
+use use_with::Use;
+
+#[inline(never)]
+#[no_mangle]
+pub fn body(value: u64) -> u64 {
+    value.rotate_left(7) ^ 0x5a5a
+}
+
+#[no_mangle]
+pub fn direct(value: u64) -> u64 {
+    body(value) + 1
+}
+
+#[no_mangle]
+pub fn scoped(value: u64) -> u64 {
+    value.use_with(body) + 1
+}
+"#;
+
+/// Builds the fixture with the given features of this crate and returns its assembly.
+fn assembly(std: bool) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let name = if std { "codegen-std" } else { "codegen-core" };
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        format!(
+            r#"[package]
+name = "{name}"
+version = "0.0.0"
+edition = "2021"
+
+[dependencies]
+use-with = {{ path = {root:?}, default-features = false, features = {features} }}
+
+[workspace]
+"#,
+            features = if std { r#"["std"]"# } else { "[]" },
+        ),
+    )
+    .unwrap();
+    fs::write(dir.join("src/lib.rs"), FIXTURE).unwrap();
+    // Resolving the same versions as this crate keeps the build offline.
+    if let Ok(lock) = fs::read(root.join("Cargo.lock")) {
+        fs::write(dir.join("Cargo.lock"), lock).unwrap();
+    }
+
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo
+        .current_dir(&dir)
+        .args([
+            "rustc",
+            "--quiet",
+            "--release",
+            "--lib",
+            "--target-dir",
+            "target",
+        ])
+        .args(["--", "--emit", "asm", "-C", "codegen-units=1"]);
+    // Flags meant for this crate, such as coverage instrumentation, would change the code.
+    for variable in [
+        "RUSTFLAGS",
+        "CARGO_ENCODED_RUSTFLAGS",
+        "CARGO_BUILD_RUSTFLAGS",
+        "RUSTC_WRAPPER",
+        "RUSTC_WORKSPACE_WRAPPER",
+        "CARGO_TARGET_DIR",
+        "CARGO_BUILD_TARGET_DIR",
+    ] {
+        cargo.env_remove(variable);
+    }
+    let status = cargo.status().unwrap();
+    assert!(status.success(), "building the fixture failed");
+
+    let deps = dir.join("target/release/deps");
+    let prefix = format!("{}-", name.replace('-', "_"));
+    let file = fs::read_dir(&deps)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let file_name = path.file_name().unwrap().to_string_lossy();
+            file_name.starts_with(&prefix) && file_name.ends_with(".s")
+        })
+        .max_by_key(|path| fs::metadata(path).unwrap().modified().unwrap())
+        .expect("the fixture emitted its assembly");
+    fs::read_to_string(file).unwrap()
+}
+
+/// The machine code of a function in the assembly of the fixture.
+enum Function {
+    /// The function is an alias of another function with identical code.
+    Alias(String),
+    Instructions(Vec<String>),
+}
+
+fn function(assembly: &str, name: &str) -> Function {
+    let alias = format!("{name} = ");
+    if let Some(line) = assembly.lines().find(|line| line.starts_with(&alias)) {
+        return Function::Alias(line[alias.len()..].trim().to_owned());
+    }
+    let label = format!("{name}:");
+    let instructions = assembly
+        .lines()
+        .skip_while(|line| *line != label)
+        .skip(1)
+        .take_while(|line| !line.starts_with(".Lfunc_end"))
+        .filter(|line| line.starts_with('\t') && !line.starts_with("\t."))
+        .map(|line| line.trim().to_owned())
+        .collect::<Vec<_>>();
+    assert!(!instructions.is_empty(), "`{name}` is missing");
+    Function::Instructions(instructions)
+}
+
+#[test]
+fn use_with_compiles_to_a_direct_call_without_std() {
+    let assembly = assembly(false);
+    match (function(&assembly, "scoped"), function(&assembly, "direct")) {
+        (Function::Alias(target), _) => assert_eq!(target, "direct"),
+        (Function::Instructions(scoped), Function::Instructions(direct)) => {
+            assert_eq!(scoped, direct)
+        }
+        (Function::Instructions(_), Function::Alias(_)) => panic!("`direct` is an alias"),
+    }
+}
+
+#[test]
+fn use_with_only_checks_a_flag_with_std() {
+    let assembly = assembly(true);
+    let Function::Instructions(scoped) = function(&assembly, "scoped") else {
+        panic!("`scoped` is an alias despite its instrumentation");
+    };
+
+    // No identifier or other shared counter is updated inline.
+    assert!(
+        scoped
+            .iter()
+            .all(|instruction| !instruction.starts_with("lock")),
+        "{scoped:#?}"
+    );
+
+    // Until the flag is checked, the scope does nothing but save registers.
+    let branch = scoped
+        .iter()
+        .position(|instruction| instruction.starts_with('j'))
+        .unwrap();
+    let check = &scoped[..branch];
+    assert!(
+        check
+            .iter()
+            .any(|instruction| instruction.contains("instrument9CONSUMERS")),
+        "{check:#?}"
+    );
+    assert!(
+        check
+            .iter()
+            .all(|instruction| !instruction.starts_with("call")),
+        "{check:#?}"
+    );
+
+    // If nothing consumes the scope, it calls the body and returns.
+    let unobserved = scoped[branch + 1..]
+        .iter()
+        .take_while(|instruction| !instruction.starts_with("ret"))
+        .filter(|instruction| instruction.starts_with("call") || instruction.starts_with('j'))
+        .collect::<Vec<_>>();
+    assert_eq!(unobserved, ["callq\t*body@GOTPCREL(%rip)"]);
+}
@@ -0,0 +1,19 @@
+#[test]
+fn sealed_resources_cannot_escape() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/sealed_*.rs");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn catch_unwind_requires_unwind_safety() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/unwind_*.rs");
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn use_fixture_rejects_invalid_bindings() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/fixture_*.rs");
+}
@@ -0,0 +1,8 @@
+use use_with::use_fixture;
+
+#[use_fixture(value = 1u32, other = 2u32)]
+fn missing(value: u32) {
+    let _ = value;
+}
+
+fn main() {}
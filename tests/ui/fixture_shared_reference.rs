@@ -0,0 +1,8 @@
+use use_with::use_fixture;
+
+#[use_fixture(value = String::new())]
+fn shared(value: &String) {
+    let _ = value;
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use use_with::use_fixture;
+
+#[use_fixture(value = 1u32)]
+fn unbound(value: u32, other: u32) {
+    let _ = (value, other);
+}
+
+fn main() {}
@@ -0,0 +1,5 @@
+use use_with::Use;
+
+fn main() {
+    let _leaked = String::from("secret").use_sealed(|sealed| sealed.into_mut());
+}
@@ -0,0 +1,7 @@
+use use_with::Use;
+
+fn main() {
+    String::from("secret").use_sealed(|sealed| {
+        std::thread::spawn(move || sealed.len());
+    });
+}
@@ -0,0 +1,9 @@
+use use_with::{Sealed, Use};
+
+fn main() {
+    let mut outside: Option<Sealed<'_, String>> = None;
+    String::from("secret").use_sealed(|sealed| {
+        outside = Some(sealed);
+    });
+    drop(outside);
+}
@@ -0,0 +1,8 @@
+use use_with::Use;
+
+struct Plugin;
+
+fn main() {
+    let mut log = Vec::new();
+    let _ = Plugin.use_with_catch_unwind(|_plugin| log.push("started"));
+}
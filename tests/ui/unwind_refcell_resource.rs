@@ -0,0 +1,7 @@
+use std::cell::RefCell;
+use use_with::Use;
+
+fn main() {
+    let state = RefCell::new(0);
+    let _ = (&state).use_with_catch_unwind(|state| *state.borrow_mut() += 1);
+}
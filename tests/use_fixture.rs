@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use use_with::{use_fixture, AsyncClose, Close};
+
+#[derive(Debug, PartialEq, Eq)]
+struct CloseError;
+
+/// A fixture counting its closes in a static that is exclusive to a single test.
+struct TestDb {
+    closed: &'static AtomicUsize,
+    fail: bool,
+}
+
+impl TestDb {
+    fn new(closed: &'static AtomicUsize) -> Self {
+        Self {
+            closed,
+            fail: false,
+        }
+    }
+
+    fn failing() -> Self {
+        static FAILED_CLOSES: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            closed: &FAILED_CLOSES,
+            fail: true,
+        }
+    }
+}
+
+impl Close for TestDb {
+    type Error = CloseError;
+
+    fn close(self) -> Result<(), Self::Error> {
+        self.closed.fetch_add(1, Ordering::SeqCst);
+        if self.fail {
+            Err(CloseError)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl AsyncClose for TestDb {
+    type Error = CloseError;
+
+    async fn close_async(self) -> Result<(), Self::Error> {
+        self.close()
+    }
+}
+
+struct Scratch(Vec<&'static str>);
+
+static SYNC_CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+struct Counted;
+
+impl Close for Counted {
+    type Error = CloseError;
+
+    fn close(self) -> Result<(), Self::Error> {
+        SYNC_CLOSED.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_fixture_is_closed() {
+    #[use_fixture(res = Counted)]
+    fn inner(res: &mut Counted) -> usize {
+        let _ = res;
+        SYNC_CLOSED.load(Ordering::SeqCst)
+    }
+
+    let before = SYNC_CLOSED.load(Ordering::SeqCst);
+    assert_eq!(inner(), before);
+    assert_eq!(SYNC_CLOSED.load(Ordering::SeqCst), before + 1);
+}
+
+#[test]
+fn test_multiple_fixtures() {
+    static CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+    #[use_fixture(db = TestDb::new(&CLOSED), scratch = Scratch(vec![]))]
+    fn inner(db: &mut TestDb, mut scratch: Scratch) -> Vec<&'static str> {
+        scratch.0.push("written");
+        assert_eq!(db.closed.load(Ordering::SeqCst), 0);
+        scratch.0
+    }
+
+    assert_eq!(inner(), ["written"]);
+    assert_eq!(CLOSED.load(Ordering::SeqCst), 1);
+}
+
+#[use_fixture(db = TestDb::failing())]
+#[test]
+#[should_panic(expected = "failed to close fixture `db`")]
+fn test_failing_close_fails_test(db: &mut TestDb) {
+    let _ = db;
+}
+
+static RESULT_CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+#[use_fixture(db = TestDb::new(&RESULT_CLOSED))]
+#[test]
+fn test_result_returning_test(db: &mut TestDb) -> Result<(), String> {
+    if db.fail {
+        return Err("unexpected".into());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_fixtures() {
+    static CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+    #[use_fixture(db = TestDb::new(&CLOSED), scratch = Scratch(vec!["async"]))]
+    async fn inner(db: &mut TestDb, scratch: Scratch) -> usize {
+        tokio::task::yield_now().await;
+        assert!(!db.fail);
+        assert_eq!(scratch.0, ["async"]);
+        db.closed.load(Ordering::SeqCst)
+    }
+
+    assert_eq!(inner().await, 0);
+    assert_eq!(CLOSED.load(Ordering::SeqCst), 1);
+}
+
+#[use_fixture(db = TestDb::failing())]
+#[tokio::test]
+#[should_panic(expected = "failed to close fixture `db`")]
+async fn test_async_failing_close(db: &mut TestDb) {
+    tokio::task::yield_now().await;
+    let _ = db;
+}
@@ -0,0 +1,164 @@
+//! Verifies that plain use scopes add no allocations and at most a pointer of state.
+
+use use_with::Use;
+
+/// Returns the sizes of the future of a body on a `u64` resource and of `use_with_async` with it.
+fn sized_on_u64<const N: usize>() -> (usize, usize) {
+    let payload = [1u8; N];
+    let body = move |resource: u64| async move { u64::from(payload[N - 1]) + resource };
+    let direct = std::mem::size_of_val(&body(1));
+    (direct, std::mem::size_of_val(&1u64.use_with_async(body)))
+}
+
+/// Returns the sizes of the future of a body on a zero-sized resource and of `use_with_async` with it.
+fn sized_on_unit<const N: usize>() -> (usize, usize) {
+    let payload = [1u8; N];
+    let body = move |()| async move { payload[N - 1] };
+    let direct = std::mem::size_of_val(&body(()));
+    (direct, std::mem::size_of_val(&().use_with_async(body)))
+}
+
+#[test]
+fn use_with_async_is_at_most_a_resource_larger_than_its_body() {
+    for (direct, scoped) in [
+        sized_on_u64::<1>(),
+        sized_on_u64::<8>(),
+        sized_on_u64::<8192>(),
+    ] {
+        assert!(
+            scoped <= direct + std::mem::size_of::<u64>(),
+            "{scoped} > {direct} + 8"
+        );
+    }
+}
+
+/// Without features that could observe them, plain scopes keep no state at all.
+#[cfg(not(any(feature = "std", feature = "log")))]
+#[test]
+fn use_with_async_on_zero_sized_resources_is_as_large_as_its_body() {
+    for (direct, scoped) in [
+        sized_on_unit::<1>(),
+        sized_on_unit::<8>(),
+        sized_on_unit::<8192>(),
+    ] {
+        assert_eq!(scoped, direct);
+    }
+}
+
+/// Otherwise, they keep a pointer to the state of the scope, in case it is observed.
+#[cfg(any(feature = "std", feature = "log"))]
+#[test]
+fn use_with_async_on_zero_sized_resources_adds_a_pointer_to_its_body() {
+    let pointer = std::mem::size_of::<usize>();
+    for (direct, scoped) in [
+        sized_on_unit::<1>(),
+        sized_on_unit::<8>(),
+        sized_on_unit::<8192>(),
+    ] {
+        assert!(scoped <= direct.next_multiple_of(pointer) + pointer);
+    }
+}
+
+/// Counts the allocations of the current thread through a wrapping global allocator.
+///
+/// Skipped with features that record scopes into shared registries or instrumentation backends.
+#[cfg(not(any(
+    feature = "leak-detector",
+    feature = "diagnostics",
+    feature = "metrics",
+    feature = "otel"
+)))]
+mod allocations {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use use_with::{ExitStack, Use};
+
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            // Thread-local storage may be torn down while the thread deallocates its last values.
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Returns the number of allocations performed by `f` on the current thread.
+    fn allocations<U>(f: impl FnOnce() -> U) -> (U, usize) {
+        let before = ALLOCATIONS.with(Cell::get);
+        let result = f();
+        (result, ALLOCATIONS.with(Cell::get) - before)
+    }
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Polls a future that completes without waiting.
+    fn poll_ready<F: Future>(future: F, waker: &Waker) -> F::Output {
+        let mut cx = Context::from_waker(waker);
+        match pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the future did not complete immediately"),
+        }
+    }
+
+    #[test]
+    fn use_with_does_not_allocate() {
+        let (result, count) = allocations(|| 41u64.use_with(|value| value + 1));
+        assert_eq!((result, count), (42, 0));
+    }
+
+    #[test]
+    fn use_with_async_does_not_allocate() {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let (result, count) = allocations(|| {
+            poll_ready(
+                41u64.use_with_async(|value| async move { value + 1 }),
+                &waker,
+            )
+        });
+        assert_eq!((result, count), (42, 0));
+    }
+
+    #[test]
+    fn exit_stack_allocates_only_for_capturing_actions() {
+        let ((), count) = allocations(|| {
+            let mut stack = ExitStack::new();
+            for _ in 0..3 {
+                stack.callback(|| ());
+            }
+            stack.push(());
+            stack.close();
+        });
+        assert_eq!(count, 0);
+
+        let closed = Cell::new(0);
+        let ((), count) = allocations(|| {
+            let mut stack = ExitStack::new();
+            for _ in 0..3 {
+                stack.callback(|| closed.set(closed.get() + 1));
+            }
+            stack.push(1u64);
+            stack.close();
+        });
+        assert_eq!((closed.get(), count), (3, 4));
+    }
+}
@@ -0,0 +1,219 @@
+//! # use_with_macros
+//!
+//! Procedural macros for the [`use-with`](https://docs.rs/use-with) crate. Use them through the
+//! re-exports of `use_with` with its `macros` feature enabled rather than depending on this crate
+//! directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Expr, FnArg, Ident, ItemFn, Pat, Token, Type};
+
+/// Provides test functions with fixtures that are torn down once the test completes.
+///
+/// Each `name = expression` argument acquires a fixture by evaluating `expression` before the test
+/// body runs, and binds it to the function parameter called `name`. The body is then wrapped into
+/// use scopes of the fixtures, which guarantees their teardown even if the test panics or returns
+/// early. Fixtures are acquired in the order they are listed and released in reverse order.
+///
+/// All fixtures are acquired up front, before the first use scope is entered. If an initializer
+/// panics, the fixtures acquired before it are therefore dropped without being closed, and
+/// fixtures taken by `&mut` reference should release what they hold in `Drop` as well.
+///
+/// How a fixture is torn down depends on the type of its parameter:
+/// - A parameter taking the fixture **by value** runs the body through `Use::use_with`, dropping
+///   the fixture at the latest when the body completes.
+/// - A parameter taking the fixture **by mutable reference** runs the body through
+///   `Use::use_close` and explicitly closes the fixture afterwards. A failing close fails the test.
+///   The fixture must implement `Close`, or `AsyncClose` for `async` tests, with a `Debug` error.
+///
+/// `async` test functions use the asynchronous counterparts of these combinators, so the
+/// attribute composes with runtime test attributes such as `#[tokio::test]`. Place
+/// `#[use_fixture]` above the test attribute, so that it is expanded first.
+///
+/// # Examples
+/// ```rust
+/// use use_with::{use_fixture, Close};
+///
+/// struct TestDb;
+///
+/// impl TestDb {
+///     fn new() -> Self {
+///         TestDb
+///     }
+/// }
+///
+/// impl Close for TestDb {
+///     type Error = std::io::Error;
+///
+///     fn close(self) -> Result<(), Self::Error> {
+///         // Drop the test schema, ...
+///         Ok(())
+///     }
+/// }
+///
+/// struct Scratch(Vec<u8>);
+///
+/// #[use_fixture(db = TestDb::new(), scratch = Scratch(Vec::new()))]
+/// fn my_test(db: &mut TestDb, mut scratch: Scratch) {
+///     scratch.0.push(42);
+///     // ...
+/// }
+/// # my_test();
+/// ```
+#[proc_macro_attribute]
+pub fn use_fixture(args: TokenStream, item: TokenStream) -> TokenStream {
+    let fixtures = parse_macro_input!(args with Punctuated::<Fixture, Token![,]>::parse_terminated);
+    let function = parse_macro_input!(item as ItemFn);
+    expand(fixtures.into_iter().collect(), function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A single `name = expression` argument of [`use_fixture`].
+struct Fixture {
+    name: Ident,
+    init: Expr,
+}
+
+impl Parse for Fixture {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let init = input.parse()?;
+        Ok(Self { name, init })
+    }
+}
+
+/// A fixture matched to the test function parameter it is bound to.
+struct Binding {
+    fixture: Fixture,
+    pat: Pat,
+    ty: Type,
+    close: bool,
+}
+
+fn expand(fixtures: Vec<Fixture>, mut function: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let mut params = Vec::new();
+    for input in std::mem::take(&mut function.sig.inputs) {
+        let FnArg::Typed(param) = input else {
+            return Err(syn::Error::new(
+                input.span(),
+                "fixtures cannot be bound to `self`",
+            ));
+        };
+        params.push(param);
+    }
+
+    let mut bindings = Vec::with_capacity(fixtures.len());
+    for fixture in fixtures {
+        if bindings
+            .iter()
+            .any(|binding: &Binding| binding.fixture.name == fixture.name)
+        {
+            return Err(syn::Error::new(
+                fixture.name.span(),
+                format!("fixture `{}` is defined more than once", fixture.name),
+            ));
+        }
+
+        let position = params
+            .iter()
+            .position(|param| matches!(&*param.pat, Pat::Ident(pat) if pat.ident == fixture.name))
+            .ok_or_else(|| {
+                syn::Error::new(
+                    fixture.name.span(),
+                    format!("the test function has no parameter `{}`", fixture.name),
+                )
+            })?;
+        let param = params.remove(position);
+
+        let (ty, close) = match *param.ty {
+            Type::Reference(reference) if reference.mutability.is_some() => (*reference.elem, true),
+            Type::Reference(reference) => {
+                return Err(syn::Error::new_spanned(
+                    reference,
+                    "fixtures are taken either by value or by `&mut` reference",
+                ))
+            }
+            ty => (ty, false),
+        };
+
+        bindings.push(Binding {
+            fixture,
+            pat: *param.pat,
+            ty,
+            close,
+        });
+    }
+
+    if let Some(param) = params.first() {
+        return Err(syn::Error::new_spanned(
+            param,
+            "every parameter of the test function must be bound to a fixture",
+        ));
+    }
+
+    let asyncness = function.sig.asyncness.is_some();
+    let mut body = {
+        let block = &function.block;
+        quote!(#block)
+    };
+
+    for binding in bindings.iter().rev() {
+        body = wrap(binding, body, asyncness);
+    }
+
+    let acquire = bindings.iter().map(|binding| {
+        let var = fixture_var(&binding.fixture.name);
+        let ty = &binding.ty;
+        let init = &binding.fixture.init;
+        quote_spanned!(init.span()=> let #var: #ty = #init;)
+    });
+
+    function.block = syn::parse_quote!({
+        #(#acquire)*
+        #body
+    });
+
+    Ok(quote!(#function))
+}
+
+/// Wraps `body` into the use scope of a single fixture.
+fn wrap(
+    binding: &Binding,
+    body: proc_macro2::TokenStream,
+    asyncness: bool,
+) -> proc_macro2::TokenStream {
+    let var = fixture_var(&binding.fixture.name);
+    let pat = &binding.pat;
+    let ty = &binding.ty;
+    let message = format!("failed to close fixture `{}`", binding.fixture.name);
+
+    match (binding.close, asyncness) {
+        (false, false) => quote! {
+            ::use_with::Use::use_with(#var, |#pat: #ty| { #body })
+        },
+        (false, true) => quote! {
+            ::use_with::Use::use_with_async(#var, |#pat: #ty| async move { #body }).await
+        },
+        (true, false) => quote! {
+            ::use_with::Use::use_close(#var, |#pat: &mut #ty| { #body }).expect(#message)
+        },
+        (true, true) => quote! {
+            ::use_with::Use::use_close_async(#var, |#pat: &mut #ty| {
+                ::std::boxed::Box::pin(async move { #body })
+            })
+            .await
+            .expect(#message)
+        },
+    }
+}
+
+/// Returns the hygienic name of the variable holding a fixture until its use scope is entered.
+fn fixture_var(name: &Ident) -> Ident {
+    format_ident!("__use_fixture_{}", name, span = Span::mixed_site())
+}